@@ -1,211 +1,180 @@
-use colored::Colorize;
-use std::error::Error;
-use std::future::Future;
-use std::io;
-use std::path::Path;
-use std::pin::Pin;
-use std::process::exit;
-use std::sync::Arc;
-use std::time::Duration;
-use tokio::runtime::Builder;
-use tokio::time::Instant;
-use tracing::info;
-use UltraFastFileSearch::config::constants::MAX_TEMP_FILES_HDD_BATCH;
-use UltraFastFileSearch::directory_reader::directory_reader_impl::ReadDirectories4;
-use UltraFastFileSearch::disk_reader::disk_reader_impl::{init_drives, process_all_disks};
-use UltraFastFileSearch::logger::logger_impl::init_logger;
-use UltraFastFileSearch::utils::temp_files_dirs_impl::{
-    create_temp_dir_with_files_hdd, UffsTempDir,
-};
-use UltraFastFileSearch::utils::temp_files_dirs_impl::{
-    create_temp_dir_with_files_hdd_tokio, create_temp_dir_with_files_ssd,
-};
-use UltraFastFileSearch::utils::utils_impl::measure_time_normal;
-use UltraFastFileSearch::utils::utils_impl::measure_time_tokio;
-use UltraFastFileSearch::utils::utils_impl::optimize_parameter;
-use UltraFastFileSearch::utils::utils_impl::{
-    count_files_in_dir, format_duration, generate_fibonacci, measure_time_normal_bench,
-    measure_time_tokio_bench, optimize_parameter_tokio,
-};
-
-fn main_bench() -> std::io::Result<()> {
-    // Initialize the logger
-    let _guard = init_logger();
-
-    info!("Application started...");
-
-    // Configure Tokio runtime with optimized settings for high-performance system
-    let runtime = Builder::new_multi_thread()
-        .worker_threads(24)
-        .max_blocking_threads(24)
-        .enable_all()
-        .build()
-        .expect("Failed to create Tokio runtime");
-
-    runtime.block_on(async {
-        let hdd_path = Path::new("D:\\temp_test");
-        let ssd_path = Path::new("C:\\temp_test");
-
-        let initial_value = MAX_TEMP_FILES_HDD_BATCH;
-        let threshold = Duration::from_millis(500);
-
-        let batch_sizes = generate_fibonacci(10);
-
-        // // Example operation function
-        // let operation = |batch_size: usize| -> Result<UffsTempDir, Box<dyn std::error::Error>> {
-        //     create_temp_dir_with_files_hdd(&hdd_path, batch_size).map_err(|e| e.into())
-        // };
-        // for &batch_size in &batch_sizes {
-        //     let duration = measure_time_normal_bench(|| operation(batch_size));
-        //     println!("Batch size: {}, Duration: {:?}", batch_size, duration);
-        // }
-        // let optimized_value = optimize_parameter(initial_value, operation);
-        // info!("Optimized batch size: {}", optimized_value);
-
-        // // Example operation function
-        // let operation = |batch_size: usize| -> Pin<Box<dyn Future<Output = Result<UffsTempDir, Box<dyn Error + Send + Sync>>> + Send>> {
-        //     let hdd_path = hdd_path.to_path_buf(); // Clone the path to move into async block
-        //     Box::pin(async move {
-        //         create_temp_dir_with_files_hdd_tokio(&hdd_path, batch_size).await.map_err(|e| e.into())
-        //     })
-        // };
-        //
-        // for &batch_size in &batch_sizes {
-        //     let duration = measure_time_tokio_bench(|| operation(batch_size)).await;
-        //     println!("Batch size: {}, Duration: {:?}", batch_size, duration);
-        // }
-        //
-        // println!("\n______________________________\n");
-        //
-        // // exit(1);
-        //
-        // let optimized_value = optimize_parameter_tokio(initial_value, operation).await;
-        // info!("Optimized batch size: {}", optimized_value);
-
-        let batch_size = MAX_TEMP_FILES_HDD_BATCH;
-        let operation = |batch_size: usize| -> Pin<
-            Box<dyn Future<Output = Result<UffsTempDir, Box<dyn Error + Send + Sync>>> + Send>,
-        > {
-            let hdd_path = hdd_path.to_path_buf(); // Clone the path to move into async block
-            Box::pin(async move {
-                create_temp_dir_with_files_hdd_tokio(&hdd_path, batch_size)
-                    .await
-                    .map_err(|e| e.into())
-            })
-        };
-        let duration_hdd = measure_time_tokio_bench(|| operation(batch_size)).await;
-        println!("HDD temp dir: {:?}", hdd_path);
-        println!("HDD creation took: {:?}", format_duration(duration_hdd));
-
-        let (test_ssd, duration_ssd) =
-            measure_time_normal(|| create_temp_dir_with_files_ssd(hdd_path).unwrap());
-
-        let number = count_files_in_dir(&test_ssd.path()).await.unwrap();
-        println!("Number of files: \t{}", number);
-        println!("HDD temp dir: {:?}", test_ssd.path());
-        println!("HDD creation took: {:?}", format_duration(duration_ssd));
-
-        let (test_ssd, duration_ssd) =
-            measure_time_normal(|| create_temp_dir_with_files_ssd(ssd_path).unwrap());
-        let number = count_files_in_dir(&test_ssd.path()).await.unwrap();
-        println!("Number of files: \t{}", number);
-        println!("SSD temp dir: {:?}", test_ssd.path());
-        println!("SSD creation took: {:?}", format_duration(duration_ssd));
+//! Compares the directory readers and the counting path on generated trees of several shapes.
+//!
+//! `cargo bench` measures with a warm cache: each iteration reads a tree the one before just
+//! read. For the cold cache set `UFFS_BENCH_DROP_CACHES` to a command emptying the file system
+//! cache, it runs before every cold iteration, e.g.
+//!
+//! ```text
+//! Linux:   sync; echo 3 | sudo tee /proc/sys/vm/drop_caches
+//! Windows: RAMMap64.exe -Et
+//! ```
+//!
+//! Criterion keeps the results in target/criterion. To compare two commits, run
+//! `cargo bench -- --save-baseline <commit>` on the first and `cargo bench -- --baseline <commit>`
+//! on the second.
+
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkGroup, BenchmarkId};
+use criterion::{measurement::WallTime, Criterion, Throughput};
+use std::env;
+use std::process::Command;
+use tempfile::TempDir;
+use tokio::runtime::Runtime;
+use UltraFastFileSearch_library::modules::directory_reader::directory_reader_impl::count_entries;
+use UltraFastFileSearch_library::modules::ignore_filter::ignore_filter_impl::IgnoreFilter;
+use UltraFastFileSearch_library::modules::tree_gen::distribution::Distribution;
+use UltraFastFileSearch_library::modules::tree_gen::tree_gen_impl::TreeSpec;
+use UltraFastFileSearch_library::Scanner;
+
+const DROP_CACHES_VAR: &str = "UFFS_BENCH_DROP_CACHES";
+
+// The same seed on every run, so that the numbers of two commits are about the same trees
+const SEED: u64 = 42;
+
+struct Tree {
+    shape: &'static str,
+    dir: TempDir,
+    entries: u64,
+}
 
-        // Get current disk information
-        // Read from file or re-create in case not done / too old
-        // let current_drive_info = init_drives().await;
+fn shapes() -> Vec<(&'static str, TreeSpec)> {
+    let spec = TreeSpec::default().with_seed(SEED);
+    vec![
+        (
+            "balanced",
+            TreeSpec {
+                max_entries: 20_000,
+                ..spec.clone()
+            },
+        ),
+        // Many siblings, the concurrent readers have the most to do at once
+        (
+            "wide",
+            TreeSpec {
+                depth: 1,
+                fan_out: Distribution::Fixed { value: 200 },
+                files_per_dir: Distribution::Fixed { value: 50 },
+                ..spec.clone()
+            },
+        ),
+        // A single chain of directories, one read at a time whatever the reader. Kept below
+        // MAX_PATH for the counting path.
+        (
+            "deep",
+            TreeSpec {
+                depth: 20,
+                fan_out: Distribution::Fixed { value: 1 },
+                files_per_dir: Distribution::Fixed { value: 100 },
+                long_names: 0.0,
+                ..spec.clone()
+            },
+        ),
+        (
+            "flat",
+            TreeSpec {
+                depth: 0,
+                files_per_dir: Distribution::Fixed { value: 20_000 },
+                max_entries: 20_000,
+                ..spec
+            },
+        ),
+    ]
+}
 
-        // println!("{:?}", current_drive_info);
-        // Process with ReadDirectories1
-        // let directory_reader = select_algorithm(&mut new_drive_info);
+fn generate_trees() -> Vec<Tree> {
+    shapes()
+        .into_iter()
+        .map(|(shape, spec)| {
+            let dir = TempDir::new().expect("Failed to create a temporary directory");
+            let stats = spec
+                .generate()
+                .write_to(dir.path())
+                .expect("Failed to write the tree");
+            let entries = stats.dirs + stats.files + stats.symlinks + stats.hard_links;
+            Tree {
+                shape,
+                dir,
+                entries,
+            }
+        })
+        .collect()
+}
 
-        // let path = get_path().expect("Error getting path to process");
-        // process_one_path(current_drive_info).await;
+fn drop_caches(command: &str) {
+    #[cfg(windows)]
+    let status = Command::new("cmd").arg("/C").arg(command).status();
+    #[cfg(not(windows))]
+    let status = Command::new("sh").arg("-c").arg(command).status();
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(status) => panic!("{} failed with {}", DROP_CACHES_VAR, status),
+        Err(e) => panic!("Failed to run {}: {}", DROP_CACHES_VAR, e),
+    }
+}
 
-        // process_test_disk(current_drive_info).await;
-        // process_all_disks(current_drive_info).await;
+// Every reader and the counting path on one tree, the caches are dropped first when given a
+// command for it
+fn bench_tree(
+    group: &mut BenchmarkGroup<'_, WallTime>,
+    runtime: &Runtime,
+    tree: &Tree,
+    drop_command: Option<&str>,
+) {
+    let root = tree.dir.path().to_path_buf();
+    let ignore = IgnoreFilter::default();
+    let setup = || {
+        if let Some(command) = drop_command {
+            drop_caches(command);
+        }
+    };
+
+    for reader in Scanner::readers() {
+        let scanner = Scanner::new().root(&root).reader(reader);
+        group.bench_function(BenchmarkId::new(reader, tree.shape), |b| {
+            b.iter_batched(
+                setup,
+                |()| runtime.block_on(scanner.scan_async()).unwrap(),
+                BatchSize::PerIteration,
+            )
+        });
+    }
+
+    group.bench_function(BenchmarkId::new("count", tree.shape), |b| {
+        b.iter_batched(
+            setup,
+            |()| runtime.block_on(count_entries(&root, &ignore)).unwrap(),
+            BatchSize::PerIteration,
+        )
     });
+}
 
-    info!("Application finished.");
-
-    Ok(())
+fn bench_readers(c: &mut Criterion) {
+    let runtime = Runtime::new().expect("Failed to create Tokio runtime");
+    let trees = generate_trees();
+    let drop_command = env::var(DROP_CACHES_VAR).ok();
+
+    for tree in &trees {
+        let mut group = c.benchmark_group("warm");
+        group.throughput(Throughput::Elements(tree.entries));
+        bench_tree(&mut group, &runtime, tree, None);
+        group.finish();
+    }
+
+    let Some(drop_command) = drop_command else {
+        eprintln!(
+            "Skipping the cold cache benchmarks, {} is not set",
+            DROP_CACHES_VAR
+        );
+        return;
+    };
+    for tree in &trees {
+        let mut group = c.benchmark_group("cold");
+        group.throughput(Throughput::Elements(tree.entries));
+        // Dropping the caches takes longer than most reads
+        group.sample_size(10);
+        bench_tree(&mut group, &runtime, tree, Some(&drop_command));
+        group.finish();
+    }
 }
-//
-// fn main() -> IoResult<()> {
-//     // Initialize the logger
-//     let _guard = init_logger();
-//
-//     info!("Application started...");
-//
-//     // Define different configurations for optimization
-//     let configurations = vec![
-//         (24, 48), // Best performer overall
-//         (26, 52), // Consistently good performer
-//         (25, 50), // Slightly adjusted from best performer
-//         (27, 54), // Variation around the best
-//         (23, 46), // Slightly lower threads
-//         (22, 44), // Consistent performance
-//     ];
-//
-//     let best_config = find_best_configuration(configurations);
-//
-//     println!("Best configuration: worker_threads = {}, blocking_threads = {}", best_config.0, best_config.1);
-//
-//
-//     info!("Application finished.");
-//
-//     Ok(())
-// }
-//
-// fn find_best_configuration(configurations: Vec<(usize, usize)>) -> (usize, usize) {
-//     let mut best_duration = std::time::Duration::MAX;
-//     let mut best_config = (0, 0);
-//
-//     for (worker_threads, blocking_threads) in configurations {
-//         let duration = run_with_configuration(worker_threads, blocking_threads);
-//         println!("Configuration with {} worker threads and {} blocking threads took {:?}", worker_threads, blocking_threads, format_duration(duration));
-//
-//         if duration < best_duration {
-//             best_duration = duration;
-//             best_config = (worker_threads, blocking_threads);
-//         }
-//     }
-//
-//     best_config
-// }
-//
-// fn run_with_configuration(worker_threads: usize, blocking_threads: usize) -> std::time::Duration {
-//     let mut time_used = Default::default();
-//     // Configure Tokio runtime with optimized settings for high-performance system
-//     let runtime = Builder::new_multi_thread()
-//         .worker_threads(worker_threads)
-//         .max_blocking_threads(blocking_threads)
-//         .enable_all()
-//         .build()
-//         .expect("Failed to create Tokio runtime");
-//
-//     // Run the async function using the configured runtime
-//     runtime.block_on(async {
-//         let start = Instant::now();
-//
-//         let separator1 = "=".repeat(50).green().to_string();
-//         let separator2 = "-".repeat(50).red().to_string();
-//
-//         println!("{}", separator1);
-//         println!("\nReadDirectories4\n");
-//         println!("{}", separator2);
-//
-//         let directory_reader = Arc::new(ReadDirectories4);
-//
-//         process_all_disks(directory_reader).await;
-//
-//         time_used = Instant::now() - start;
-//
-//     });
-//
-//     info!("Application finished.");
-//
-//     time_used
-//
-// }
+
+criterion_group!(benches, bench_readers);
+criterion_main!(benches);
@@ -355,6 +355,7 @@ fn main() -> std::io::Result<()> {
     log::info!("Package name: {:?}", package_name);
 
     // Conditionally add .exe extension to package_name on Windows
+    #[cfg_attr(not(target_os = "windows"), allow(unused_mut))]
     let mut binary_name = package_name.to_string();
 
     #[cfg(target_os = "windows")]
@@ -484,10 +485,7 @@ fi
                     profile, output
                 );
                 log::error!("{}", error_message);
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    error_message,
-                ));
+                return Err(std::io::Error::other(error_message));
             }
             Err(e) => {
                 log::error!("Failed to execute xattr command for shell script: {:?}", e);
@@ -1,3 +0,0 @@
-pub mod cli;
-
-fn main() {}
@@ -0,0 +1,18 @@
+// src/bin/uffsd.rs
+
+use clap::Parser;
+use std::env;
+use std::ffi::OsString;
+use std::iter::once;
+use UltraFastFileSearch_library::modules::cli::cli_impl::Cli;
+use UltraFastFileSearch_library::modules::utils::initialization::{initialize_app, run_app};
+
+// `uffsd [ROOT]... [--index FILE] [--socket FILE]` runs `uffs serve`
+fn main() {
+    initialize_app();
+
+    let args = once(OsString::from("uffsd"))
+        .chain(once(OsString::from("serve")))
+        .chain(env::args_os().skip(1));
+    run_app(Cli::parse_from(args));
+}
@@ -1,10 +1,36 @@
-pub(crate) const MAX_DIRS_ALL: usize = 2_000_000;
 pub(crate) const MAX_DIRS: usize = 18_000;
-pub(crate) const MAX_FILES_ALL: usize = 10_000_000;
-pub(crate) const MAX_FILES: usize = 2_000_000;
 pub(crate) const LOG_DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
 pub(crate) const MAX_CONCURRENT_READS: usize = 100;
 pub(crate) const MAX_TEMP_FILES: usize = 50_000;
 pub const MAX_TEMP_FILES_HDD_BATCH: usize = 16;
 pub const WORKER_THREADS: usize = 22;
 pub const BLOCKING_THREADS: usize = 44;
+pub(crate) const LARGE_DRIVE_FILES: u64 = 2_000_000;
+pub(crate) const MAX_BENCHMARK_RECORDS: usize = 10;
+pub(crate) const MIN_TUNING_THREADS: usize = 2;
+pub(crate) const MIN_TUNING_CONCURRENT_READS: usize = 8;
+pub(crate) const ADAPTIVE_MIN_CONCURRENT_READS: usize = 4;
+pub(crate) const ADAPTIVE_MAX_CONCURRENT_READS: usize = 1_024;
+pub(crate) const ADAPTIVE_WINDOW_READS: usize = 32;
+pub(crate) const ADAPTIVE_LATENCY_TOLERANCE: f64 = 2.0;
+pub(crate) const ADAPTIVE_DECREASE_FACTOR: f64 = 0.5;
+pub(crate) const ARENA_INITIAL_ENTRIES: usize = 65_536;
+pub(crate) const ARENA_INITIAL_NAME_BYTES: usize = 1_048_576;
+pub(crate) const TUI_MAX_RESULTS: usize = 10_000;
+pub(crate) const EXPORT_QUEUE_LISTINGS: usize = 1_024;
+#[cfg(feature = "sqlite")]
+pub(crate) const SQLITE_BATCH_ROWS: usize = 50_000;
+#[cfg(feature = "parquet")]
+pub(crate) const PARQUET_ROW_GROUP_ROWS: usize = 1_048_576;
+#[cfg(target_os = "linux")]
+pub(crate) const WATCH_BUFFER_BYTES: usize = 65_536;
+#[cfg(target_os = "linux")]
+pub(crate) const WATCH_SETTLE_MS: u64 = 2_000;
+#[cfg(target_os = "linux")]
+pub(crate) const WATCH_MAX_DELAY_MS: u64 = 30_000;
+#[cfg(target_os = "linux")]
+pub(crate) const DAEMON_POLL_MS: u64 = 100;
+#[cfg(target_os = "linux")]
+pub(crate) const DAEMON_WRITE_TIMEOUT_MS: u64 = 5_000;
+#[cfg(unix)]
+pub(crate) const DAEMON_REQUEST_TIMEOUT_MS: u64 = 30_000;
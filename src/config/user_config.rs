@@ -0,0 +1,56 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::modules::errors::UFFSError;
+use crate::modules::ignore_filter::IgnoreOptions;
+use crate::modules::utils::get_config_dir;
+
+pub(crate) const USER_CONFIG_FILE_NAME: &str = "uffs.toml";
+
+// Settings read from `uffs.toml`, e.g.
+//
+// [readers]
+// default = "all_at_once"
+// adaptive_concurrency = true
+//
+// [readers.drives]
+// "D:\\" = "sequential"
+//
+// [ignore]
+// ignore_files = true
+// exclude = ["node_modules/"]
+#[derive(Debug, Default, Deserialize)]
+pub struct UserConfig {
+    #[serde(default)]
+    pub readers: ReaderConfig,
+    #[serde(default)]
+    pub ignore: IgnoreOptions,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ReaderConfig {
+    pub default: Option<String>,
+    #[serde(default)]
+    pub adaptive_concurrency: bool,
+    #[serde(default)]
+    pub drives: HashMap<String, String>,
+}
+
+impl UserConfig {
+    pub(crate) fn default_path() -> Result<PathBuf, UFFSError> {
+        Ok(get_config_dir()?.join(USER_CONFIG_FILE_NAME))
+    }
+
+    /// Loads the configuration from `path`, a missing file yields the default configuration.
+    pub(crate) fn load(path: &Path) -> Result<UserConfig, UFFSError> {
+        if !path.exists() {
+            return Ok(UserConfig::default());
+        }
+
+        let content = fs::read_to_string(path)?;
+        toml::from_str(&content)
+            .map_err(|e| UFFSError::ConfigError(format!("{}: {}", path.display(), e)))
+    }
+}
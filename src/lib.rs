@@ -1,7 +1,28 @@
+//! Scans directory trees into compact in-memory indexes.
+//!
+//! The items re-exported here are the public API of the library and follow semantic
+//! versioning. The `config` and `modules` trees are the internals of the `uffs` binaries and may
+//! change in any release.
+//!
+//! ```no_run
+//! use UltraFastFileSearch_library::Scanner;
+//!
+//! let result = Scanner::new().root("/home").name_contains("report").scan()?;
+//! println!("{} files, {} directories", result.num_files(), result.num_dirs());
+//! # Ok::<(), UltraFastFileSearch_library::ScanError>(())
+//! ```
+
+// The library name predates the binaries and is kept for the existing users
+#![allow(non_snake_case)]
+
+#[doc(hidden)]
 pub mod config;
+#[doc(hidden)]
 pub mod modules;
 
-// Re-export only the necessary functions or types to be exposed publicly
-// pub use crate::modules::directory_reader::;
-// pub use crate::modules::directory_reader::read_directory;
-// pub use crate::modules::utils::print_directory_tree; // Re-export the function
+pub use crate::modules::errors::ScanError;
+pub use crate::modules::path_arena::path_arena_impl::{EntryId, EntrySize, PathArena};
+pub use crate::modules::scanner::{
+    Entry, EntryMetadata, MetadataMask, ReadError, ScanResult, ScanSummary, Scanner,
+    VolumeScanResult,
+};
@@ -1,130 +1,20 @@
-use std::error::Error;
-use std::future::Future;
-use std::io;
-use std::path::Path;
-use std::pin::Pin;
-use std::process::exit;
-use std::sync::Arc;
-use std::time::Duration;
-use tokio::runtime::Builder;
-use tracing::info;
-use UltraFastFileSearch_library::config::constants::{
-    BLOCKING_THREADS, MAX_TEMP_FILES_HDD_BATCH, WORKER_THREADS,
-};
-use UltraFastFileSearch_library::modules::directory_reader::directory_reader_impl::ReadDirectories1;
-use UltraFastFileSearch_library::modules::directory_reader::directory_reader_impl::ReadDirectories2;
-use UltraFastFileSearch_library::modules::directory_reader::directory_reader_impl::ReadDirectories3;
-use UltraFastFileSearch_library::modules::directory_reader::directory_reader_impl::ReadDirectories4;
-use UltraFastFileSearch_library::modules::disk_reader::disk_reader_impl::init_drives;
-use UltraFastFileSearch_library::modules::disk_reader::disk_reader_impl::process_all_disks;
-use UltraFastFileSearch_library::modules::logger::logger_impl::init_logger;
-use UltraFastFileSearch_library::modules::utils::temp_files_dirs_impl::{
-    create_temp_dir_with_files_hdd, UffsTempDir,
-};
-
-use UltraFastFileSearch_library::modules::utils::temp_files_dirs_impl::{
-    create_temp_dir_with_files_hdd_tokio, create_temp_dir_with_files_ssd,
-};
-use UltraFastFileSearch_library::modules::utils::utils_impl::measure_time_normal;
-use UltraFastFileSearch_library::modules::utils::utils_impl::measure_time_tokio;
-use UltraFastFileSearch_library::modules::utils::utils_impl::optimize_parameter;
-use UltraFastFileSearch_library::modules::utils::utils_impl::read_directory_all_at_once;
-use UltraFastFileSearch_library::modules::utils::utils_impl::{
-    count_disk_entries_all_at_once, count_files_in_dir, format_duration, generate_fibonacci,
-    measure_time_normal_bench, measure_time_tokio_bench, optimize_parameter_tokio,
-};
-
-use colored::Colorize;
-use log::error;
-use once_cell::sync::Lazy;
-use std::ffi::OsString;
+use clap::Parser;
 use std::io::Result as IoResult;
-use std::iter::once;
-use std::os::windows::ffi::OsStringExt;
-use std::os::windows::prelude::OsStrExt;
-use tempfile::TempDir;
-use tokio::sync::RwLock;
-use tokio::time::Instant;
-use tracing::warn;
-use winapi::shared::minwindef::DWORD;
-use winapi::shared::winerror::{ERROR_ACCESS_DENIED, ERROR_NO_MORE_FILES};
-use winapi::um::errhandlingapi::GetLastError;
-use winapi::um::fileapi::{FindClose, FindFirstFileW, FindNextFileW};
-use winapi::um::handleapi::INVALID_HANDLE_VALUE;
-use winapi::um::minwinbase::WIN32_FIND_DATAW;
-use winapi::um::winnt::FILE_ATTRIBUTE_DIRECTORY;
-use UltraFastFileSearch_library::modules::utils::tree_printer::print_directory_tree;
+use tracing::info;
 
-use UltraFastFileSearch_library::modules::utils::initialization::{
-    initialize_app, run_app, set_threads_count,
-};
-use UltraFastFileSearch_library::modules::utils::utils_impl::hello;
+use UltraFastFileSearch_library::modules::cli::cli_impl::Cli;
+use UltraFastFileSearch_library::modules::utils::initialization::{initialize_app, run_app};
 
 fn main() -> IoResult<()> {
     initialize_app();
 
-    run_app();
+    run_app(Cli::parse());
 
     info!("Application finished.");
 
     Ok(())
 }
 
-fn find_best_configuration(configurations: Vec<(usize, usize)>) -> (usize, usize) {
-    let mut best_duration = Duration::MAX;
-    let mut best_config = (0, 0);
-
-    for (worker_threads, blocking_threads) in configurations {
-        let duration = run_with_configuration(worker_threads, blocking_threads);
-        println!(
-            "Configuration with {} worker threads and {} blocking threads took {:?}",
-            worker_threads,
-            blocking_threads,
-            format_duration(duration)
-        );
-
-        if duration < best_duration {
-            best_duration = duration;
-            best_config = (worker_threads, blocking_threads);
-        }
-    }
-
-    best_config
-}
-
-fn run_with_configuration(worker_threads: usize, blocking_threads: usize) -> Duration {
-    let mut time_used = Default::default();
-    // Configure Tokio runtime with optimized settings for high-performance system
-    let runtime = Builder::new_multi_thread()
-        .worker_threads(worker_threads)
-        .max_blocking_threads(blocking_threads)
-        .enable_all()
-        .build()
-        .expect("Failed to create Tokio runtime");
-
-    // Run the async function using the configured runtime
-    runtime.block_on(async {
-        let start = Instant::now();
-
-        let separator1 = "=".repeat(50).green().to_string();
-        let separator2 = "-".repeat(50).red().to_string();
-
-        println!("{}", separator1);
-        println!("\nReadDirectories4\n");
-        println!("{}", separator2);
-
-        let directory_reader = Arc::new(ReadDirectories4);
-
-        process_all_disks(directory_reader).await;
-
-        time_used = Instant::now() - start;
-    });
-
-    info!("Application finished.");
-
-    time_used
-}
-
 // fn main_opti(WORKER_THREADS: usize, BLOCKING_THREADS: usize) -> Result<(), Box<dyn Error + Send + Sync>> {
 //     // Configure Tokio runtime with optimized settings for high-performance system
 //     let runtime = Builder::new_multi_thread()
@@ -1,35 +1,403 @@
-// use crate::directory_reader::{DirectoryReader, ReadDirectories1, ReadDirectories2};
-// use crate::disk_reader::DriveInfo;
-// use crate::errors::UFFSError;
-// use std::sync::Arc;
-// 
-// pub fn select_algorithm(drive_info: &mut DriveInfo) -> Result<Arc<dyn DirectoryReader>, UFFSError> {
-//     if drive_info.num_files == 0 && drive_info.num_dirs == 0 {
-//         return Err(UFFSError::EmptyDriveInfo);
-//     }
-// 
-//     let directory_reader_1 = Arc::new(ReadDirectories1);
-//     let directory_reader_2 = Arc::new(ReadDirectories2);
-//     // let directory_reader_3 = Arc::new(ReadDirectories3);
-// 
-//     let num_files = drive_info.num_files;
-//     let num_dirs = drive_info.num_dirs;
-//     let drive_type = &drive_info.drive_type; // "SSD" or "HDD"
-// 
-//     if drive_type == "SSD" {
-//         if num_files < 2_000_000 {
-//             return Ok(directory_reader_1);
-//         } else {
-//             return Ok(directory_reader_2);
-//         }
-//     } else if drive_type == "HDD" {
-//         return Ok(directory_reader_2);
-//     }
-// 
-//     // Default case, just in case
-//     return Ok(directory_reader_1);
-// }
-// 
-// // // Process with ReadDirectories2
-// // let directory_reader_3 = Arc::new(ReadDirectories3);
-// // process_all_disks(directory_reader_3).await;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use tracing::info;
+
+use crate::config::constants::LARGE_DRIVE_FILES;
+use crate::config::UserConfig;
+use crate::modules::algo_selector::BenchmarkHistory;
+use crate::modules::directory_reader::{
+    DirectoryReader, ReadDirectories1, ReadDirectories2, ReadDirectories3, ReadDirectories4,
+};
+use crate::modules::disk_reader::{DriveInfo, DriveType};
+use crate::modules::errors::UFFSError;
+use crate::modules::file_system::{OsFileSystem, SharedFileSystem};
+use crate::modules::ignore_filter::IgnoreFilter;
+
+pub(crate) type SharedDirectoryReader = Arc<dyn DirectoryReader + Send + Sync + 'static>;
+
+pub(crate) const DEFAULT_READER: &str = "all_at_once";
+
+/// How every reader of a `ReaderRegistry` reads.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ReaderOptions {
+    // Of the semaphore reader, see `ReadDirectories1::with_adaptive_concurrency`
+    pub(crate) adaptive_concurrency: bool,
+    // Keep the sizes of the entries, for the usage reports
+    pub(crate) sizes: bool,
+    // The entries left out, with everything below them
+    pub(crate) ignore: IgnoreFilter,
+}
+
+/// All available `DirectoryReader` implementations, looked up by their name.
+pub(crate) struct ReaderRegistry {
+    readers: Vec<SharedDirectoryReader>,
+    // What the readers read from, for the code that reads single listings
+    fs: SharedFileSystem,
+}
+
+impl Default for ReaderRegistry {
+    fn default() -> Self {
+        Self::new(ReaderOptions::default())
+    }
+}
+
+impl ReaderRegistry {
+    pub(crate) fn new(options: ReaderOptions) -> Self {
+        let fs = match options.sizes {
+            true => OsFileSystem::listing_sizes(),
+            false => OsFileSystem::shared(),
+        };
+
+        let mut registry = ReaderRegistry {
+            readers: vec![],
+            fs: fs.clone(),
+        };
+        registry.register(Arc::new(
+            ReadDirectories1::default()
+                .with_file_system(fs.clone())
+                .with_adaptive_concurrency(options.adaptive_concurrency)
+                .with_ignore(options.ignore.clone()),
+        ));
+        registry.register(Arc::new(
+            ReadDirectories2::default()
+                .with_file_system(fs.clone())
+                .with_ignore(options.ignore.clone()),
+        ));
+        registry.register(Arc::new(
+            ReadDirectories3::default()
+                .with_sizes(options.sizes)
+                .with_ignore(options.ignore.clone()),
+        ));
+        registry.register(Arc::new(
+            ReadDirectories4::default()
+                .with_file_system(fs)
+                .with_ignore(options.ignore),
+        ));
+        registry
+    }
+
+    pub(crate) fn register(&mut self, reader: SharedDirectoryReader) {
+        // A reader registered under an existing name replaces the old one
+        self.readers.retain(|r| r.name() != reader.name());
+        self.readers.push(reader);
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Result<SharedDirectoryReader, UFFSError> {
+        self.readers
+            .iter()
+            .find(|r| r.name() == name)
+            .cloned()
+            .ok_or_else(|| UFFSError::UnknownReader {
+                name: name.to_string(),
+                available: self.names().join(", "),
+            })
+    }
+
+    pub(crate) fn names(&self) -> Vec<&'static str> {
+        self.readers.iter().map(|r| r.name()).collect()
+    }
+
+    pub(crate) fn file_system(&self) -> SharedFileSystem {
+        Arc::clone(&self.fs)
+    }
+}
+
+/// A `--reader` argument: `NAME` applies to all drives, `ROOT=NAME` to a single drive.
+#[derive(Debug, Clone)]
+pub struct ReaderOverride {
+    pub root_path: Option<String>,
+    pub reader: String,
+}
+
+impl FromStr for ReaderOverride {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (root_path, reader) = match s.rsplit_once('=') {
+            Some((root, reader)) => (Some(root.trim().to_string()), reader.trim()),
+            None => (None, s.trim()),
+        };
+
+        if reader.is_empty() {
+            return Err(format!("missing reader name in '{}'", s));
+        }
+
+        Ok(ReaderOverride {
+            root_path,
+            reader: reader.to_string(),
+        })
+    }
+}
+
+/// Reader choices forced by the user, the CLI takes precedence over the config file: a
+/// `--reader NAME` for all drives also replaces the drives of the config file, a
+/// `--reader ROOT=NAME` wins for its drive.
+#[derive(Debug, Default)]
+pub(crate) struct ReaderOverrides {
+    default: Option<String>,
+    per_root: HashMap<String, String>,
+}
+
+impl ReaderOverrides {
+    pub(crate) fn new(config: &UserConfig, cli_overrides: &[ReaderOverride]) -> Self {
+        let mut overrides = ReaderOverrides {
+            default: config.readers.default.clone(),
+            per_root: config
+                .readers
+                .drives
+                .iter()
+                .map(|(root, reader)| (normalize_root(root), reader.clone()))
+                .collect(),
+        };
+
+        if let Some(cli_default) = cli_overrides.iter().rfind(|o| o.root_path.is_none()) {
+            overrides.default = Some(cli_default.reader.clone());
+            overrides.per_root.clear();
+        }
+        for cli_override in cli_overrides {
+            if let Some(root) = &cli_override.root_path {
+                overrides
+                    .per_root
+                    .insert(normalize_root(root), cli_override.reader.clone());
+            }
+        }
+
+        overrides
+    }
+
+    pub(crate) fn reader_for(&self, root_path: &str) -> Option<&str> {
+        self.per_root
+            .get(&normalize_root(root_path))
+            .or(self.default.as_ref())
+            .map(String::as_str)
+    }
+
+    // Fail early on typos instead of when the drive comes up
+    pub(crate) fn validate(&self, registry: &ReaderRegistry) -> Result<(), UFFSError> {
+        for name in self.default.iter().chain(self.per_root.values()) {
+            registry.get(name)?;
+        }
+        Ok(())
+    }
+}
+
+// Root paths are compared without trailing separators, and case-insensitively on Windows
+pub(crate) fn normalize_root(root_path: &str) -> String {
+    let trimmed = root_path.trim_end_matches(['\\', '/']);
+    let trimmed = if trimmed.is_empty() {
+        root_path
+    } else {
+        trimmed
+    };
+
+    if cfg!(target_os = "windows") {
+        trimmed.to_uppercase()
+    } else {
+        trimmed.to_string()
+    }
+}
+
+pub(crate) fn same_root(a: &str, b: &str) -> bool {
+    normalize_root(a) == normalize_root(b)
+}
+
+/// Picks the `DirectoryReader` for a drive: a user override wins, then the fastest reader
+/// from the benchmark history, and finally heuristics on the drive properties.
+pub(crate) fn select_algorithm(
+    drive_info: &DriveInfo,
+    registry: &ReaderRegistry,
+    history: &BenchmarkHistory,
+    overrides: &ReaderOverrides,
+) -> Result<SharedDirectoryReader, UFFSError> {
+    let root_path = drive_info.root_path.to_string_lossy();
+
+    if let Some(name) = overrides.reader_for(&root_path) {
+        info!("{}: using reader '{}' (override)", root_path, name);
+        return registry.get(name);
+    }
+
+    if let Some(name) = history.fastest_reader_for(&root_path) {
+        if let Ok(reader) = registry.get(name) {
+            info!("{}: using reader '{}' (benchmark history)", root_path, name);
+            return Ok(reader);
+        }
+    }
+
+    let name = heuristic_reader_name(drive_info);
+    info!("{}: using reader '{}' (heuristics)", root_path, name);
+    registry.get(name)
+}
+
+fn heuristic_reader_name(drive_info: &DriveInfo) -> &'static str {
+    let file_system = drive_info.file_system.to_lowercase();
+
+    // Network and cloud mounts answer slowly, parallel requests only pile up
+    let is_remote = ["nfs", "cifs", "smb", "fuse", "9p", "webdav"]
+        .iter()
+        .any(|fs| file_system.contains(fs));
+
+    // FAT volumes have no directory index, every lookup scans the parent
+    let is_fat = file_system.contains("fat");
+
+    match drive_info.drive_type {
+        _ if is_remote || is_fat => "sequential",
+        // Concurrent reads make the heads of a spinning disk thrash
+        DriveType::Hdd => "sequential",
+        DriveType::Ssd
+            if drive_info.num_files >= LARGE_DRIVE_FILES || drive_info.size_gb >= 4096.0 =>
+        {
+            "jwalk"
+        }
+        DriveType::Ssd => DEFAULT_READER,
+        DriveType::Unknown => "sequential",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::{Path, PathBuf};
+
+    fn drive(root: &str, drive_type: DriveType, file_system: &str, num_files: u64) -> DriveInfo {
+        DriveInfo::new(
+            PathBuf::from(root),
+            drive_type,
+            vec!["sda".to_string()],
+            file_system.to_string(),
+            512.0,
+            num_files,
+            num_files / 10,
+            0.0,
+            Arc::new(ReadDirectories4::default()),
+        )
+    }
+
+    fn select(
+        drive: &DriveInfo,
+        history: &BenchmarkHistory,
+        overrides: &ReaderOverrides,
+    ) -> &'static str {
+        select_algorithm(drive, &ReaderRegistry::default(), history, overrides)
+            .unwrap()
+            .name()
+    }
+
+    fn overrides(arguments: &[&str]) -> ReaderOverrides {
+        let arguments: Vec<ReaderOverride> = arguments.iter().map(|a| a.parse().unwrap()).collect();
+        ReaderOverrides::new(&UserConfig::default(), &arguments)
+    }
+
+    #[test]
+    fn heuristics_follow_the_drive() {
+        let none = (BenchmarkHistory::default(), ReaderOverrides::default());
+        let pick = |drive: DriveInfo| select(&drive, &none.0, &none.1);
+
+        assert_eq!(
+            pick(drive("/", DriveType::Ssd, "ext4", 1_000)),
+            DEFAULT_READER
+        );
+        assert_eq!(
+            pick(drive("/", DriveType::Ssd, "ext4", LARGE_DRIVE_FILES)),
+            "jwalk"
+        );
+        assert_eq!(
+            pick(drive("/", DriveType::Hdd, "ext4", 1_000)),
+            "sequential"
+        );
+        assert_eq!(
+            pick(drive("/", DriveType::Ssd, "vfat", 1_000)),
+            "sequential"
+        );
+        assert_eq!(
+            pick(drive("/", DriveType::Ssd, "nfs4", 1_000)),
+            "sequential"
+        );
+        assert_eq!(
+            pick(drive("/", DriveType::Unknown, "ext4", 1_000)),
+            "sequential"
+        );
+    }
+
+    #[test]
+    fn overrides_win_per_root_then_for_all() {
+        let history = BenchmarkHistory::default();
+        let overrides = overrides(&["semaphore", "/data/=jwalk"]);
+
+        let data = drive("/data", DriveType::Hdd, "ext4", 1_000);
+        let home = drive("/home", DriveType::Hdd, "ext4", 1_000);
+        assert_eq!(select(&data, &history, &overrides), "jwalk");
+        assert_eq!(select(&home, &history, &overrides), "semaphore");
+    }
+
+    #[test]
+    fn the_command_line_wins_over_the_config_file() {
+        let history = BenchmarkHistory::default();
+        let mut config = UserConfig::default();
+        config.readers.default = Some("sequential".to_string());
+        config
+            .readers
+            .drives
+            .insert("/data".to_string(), "jwalk".to_string());
+        let data = drive("/data", DriveType::Hdd, "ext4", 1_000);
+        let home = drive("/home", DriveType::Hdd, "ext4", 1_000);
+
+        let with = |arguments: &[&str]| {
+            let arguments: Vec<ReaderOverride> =
+                arguments.iter().map(|a| a.parse().unwrap()).collect();
+            ReaderOverrides::new(&config, &arguments)
+        };
+
+        let overrides = with(&[]);
+        assert_eq!(select(&data, &history, &overrides), "jwalk");
+        assert_eq!(select(&home, &history, &overrides), "sequential");
+
+        // A reader for all drives replaces the drives of the config file too
+        let overrides = with(&["semaphore"]);
+        assert_eq!(select(&data, &history, &overrides), "semaphore");
+        assert_eq!(select(&home, &history, &overrides), "semaphore");
+
+        let overrides = with(&["/home=all_at_once"]);
+        assert_eq!(select(&data, &history, &overrides), "jwalk");
+        assert_eq!(select(&home, &history, &overrides), "all_at_once");
+
+        let overrides = with(&["/data=all_at_once", "semaphore"]);
+        assert_eq!(select(&data, &history, &overrides), "all_at_once");
+        assert_eq!(select(&home, &history, &overrides), "semaphore");
+    }
+
+    #[test]
+    fn unknown_overrides_are_errors() {
+        let overrides = overrides(&["fastest"]);
+        let drive = drive("/", DriveType::Ssd, "ext4", 1_000);
+
+        let registry = ReaderRegistry::default();
+        assert!(overrides.validate(&registry).is_err());
+        let selected =
+            select_algorithm(&drive, &registry, &BenchmarkHistory::default(), &overrides);
+        assert!(matches!(selected, Err(UFFSError::UnknownReader { .. })));
+    }
+
+    #[test]
+    fn history_takes_over_once_two_readers_are_measured() {
+        let drive = drive("/", DriveType::Hdd, "ext4", 1_000);
+        let overrides = ReaderOverrides::default();
+        let mut history = BenchmarkHistory::default();
+
+        history.record(Path::new("/"), "jwalk", 1_000, 100, 1.0);
+        assert_eq!(select(&drive, &history, &overrides), "sequential");
+
+        history.record(Path::new("/"), "semaphore", 1_000, 100, 0.5);
+        assert_eq!(select(&drive, &history, &overrides), "semaphore");
+
+        // Measurements of another volume don't count
+        history.record(Path::new("/data"), "jwalk", 1_000, 100, 0.1);
+        assert_eq!(select(&drive, &history, &overrides), "semaphore");
+
+        // Overrides still win over the history
+        assert_eq!(
+            select(&drive, &history, &self::overrides(&["jwalk"])),
+            "jwalk"
+        );
+    }
+}
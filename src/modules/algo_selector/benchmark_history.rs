@@ -0,0 +1,136 @@
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+use crate::config::constants::{LOG_DATE_FORMAT, MAX_BENCHMARK_RECORDS};
+use crate::modules::algo_selector::algo_selector_impl::same_root;
+use crate::modules::errors::UFFSError;
+use crate::modules::utils::get_config_dir;
+
+pub(crate) const BENCHMARK_HISTORY_FILE_NAME: &str = "benchmark_history.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct BenchmarkRecord {
+    pub(crate) root_path: String,
+    pub(crate) reader: String,
+    pub(crate) num_files: u64,
+    pub(crate) num_dirs: u64,
+    pub(crate) seconds: f64,
+    pub(crate) timestamp: String,
+}
+
+impl BenchmarkRecord {
+    // Entries per second, the file count changes between runs so raw durations don't compare
+    pub(crate) fn throughput(&self) -> f64 {
+        if self.seconds <= 0.0 {
+            return 0.0;
+        }
+        (self.num_files + self.num_dirs) as f64 / self.seconds
+    }
+}
+
+/// Timings of previous scans, per root path and reader, persisted as JSON in the config directory.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct BenchmarkHistory {
+    records: Vec<BenchmarkRecord>,
+}
+
+impl BenchmarkHistory {
+    pub(crate) fn default_path() -> Result<PathBuf, UFFSError> {
+        Ok(get_config_dir()?.join(BENCHMARK_HISTORY_FILE_NAME))
+    }
+
+    /// Loads the history from `path`. A missing or unreadable file starts a fresh history.
+    pub(crate) fn load(path: &Path) -> BenchmarkHistory {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(_) => return BenchmarkHistory::default(),
+        };
+
+        serde_json::from_str(&content).unwrap_or_else(|e| {
+            warn!("Ignoring unreadable benchmark history {:?}: {}", path, e);
+            BenchmarkHistory::default()
+        })
+    }
+
+    pub(crate) fn save(&self, path: &Path) -> Result<(), UFFSError> {
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| UFFSError::ConfigError(e.to_string()))?;
+        fs::write(path, content)?;
+        Ok(())
+    }
+
+    pub(crate) fn record(
+        &mut self,
+        root_path: &Path,
+        reader: &str,
+        num_files: u64,
+        num_dirs: u64,
+        seconds: f64,
+    ) {
+        let root_path = root_path.to_string_lossy().into_owned();
+
+        self.records.push(BenchmarkRecord {
+            root_path: root_path.clone(),
+            reader: reader.to_string(),
+            num_files,
+            num_dirs,
+            seconds,
+            timestamp: Local::now().format(LOG_DATE_FORMAT).to_string(),
+        });
+
+        // Keep only the most recent records for each root / reader pair
+        let same_pair =
+            |r: &BenchmarkRecord| same_root(&r.root_path, &root_path) && r.reader == reader;
+        let count = self.records.iter().filter(|r| same_pair(r)).count();
+        if count > MAX_BENCHMARK_RECORDS {
+            let mut to_drop = count - MAX_BENCHMARK_RECORDS;
+            self.records.retain(|r| {
+                if to_drop > 0 && same_pair(r) {
+                    to_drop -= 1;
+                    false
+                } else {
+                    true
+                }
+            });
+        }
+    }
+
+    pub(crate) fn latest_for(&self, root_path: &str) -> Option<&BenchmarkRecord> {
+        self.records
+            .iter()
+            .rev()
+            .find(|r| same_root(&r.root_path, root_path))
+    }
+
+    /// The reader with the best average throughput on `root_path`. Returns `None` unless at least
+    /// two readers have been measured, a single measurement has nothing to compare against.
+    pub(crate) fn fastest_reader_for(&self, root_path: &str) -> Option<&str> {
+        let mut throughputs: HashMap<&str, (f64, usize)> = HashMap::new();
+
+        for record in self
+            .records
+            .iter()
+            .filter(|r| same_root(&r.root_path, root_path))
+        {
+            let entry = throughputs
+                .entry(record.reader.as_str())
+                .or_insert((0.0, 0));
+            entry.0 += record.throughput();
+            entry.1 += 1;
+        }
+
+        if throughputs.len() < 2 {
+            return None;
+        }
+
+        throughputs
+            .into_iter()
+            .map(|(reader, (sum, count))| (reader, sum / count as f64))
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(reader, _)| reader)
+    }
+}
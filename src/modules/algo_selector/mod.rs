@@ -1,3 +1,11 @@
-// pub(crate) mod algo_selector_impl;
-//
-// pub(crate) use algo_selector_impl::select_algorithm;
+pub mod algo_selector_impl;
+mod benchmark_history;
+
+pub(crate) use algo_selector_impl::select_algorithm;
+pub(crate) use algo_selector_impl::ReaderOptions;
+pub(crate) use algo_selector_impl::ReaderOverride;
+pub(crate) use algo_selector_impl::ReaderOverrides;
+pub(crate) use algo_selector_impl::ReaderRegistry;
+pub(crate) use algo_selector_impl::SharedDirectoryReader;
+
+pub(crate) use benchmark_history::BenchmarkHistory;
@@ -0,0 +1,218 @@
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+use crate::modules::algo_selector::ReaderOverride;
+use crate::modules::output::{OutputFormat, Quoting};
+
+#[derive(Debug, Parser)]
+#[command(name = "uffs", version, about = "Ultra Fast File Search")]
+pub struct Cli {
+    /// Directory reader to use, for all drives (NAME) or for a single drive (ROOT=NAME), over the
+    /// readers of the config file
+    #[arg(long = "reader", value_name = "[ROOT=]NAME", global = true)]
+    pub reader_overrides: Vec<ReaderOverride>,
+
+    /// Adapt the in-flight reads of the semaphore reader to the observed latency and throughput
+    #[arg(long, global = true)]
+    pub adaptive_concurrency: bool,
+
+    /// Honor the .gitignore, .ignore and .uffsignore files, the ignored entries aren't read
+    #[arg(long, global = true)]
+    pub ignore_files: bool,
+
+    /// Leave out the entries matching PATTERN, written as in a .gitignore (e.g. node_modules/)
+    #[arg(long, value_name = "PATTERN", global = true)]
+    pub exclude: Vec<String>,
+
+    /// Leave out the names starting with a dot
+    #[arg(long, global = true)]
+    pub skip_hidden: bool,
+
+    /// Configuration file, defaults to uffs.toml in the UFFS config directory
+    #[arg(long, value_name = "FILE", global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Output format of the results, colors are only used for a table on a terminal
+    #[arg(long, value_enum, default_value_t = OutputFormat::Table, global = true)]
+    pub format: OutputFormat,
+
+    /// How the table and CSV output write names that aren't plain text
+    #[arg(long, value_enum, default_value_t = Quoting::Escape, global = true)]
+    pub quoting: Quoting,
+
+    /// List every file and directory found instead of the per-volume summary
+    #[arg(long, global = true)]
+    pub list: bool,
+
+    /// Also write the entries to FILE as an Everything file list (EFU)
+    #[arg(long, value_name = "FILE", global = true)]
+    pub efu: Option<PathBuf>,
+
+    /// Also write the entries to FILE as an mlocate database, for `locate -d FILE`
+    #[arg(long, value_name = "FILE", global = true)]
+    pub mlocate: Option<PathBuf>,
+
+    /// Also write the entries to FILE as a plocate database (needs `plocate-build`)
+    #[arg(long, value_name = "FILE", global = true)]
+    pub plocate: Option<PathBuf>,
+
+    /// Also write the entries to FILE as Parquet, for DuckDB, Polars and the like
+    #[arg(long, value_name = "FILE", global = true)]
+    pub parquet: Option<PathBuf>,
+
+    /// Also add the scan run with its volumes and entries to the SQLite database FILE
+    #[arg(long, value_name = "FILE", global = true)]
+    pub sqlite: Option<PathBuf>,
+
+    /// Socket of the index daemon, defaults to uffsd.sock in the UFFS config directory
+    #[arg(long, value_name = "FILE", global = true)]
+    pub socket: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Default, Subcommand)]
+pub enum Command {
+    /// Scan all drives (default), or read the index of the running daemon
+    #[default]
+    Scan,
+    /// List the available directory readers
+    Readers,
+    /// Find and save the fastest thread counts and concurrent reads per volume
+    Tune {
+        /// Volumes to tune, all mounted volumes if none are given
+        #[arg(value_name = "ROOT")]
+        roots: Vec<PathBuf>,
+    },
+    /// Search an Everything file list (EFU) instead of the drives
+    Efu {
+        /// File list exported by Everything, the C++ UFFS or `--efu`
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+        /// Only entries whose name contains PATTERN, ignoring case
+        #[arg(value_name = "PATTERN")]
+        pattern: Option<String>,
+    },
+    /// Show the directories and files taking the most space, or the usage per directory, of the
+    /// drives or of the index of the running daemon
+    Usage {
+        /// Volumes to scan, all mounted volumes if none are given
+        #[arg(value_name = "ROOT")]
+        roots: Vec<PathBuf>,
+        /// Number of directories and files to show per volume
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+        /// Show every directory down to DEPTH instead, like `du -d DEPTH`
+        #[arg(short = 'd', long, value_name = "DEPTH")]
+        depth: Option<usize>,
+    },
+    /// Browse and search the entries in a full-screen terminal UI, those of the running daemon,
+    /// which keeps no sizes, if one is running
+    Tui {
+        /// Index of the last scan, defaults to index.db in the UFFS config directory
+        #[arg(long, value_name = "FILE")]
+        index: Option<PathBuf>,
+        /// Scan the drives again instead of opening the index of the last scan
+        #[arg(long)]
+        rescan: bool,
+    },
+    /// Scan, then keep the index up to date with the changes to the volumes (Linux)
+    Watch {
+        /// Volumes to watch, all mounted volumes if none are given
+        #[arg(value_name = "ROOT")]
+        roots: Vec<PathBuf>,
+        /// Index to keep up to date, defaults to index.db in the UFFS config directory
+        #[arg(long, value_name = "FILE")]
+        index: Option<PathBuf>,
+    },
+    /// Update the index of the last scan, reading only the directories changed since. The
+    /// ignore options apply as they do to a scan.
+    Refresh {
+        /// Index to update, defaults to index.db in the UFFS config directory
+        #[arg(long, value_name = "FILE")]
+        index: Option<PathBuf>,
+    },
+    /// Keep the index up to date like `watch` and answer queries on a socket (Linux), as `uffsd`
+    Serve {
+        /// Volumes to index, all mounted volumes if none are given
+        #[arg(value_name = "ROOT")]
+        roots: Vec<PathBuf>,
+        /// Index to keep up to date, defaults to index.db in the UFFS config directory
+        #[arg(long, value_name = "FILE")]
+        index: Option<PathBuf>,
+    },
+    /// Search the index of the running daemon, or scan the drives if none is running
+    Search {
+        /// Only entries whose name contains PATTERN, ignoring case
+        #[arg(value_name = "PATTERN")]
+        pattern: String,
+        /// Show at most LIMIT entries
+        #[arg(long, value_name = "LIMIT")]
+        limit: Option<usize>,
+    },
+    /// Query or control the running daemon, answers are printed as JSON
+    Daemon {
+        #[command(subcommand)]
+        command: DaemonCommand,
+    },
+    /// Search an mlocate database, e.g. /var/lib/mlocate/mlocate.db, instead of the drives
+    Locate {
+        /// Database written by `updatedb` or `--mlocate`
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+        /// Only entries whose name contains PATTERN, ignoring case
+        #[arg(value_name = "PATTERN")]
+        pattern: Option<String>,
+    },
+    /// Create a synthetic directory tree for benchmarks and tests, the same seed gives the same tree
+    GenTree {
+        /// Directory to create the tree in, must be missing or empty
+        #[arg(value_name = "DIR")]
+        dir: PathBuf,
+        /// TOML file with the layout of the tree, the default layout otherwise
+        #[arg(long, value_name = "FILE")]
+        spec: Option<PathBuf>,
+        /// Seed of the random choices, overrides the seed of the spec
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    /// Read the same roots with several readers and the counting path, and report where they differ
+    Verify {
+        /// Directories to verify, they should not change in the meantime
+        #[arg(value_name = "ROOT", required_unless_present = "generated")]
+        roots: Vec<PathBuf>,
+        /// Readers to compare, all of them if none are given. The generated trees can be read by
+        /// the readers that read from memory, all but jwalk.
+        #[arg(long, value_name = "NAME", value_delimiter = ',')]
+        readers: Vec<String>,
+        /// Check the readers against COUNT trees generated in memory instead, with the seeds
+        /// 0 to COUNT - 1
+        #[arg(long, value_name = "COUNT", conflicts_with = "roots")]
+        generated: Option<u64>,
+        /// TOML file with the layout of the generated trees, see `gen-tree`
+        #[arg(long, value_name = "FILE", requires = "generated")]
+        spec: Option<PathBuf>,
+        /// Share of the reads of the generated trees that fail on purpose, from 0 to 1
+        #[arg(
+            long,
+            value_name = "SHARE",
+            default_value_t = 0.0,
+            requires = "generated"
+        )]
+        faults: f64,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum DaemonCommand {
+    /// Show the volumes in the index and their number of entries
+    Stats,
+    /// Read the directories below PATH again, or all of them
+    Rescan {
+        #[arg(value_name = "PATH")]
+        path: Option<PathBuf>,
+    },
+    /// Print the changes to the index as they happen, one JSON object per line
+    Subscribe,
+}
@@ -0,0 +1,5 @@
+pub mod cli_impl;
+
+pub(crate) use cli_impl::Cli;
+pub(crate) use cli_impl::Command;
+pub(crate) use cli_impl::DaemonCommand;
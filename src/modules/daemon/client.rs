@@ -0,0 +1,289 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::config::constants::DAEMON_REQUEST_TIMEOUT_MS;
+use crate::modules::algo_selector::algo_selector_impl::same_root;
+use crate::modules::cli::DaemonCommand;
+use crate::modules::daemon::protocol::{
+    FlushResult, Notification, Request, RescanParams, RescanResult, Response, SearchParams,
+    SearchResult, Stats, JSONRPC_VERSION,
+};
+use crate::modules::directory_reader::ReaderStats;
+use crate::modules::errors::UFFSError;
+use crate::modules::locate::LocateDb;
+use crate::modules::output::{write_entries, OutputOptions};
+use crate::modules::path_arena::{EntryId, PathArena};
+use crate::modules::scanner::{ScanResult, VolumeScanResult};
+use crate::modules::utils::get_config_dir;
+
+pub(crate) const SOCKET_FILE_NAME: &str = "uffsd.sock";
+
+// Reported as the reader of the volumes served from the index of the daemon
+const DAEMON_READER: &str = "daemon";
+
+/// The socket `uffsd` listens on unless told otherwise.
+pub(crate) fn default_socket_path() -> Result<PathBuf, UFFSError> {
+    Ok(get_config_dir()?.join(SOCKET_FILE_NAME))
+}
+
+fn socket_path(socket: Option<&Path>) -> Result<PathBuf, UFFSError> {
+    socket
+        .map(|socket| Ok(socket.to_path_buf()))
+        .unwrap_or_else(default_socket_path)
+}
+
+fn daemon_error(message: impl ToString) -> UFFSError {
+    UFFSError::Daemon {
+        message: message.to_string(),
+    }
+}
+
+/// A connection to a running `uffsd`.
+pub(crate) struct DaemonClient {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+    next_id: u64,
+}
+
+impl DaemonClient {
+    /// Connects to the daemon listening on `socket`. None when no daemon is running.
+    pub(crate) fn connect(socket: &Path) -> Option<Self> {
+        let stream = UnixStream::connect(socket).ok()?;
+        stream
+            .set_read_timeout(Some(Duration::from_millis(DAEMON_REQUEST_TIMEOUT_MS)))
+            .ok()?;
+
+        Some(Self {
+            reader: BufReader::new(stream.try_clone().ok()?),
+            writer: stream,
+            next_id: 0,
+        })
+    }
+
+    fn call<P: Serialize, R: DeserializeOwned>(
+        &mut self,
+        method: &str,
+        params: P,
+    ) -> Result<R, UFFSError> {
+        self.next_id += 1;
+        let request = Request {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id: Some(self.next_id.into()),
+            method: method.to_string(),
+            params: serde_json::to_value(params).map_err(daemon_error)?,
+        };
+        let mut line = serde_json::to_string(&request).map_err(daemon_error)?;
+        line.push('\n');
+        self.writer.write_all(line.as_bytes())?;
+
+        loop {
+            line.clear();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Err(daemon_error("the daemon closed the connection"));
+            }
+            let message: Value = serde_json::from_str(&line).map_err(daemon_error)?;
+            // A change notification sent before the response
+            if message.get("method").is_some() {
+                continue;
+            }
+
+            let response: Response = serde_json::from_value(message).map_err(daemon_error)?;
+            if let Some(error) = response.error {
+                return Err(daemon_error(error.message));
+            }
+            return serde_json::from_value(response.result.unwrap_or_default())
+                .map_err(daemon_error);
+        }
+    }
+
+    pub(crate) fn search(
+        &mut self,
+        pattern: &str,
+        limit: Option<usize>,
+    ) -> Result<SearchResult, UFFSError> {
+        let params = SearchParams {
+            pattern: pattern.to_string(),
+            limit,
+        };
+        self.call("search", params)
+    }
+
+    pub(crate) fn stats(&mut self) -> Result<Stats, UFFSError> {
+        self.call("stats", Value::Null)
+    }
+
+    pub(crate) fn rescan(&mut self, path: Option<PathBuf>) -> Result<RescanResult, UFFSError> {
+        self.call("rescan", RescanParams { path })
+    }
+
+    pub(crate) fn flush(&mut self) -> Result<FlushResult, UFFSError> {
+        self.call("flush", Value::Null)
+    }
+
+    /// Calls `on_changes` with the params of every change notification until the daemon stops
+    /// or `on_changes` fails.
+    pub(crate) fn subscribe<F>(&mut self, mut on_changes: F) -> Result<(), UFFSError>
+    where
+        F: FnMut(Value) -> Result<(), UFFSError>,
+    {
+        let _: bool = self.call("subscribe", Value::Null)?;
+        // Changes can be hours apart
+        self.reader.get_ref().set_read_timeout(None)?;
+
+        for line in (&mut self.reader).lines() {
+            let notification: Notification<Value> =
+                serde_json::from_str(&line?).map_err(daemon_error)?;
+            on_changes(notification.params)?;
+        }
+        Ok(())
+    }
+}
+
+/// Answers a search from the running daemon. False when there is none, or it failed, and the
+/// volumes have to be scanned instead.
+pub(crate) fn search_daemon(
+    socket: Option<&Path>,
+    pattern: &str,
+    limit: Option<usize>,
+    output: &OutputOptions,
+) -> bool {
+    let Some(mut client) = connect(socket) else {
+        return false;
+    };
+
+    match client.search(pattern, limit) {
+        Ok(result) => {
+            let shown = result.entries.len();
+            let mut entries = result.entries.into_iter().map(|entry| {
+                let kind = if entry.kind == "dir" { "dir" } else { "file" };
+                (PathBuf::from(entry.path), kind)
+            });
+            write_entries(output, &mut entries);
+            if result.truncated {
+                info!("Showing the first {} matches", shown);
+            }
+            true
+        }
+        Err(e) => {
+            warn!("The daemon could not search, scanning instead: {}", e);
+            false
+        }
+    }
+}
+
+fn connect(socket: Option<&Path>) -> Option<DaemonClient> {
+    socket_path(socket)
+        .ok()
+        .and_then(|socket| DaemonClient::connect(&socket))
+}
+
+// One arena per root of `arena`, every parent coming before its children
+fn split_volumes(arena: &PathArena) -> Vec<PathArena> {
+    let mut volumes: Vec<PathArena> = vec![];
+    // By id in `arena`, the volume of the entry and its id there
+    let mut moved: Vec<(usize, EntryId)> = Vec::with_capacity(arena.len());
+    for id in 0..arena.len() as EntryId {
+        let entry = match arena.parent(id) {
+            None => {
+                let mut volume = PathArena::new();
+                let root = volume.add_root(&arena.path(id));
+                volumes.push(volume);
+                (volumes.len() - 1, root)
+            }
+            Some(parent) => {
+                let (index, parent) = moved[parent as usize];
+                let volume = &mut volumes[index];
+                let moved_id = if arena.is_dir(id) {
+                    volume.add_dir(parent, arena.name(id))
+                } else {
+                    volume.add_file(parent, arena.name(id))
+                };
+                (index, moved_id)
+            }
+        };
+        moved.push(entry);
+    }
+    volumes
+}
+
+/// The entries the running daemon indexes, one arena per volume, current as the daemon writes
+/// its index first. None when there is no daemon, or it failed, and the volumes have to be
+/// scanned instead.
+pub(crate) fn daemon_arenas(socket: Option<&Path>) -> Option<Vec<PathArena>> {
+    let mut client = connect(socket)?;
+    let database = client
+        .flush()
+        .and_then(|flushed| LocateDb::load(&flushed.index))
+        .inspect_err(|e| {
+            warn!(
+                "Could not read the index of the daemon, scanning instead: {}",
+                e
+            )
+        })
+        .ok()?;
+    Some(split_volumes(database.arena()))
+}
+
+/// The volumes below `roots`, all when empty, as the running daemon indexes them. None when
+/// there is no daemon or it doesn't index every root.
+pub(crate) fn daemon_scan(socket: Option<&Path>, roots: &[PathBuf]) -> Option<ScanResult> {
+    let start = Instant::now();
+    let volumes: Vec<VolumeScanResult> = daemon_arenas(socket)?
+        .into_iter()
+        .filter_map(|arena| {
+            let root = arena.path(arena.roots().next()?);
+            let requested = roots.is_empty()
+                || roots
+                    .iter()
+                    .any(|r| same_root(&r.to_string_lossy(), &root.to_string_lossy()));
+            requested.then(|| {
+                VolumeScanResult::new(
+                    &root,
+                    DAEMON_READER,
+                    arena,
+                    Duration::ZERO,
+                    ReaderStats::default(),
+                )
+            })
+        })
+        .collect();
+
+    if volumes.is_empty() || volumes.len() < roots.len() {
+        info!("The daemon doesn't index every volume asked for, scanning instead");
+        return None;
+    }
+    Some(ScanResult::new(volumes, start.elapsed()))
+}
+
+fn print_json(value: &impl Serialize) -> Result<(), UFFSError> {
+    let json = serde_json::to_string(value).map_err(daemon_error)?;
+    writeln!(io::stdout().lock(), "{}", json)?;
+    Ok(())
+}
+
+/// Queries or controls the running daemon and prints its answers as JSON.
+pub(crate) fn run_daemon_command(
+    socket: Option<&Path>,
+    command: DaemonCommand,
+) -> Result<(), UFFSError> {
+    let socket = socket_path(socket)?;
+    let mut client = DaemonClient::connect(&socket).ok_or_else(|| {
+        daemon_error(format!(
+            "no daemon is listening on {}, start one with `uffsd`",
+            socket.display()
+        ))
+    })?;
+
+    match command {
+        DaemonCommand::Stats => print_json(&client.stats()?),
+        DaemonCommand::Rescan { path } => print_json(&client.rescan(path)?),
+        // One line of JSON per notification
+        DaemonCommand::Subscribe => client.subscribe(|changes| print_json(&changes)),
+    }
+}
@@ -0,0 +1,35 @@
+use std::path::{Path, PathBuf};
+
+use crate::modules::cli::DaemonCommand;
+use crate::modules::errors::UFFSError;
+use crate::modules::output::OutputOptions;
+use crate::modules::path_arena::PathArena;
+use crate::modules::scanner::ScanResult;
+
+// The daemon listens on a Unix domain socket, the commands always scan elsewhere
+pub(crate) fn daemon_arenas(_socket: Option<&Path>) -> Option<Vec<PathArena>> {
+    None
+}
+
+pub(crate) fn daemon_scan(_socket: Option<&Path>, _roots: &[PathBuf]) -> Option<ScanResult> {
+    None
+}
+
+pub(crate) fn search_daemon(
+    _socket: Option<&Path>,
+    _pattern: &str,
+    _limit: Option<usize>,
+    _output: &OutputOptions,
+) -> bool {
+    false
+}
+
+pub(crate) fn run_daemon_command(
+    _socket: Option<&Path>,
+    _command: DaemonCommand,
+) -> Result<(), UFFSError> {
+    Err(UFFSError::Unsupported {
+        feature: "The index daemon".to_string(),
+        platform: "Linux".to_string(),
+    })
+}
@@ -0,0 +1,424 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::fs::{self, Permissions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{debug, error, info};
+
+use crate::config::constants::{DAEMON_POLL_MS, DAEMON_WRITE_TIMEOUT_MS};
+use crate::modules::daemon::client::default_socket_path;
+use crate::modules::daemon::protocol::{
+    FlushResult, Notification, Request, RescanParams, RescanResult, Response, RpcError,
+    SearchEntry, SearchParams, SearchResult, Stats, CHANGES_NOTIFICATION, INTERNAL_ERROR,
+    INVALID_PARAMS, INVALID_REQUEST, JSONRPC_VERSION, METHOD_NOT_FOUND, PARSE_ERROR,
+};
+use crate::modules::errors::UFFSError;
+use crate::modules::path_arena::PathArena;
+use crate::modules::watch::{write_index, Applied, LiveIndex, PendingWrite, Watcher};
+
+// The writing half of a connection, shared with the subscriber list
+type Client = Arc<Mutex<UnixStream>>;
+
+// Rescans and index writes are run by the thread owning the watcher, which answers on `done`
+enum Task {
+    Rescan {
+        path: Option<PathBuf>,
+        done: flume::Sender<bool>,
+    },
+    Flush {
+        done: flume::Sender<()>,
+    },
+}
+
+// What the connections share with the watcher thread
+struct Shared {
+    index: RwLock<LiveIndex>,
+    index_path: PathBuf,
+    backend: &'static str,
+    started: Instant,
+    subscribers: Mutex<Vec<Client>>,
+    tasks: flume::Sender<Task>,
+}
+
+/// Keeps the entries of `arenas` up to date like `watch_volumes` and answers queries about them
+/// on the Unix domain socket `socket` (see `protocol`), until the process is stopped.
+pub(crate) fn serve(
+    arenas: Vec<PathArena>,
+    index_path: &Path,
+    socket: Option<&Path>,
+    scanned_at: SystemTime,
+) -> Result<(), UFFSError> {
+    let socket = match socket {
+        Some(socket) => socket.to_path_buf(),
+        None => default_socket_path()?,
+    };
+    let listener = bind(&socket)?;
+
+    let mut index = LiveIndex::new(arenas);
+    let mut watcher = Watcher::new(&mut index, scanned_at)?;
+    let (tasks, requested) = flume::unbounded();
+    let shared = Arc::new(Shared {
+        index: RwLock::new(index),
+        index_path: index_path.to_path_buf(),
+        backend: watcher.backend(),
+        started: Instant::now(),
+        subscribers: Mutex::new(vec![]),
+        tasks,
+    });
+    write_index(&shared.index, index_path);
+
+    info!("Answering queries on {}", socket.display());
+    let accepting = Arc::clone(&shared);
+    thread::spawn(move || accept(listener, accepting));
+
+    // Short waits, so that rescans don't wait for changes to come
+    let poll = Duration::from_millis(DAEMON_POLL_MS);
+    let mut pending = PendingWrite::default();
+    loop {
+        let mut changed = false;
+        if watcher.wait(poll)? {
+            let applied = watcher.apply(&mut shared.index.write().unwrap());
+            if !applied.is_empty() {
+                notify(&shared, &applied);
+                changed = true;
+            }
+        }
+
+        for task in requested.try_iter() {
+            match task {
+                Task::Rescan { path, done } => {
+                    let rescanned =
+                        watcher.rescan(&mut shared.index.write().unwrap(), path.as_deref());
+                    if rescanned {
+                        let applied = Applied {
+                            rescanned,
+                            ..Applied::default()
+                        };
+                        notify(&shared, &applied);
+                        changed = true;
+                    }
+                    let _ = done.send(rescanned);
+                }
+                Task::Flush { done } => {
+                    write_index(&shared.index, &shared.index_path);
+                    let _ = done.send(());
+                }
+            }
+        }
+
+        if pending.due(changed) {
+            write_index(&shared.index, &shared.index_path);
+        }
+    }
+}
+
+fn bind(socket: &Path) -> Result<UnixListener, UFFSError> {
+    if UnixStream::connect(socket).is_ok() {
+        return Err(UFFSError::Daemon {
+            message: format!("a daemon is already listening on {}", socket.display()),
+        });
+    }
+    // Left behind by a daemon that was killed
+    let _ = fs::remove_file(socket);
+
+    let listener = UnixListener::bind(socket)?;
+    // The index holds the name of every file, only the owner may query it
+    fs::set_permissions(socket, Permissions::from_mode(0o600))?;
+    Ok(listener)
+}
+
+fn accept(listener: UnixListener, shared: Arc<Shared>) {
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let shared = Arc::clone(&shared);
+                thread::spawn(move || {
+                    if let Err(e) = handle_connection(stream, &shared) {
+                        debug!("Connection closed: {}", e);
+                    }
+                });
+            }
+            Err(e) => error!("Failed to accept a connection: {}", e),
+        }
+    }
+}
+
+fn handle_connection(stream: UnixStream, shared: &Shared) -> io::Result<()> {
+    // A client that stops reading doesn't hold up the others
+    stream.set_write_timeout(Some(Duration::from_millis(DAEMON_WRITE_TIMEOUT_MS)))?;
+    let client: Client = Arc::new(Mutex::new(stream.try_clone()?));
+
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let request = match serde_json::from_str::<Value>(&line) {
+            Ok(request) => serde_json::from_value::<Request>(request),
+            Err(e) => {
+                let error = RpcError::new(PARSE_ERROR, e.to_string());
+                send(&client, &Response::new(Value::Null, Err(error)))?;
+                continue;
+            }
+        };
+        let request = match request {
+            Ok(request) if request.jsonrpc == JSONRPC_VERSION => request,
+            Ok(_) => {
+                let error = RpcError::new(INVALID_REQUEST, "jsonrpc must be \"2.0\"");
+                send(&client, &Response::new(Value::Null, Err(error)))?;
+                continue;
+            }
+            Err(e) => {
+                let error = RpcError::new(INVALID_REQUEST, e.to_string());
+                send(&client, &Response::new(Value::Null, Err(error)))?;
+                continue;
+            }
+        };
+
+        let result = handle_request(&request, shared);
+        let subscribed = request.method == "subscribe" && result.is_ok();
+        if let Some(id) = request.id {
+            send(&client, &Response::new(id, result))?;
+        }
+        // Changes are sent from the next one on, after the response
+        if subscribed {
+            shared.subscribers.lock().unwrap().push(Arc::clone(&client));
+        }
+    }
+
+    Ok(())
+}
+
+fn params<T: DeserializeOwned>(params: &Value) -> Result<T, RpcError> {
+    let params = match params {
+        Value::Null => Value::Object(Default::default()),
+        params => params.clone(),
+    };
+    serde_json::from_value(params).map_err(|e| RpcError::new(INVALID_PARAMS, e.to_string()))
+}
+
+fn to_result(value: impl Serialize) -> Result<Value, RpcError> {
+    Ok(serde_json::to_value(value).unwrap_or_default())
+}
+
+fn handle_request(request: &Request, shared: &Shared) -> Result<Value, RpcError> {
+    match request.method.as_str() {
+        "search" => {
+            let params: SearchParams = params(&request.params)?;
+            to_result(search(&shared.index.read().unwrap(), &params))
+        }
+        "stats" => to_result(Stats {
+            backend: shared.backend.to_string(),
+            uptime_seconds: shared.started.elapsed().as_secs(),
+            index: shared.index_path.clone(),
+            volumes: shared.index.read().unwrap().stats(),
+        }),
+        "rescan" => {
+            let params: RescanParams = params(&request.params)?;
+            let (done, rescanned) = flume::bounded(1);
+            let rescan = Task::Rescan {
+                path: params.path,
+                done,
+            };
+            // Both fail only once the watcher thread is gone, i.e. the daemon is stopping
+            let changed = shared
+                .tasks
+                .send(rescan)
+                .ok()
+                .and_then(|_| rescanned.recv().ok())
+                .unwrap_or(false);
+            to_result(RescanResult { changed })
+        }
+        "flush" => {
+            let (done, flushed) = flume::bounded(1);
+            shared
+                .tasks
+                .send(Task::Flush { done })
+                .ok()
+                .and_then(|_| flushed.recv().ok())
+                .ok_or_else(|| RpcError::new(INTERNAL_ERROR, "the daemon is stopping"))?;
+            to_result(FlushResult {
+                index: shared.index_path.clone(),
+            })
+        }
+        "subscribe" => Ok(Value::Bool(true)),
+        method => Err(RpcError::new(
+            METHOD_NOT_FOUND,
+            format!("unknown method {}", method),
+        )),
+    }
+}
+
+fn search(index: &LiveIndex, params: &SearchParams) -> SearchResult {
+    let mut matches = index.search(&params.pattern);
+    let entries = matches
+        .by_ref()
+        .take(params.limit.unwrap_or(usize::MAX))
+        .map(|(path, kind)| SearchEntry {
+            path: path.to_string_lossy().into_owned(),
+            kind: kind.to_string(),
+        })
+        .collect();
+
+    SearchResult {
+        entries,
+        truncated: matches.next().is_some(),
+    }
+}
+
+fn write_line(stream: &mut UnixStream, message: &impl Serialize) -> io::Result<()> {
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+    stream.write_all(&line)
+}
+
+fn send(client: &Client, message: &impl Serialize) -> io::Result<()> {
+    write_line(&mut client.lock().unwrap(), message)
+}
+
+// Sends the changes to the subscribers, dropping those that can't keep up or are gone
+fn notify(shared: &Shared, applied: &Applied) {
+    let mut subscribers = shared.subscribers.lock().unwrap();
+    if subscribers.is_empty() {
+        return;
+    }
+
+    let notification = Notification {
+        jsonrpc: JSONRPC_VERSION.to_string(),
+        method: CHANGES_NOTIFICATION.to_string(),
+        params: applied,
+    };
+    subscribers.retain(|client| send(client, &notification).is_ok());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::daemon::client::{daemon_scan, DaemonClient};
+    use crate::modules::locate::LocateDb;
+    use std::slice;
+
+    // A daemon on a socket in `dir` indexing a volume per root, left running until the tests exit
+    fn start_daemon(dir: &Path, roots: &[&Path]) -> PathBuf {
+        let arenas = roots
+            .iter()
+            .map(|root| {
+                let mut arena = PathArena::new();
+                let root_id = arena.add_root(root);
+                let docs = arena.add_dir(root_id, "docs".as_ref());
+                arena.add_file(docs, "report.pdf".as_ref());
+                arena.add_file(root_id, "notes.txt".as_ref());
+                arena
+            })
+            .collect();
+        let socket = dir.join("uffsd.sock");
+        let index = dir.join("index.db");
+
+        let serving = socket.clone();
+        thread::spawn(move || serve(arenas, &index, Some(&serving), SystemTime::now()));
+        for _ in 0..100 {
+            if UnixStream::connect(&socket).is_ok() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(20));
+        }
+        socket
+    }
+
+    fn volume(parent: &Path, name: &str) -> PathBuf {
+        let root = parent.join(name);
+        fs::create_dir_all(root.join("docs")).unwrap();
+        fs::write(root.join("docs").join("report.pdf"), b"").unwrap();
+        fs::write(root.join("notes.txt"), b"").unwrap();
+        root
+    }
+
+    // Sends `line` as is and parses the response
+    fn send_line(socket: &Path, line: &str) -> Response {
+        let mut stream = UnixStream::connect(socket).unwrap();
+        writeln!(stream, "{}", line).unwrap();
+        let mut response = String::new();
+        BufReader::new(stream).read_line(&mut response).unwrap();
+        serde_json::from_str(&response).unwrap()
+    }
+
+    #[test]
+    fn clients_query_the_daemon() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = volume(dir.path(), "first");
+        let second = volume(dir.path(), "second");
+        let socket = start_daemon(dir.path(), &[&first, &second]);
+        let mut client = DaemonClient::connect(&socket).unwrap();
+
+        let result = client.search("REPORT", None).unwrap();
+        let mut paths: Vec<String> = result.entries.iter().map(|e| e.path.clone()).collect();
+        paths.sort();
+        assert_eq!(
+            paths,
+            vec![
+                first.join("docs").join("report.pdf").to_string_lossy(),
+                second.join("docs").join("report.pdf").to_string_lossy(),
+            ]
+        );
+        assert!(result.entries.iter().all(|e| e.kind == "file"));
+        let result = client.search("", Some(1)).unwrap();
+        assert_eq!(result.entries.len(), 1);
+        assert!(result.truncated);
+
+        let stats = client.stats().unwrap();
+        assert_eq!(stats.index, dir.path().join("index.db"));
+        assert_eq!(stats.volumes.len(), 2);
+        assert!(stats.volumes.iter().all(|v| v.files == 2 && v.dirs == 1));
+
+        // What the other commands read
+        let flushed = client.flush().unwrap();
+        assert_eq!(flushed.index, stats.index);
+        assert_eq!(LocateDb::load(&flushed.index).unwrap().len(), 6);
+
+        let scan = daemon_scan(Some(&socket), slice::from_ref(&second)).unwrap();
+        assert_eq!(scan.volumes().len(), 1);
+        assert_eq!(scan.volumes()[0].root(), second);
+        assert_eq!(scan.num_files(), 2);
+        assert_eq!(scan.num_dirs(), 1);
+        let scan = daemon_scan(Some(&socket), &[]).unwrap();
+        assert_eq!(scan.volumes().len(), 2);
+        assert!(daemon_scan(Some(&socket), &[dir.path().join("elsewhere")]).is_none());
+    }
+
+    #[test]
+    fn invalid_requests_get_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = volume(dir.path(), "root");
+        let socket = start_daemon(dir.path(), &[&root]);
+
+        let code = |line: &str| send_line(&socket, line).error.unwrap().code;
+        assert_eq!(code("not json"), PARSE_ERROR);
+        assert_eq!(
+            code(r#"{"jsonrpc": "1.0", "id": 1, "method": "stats"}"#),
+            INVALID_REQUEST
+        );
+        assert_eq!(code(r#"{"jsonrpc": "2.0", "id": 1}"#), INVALID_REQUEST);
+        assert_eq!(
+            code(r#"{"jsonrpc": "2.0", "id": 1, "method": "nope"}"#),
+            METHOD_NOT_FOUND
+        );
+        assert_eq!(
+            code(r#"{"jsonrpc": "2.0", "id": 1, "method": "search", "params": {"limit": 1}}"#),
+            INVALID_PARAMS
+        );
+
+        let response = send_line(
+            &socket,
+            r#"{"jsonrpc": "2.0", "id": "a", "method": "stats"}"#,
+        );
+        assert_eq!(response.id, Value::from("a"));
+        assert!(response.error.is_none());
+    }
+}
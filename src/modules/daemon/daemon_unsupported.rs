@@ -0,0 +1,18 @@
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::modules::errors::UFFSError;
+use crate::modules::path_arena::PathArena;
+
+// Stands in for the daemon on platforms without inotify and fanotify
+pub(crate) fn serve(
+    _arenas: Vec<PathArena>,
+    _index_path: &Path,
+    _socket: Option<&Path>,
+    _scanned_at: SystemTime,
+) -> Result<(), UFFSError> {
+    Err(UFFSError::Unsupported {
+        feature: "The index daemon".to_string(),
+        platform: "Linux".to_string(),
+    })
+}
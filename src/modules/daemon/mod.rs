@@ -0,0 +1,33 @@
+#[cfg(unix)]
+mod client;
+#[cfg(not(unix))]
+mod client_unsupported;
+#[cfg(target_os = "linux")]
+pub mod daemon_impl;
+#[cfg(not(target_os = "linux"))]
+mod daemon_unsupported;
+#[cfg(unix)]
+pub mod protocol;
+
+#[cfg(unix)]
+pub(crate) use client::daemon_arenas;
+#[cfg(unix)]
+pub(crate) use client::daemon_scan;
+#[cfg(unix)]
+pub(crate) use client::run_daemon_command;
+#[cfg(unix)]
+pub(crate) use client::search_daemon;
+#[cfg(not(unix))]
+pub(crate) use client_unsupported::daemon_arenas;
+#[cfg(not(unix))]
+pub(crate) use client_unsupported::daemon_scan;
+#[cfg(not(unix))]
+pub(crate) use client_unsupported::run_daemon_command;
+#[cfg(not(unix))]
+pub(crate) use client_unsupported::search_daemon;
+#[cfg(target_os = "linux")]
+pub(crate) use daemon_impl::serve;
+#[cfg(not(target_os = "linux"))]
+pub(crate) use daemon_unsupported::serve;
+#[cfg(unix)]
+pub(crate) use protocol::VolumeStats;
@@ -0,0 +1,292 @@
+//! The protocol `uffsd` answers on its Unix domain socket: JSON-RPC 2.0, one message per line.
+//!
+//! Methods:
+//!
+//! - `search` with `{"pattern": "report", "limit": 100}`: the entries whose name contains the
+//!   pattern, ignoring case, as `{"entries": [{"path": "/home/a/report.pdf", "type": "file"}],
+//!   "truncated": false}`. Without `limit` every match is returned.
+//! - `stats`: `{"backend": "fanotify", "uptime_seconds": 12, "index": "/path/index.db",
+//!   "volumes": [{"root": "/", "files": 1200, "dirs": 80}]}`.
+//! - `rescan` with `{"path": "/home"}`, or no params for every volume: rereads the directories
+//!   below the path and answers `{"changed": true}` once done.
+//! - `subscribe`: answers `true`, then sends a `changes` notification whenever the index changes,
+//!   `{"jsonrpc": "2.0", "method": "changes", "params": {"changes": [{"kind": "created", "path":
+//!   "/tmp/a", "is_dir": false}], "rescanned": false}}`. The kinds are `created`, `removed` and
+//!   `modified`, `rescanned` is set when directories were reread because the kernel dropped
+//!   changes.
+//! - `flush`: writes the index to its file now and answers `{"index": "/path/index.db"}` once
+//!   done, for the commands reading all entries from it.
+//!
+//! ```text
+//! --> {"jsonrpc": "2.0", "id": 1, "method": "search", "params": {"pattern": "uffs"}}
+//! <-- {"jsonrpc": "2.0", "id": 1, "result": {"entries": [...], "truncated": false}}
+//! ```
+//!
+//! Errors use the JSON-RPC codes: -32700 for a line that isn't JSON, -32600 for an invalid
+//! request, -32601 for an unknown method, -32602 for invalid params and -32603 when the daemon
+//! is stopping.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::PathBuf;
+
+pub(crate) const JSONRPC_VERSION: &str = "2.0";
+
+pub(crate) const PARSE_ERROR: i64 = -32700;
+pub(crate) const INVALID_REQUEST: i64 = -32600;
+pub(crate) const METHOD_NOT_FOUND: i64 = -32601;
+pub(crate) const INVALID_PARAMS: i64 = -32602;
+pub(crate) const INTERNAL_ERROR: i64 = -32603;
+
+pub(crate) const CHANGES_NOTIFICATION: &str = "changes";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Request {
+    pub(crate) jsonrpc: String,
+    // Notifications have none and get no response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) id: Option<Value>,
+    pub(crate) method: String,
+    #[serde(default)]
+    pub(crate) params: Value,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Response {
+    pub(crate) jsonrpc: String,
+    pub(crate) id: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) error: Option<RpcError>,
+}
+
+impl Response {
+    pub(crate) fn new(id: Value, result: Result<Value, RpcError>) -> Self {
+        let (result, error) = match result {
+            Ok(result) => (Some(result), None),
+            Err(error) => (None, Some(error)),
+        };
+        Self {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id,
+            result,
+            error,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct RpcError {
+    pub(crate) code: i64,
+    pub(crate) message: String,
+}
+
+impl RpcError {
+    pub(crate) fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Notification<P> {
+    pub(crate) jsonrpc: String,
+    pub(crate) method: String,
+    pub(crate) params: P,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct SearchParams {
+    pub(crate) pattern: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct SearchEntry {
+    pub(crate) path: String,
+    // "file" or "dir"
+    #[serde(rename = "type")]
+    pub(crate) kind: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct SearchResult {
+    pub(crate) entries: Vec<SearchEntry>,
+    // More entries matched than the limit
+    pub(crate) truncated: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct RescanParams {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) path: Option<PathBuf>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct RescanResult {
+    pub(crate) changed: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct FlushResult {
+    pub(crate) index: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub(crate) struct Stats {
+    // inotify or fanotify
+    pub(crate) backend: String,
+    pub(crate) uptime_seconds: u64,
+    pub(crate) index: PathBuf,
+    pub(crate) volumes: Vec<VolumeStats>,
+}
+
+/// Entries of a volume in the index.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct VolumeStats {
+    pub(crate) root: String,
+    pub(crate) files: usize,
+    pub(crate) dirs: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::de::DeserializeOwned;
+    use serde_json::json;
+
+    // Serializes `value` to one line and parses it back
+    fn round_trip<T: Serialize + DeserializeOwned>(value: &T) -> T {
+        let line = serde_json::to_string(value).unwrap();
+        assert!(!line.contains('\n'));
+        serde_json::from_str(&line).unwrap()
+    }
+
+    #[test]
+    fn requests_round_trip() {
+        let request = Request {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id: Some(json!(7)),
+            method: "search".to_string(),
+            params: serde_json::to_value(SearchParams {
+                pattern: "report".to_string(),
+                limit: Some(100),
+            })
+            .unwrap(),
+        };
+        let parsed = round_trip(&request);
+        assert_eq!(parsed.jsonrpc, "2.0");
+        assert_eq!(parsed.id, Some(json!(7)));
+        assert_eq!(parsed.method, "search");
+        let params: SearchParams = serde_json::from_value(parsed.params).unwrap();
+        assert_eq!(params.pattern, "report");
+        assert_eq!(params.limit, Some(100));
+    }
+
+    #[test]
+    fn notifications_have_no_id_and_params_are_optional() {
+        let request: Request =
+            serde_json::from_str(r#"{"jsonrpc": "2.0", "method": "rescan"}"#).unwrap();
+        assert_eq!(request.id, None);
+        assert_eq!(request.params, Value::Null);
+
+        let line = serde_json::to_string(&request).unwrap();
+        assert!(!line.contains("\"id\""));
+
+        let params: SearchParams = serde_json::from_str(r#"{"pattern": "a"}"#).unwrap();
+        assert_eq!(params.limit, None);
+        let params: RescanParams = serde_json::from_str("{}").unwrap();
+        assert_eq!(params.path, None);
+    }
+
+    #[test]
+    fn responses_round_trip() {
+        let result = SearchResult {
+            entries: vec![SearchEntry {
+                path: "/home/a/report.pdf".to_string(),
+                kind: "file".to_string(),
+            }],
+            truncated: true,
+        };
+        let response = Response::new(json!(1), Ok(serde_json::to_value(result).unwrap()));
+        let line = serde_json::to_string(&response).unwrap();
+        assert!(!line.contains("\"error\""));
+
+        let parsed = round_trip(&response);
+        assert_eq!(parsed.id, json!(1));
+        assert!(parsed.error.is_none());
+        let result: SearchResult = serde_json::from_value(parsed.result.unwrap()).unwrap();
+        assert_eq!(result.entries.len(), 1);
+        assert_eq!(result.entries[0].path, "/home/a/report.pdf");
+        assert_eq!(result.entries[0].kind, "file");
+        assert!(result.truncated);
+
+        // The kind is sent as "type"
+        let entry = serde_json::to_value(SearchEntry {
+            path: "/tmp".to_string(),
+            kind: "dir".to_string(),
+        })
+        .unwrap();
+        assert_eq!(entry, json!({"path": "/tmp", "type": "dir"}));
+    }
+
+    #[test]
+    fn errors_round_trip() {
+        for code in [
+            PARSE_ERROR,
+            INVALID_REQUEST,
+            METHOD_NOT_FOUND,
+            INVALID_PARAMS,
+            INTERNAL_ERROR,
+        ] {
+            let error = RpcError::new(code, "failed");
+            let response = round_trip(&Response::new(Value::Null, Err(error)));
+            assert!(response.result.is_none());
+            let error = response.error.unwrap();
+            assert_eq!(error.code, code);
+            assert_eq!(error.message, "failed");
+        }
+    }
+
+    #[test]
+    fn results_round_trip() {
+        let stats = round_trip(&Stats {
+            backend: "inotify".to_string(),
+            uptime_seconds: 12,
+            index: PathBuf::from("/tmp/index.db"),
+            volumes: vec![VolumeStats {
+                root: "/".to_string(),
+                files: 1200,
+                dirs: 80,
+            }],
+        });
+        assert_eq!(stats.backend, "inotify");
+        assert_eq!(stats.uptime_seconds, 12);
+        assert_eq!(stats.index, PathBuf::from("/tmp/index.db"));
+        assert_eq!(stats.volumes[0].root, "/");
+        assert_eq!(stats.volumes[0].files, 1200);
+        assert_eq!(stats.volumes[0].dirs, 80);
+
+        let rescan = round_trip(&RescanParams {
+            path: Some(PathBuf::from("/home")),
+        });
+        assert_eq!(rescan.path, Some(PathBuf::from("/home")));
+        assert!(round_trip(&RescanResult { changed: true }).changed);
+        let flush = round_trip(&FlushResult {
+            index: PathBuf::from("/tmp/index.db"),
+        });
+        assert_eq!(flush.index, PathBuf::from("/tmp/index.db"));
+
+        let notification = round_trip(&Notification {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            method: CHANGES_NOTIFICATION.to_string(),
+            params: json!({"changes": [], "rescanned": true}),
+        });
+        assert_eq!(notification.method, "changes");
+        assert_eq!(notification.params["rescanned"], json!(true));
+    }
+}
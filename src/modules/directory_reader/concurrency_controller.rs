@@ -0,0 +1,321 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::config::constants::{
+    ADAPTIVE_DECREASE_FACTOR, ADAPTIVE_LATENCY_TOLERANCE, ADAPTIVE_MAX_CONCURRENT_READS,
+    ADAPTIVE_MIN_CONCURRENT_READS, ADAPTIVE_WINDOW_READS,
+};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(crate) struct ConcurrencyStats {
+    pub(crate) adaptive: bool,
+    pub(crate) initial_limit: usize,
+    pub(crate) final_limit: usize,
+    pub(crate) min_limit: usize,
+    pub(crate) max_limit: usize,
+    pub(crate) increases: u64,
+    pub(crate) decreases: u64,
+    pub(crate) reads: u64,
+    #[serde(skip)]
+    pub(crate) total_latency: Duration,
+    pub(crate) peak_throughput: f64,
+}
+
+impl ConcurrencyStats {
+    pub(crate) fn average_latency(&self) -> Duration {
+        if self.reads == 0 {
+            return Duration::ZERO;
+        }
+        self.total_latency / self.reads as u32
+    }
+}
+
+impl fmt::Display for ConcurrencyStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} concurrency {} -> {} (min {}, max {}), +{}/-{} adjustments, {} reads, avg latency {:.2} ms, peak {:.0} entries/s",
+            if self.adaptive { "adaptive" } else { "fixed" },
+            self.initial_limit,
+            self.final_limit,
+            self.min_limit,
+            self.max_limit,
+            self.increases,
+            self.decreases,
+            self.reads,
+            self.average_latency().as_secs_f64() * 1000.0,
+            self.peak_throughput,
+        )
+    }
+}
+
+struct ControllerState {
+    limit: usize,
+    // Permits to drop instead of returning them, once the limit went below the permits in use
+    forget_debt: usize,
+    window_start: Instant,
+    window_reads: usize,
+    window_entries: usize,
+    window_latency: Duration,
+    baseline_latency: Option<Duration>,
+    best_throughput: f64,
+    stats: ConcurrencyStats,
+}
+
+/// Limits the in-flight directory reads with a semaphore. In adaptive mode the limit follows
+/// AIMD: it grows by one permit per window while the read latency stays close to the lowest
+/// latency seen, and is cut by `ADAPTIVE_DECREASE_FACTOR` once latency climbs without the
+/// throughput improving, i.e. the device is saturated and reads only queue up.
+pub(crate) struct ConcurrencyController {
+    semaphore: Arc<Semaphore>,
+    adaptive: bool,
+    state: Mutex<ControllerState>,
+}
+
+impl ConcurrencyController {
+    pub(crate) fn fixed(limit: usize) -> Self {
+        Self::new(limit.max(1), false)
+    }
+
+    pub(crate) fn adaptive(initial_limit: usize) -> Self {
+        Self::new(
+            initial_limit.clamp(ADAPTIVE_MIN_CONCURRENT_READS, ADAPTIVE_MAX_CONCURRENT_READS),
+            true,
+        )
+    }
+
+    fn new(limit: usize, adaptive: bool) -> Self {
+        Self {
+            semaphore: Arc::new(Semaphore::new(limit)),
+            adaptive,
+            state: Mutex::new(ControllerState {
+                limit,
+                forget_debt: 0,
+                window_start: Instant::now(),
+                window_reads: 0,
+                window_entries: 0,
+                window_latency: Duration::ZERO,
+                baseline_latency: None,
+                best_throughput: 0.0,
+                stats: ConcurrencyStats {
+                    adaptive,
+                    initial_limit: limit,
+                    final_limit: limit,
+                    min_limit: limit,
+                    max_limit: limit,
+                    ..Default::default()
+                },
+            }),
+        }
+    }
+
+    pub(crate) async fn acquire(&self) -> OwnedSemaphorePermit {
+        self.semaphore
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("Concurrency semaphore closed")
+    }
+
+    /// Returns the permit of a finished read and feeds its latency and entry count to the controller.
+    pub(crate) fn complete(&self, permit: OwnedSemaphorePermit, latency: Duration, entries: usize) {
+        let mut state = self.state.lock().unwrap();
+
+        state.stats.reads += 1;
+        state.stats.total_latency += latency;
+        state.window_reads += 1;
+        state.window_entries += entries;
+        state.window_latency += latency;
+
+        // A window covers at least one full round of the permits
+        if state.window_reads >= ADAPTIVE_WINDOW_READS.max(state.limit) {
+            self.end_window(&mut state);
+        }
+
+        if state.forget_debt > 0 {
+            state.forget_debt -= 1;
+            permit.forget();
+        } else {
+            drop(permit);
+        }
+    }
+
+    fn end_window(&self, state: &mut ControllerState) {
+        let elapsed = state.window_start.elapsed().as_secs_f64().max(f64::EPSILON);
+        let throughput = state.window_entries as f64 / elapsed;
+        let average_latency = state.window_latency / state.window_reads as u32;
+
+        state.stats.peak_throughput = state.stats.peak_throughput.max(throughput);
+
+        if self.adaptive {
+            let baseline = *state.baseline_latency.get_or_insert(average_latency);
+            let congested = average_latency.as_secs_f64()
+                > baseline.as_secs_f64() * ADAPTIVE_LATENCY_TOLERANCE
+                && throughput <= state.best_throughput;
+
+            if congested {
+                let new_limit = ((state.limit as f64 * ADAPTIVE_DECREASE_FACTOR) as usize)
+                    .max(ADAPTIVE_MIN_CONCURRENT_READS);
+                let by = state.limit - new_limit;
+                self.decrease(state, by);
+            } else if state.limit < ADAPTIVE_MAX_CONCURRENT_READS {
+                self.increase(state);
+            }
+
+            state.baseline_latency = Some(baseline.min(average_latency));
+            state.best_throughput = state.best_throughput.max(throughput);
+        }
+
+        state.window_start = Instant::now();
+        state.window_reads = 0;
+        state.window_entries = 0;
+        state.window_latency = Duration::ZERO;
+    }
+
+    fn increase(&self, state: &mut ControllerState) {
+        if state.forget_debt > 0 {
+            state.forget_debt -= 1;
+        } else {
+            self.semaphore.add_permits(1);
+        }
+        state.limit += 1;
+        state.stats.increases += 1;
+        state.stats.max_limit = state.stats.max_limit.max(state.limit);
+    }
+
+    fn decrease(&self, state: &mut ControllerState, by: usize) {
+        if by == 0 {
+            return;
+        }
+        // Permits held by running reads are dropped as those reads complete
+        let forgotten = self.semaphore.forget_permits(by);
+        state.forget_debt += by - forgotten;
+        state.limit -= by;
+        state.stats.decreases += 1;
+        state.stats.min_limit = state.stats.min_limit.min(state.limit);
+    }
+
+    pub(crate) fn stats(&self) -> ConcurrencyStats {
+        let state = self.state.lock().unwrap();
+        ConcurrencyStats {
+            final_limit: state.limit,
+            ..state.stats.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FAST: Duration = Duration::from_millis(1);
+    const SLOW: Duration = Duration::from_millis(10);
+
+    // Reads one after the other until the controller ends its window
+    async fn run_window(controller: &ConcurrencyController, latency: Duration, entries: usize) {
+        let reads = ADAPTIVE_WINDOW_READS.max(controller.stats().final_limit);
+        for _ in 0..reads {
+            let permit = controller.acquire().await;
+            controller.complete(permit, latency, entries);
+        }
+    }
+
+    fn assert_limit(controller: &ConcurrencyController, limit: usize) {
+        assert_eq!(controller.stats().final_limit, limit);
+        assert_eq!(controller.semaphore.available_permits(), limit);
+    }
+
+    #[tokio::test]
+    async fn the_limit_grows_by_one_while_the_latency_holds() {
+        let controller = ConcurrencyController::adaptive(8);
+        for limit in 9..12 {
+            run_window(&controller, FAST, 10).await;
+            assert_limit(&controller, limit);
+        }
+        let stats = controller.stats();
+        assert_eq!((stats.increases, stats.decreases), (3, 0));
+        assert_eq!((stats.min_limit, stats.max_limit), (8, 11));
+        assert_eq!(stats.reads, 3 * ADAPTIVE_WINDOW_READS as u64);
+    }
+
+    #[tokio::test]
+    async fn the_limit_is_cut_once_the_latency_climbs_without_more_throughput() {
+        let controller = ConcurrencyController::adaptive(16);
+        run_window(&controller, FAST, 0).await;
+        assert_limit(&controller, 17);
+        run_window(&controller, SLOW, 0).await;
+        assert_limit(&controller, 8);
+
+        let stats = controller.stats();
+        assert_eq!((stats.increases, stats.decreases), (1, 1));
+        assert_eq!((stats.min_limit, stats.max_limit), (8, 17));
+    }
+
+    #[tokio::test]
+    async fn the_limit_stays_within_its_bounds() {
+        assert_limit(
+            &ConcurrencyController::adaptive(0),
+            ADAPTIVE_MIN_CONCURRENT_READS,
+        );
+
+        let controller = ConcurrencyController::adaptive(ADAPTIVE_MIN_CONCURRENT_READS);
+        run_window(&controller, FAST, 0).await;
+        for _ in 0..3 {
+            run_window(&controller, SLOW, 0).await;
+            assert_limit(&controller, ADAPTIVE_MIN_CONCURRENT_READS);
+        }
+        assert_eq!(controller.stats().decreases, 1);
+
+        let controller = ConcurrencyController::adaptive(usize::MAX);
+        assert_limit(&controller, ADAPTIVE_MAX_CONCURRENT_READS);
+        run_window(&controller, FAST, 10).await;
+        assert_limit(&controller, ADAPTIVE_MAX_CONCURRENT_READS);
+        assert_eq!(controller.stats().increases, 0);
+    }
+
+    #[tokio::test]
+    async fn a_fixed_limit_never_moves() {
+        assert_limit(&ConcurrencyController::fixed(0), 1);
+
+        let controller = ConcurrencyController::fixed(8);
+        run_window(&controller, FAST, 10).await;
+        run_window(&controller, SLOW, 0).await;
+        assert_limit(&controller, 8);
+        let stats = controller.stats();
+        assert_eq!((stats.increases, stats.decreases), (0, 0));
+        assert_eq!(stats.reads, 2 * ADAPTIVE_WINDOW_READS as u64);
+    }
+
+    #[tokio::test]
+    async fn permits_of_running_reads_are_forgotten_as_they_complete() {
+        let controller = ConcurrencyController::adaptive(8);
+        run_window(&controller, FAST, 0).await;
+        assert_limit(&controller, 9);
+
+        // All but one permit are taken by running reads when the window ends
+        let mut running = vec![];
+        for _ in 0..8 {
+            running.push(controller.acquire().await);
+        }
+        for _ in 1..ADAPTIVE_WINDOW_READS {
+            let permit = controller.acquire().await;
+            controller.complete(permit, SLOW, 0);
+        }
+        controller.complete(running.pop().unwrap(), SLOW, 0);
+
+        // 9 * 0.5 = 4: the free permit and the one returned are forgotten, 3 more are owed
+        assert_eq!(controller.stats().final_limit, 4);
+        assert_eq!(controller.semaphore.available_permits(), 0);
+        for (returned, permit) in running.into_iter().enumerate() {
+            controller.complete(permit, FAST, 0);
+            assert_eq!(
+                controller.semaphore.available_permits(),
+                returned.saturating_sub(2)
+            );
+        }
+        assert_limit(&controller, 4);
+    }
+}
@@ -1,142 +1,410 @@
 use async_trait::async_trait;
-use std::collections::VecDeque;
-use std::ffi::OsString;
-use std::os::windows::prelude::{OsStrExt, OsStringExt};
+use std::collections::HashMap;
+use std::mem;
 use std::path::{Path, PathBuf};
-use std::process::exit;
 use std::sync::Arc;
 use std::time::Instant;
 
-use async_recursion::async_recursion;
-// use async_std::fs::DirEntry;
 use crate::config::constants::{MAX_CONCURRENT_READS, MAX_DIRS};
-use crate::modules::directory_reader;
-use crate::modules::errors::UFFSError;
-use crate::modules::utils::utils_impl::{
-    count_disk_entries_all_at_once, count_disk_entries_all_at_once_new,
-};
-use crate::modules::utils::{
-    count_disk_entries, format_duration, format_number, format_size, read_directory_all_at_once,
-    read_directory_entries,
+use crate::modules::directory_reader::concurrency_controller::{
+    ConcurrencyController, ConcurrencyStats,
 };
-use chrono::Local;
-use colored::*;
-use futures::future::join_all;
-use jwalk::WalkDir;
-use num_format::Locale::se;
-use tokio::fs::DirEntry;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::sync::{Mutex, RwLock, Semaphore};
-use tokio::{io, task};
-use tracing::info;
+use crate::modules::directory_reader::ListingSink;
+use crate::modules::errors::UFFSError;
+use crate::modules::file_system::{DirEntry, FileKind, OsFileSystem, SharedFileSystem};
+use crate::modules::ignore_filter::IgnoreFilter;
+use crate::modules::path_arena::{EntryId, PathArena};
+use crate::modules::raw_path::RawPath;
+use crate::modules::scanner::ReadError;
+use crate::modules::utils::utils_impl::count_disk_entries_all_at_once_new;
+use crate::modules::utils::{read_directory_all_at_once, read_directory_entries};
+use async_recursion::async_recursion;
+use jwalk::WalkDirGeneric;
+use tokio::io;
+use tokio::sync::RwLock;
+use tokio::task::{JoinError, JoinSet};
+use tracing::{error, warn};
+
+/// Statistics a reader collects during one scan, on top of the files and dirs it found.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ReaderStats {
+    pub(crate) concurrency: Option<ConcurrencyStats>,
+    // Directories that could not be read, their entries are missing
+    pub(crate) errors: Vec<ReadError>,
+}
 
 #[async_trait]
 pub(crate) trait DirectoryReader {
+    /// Name under which the reader is registered and selected from the CLI or config.
+    fn name(&self) -> &'static str;
+
+    /// A copy of this reader limited to `max_concurrent_reads` in-flight reads, `None` for
+    /// readers without such a limit.
+    fn with_max_concurrent_reads(
+        &self,
+        _max_concurrent_reads: usize,
+    ) -> Option<Arc<dyn DirectoryReader + Send + Sync>> {
+        None
+    }
+
+    /// A copy of this reader leaving out the entries `ignore` filters, and everything below the
+    /// directories it filters.
+    fn with_ignore_filter(&self, ignore: IgnoreFilter) -> Arc<dyn DirectoryReader + Send + Sync>;
+
+    /// Reads the directories in `paths_queue` and everything below them into `arena`. The queue
+    /// holds the arena id of each directory along with its full path. The entries stored from
+    /// each directory are also sent to `sink`.
     async fn read_directories(
         &self,
-        files: &Arc<RwLock<Vec<PathBuf>>>,
-        dirs: &Arc<RwLock<Vec<PathBuf>>>,
-        paths_queue: &Arc<RwLock<Vec<PathBuf>>>,
+        arena: &Arc<RwLock<PathArena>>,
+        paths_queue: &Arc<RwLock<Vec<(EntryId, PathBuf)>>>,
+        sink: &ListingSink,
+    ) -> ReaderStats;
+}
+
+// The ignore filter of each queued directory by its arena id, the directories a scan starts
+// from take the filter of the reader
+struct QueuedFilters {
+    base: IgnoreFilter,
+    queued: std::sync::Mutex<HashMap<EntryId, IgnoreFilter>>,
+}
+
+impl QueuedFilters {
+    fn new(base: &IgnoreFilter) -> Self {
+        Self {
+            base: base.clone(),
+            queued: std::sync::Mutex::new(HashMap::new()),
+        }
+    }
+
+    // The filter for the entries of a directory that was read
+    fn enter(&self, id: EntryId, path: &Path) -> IgnoreFilter {
+        if !self.base.is_active() {
+            return self.base.clone();
+        }
+        let parent = self.queued.lock().unwrap().remove(&id);
+        parent.as_ref().unwrap_or(&self.base).enter(path)
+    }
+
+    fn queue(&self, ids: &[EntryId], filter: &IgnoreFilter) {
+        if !self.base.is_active() {
+            return;
+        }
+        let mut queued = self.queued.lock().unwrap();
+        queued.extend(ids.iter().map(|&id| (id, filter.clone())));
+    }
+}
+
+// Moves the entries read from one directory into the arena, leaving out the ignored ones, sends
+// them to the sink and queues its subdirectories
+#[allow(clippy::too_many_arguments)]
+async fn store_listing(
+    arena: &Arc<RwLock<PathArena>>,
+    paths_queue: &Arc<RwLock<Vec<(EntryId, PathBuf)>>>,
+    sink: &ListingSink,
+    filters: &QueuedFilters,
+    parent: EntryId,
+    parent_path: &Path,
+    files: &mut Vec<DirEntry>,
+    dirs: &mut Vec<DirEntry>,
+) {
+    let filter = filters.enter(parent, parent_path);
+    filter.retain(parent_path, files, dirs);
+
+    let listed = sink.is_active();
+    let mut file_ids = vec![];
+    let dir_ids: Vec<EntryId> = {
+        let mut arena_lock = arena.write().await;
+        for file in files.iter() {
+            let id = arena_lock.add_file(parent, &file.name);
+            if let Some(size) = file.size {
+                arena_lock.set_size(id, size);
+            }
+            if listed {
+                file_ids.push(id);
+            }
+        }
+        dirs.iter()
+            .map(|dir| {
+                let id = arena_lock.add_dir(parent, &dir.name);
+                if let Some(size) = dir.size {
+                    arena_lock.set_size(id, size);
+                }
+                id
+            })
+            .collect()
+    };
+    filters.queue(&dir_ids, &filter);
+
+    if listed {
+        let entries = file_ids
+            .into_iter()
+            .zip(files.drain(..))
+            .chain(dir_ids.iter().copied().zip(dirs.iter().cloned()))
+            .collect();
+        // Sent before the subdirectories are queued, their listings can only come after it
+        sink.send(parent, parent_path, entries).await;
+    }
+
+    let mut queue_lock = paths_queue.write().await;
+    queue_lock.extend(
+        dir_ids
+            .into_iter()
+            .zip(dirs)
+            .map(|(id, dir)| (id, parent_path.join(&dir.name))),
     );
 }
 
-pub struct ReadDirectories1;
+#[derive(Clone)]
+pub struct ReadDirectories1 {
+    max_concurrent_reads: usize,
+    adaptive_concurrency: bool,
+    fs: SharedFileSystem,
+    ignore: IgnoreFilter,
+}
+
+impl ReadDirectories1 {
+    pub fn new(max_concurrent_reads: usize) -> Self {
+        Self {
+            max_concurrent_reads: max_concurrent_reads.max(1),
+            adaptive_concurrency: false,
+            fs: OsFileSystem::shared(),
+            ignore: IgnoreFilter::default(),
+        }
+    }
+
+    /// Let the number of in-flight reads follow the observed latency and throughput, starting
+    /// from `max_concurrent_reads`.
+    pub fn with_adaptive_concurrency(mut self, adaptive_concurrency: bool) -> Self {
+        self.adaptive_concurrency = adaptive_concurrency;
+        self
+    }
+
+    /// Read from `fs` instead of the disk, e.g. a `MemoryFileSystem`.
+    pub fn with_file_system(mut self, fs: SharedFileSystem) -> Self {
+        self.fs = fs;
+        self
+    }
+
+    pub fn with_ignore(mut self, ignore: IgnoreFilter) -> Self {
+        self.ignore = ignore;
+        self
+    }
+}
+
+impl Default for ReadDirectories1 {
+    fn default() -> Self {
+        Self::new(MAX_CONCURRENT_READS)
+    }
+}
 
 #[async_trait]
 impl DirectoryReader for ReadDirectories1 {
+    fn name(&self) -> &'static str {
+        "semaphore"
+    }
+
+    fn with_max_concurrent_reads(
+        &self,
+        max_concurrent_reads: usize,
+    ) -> Option<Arc<dyn DirectoryReader + Send + Sync>> {
+        Some(Arc::new(ReadDirectories1 {
+            max_concurrent_reads: max_concurrent_reads.max(1),
+            ..self.clone()
+        }))
+    }
+
+    fn with_ignore_filter(&self, ignore: IgnoreFilter) -> Arc<dyn DirectoryReader + Send + Sync> {
+        Arc::new(self.clone().with_ignore(ignore))
+    }
+
     async fn read_directories(
         &self,
-        files: &Arc<RwLock<Vec<PathBuf>>>,
-        dirs: &Arc<RwLock<Vec<PathBuf>>>,
-        paths_queue: &Arc<RwLock<Vec<PathBuf>>>,
-    ) {
-        read_directories_1(files, dirs, paths_queue).await;
+        arena: &Arc<RwLock<PathArena>>,
+        paths_queue: &Arc<RwLock<Vec<(EntryId, PathBuf)>>>,
+        sink: &ListingSink,
+    ) -> ReaderStats {
+        let controller = if self.adaptive_concurrency {
+            ConcurrencyController::adaptive(self.max_concurrent_reads)
+        } else {
+            ConcurrencyController::fixed(self.max_concurrent_reads)
+        };
+        let controller = Arc::new(controller);
+
+        let errors = read_directories_1(
+            &self.fs,
+            &self.ignore,
+            arena,
+            paths_queue,
+            sink,
+            &controller,
+        )
+        .await;
+
+        ReaderStats {
+            concurrency: Some(controller.stats()),
+            errors,
+        }
     }
 }
 
 #[async_recursion]
 pub(crate) async fn read_directories_1(
-    files: &Arc<RwLock<Vec<PathBuf>>>,
-    dirs: &Arc<RwLock<Vec<PathBuf>>>,
-    paths_queue: &Arc<RwLock<Vec<PathBuf>>>,
-) {
+    fs: &SharedFileSystem,
+    ignore: &IgnoreFilter,
+    arena: &Arc<RwLock<PathArena>>,
+    paths_queue: &Arc<RwLock<Vec<(EntryId, PathBuf)>>>,
+    sink: &ListingSink,
+    controller: &Arc<ConcurrencyController>,
+) -> Vec<ReadError> {
     // info!("Started: read_directories_1");
 
-    // #[async_recursion]
-    // pub(crate) async fn read_directories(
-    //     files: &Arc<RwLock<Vec<DirEntry>>>,
-    //     dirs: &Arc<RwLock<Vec<DirEntry>>>,
-    //     paths_queue: &Arc<RwLock<VecDeque<PathBuf>>>,
-    //     semaphore: &Arc<Semaphore>,
-    // ) {
-    let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENT_READS));
-
-    // info!("Called with paths_queue: {:?}", paths_queue);
-
-    let max_files = 100_000;
-    let max_dirs = 18_000;
-
-    // info!("START: paths_queue here: {:?}", paths_queue);
-    while let Some(current_path) = {
-        let mut queue_guard = paths_queue.write().await;
-        queue_guard.pop()
-    } {
-        let permit = semaphore.clone().acquire_owned().await.unwrap();
-
-        let files_clone = Arc::clone(&files);
-        let dirs_clone = Arc::clone(&dirs);
-        let paths_queue_clone = Arc::clone(&paths_queue);
-
-        let task = task::spawn(async move {
-            let mut new_files = Vec::with_capacity(max_files);
-            let mut new_dirs = Vec::with_capacity(max_dirs);
-            let mut new_paths = Vec::with_capacity(max_dirs);
-
-            if let Err(e) =
-                read_directory_entries(&current_path, &mut new_files, &mut new_dirs, &mut new_paths)
-                    .await
-            {
-                eprintln!("Failed to read directory entries: {}", e);
-            } else {
-                {
-                    let mut files_lock = files_clone.write().await;
-                    let mut dirs_lock = dirs_clone.write().await;
-                    let mut queue_lock = paths_queue_clone.write().await;
-
-                    // Move the items into the respective collections
-                    files_lock.append(&mut new_files); // Moves new_files into files_lock
-                    dirs_lock.append(&mut new_dirs); // Moves new_dirs into dirs_lock
-                    queue_lock.append(&mut new_paths); // Moves local_paths_queue into queue_lock
+    let mut tasks = JoinSet::new();
+    let mut errors = vec![];
+    let filters = Arc::new(QueuedFilters::new(ignore));
+    let sink = Arc::new(sink.clone());
+
+    loop {
+        let next_path = {
+            let mut queue_guard = paths_queue.write().await;
+            queue_guard.pop()
+        };
+
+        let Some((current_id, current_path)) = next_path else {
+            // The queue is empty, wait for a running read to add more directories
+            match tasks.join_next().await {
+                Some(finished) => {
+                    collect_read(finished, &mut errors);
+                    continue;
+                }
+                None => break,
+            }
+        };
+
+        let permit = controller.acquire().await;
+
+        let arena_clone = Arc::clone(arena);
+        let paths_queue_clone = Arc::clone(paths_queue);
+        let controller_clone = Arc::clone(controller);
+        let filters = Arc::clone(&filters);
+        let sink = Arc::clone(&sink);
+        let fs = Arc::clone(fs);
+
+        tasks.spawn(async move {
+            // Up to the limit of the controller run at once, don't reserve much for each
+            let mut new_files = vec![];
+            let mut new_dirs = vec![];
+
+            let read_start = Instant::now();
+            let result =
+                read_directory_entries(fs.as_ref(), &current_path, &mut new_files, &mut new_dirs)
+                    .await;
+            let latency = read_start.elapsed();
+            let entries = new_files.len() + new_dirs.len();
+
+            // Release the semaphore permit before waiting for the shared collections
+            controller_clone.complete(permit, latency, entries);
+
+            if let Err(e) = result {
+                if e.kind() != io::ErrorKind::PermissionDenied {
+                    warn!("Failed to read {}: {}", current_path.display(), e);
                 }
+                return Some(ReadError::new(&current_path, &e));
             }
-            drop(permit); // Release the semaphore permit
+            store_listing(
+                &arena_clone,
+                &paths_queue_clone,
+                &sink,
+                &filters,
+                current_id,
+                &current_path,
+                &mut new_files,
+                &mut new_dirs,
+            )
+            .await;
+            None
         });
-        task.await.unwrap();
+
+        // Reap the finished reads so the set doesn't grow with the number of directories
+        while let Some(finished) = tasks.try_join_next() {
+            collect_read(finished, &mut errors);
+        }
     }
+
+    errors
+}
+
+fn collect_read(finished: Result<Option<ReadError>, JoinError>, errors: &mut Vec<ReadError>) {
+    match finished {
+        Ok(Some(error)) => errors.push(error),
+        Ok(None) => {}
+        Err(e) => error!("A directory read failed: {}", e),
+    }
+}
+
+#[derive(Clone)]
+pub struct ReadDirectories2 {
+    fs: SharedFileSystem,
+    ignore: IgnoreFilter,
 }
 
-pub struct ReadDirectories2;
+impl ReadDirectories2 {
+    /// Read from `fs` instead of the disk, e.g. a `MemoryFileSystem`.
+    pub fn with_file_system(mut self, fs: SharedFileSystem) -> Self {
+        self.fs = fs;
+        self
+    }
+
+    pub fn with_ignore(mut self, ignore: IgnoreFilter) -> Self {
+        self.ignore = ignore;
+        self
+    }
+}
+
+impl Default for ReadDirectories2 {
+    fn default() -> Self {
+        Self {
+            fs: OsFileSystem::shared(),
+            ignore: IgnoreFilter::default(),
+        }
+    }
+}
 
 #[async_trait]
 impl DirectoryReader for ReadDirectories2 {
+    fn name(&self) -> &'static str {
+        "sequential"
+    }
+
+    fn with_ignore_filter(&self, ignore: IgnoreFilter) -> Arc<dyn DirectoryReader + Send + Sync> {
+        Arc::new(self.clone().with_ignore(ignore))
+    }
+
     async fn read_directories(
         &self,
-        files: &Arc<RwLock<Vec<PathBuf>>>,
-        dirs: &Arc<RwLock<Vec<PathBuf>>>,
-        paths_queue: &Arc<RwLock<Vec<PathBuf>>>,
-    ) {
-        read_directories_2(files, dirs, paths_queue).await;
+        arena: &Arc<RwLock<PathArena>>,
+        paths_queue: &Arc<RwLock<Vec<(EntryId, PathBuf)>>>,
+        sink: &ListingSink,
+    ) -> ReaderStats {
+        ReaderStats {
+            errors: read_directories_2(&self.fs, &self.ignore, arena, paths_queue, sink).await,
+            ..ReaderStats::default()
+        }
     }
 }
 
 #[async_recursion]
 pub(crate) async fn read_directories_2(
-    files: &Arc<RwLock<Vec<PathBuf>>>,
-    dirs: &Arc<RwLock<Vec<PathBuf>>>,
-    paths_queue: &Arc<RwLock<Vec<PathBuf>>>,
-) {
+    fs: &SharedFileSystem,
+    ignore: &IgnoreFilter,
+    arena: &Arc<RwLock<PathArena>>,
+    paths_queue: &Arc<RwLock<Vec<(EntryId, PathBuf)>>>,
+    sink: &ListingSink,
+) -> Vec<ReadError> {
     // info!("Started: read_directories_2");
-    while let Some(current_path) = {
+    let mut errors = vec![];
+    let filters = QueuedFilters::new(ignore);
+    while let Some((current_id, current_path)) = {
         let mut queue_guard = paths_queue.write().await;
         queue_guard.pop()
     } {
@@ -145,137 +413,284 @@ pub(crate) async fn read_directories_2(
 
         let mut new_files = Vec::with_capacity(max_files);
         let mut new_dirs = Vec::with_capacity(max_dirs);
-        let mut new_paths = Vec::with_capacity(max_dirs);
-
-        read_directory_entries(&current_path, &mut new_files, &mut new_dirs, &mut new_paths)
-            .await
-            .expect("Thought we g");
-
-        {
-            let mut files_lock = files.write().await;
-            files_lock.extend(new_files);
-        }
 
+        if let Err(e) =
+            read_directory_entries(fs.as_ref(), &current_path, &mut new_files, &mut new_dirs).await
         {
-            let mut dirs_lock = dirs.write().await;
-            dirs_lock.extend(new_dirs);
+            errors.push(ReadError::new(&current_path, &e));
+            continue;
         }
 
-        {
-            let mut paths_queue_lock = paths_queue.write().await;
-            paths_queue_lock.extend(new_paths);
-        }
+        store_listing(
+            arena,
+            paths_queue,
+            sink,
+            &filters,
+            current_id,
+            &current_path,
+            &mut new_files,
+            &mut new_dirs,
+        )
+        .await;
     }
+
+    errors
+}
+
+/// Walks the disk with jwalk, which reads the directories itself: unlike the other readers it
+/// cannot read from a `FileSystem` such as a `MemoryFileSystem`.
+#[derive(Clone, Default)]
+pub struct ReadDirectories3 {
+    ignore: IgnoreFilter,
+    sizes: bool,
 }
 
-pub struct ReadDirectories3;
+impl ReadDirectories3 {
+    pub fn with_ignore(mut self, ignore: IgnoreFilter) -> Self {
+        self.ignore = ignore;
+        self
+    }
+
+    /// Keep the sizes of the entries like `OsFileSystem::listing_sizes`.
+    pub fn with_sizes(mut self, sizes: bool) -> Self {
+        self.sizes = sizes;
+        self
+    }
+}
 
 #[async_trait]
 impl DirectoryReader for ReadDirectories3 {
+    fn name(&self) -> &'static str {
+        "jwalk"
+    }
+
+    fn with_ignore_filter(&self, ignore: IgnoreFilter) -> Arc<dyn DirectoryReader + Send + Sync> {
+        Arc::new(self.clone().with_ignore(ignore))
+    }
+
     async fn read_directories(
         &self,
-        files: &Arc<RwLock<Vec<PathBuf>>>,
-        dirs: &Arc<RwLock<Vec<PathBuf>>>,
-        paths_queue: &Arc<RwLock<Vec<PathBuf>>>,
-    ) {
-        read_directories_3(files, dirs, paths_queue).await;
+        arena: &Arc<RwLock<PathArena>>,
+        paths_queue: &Arc<RwLock<Vec<(EntryId, PathBuf)>>>,
+        sink: &ListingSink,
+    ) -> ReaderStats {
+        ReaderStats {
+            errors: read_directories_3(&self.ignore, self.sizes, arena, paths_queue, sink).await,
+            ..ReaderStats::default()
+        }
     }
 }
 
 #[async_recursion]
 pub(crate) async fn read_directories_3(
-    files: &Arc<RwLock<Vec<PathBuf>>>,
-    dirs: &Arc<RwLock<Vec<PathBuf>>>,
-    paths_queue: &Arc<RwLock<Vec<PathBuf>>>,
-) {
+    ignore: &IgnoreFilter,
+    sizes: bool,
+    arena: &Arc<RwLock<PathArena>>,
+    paths_queue: &Arc<RwLock<Vec<(EntryId, PathBuf)>>>,
+    sink: &ListingSink,
+) -> Vec<ReadError> {
     // info!("Started: read_directories_3");
-    while let Some(start_path) = {
+    let mut errors = vec![];
+    while let Some((start_id, start_path)) = {
         let mut queue_guard = paths_queue.write().await;
         queue_guard.pop()
     } {
-        for entry in WalkDir::new(start_path).into_iter().filter_map(|e| e.ok()) {
-            let path = entry.path().to_path_buf();
-            if path.is_dir() {
-                {
-                    let mut dirs_lock = dirs.write().await;
-                    dirs_lock.push(path.clone());
+        // jwalk descends into the subdirectories itself, so they are not queued. Their ids are
+        // kept until the walk is done to look up the parent of each entry.
+        let mut dir_ids: HashMap<PathBuf, EntryId> = HashMap::new();
+        dir_ids.insert(start_path.clone(), start_id);
+
+        // The filter of each directory is handed down to its subdirectories as the state of
+        // the read. Hidden names are left to the filter like in the other readers.
+        let walk = WalkDirGeneric::<(IgnoreFilter, ())>::new(&start_path)
+            .skip_hidden(false)
+            .min_depth(1)
+            .root_read_dir_state(ignore.clone())
+            .process_read_dir(|depth, path, filter, children| {
+                if depth.is_none() || !filter.is_active() {
+                    return;
+                }
+                *filter = filter.enter(path);
+                children.retain(|child| match child {
+                    Ok(child) => !filter.is_ignored(&child.path(), child.file_type().is_dir()),
+                    Err(_) => true,
+                });
+            });
+
+        // The entries of a directory come one after the other and are sent together
+        let mut listed_dir: Option<(EntryId, PathBuf)> = None;
+        let mut listed = vec![];
+        for entry in walk {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    let path = e.path().unwrap_or(&start_path).to_path_buf();
+                    errors.push(ReadError::new(&path, &e));
+                    continue;
+                }
+            };
+
+            let Some(&parent) = dir_ids.get(entry.parent_path.as_ref()) else {
+                continue;
+            };
+
+            let metadata = match sizes {
+                true => entry.metadata().ok(),
+                false => None,
+            };
+            let dir_entry = DirEntry::listed(
+                entry.file_name().to_owned(),
+                FileKind::from(entry.file_type()),
+                &entry.path(),
+                metadata.as_ref(),
+            );
+            let id = {
+                let mut arena_lock = arena.write().await;
+                let id = if entry.file_type().is_dir() {
+                    let id = arena_lock.add_dir(parent, entry.file_name());
+                    dir_ids.insert(entry.path(), id);
+                    id
+                } else {
+                    arena_lock.add_file(parent, entry.file_name())
+                };
+                if let Some(size) = dir_entry.size {
+                    arena_lock.set_size(id, size);
                 }
-                {
-                    let mut paths_queue_lock = paths_queue.write().await;
-                    paths_queue_lock.push(path);
+                id
+            };
+
+            if sink.is_active() {
+                if listed_dir.as_ref().is_some_and(|(dir, _)| *dir != parent) {
+                    let (dir, dir_path) = listed_dir.take().unwrap();
+                    sink.send(dir, &dir_path, mem::take(&mut listed)).await;
                 }
-            } else {
-                let mut files_lock = files.write().await;
-                files_lock.push(path);
+                listed_dir.get_or_insert_with(|| (parent, entry.parent_path.to_path_buf()));
+                listed.push((id, dir_entry));
             }
         }
+        if let Some((dir, dir_path)) = listed_dir {
+            sink.send(dir, &dir_path, listed).await;
+        }
     }
+
+    errors
 }
 
-pub struct ReadDirectories4;
+#[derive(Clone)]
+pub struct ReadDirectories4 {
+    fs: SharedFileSystem,
+    ignore: IgnoreFilter,
+}
+
+impl ReadDirectories4 {
+    /// Read from `fs` instead of the disk, e.g. a `MemoryFileSystem`.
+    pub fn with_file_system(mut self, fs: SharedFileSystem) -> Self {
+        self.fs = fs;
+        self
+    }
+
+    pub fn with_ignore(mut self, ignore: IgnoreFilter) -> Self {
+        self.ignore = ignore;
+        self
+    }
+}
+
+impl Default for ReadDirectories4 {
+    fn default() -> Self {
+        Self {
+            fs: OsFileSystem::shared(),
+            ignore: IgnoreFilter::default(),
+        }
+    }
+}
 
 #[async_trait]
 impl DirectoryReader for crate::modules::directory_reader::ReadDirectories4 {
+    fn name(&self) -> &'static str {
+        "all_at_once"
+    }
+
+    fn with_ignore_filter(&self, ignore: IgnoreFilter) -> Arc<dyn DirectoryReader + Send + Sync> {
+        Arc::new(self.clone().with_ignore(ignore))
+    }
+
     async fn read_directories(
         &self,
-        files: &Arc<RwLock<Vec<PathBuf>>>,
-        dirs: &Arc<RwLock<Vec<PathBuf>>>,
-        paths_queue: &Arc<RwLock<Vec<PathBuf>>>,
-    ) {
-        crate::modules::directory_reader::directory_reader_impl::read_directories_4(
-            files,
-            dirs,
+        arena: &Arc<RwLock<PathArena>>,
+        paths_queue: &Arc<RwLock<Vec<(EntryId, PathBuf)>>>,
+        sink: &ListingSink,
+    ) -> ReaderStats {
+        let errors = crate::modules::directory_reader::directory_reader_impl::read_directories_4(
+            &self.fs,
+            &self.ignore,
+            arena,
             paths_queue,
+            sink,
         )
         .await;
+        ReaderStats {
+            errors,
+            ..ReaderStats::default()
+        }
     }
 }
 
 #[async_recursion]
 pub(crate) async fn read_directories_4(
-    files: &Arc<RwLock<Vec<PathBuf>>>,
-    dirs: &Arc<RwLock<Vec<PathBuf>>>,
-    paths_queue: &Arc<RwLock<Vec<PathBuf>>>,
-) {
+    fs: &SharedFileSystem,
+    ignore: &IgnoreFilter,
+    arena: &Arc<RwLock<PathArena>>,
+    paths_queue: &Arc<RwLock<Vec<(EntryId, PathBuf)>>>,
+    sink: &ListingSink,
+) -> Vec<ReadError> {
     // info!("Started: read_directories_4");
-    while let Some(current_path) = {
+    let mut errors = vec![];
+    let filters = QueuedFilters::new(ignore);
+    while let Some((current_id, current_path)) = {
         let mut queue_guard = paths_queue.write().await;
         queue_guard.pop()
     } {
-        match read_directory_all_at_once(&current_path).await {
-            Ok((new_files, new_dirs, new_paths)) => {
-                {
-                    let mut files_lock = files.write().await;
-                    files_lock.extend(new_files);
-                }
-
-                {
-                    let mut dirs_lock = dirs.write().await;
-                    dirs_lock.extend(new_dirs);
-                }
-
-                {
-                    let mut paths_queue_lock = paths_queue.write().await;
-                    paths_queue_lock.extend(new_paths);
-                }
+        match read_directory_all_at_once(fs.as_ref(), &current_path).await {
+            Ok((mut new_files, mut new_dirs)) => {
+                store_listing(
+                    arena,
+                    paths_queue,
+                    sink,
+                    &filters,
+                    current_id,
+                    &current_path,
+                    &mut new_files,
+                    &mut new_dirs,
+                )
+                .await;
             }
             Err(err) => {
-                if err.kind() == io::ErrorKind::PermissionDenied {
-                    // eprintln!("Warning: Access denied to directory {:?}", current_path);
-                } else {
-                    eprintln!(
-                        "Error: Failed to read directory {:?} - {:?}",
-                        current_path, err
-                    );
+                if err.kind() != io::ErrorKind::PermissionDenied {
+                    warn!("Failed to read {}: {}", current_path.display(), err);
                 }
+                errors.push(ReadError::new(&current_path, &err));
             }
         }
     }
+
+    errors
+}
+
+/// Counts the files and directories below `root` without keeping their names, as the drive
+/// totals are counted. What `ignore` filters is not counted.
+pub async fn count_entries(root: &Path, ignore: &IgnoreFilter) -> io::Result<(u64, u64)> {
+    let mut num_files = 0;
+    let mut num_dirs = 0;
+    count_all_disk_entries(root, ignore, &mut num_files, &mut num_dirs)
+        .await
+        .map_err(io::Error::other)?;
+    Ok((num_files, num_dirs))
 }
 
 #[async_recursion]
 pub(crate) async fn count_all_disk_entries(
-    root_path: &PathBuf,
+    root_path: &Path,
+    ignore: &IgnoreFilter,
     num_files: &mut u64,
     num_dirs: &mut u64,
 ) -> Result<(), UFFSError> {
@@ -284,19 +699,17 @@ pub(crate) async fn count_all_disk_entries(
     //     num_files, num_dirs
     // );
 
-    let wide_root_path: Vec<u16> = root_path.as_os_str().encode_wide().collect();
-
     let paths_queue = Arc::new(RwLock::new(Vec::with_capacity(MAX_DIRS)));
     {
         let mut paths_queue_lock = paths_queue.write().await;
-        paths_queue_lock.push(wide_root_path.clone());
+        paths_queue_lock.push((RawPath::from(root_path), ignore.clone()));
     }
 
     // info!("Started: count_all_disk_entries\n\n");
     //
     // info!("\n\nroot_path:\t{:?}\n\n", root_path);
 
-    while let Some(mut current_path) = {
+    while let Some((current_path, filter)) = {
         let mut queue_guard = paths_queue.write().await;
         queue_guard.pop()
     } {
@@ -320,8 +733,9 @@ pub(crate) async fn count_all_disk_entries(
 
         // Fastest direct SYSCALLS
         // Counts are bubbled up and processed here
+        let filter = filter.enter(Path::new(&current_path.as_os_str()));
         let (new_num_files, new_num_dirs, new_paths) =
-            count_disk_entries_all_at_once_new(&current_path)?;
+            count_disk_entries_all_at_once_new(&current_path, &filter)?;
         *num_files += new_num_files;
         *num_dirs += new_num_dirs;
 
@@ -332,7 +746,7 @@ pub(crate) async fn count_all_disk_entries(
 
         {
             let mut paths_queue_lock = paths_queue.write().await;
-            paths_queue_lock.extend(new_paths);
+            paths_queue_lock.extend(new_paths.into_iter().map(|path| (path, filter.clone())));
         }
     }
     // println!(
@@ -342,3 +756,219 @@ pub(crate) async fn count_all_disk_entries(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::file_system::faulty_fs::{Fault, FaultyFileSystem};
+    use crate::modules::file_system::memory_fs::MemoryFileSystem;
+    use std::collections::BTreeSet;
+    use std::time::Duration;
+
+    const FAULTS: [Fault; 5] = [
+        Fault::PermissionDenied,
+        Fault::Vanished,
+        Fault::Io,
+        Fault::NameTooLong,
+        Fault::Slow(Duration::from_millis(5)),
+    ];
+
+    type Listing = (BTreeSet<PathBuf>, BTreeSet<PathBuf>, Vec<ReadError>);
+
+    // The files and directories below `root` as `reader` lists them, with the read errors
+    async fn read_tree(reader: &(dyn DirectoryReader + Send + Sync), root: &Path) -> Listing {
+        let mut arena = PathArena::new();
+        let root_id = arena.add_root(root);
+        let arena = Arc::new(RwLock::new(arena));
+        let paths_queue = Arc::new(RwLock::new(vec![(root_id, root.to_path_buf())]));
+
+        let stats = reader
+            .read_directories(&arena, &paths_queue, &ListingSink::default())
+            .await;
+        let arena = arena.read().await;
+        let files = arena.file_paths().collect();
+        let dirs = arena.dir_paths().filter(|dir| dir != root).collect();
+        (files, dirs, stats.errors)
+    }
+
+    fn memory_readers(fs: &SharedFileSystem) -> Vec<Arc<dyn DirectoryReader + Send + Sync>> {
+        vec![
+            Arc::new(ReadDirectories1::new(4).with_file_system(Arc::clone(fs))),
+            Arc::new(
+                ReadDirectories1::new(4)
+                    .with_adaptive_concurrency(true)
+                    .with_file_system(Arc::clone(fs)),
+            ),
+            Arc::new(ReadDirectories2::default().with_file_system(Arc::clone(fs))),
+            Arc::new(ReadDirectories4::default().with_file_system(Arc::clone(fs))),
+        ]
+    }
+
+    fn disk_readers() -> Vec<Arc<dyn DirectoryReader + Send + Sync>> {
+        vec![
+            Arc::new(ReadDirectories1::new(4)),
+            Arc::new(ReadDirectories2::default()),
+            Arc::new(ReadDirectories3::default()),
+            Arc::new(ReadDirectories4::default()),
+        ]
+    }
+
+    fn paths(root: &Path, names: &[&str]) -> BTreeSet<PathBuf> {
+        names.iter().map(|name| root.join(name)).collect()
+    }
+
+    fn memory_tree() -> MemoryFileSystem {
+        let memory = MemoryFileSystem::new();
+        for file in ["a/b/c.txt", "a/d.txt", "f.txt", "g/h/i/j.txt"] {
+            memory.add_file(Path::new("/data").join(file)).unwrap();
+        }
+        memory.create_dir_all("/data/e").unwrap();
+        memory.add_symlink("/data/link", "a").unwrap();
+        memory
+    }
+
+    #[tokio::test]
+    async fn readers_list_the_same_memory_tree() {
+        let fs: SharedFileSystem = Arc::new(memory_tree());
+
+        let root = Path::new("/data");
+        let expected_files = paths(
+            root,
+            &["a/b/c.txt", "a/d.txt", "f.txt", "g/h/i/j.txt", "link"],
+        );
+        let expected_dirs = paths(root, &["a", "a/b", "e", "g", "g/h", "g/h/i"]);
+        for reader in memory_readers(&fs) {
+            let (files, dirs, errors) = read_tree(reader.as_ref(), root).await;
+            assert_eq!(files, expected_files, "{}", reader.name());
+            assert_eq!(dirs, expected_dirs, "{}", reader.name());
+            assert!(errors.is_empty(), "{}: {:?}", reader.name(), errors);
+        }
+    }
+
+    #[tokio::test]
+    async fn readers_list_the_same_disk_tree() {
+        let root = tempfile::tempdir().unwrap();
+        for dir in ["a/b", "e", "g/h/i"] {
+            std::fs::create_dir_all(root.path().join(dir)).unwrap();
+        }
+        for file in ["a/b/c.txt", "a/d.txt", "f.txt", "g/h/i/j.txt"] {
+            std::fs::write(root.path().join(file), b"").unwrap();
+        }
+
+        let expected_files = paths(
+            root.path(),
+            &["a/b/c.txt", "a/d.txt", "f.txt", "g/h/i/j.txt"],
+        );
+        let expected_dirs = paths(root.path(), &["a", "a/b", "e", "g", "g/h", "g/h/i"]);
+        for reader in disk_readers() {
+            let (files, dirs, errors) = read_tree(reader.as_ref(), root.path()).await;
+            assert_eq!(files, expected_files, "{}", reader.name());
+            assert_eq!(dirs, expected_dirs, "{}", reader.name());
+            assert!(errors.is_empty(), "{}: {:?}", reader.name(), errors);
+        }
+    }
+
+    #[tokio::test]
+    async fn readers_send_each_directory_after_the_listing_it_is_in() {
+        let root = tempfile::tempdir().unwrap();
+        for dir in ["a/b", "e", "g/h/i"] {
+            std::fs::create_dir_all(root.path().join(dir)).unwrap();
+        }
+        for file in ["a/b/c.txt", "a/d.txt", "f.txt", "g/h/i/j.txt"] {
+            std::fs::write(root.path().join(file), b"abc").unwrap();
+        }
+
+        for reader in disk_readers() {
+            let mut sink = ListingSink::default();
+            let listings = sink.subscribe();
+            let mut arena = PathArena::new();
+            let root_id = arena.add_root(root.path());
+            let arena = Arc::new(RwLock::new(arena));
+            let paths_queue = Arc::new(RwLock::new(vec![(root_id, root.path().to_path_buf())]));
+            reader
+                .read_directories(&arena, &paths_queue, &sink.for_volume(root.path()))
+                .await;
+            drop(sink);
+
+            let mut listed_dirs = vec![root_id];
+            let mut listed = BTreeSet::new();
+            for listing in listings.drain() {
+                assert_eq!(listing.volume.as_ref(), root.path(), "{}", reader.name());
+                assert!(listed_dirs.contains(&listing.dir), "{}", reader.name());
+                for (id, entry) in &listing.entries {
+                    if entry.kind == FileKind::Dir {
+                        listed_dirs.push(*id);
+                    }
+                    listed.insert(listing.dir_path.join(&entry.name));
+                }
+            }
+            let arena = arena.read().await;
+            let expected: BTreeSet<PathBuf> = arena.file_paths().chain(arena.dir_paths()).collect();
+            assert_eq!(
+                listed,
+                &expected - &BTreeSet::from([root.path().to_path_buf()])
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn readers_report_the_faults_and_read_on() {
+        let root = Path::new("/data");
+        let memory: SharedFileSystem = Arc::new(memory_tree());
+
+        for fault in FAULTS {
+            let faulty = FaultyFileSystem::new(Arc::clone(&memory))
+                .fail_matching("/data/a", fault)
+                .unwrap();
+            let fs: SharedFileSystem = Arc::new(faulty);
+
+            // The failed directory is listed by its parent, the entries below it are lost
+            let (expected_files, expected_dirs) = match fault {
+                Fault::Slow(_) => (
+                    paths(
+                        root,
+                        &["a/b/c.txt", "a/d.txt", "f.txt", "g/h/i/j.txt", "link"],
+                    ),
+                    paths(root, &["a", "a/b", "e", "g", "g/h", "g/h/i"]),
+                ),
+                _ => (
+                    paths(root, &["f.txt", "g/h/i/j.txt", "link"]),
+                    paths(root, &["a", "e", "g", "g/h", "g/h/i"]),
+                ),
+            };
+            for reader in memory_readers(&fs) {
+                let (files, dirs, errors) = read_tree(reader.as_ref(), root).await;
+                let failed: Vec<&Path> = errors.iter().map(|e| e.path.as_path()).collect();
+                assert_eq!(files, expected_files, "{} {:?}", reader.name(), fault);
+                assert_eq!(dirs, expected_dirs, "{} {:?}", reader.name(), fault);
+                match fault {
+                    Fault::Slow(_) => assert!(failed.is_empty(), "{}", reader.name()),
+                    _ => assert_eq!(failed, [root.join("a")], "{} {:?}", reader.name(), fault),
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn readers_survive_faults_everywhere() {
+        let root = Path::new("/data");
+        let memory: SharedFileSystem = Arc::new(memory_tree());
+
+        for fault in FAULTS {
+            let faulty = FaultyFileSystem::new(Arc::clone(&memory)).fail_randomly(1.0, fault);
+            let fs: SharedFileSystem = Arc::new(faulty);
+            for reader in memory_readers(&fs) {
+                let (files, dirs, errors) = read_tree(reader.as_ref(), root).await;
+                if let Fault::Slow(_) = fault {
+                    assert_eq!(files.len(), 5, "{}", reader.name());
+                    assert_eq!(dirs.len(), 6, "{}", reader.name());
+                    assert!(errors.is_empty(), "{}", reader.name());
+                } else {
+                    // Even the root fails, the read ends with that one error
+                    assert!(files.is_empty() && dirs.is_empty(), "{}", reader.name());
+                    assert_eq!(errors.len(), 1, "{} {:?}", reader.name(), fault);
+                }
+            }
+        }
+    }
+}
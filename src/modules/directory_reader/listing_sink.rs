@@ -0,0 +1,81 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::config::constants::EXPORT_QUEUE_LISTINGS;
+use crate::modules::file_system::DirEntry;
+use crate::modules::path_arena::EntryId;
+
+/// The entries a reader stored from one directory, as they were listed.
+// Only the exports read them, which can be left out of the build. The id of the directory only
+// matters to SQLite.
+#[cfg_attr(not(feature = "sqlite"), allow(dead_code))]
+#[derive(Debug)]
+pub(crate) struct Listing {
+    // Root of the volume being scanned
+    pub(crate) volume: Arc<Path>,
+    pub(crate) dir: EntryId,
+    pub(crate) dir_path: PathBuf,
+    // The arena id of each entry along with what was listed about it
+    pub(crate) entries: Vec<(EntryId, DirEntry)>,
+}
+
+/// Hands the listings of a scan to the exports written while the volumes are read. A directory
+/// is always sent after the listing it appears in.
+#[derive(Debug, Clone)]
+pub(crate) struct ListingSink {
+    volume: Arc<Path>,
+    senders: Vec<flume::Sender<Arc<Listing>>>,
+}
+
+impl Default for ListingSink {
+    fn default() -> Self {
+        Self {
+            volume: Arc::from(Path::new("")),
+            senders: vec![],
+        }
+    }
+}
+
+impl ListingSink {
+    /// Adds an export to the ones the listings are sent to. The readers wait once it falls
+    /// `EXPORT_QUEUE_LISTINGS` listings behind.
+    pub(crate) fn subscribe(&mut self) -> flume::Receiver<Arc<Listing>> {
+        let (sender, receiver) = flume::bounded(EXPORT_QUEUE_LISTINGS);
+        self.senders.push(sender);
+        receiver
+    }
+
+    /// Whether any export still takes the listings, the readers only collect them if so.
+    pub(crate) fn is_active(&self) -> bool {
+        self.senders.iter().any(|sender| !sender.is_disconnected())
+    }
+
+    /// The sink for the scan of the volume at `root`.
+    pub(crate) fn for_volume(&self, root: &Path) -> Self {
+        Self {
+            volume: Arc::from(root),
+            senders: self.senders.clone(),
+        }
+    }
+
+    pub(crate) async fn send(
+        &self,
+        dir: EntryId,
+        dir_path: &Path,
+        entries: Vec<(EntryId, DirEntry)>,
+    ) {
+        if !self.is_active() {
+            return;
+        }
+        let listing = Arc::new(Listing {
+            volume: Arc::clone(&self.volume),
+            dir,
+            dir_path: dir_path.to_path_buf(),
+            entries,
+        });
+        for sender in &self.senders {
+            // An export that failed stopped receiving, the scan and the other exports go on
+            let _ = sender.send_async(Arc::clone(&listing)).await;
+        }
+    }
+}
@@ -1,8 +1,17 @@
+mod concurrency_controller;
 pub mod directory_reader_impl;
+mod listing_sink;
+
+pub(crate) use concurrency_controller::ConcurrencyStats;
 
 pub(crate) use directory_reader_impl::count_all_disk_entries;
+pub(crate) use directory_reader_impl::count_entries;
 pub(crate) use directory_reader_impl::DirectoryReader;
 pub(crate) use directory_reader_impl::ReadDirectories1;
 pub(crate) use directory_reader_impl::ReadDirectories2;
 pub(crate) use directory_reader_impl::ReadDirectories3;
 pub(crate) use directory_reader_impl::ReadDirectories4;
+pub(crate) use directory_reader_impl::ReaderStats;
+
+pub(crate) use listing_sink::Listing;
+pub(crate) use listing_sink::ListingSink;
@@ -0,0 +1,342 @@
+use std::ffi::OsStr;
+use std::path::Path;
+
+#[cfg(target_os = "linux")]
+use std::fs;
+#[cfg(target_os = "linux")]
+use std::path::PathBuf;
+
+/// The physical disks behind a mount point. Partitions of the same disk map to the same disk, a
+/// volume spanning several disks (LVM, md RAID, spanned Windows volumes) lists all of them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct BlockDevice {
+    // Sorted, without duplicates
+    pub(crate) disks: Vec<String>,
+    pub(crate) rotational: Option<bool>,
+}
+
+impl BlockDevice {
+    fn new(mut disks: Vec<String>, rotational: Option<bool>) -> Option<Self> {
+        if disks.is_empty() {
+            return None;
+        }
+        disks.sort();
+        disks.dedup();
+        Some(Self { disks, rotational })
+    }
+}
+
+// Resolves the mount through /sys/dev/block/MAJOR:MINOR, falling back to the device name
+// (e.g. /dev/sda1) for file systems reporting an anonymous device number like btrfs.
+#[cfg(target_os = "linux")]
+pub(crate) fn block_device_for_mount(
+    mount_point: &Path,
+    device_name: &OsStr,
+) -> Option<BlockDevice> {
+    let sys_path = sys_path_from_device_number(mount_point)
+        .or_else(|| sys_path_from_device_name(device_name))?;
+
+    let disks = physical_disks(&sys_path);
+
+    // A volume spanning a spinning disk is as slow as that disk
+    let rotational = disks
+        .iter()
+        .map(|disk| is_rotational(disk))
+        .try_fold(false, |any, rotational| Some(any || rotational?));
+
+    BlockDevice::new(disks, rotational)
+}
+
+// Asks the volume manager for the disks the extents of the drive letter's volume lie on, they
+// are named like their device, e.g. PhysicalDrive0. Whether they spin is left to sysinfo.
+#[cfg(windows)]
+pub(crate) fn block_device_for_mount(
+    mount_point: &Path,
+    _device_name: &OsStr,
+) -> Option<BlockDevice> {
+    use std::fs::OpenOptions;
+    use std::mem;
+    use std::os::windows::fs::OpenOptionsExt;
+    use std::os::windows::io::AsRawHandle;
+    use std::path::Component;
+    use std::ptr;
+    use winapi::shared::minwindef::DWORD;
+    use winapi::um::ioapiset::DeviceIoControl;
+    use winapi::um::winioctl::{DISK_EXTENT, IOCTL_VOLUME_GET_VOLUME_DISK_EXTENTS};
+    use winapi::um::winnt::{FILE_SHARE_READ, FILE_SHARE_WRITE};
+
+    // VOLUME_DISK_EXTENTS with room for a volume spanning up to 32 disks
+    #[repr(C)]
+    struct DiskExtents {
+        count: DWORD,
+        extents: [DISK_EXTENT; 32],
+    }
+
+    let Some(Component::Prefix(prefix)) = mount_point.components().next() else {
+        return None;
+    };
+    // Opening without access is enough to query the volume, and needs no elevation
+    let volume = OpenOptions::new()
+        .access_mode(0)
+        .share_mode(FILE_SHARE_READ | FILE_SHARE_WRITE)
+        .open(format!(r"\\.\{}", prefix.as_os_str().to_string_lossy()))
+        .ok()?;
+
+    let mut extents: DiskExtents = unsafe { mem::zeroed() };
+    let mut returned: DWORD = 0;
+    let succeeded = unsafe {
+        DeviceIoControl(
+            volume.as_raw_handle() as _,
+            IOCTL_VOLUME_GET_VOLUME_DISK_EXTENTS,
+            ptr::null_mut(),
+            0,
+            &mut extents as *mut DiskExtents as *mut _,
+            mem::size_of::<DiskExtents>() as DWORD,
+            &mut returned,
+            ptr::null_mut(),
+        )
+    };
+    if succeeded == 0 {
+        return None;
+    }
+
+    let count = (extents.count as usize).min(extents.extents.len());
+    let disks = extents.extents[..count]
+        .iter()
+        .map(|extent| format!("PhysicalDrive{}", extent.DiskNumber))
+        .collect();
+    BlockDevice::new(disks, None)
+}
+
+// Elsewhere the disks are unknown, every volume is taken to be on a device of its own
+#[cfg(not(any(target_os = "linux", windows)))]
+pub(crate) fn block_device_for_mount(
+    _mount_point: &Path,
+    _device_name: &OsStr,
+) -> Option<BlockDevice> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn sys_path_from_device_number(mount_point: &Path) -> Option<PathBuf> {
+    use std::os::unix::fs::MetadataExt;
+
+    let sys_path = sys_path_for_device(fs::metadata(mount_point).ok()?.dev())?;
+    sys_path.exists().then_some(sys_path)
+}
+
+// Where sysfs lists the device number `dev`. None for the anonymous devices of major 0.
+#[cfg(target_os = "linux")]
+fn sys_path_for_device(dev: u64) -> Option<PathBuf> {
+    // Same split as glibc's major() / minor()
+    let major = ((dev >> 8) & 0xfff) | ((dev >> 32) & 0xffff_f000);
+    let minor = (dev & 0xff) | ((dev >> 12) & 0xffff_ff00);
+    if major == 0 {
+        return None;
+    }
+    Some(PathBuf::from(format!("/sys/dev/block/{}:{}", major, minor)))
+}
+
+#[cfg(target_os = "linux")]
+fn sys_path_from_device_name(device_name: &OsStr) -> Option<PathBuf> {
+    // /dev/mapper/* entries are links to /dev/dm-*
+    let device = fs::canonicalize(device_name).ok()?;
+    let name = device.file_name()?;
+
+    let sys_path = Path::new("/sys/class/block").join(name);
+    sys_path.exists().then_some(sys_path)
+}
+
+#[cfg(target_os = "linux")]
+fn physical_disks(sys_path: &Path) -> Vec<String> {
+    let Ok(canonical) = fs::canonicalize(sys_path) else {
+        return vec![];
+    };
+
+    // A partition lives in the directory of its disk: .../block/sda/sda1
+    let disk_dir = if canonical.join("partition").exists() {
+        match canonical.parent() {
+            Some(parent) => parent.to_path_buf(),
+            None => return vec![],
+        }
+    } else {
+        canonical
+    };
+
+    // Device mapper and md devices list the devices they are built on as slaves
+    let slaves: Vec<PathBuf> = fs::read_dir(disk_dir.join("slaves"))
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|entry| Path::new("/sys/class/block").join(entry.file_name()))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    if slaves.is_empty() {
+        disk_dir
+            .file_name()
+            .map(|name| vec![name.to_string_lossy().into_owned()])
+            .unwrap_or_default()
+    } else {
+        slaves
+            .iter()
+            .flat_map(|slave| physical_disks(slave))
+            .collect()
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn is_rotational(disk: &str) -> Option<bool> {
+    let flag =
+        fs::read_to_string(Path::new("/sys/block").join(disk).join("queue/rotational")).ok()?;
+    Some(flag.trim() == "1")
+}
+
+/// Scan targets sharing physical disks.
+pub(crate) struct DeviceGroup<T> {
+    // Every disk of the members, sorted
+    pub(crate) disks: Vec<String>,
+    pub(crate) rotational: bool,
+    pub(crate) members: Vec<T>,
+}
+
+impl<T> DeviceGroup<T> {
+    fn shares_disk(&self, disks: &[String]) -> bool {
+        self.disks.iter().any(|disk| disks.contains(disk))
+    }
+
+    fn absorb(&mut self, other: DeviceGroup<T>) {
+        self.disks.extend(other.disks);
+        self.disks.sort();
+        self.disks.dedup();
+        self.rotational |= other.rotational;
+        self.members.extend(other.members);
+    }
+}
+
+/// Groups `items` by the disks and rotational flag `key` returns for them, in order of first
+/// appearance. Items sharing any disk are in the same group: a partition of sda goes with an
+/// LVM volume on sda and sdb, and so does a partition of sdb.
+pub(crate) fn group_by_device<T, F>(items: Vec<T>, key: F) -> Vec<DeviceGroup<T>>
+where
+    F: Fn(&T) -> (Vec<String>, bool),
+{
+    let mut groups: Vec<DeviceGroup<T>> = vec![];
+
+    for item in items {
+        let (disks, rotational) = key(&item);
+        let item = DeviceGroup {
+            disks,
+            rotational,
+            members: vec![item],
+        };
+
+        let overlapping: Vec<usize> = (0..groups.len())
+            .filter(|&i| groups[i].shares_disk(&item.disks))
+            .collect();
+        let Some((&first, later)) = overlapping.split_first() else {
+            groups.push(item);
+            continue;
+        };
+
+        // The item joins the groups of all its disks into the first one
+        let mut joined: Vec<DeviceGroup<T>> =
+            later.iter().rev().map(|&i| groups.remove(i)).collect();
+        joined.reverse();
+        joined.push(item);
+        for other in joined {
+            groups[first].absorb(other);
+        }
+    }
+
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn disks(names: &str) -> Vec<String> {
+        names.split('+').map(String::from).collect()
+    }
+
+    fn grouped(items: &[(&'static str, &str, bool)]) -> Vec<(String, bool, Vec<&'static str>)> {
+        group_by_device(items.to_vec(), |&(_, names, rotational)| {
+            (disks(names), rotational)
+        })
+        .into_iter()
+        .map(|group| {
+            let members = group.members.iter().map(|&(volume, _, _)| volume).collect();
+            (group.disks.join("+"), group.rotational, members)
+        })
+        .collect()
+    }
+
+    #[test]
+    fn partitions_of_a_disk_are_grouped() {
+        assert_eq!(
+            grouped(&[
+                ("/", "sda", false),
+                ("/home", "nvme0n1", false),
+                ("/data", "sda", true)
+            ]),
+            [
+                ("sda".to_string(), true, vec!["/", "/data"]),
+                ("nvme0n1".to_string(), false, vec!["/home"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_volume_spanning_disks_joins_their_groups() {
+        assert_eq!(
+            grouped(&[("/boot", "sda", true), ("/srv", "sda+sdb", false)]),
+            [("sda+sdb".to_string(), true, vec!["/boot", "/srv"])]
+        );
+
+        // Groups that were apart until a volume spanning both came along
+        assert_eq!(
+            grouped(&[
+                ("/a", "sda", false),
+                ("/n", "nvme0n1", false),
+                ("/c", "sdc", true),
+                ("/raid", "sdc+sda", false),
+                ("/b", "sdb+sdc", false),
+            ]),
+            [
+                (
+                    "sda+sdb+sdc".to_string(),
+                    true,
+                    vec!["/a", "/c", "/raid", "/b"]
+                ),
+                ("nvme0n1".to_string(), false, vec!["/n"]),
+            ]
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn device_numbers_are_split_like_glibc() {
+        // glibc's makedev()
+        fn device_number(major: u64, minor: u64) -> u64 {
+            ((major & 0xfff) << 8)
+                | ((major & !0xfff) << 32)
+                | (minor & 0xff)
+                | ((minor & !0xff) << 12)
+        }
+
+        let sys_path = |major, minor| sys_path_for_device(device_number(major, minor));
+        assert_eq!(sys_path(8, 1), Some(PathBuf::from("/sys/dev/block/8:1")));
+        assert_eq!(
+            sys_path(259, 3),
+            Some(PathBuf::from("/sys/dev/block/259:3"))
+        );
+        assert_eq!(
+            sys_path(4_100, 1_048_577),
+            Some(PathBuf::from("/sys/dev/block/4100:1048577"))
+        );
+        // btrfs and other file systems without a block device of their own
+        assert_eq!(sys_path(0, 42), None);
+    }
+}
@@ -1,24 +1,56 @@
 use anyhow::{Error, Result};
-use std::os::windows::prelude::OsStrExt;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::Instant;
-
-use crate::config::constants::{LOG_DATE_FORMAT, MAX_DIRS, MAX_DIRS_ALL, MAX_FILES_ALL};
-use crate::modules::directory_reader::{count_all_disk_entries, DirectoryReader, ReadDirectories4};
-use crate::modules::disk_reader::DriveInfo;
-use crate::modules::errors::UFFSError;
-use crate::modules::utils::{
-    format_duration, format_number, format_size, get_drive_letter, vec_u16_to_string,
+use std::time::{Instant, SystemTime};
+
+use crate::config::constants::MAX_DIRS;
+use crate::modules::algo_selector::algo_selector_impl::same_root;
+use crate::modules::algo_selector::{BenchmarkHistory, SharedDirectoryReader};
+use crate::modules::daemon::serve;
+use crate::modules::directory_reader::{
+    count_all_disk_entries, DirectoryReader, ListingSink, ReadDirectories4, ReaderStats,
 };
-use chrono::Local;
-use colored::*;
+use crate::modules::disk_reader::block_device::{block_device_for_mount, group_by_device};
+use crate::modules::disk_reader::{DriveInfo, DriveType};
+use crate::modules::efu::export_efu;
+use crate::modules::errors::UFFSError;
+use crate::modules::ignore_filter::IgnoreFilter;
+use crate::modules::locate::{write_mlocate_db, write_plocate_db};
+use crate::modules::output::{write_matches, write_results, write_usage, OutputOptions};
+use crate::modules::parquet_export::ParquetExport;
+use crate::modules::path_arena::PathArena;
+use crate::modules::scanner::{ScanResult, VolumeScanResult};
+use crate::modules::sqlite::SqliteExport;
+use crate::modules::tui::run_tui;
+use crate::modules::usage::usage_rows;
+use crate::modules::utils::{format_duration, format_memory, get_drive_letter};
+use crate::modules::watch::watch_volumes;
 use futures::future::join_all;
+use sysinfo::Disk;
 pub(crate) use sysinfo::Disks;
-use tokio::io::AsyncWriteExt;
 use tokio::sync::RwLock;
 use tokio::task;
-use tracing::{error, info};
+use tracing::{error, info, warn};
+
+// The physical disks a volume lives on and its type. The rotational flag of the block device is
+// more reliable than sysinfo's guess, which falls back to Unknown for partitions and LVM volumes.
+// A volume on unknown disks gets its mount point instead, it shares them with no other volume.
+fn locate_disk(disk: &Disk) -> (DriveType, Vec<String>) {
+    let mount_point = disk.mount_point();
+    match block_device_for_mount(mount_point, disk.name()) {
+        Some(device) => (
+            device
+                .rotational
+                .map(DriveType::from_rotational)
+                .unwrap_or_else(|| DriveType::from(disk.kind())),
+            device.disks,
+        ),
+        None => (
+            DriveType::from(disk.kind()),
+            vec![mount_point.to_string_lossy().into_owned()],
+        ),
+    }
+}
 
 pub async fn init_drives() -> Result<Vec<DriveInfo>> {
     info!("got to:   init_drives");
@@ -29,17 +61,19 @@ pub async fn init_drives() -> Result<Vec<DriveInfo>> {
 
     for disk in disks.iter() {
         let mount_point = disk.mount_point().to_path_buf();
-        let drive_type = format!("{:?}", disk.kind());
+        let (drive_type, disks) = locate_disk(disk);
+        let file_system = disk.file_system().to_string_lossy().into_owned();
         let size_gb = disk.total_space() as f64 / 1_073_741_824.0; // Convert bytes to GB
 
         let task = task::spawn(async move {
             let (num_files, num_dirs) = get_file_dir_len(&mount_point).await?;
-            let directory_reader: Box<dyn DirectoryReader + Send + Sync + 'static> =
-                Box::new(ReadDirectories4);
+            let directory_reader: SharedDirectoryReader = Arc::new(ReadDirectories4::default());
 
             let drive_info = DriveInfo::new(
                 mount_point,
                 drive_type,
+                disks,
+                file_system,
                 size_gb,
                 num_files,
                 num_dirs,
@@ -59,8 +93,8 @@ pub async fn init_drives() -> Result<Vec<DriveInfo>> {
     for result in drives_info_results {
         match result {
             Ok(Ok(drive_info)) => drives_info.push(drive_info),
-            Ok(Err(e)) => eprintln!("Error processing drive: {}", e),
-            Err(e) => eprintln!("Task join error: {}", e),
+            Ok(Err(e)) => error!("Error processing drive: {}", e),
+            Err(e) => error!("Task join error: {}", e),
         }
     }
 
@@ -71,271 +105,388 @@ pub async fn init_drives() -> Result<Vec<DriveInfo>> {
     Ok(drives_info)
 }
 
-pub async fn process_all_disks<T>(current_directory_reader: Arc<T>)
-where
-    T: DirectoryReader + Send + Sync + 'static,
-{
-    let start = Instant::now();
+// Lists the mounted drives without counting their entries, the counts of the last scan are
+// taken from the benchmark history instead.
+pub(crate) fn discover_drives(
+    history: &BenchmarkHistory,
+    default_reader: &SharedDirectoryReader,
+) -> Vec<DriveInfo> {
+    let disks = Disks::new_with_refreshed_list();
 
-    // let disk_info = get_disk_info();
-    let original_data = [
-        ("C:\\", "SSD", 1932735217664u64),
-        ("D:\\", "HDD", 8001427599360u64),
-        ("D:\\Google Drive\\", "Unknown(-1)", 1932735217664u64),
-        ("E:\\", "HDD", 1000202039296u64),
-        ("F:\\", "SSD", 918905880576u64),
-        ("M:\\", "HDD", 4000650883072u64),
-        ("S:\\", "HDD", 8001545039872u64),
-    ];
-    // Convert to Vec<(String, String, u64)>
-    let disk_info: Vec<(String, String, u64)> = original_data
+    let mut drives_info: Vec<DriveInfo> = disks
         .iter()
-        .map(|(a, b, c)| (a.to_string(), b.to_string(), *c))
+        .map(|disk| {
+            let mount_point = disk.mount_point().to_path_buf();
+            let (num_files, num_dirs, time_seconds) = history
+                .latest_for(&mount_point.to_string_lossy())
+                .map(|r| (r.num_files, r.num_dirs, r.seconds))
+                .unwrap_or((0, 0, 0.0));
+            let (drive_type, disks) = locate_disk(disk);
+
+            DriveInfo::new(
+                mount_point,
+                drive_type,
+                disks,
+                disk.file_system().to_string_lossy().into_owned(),
+                disk.total_space() as f64 / 1_073_741_824.0, // Convert bytes to GB
+                num_files,
+                num_dirs,
+                time_seconds,
+                Arc::clone(default_reader),
+            )
+        })
         .collect();
 
-    // println!("{:?}", disk_info);
-    // exit(1);
-
-    let paths: Vec<PathBuf> = disk_info
-        .iter()
-        .map(|(path, _, _)| PathBuf::from(path))
-        // .filter(|p| desired_drives.contains(&p.to_str().unwrap()))
-        .collect();
+    drives_info.sort_by(|a, b| a.root_path.cmp(&b.root_path));
 
-    let mut results = vec![];
+    drives_info
+}
 
-    let mut total_files = 0;
-    let mut total_dirs = 0;
-    let mut total_size = 0u64;
+// Scans every drive with the reader selected for it and records the timings in the history
+pub(crate) async fn process_drives(
+    drives: Vec<DriveInfo>,
+    history: &mut BenchmarkHistory,
+    output: &OutputOptions,
+) {
+    let start = Instant::now();
 
-    // Create tasks for each path
-    let tasks: Vec<_> = paths
+    let disk_info: Vec<(String, DriveType, u64)> = drives
         .iter()
-        .map(|path| {
-            let path = path.clone(); // Clone the PathBuf to extend its lifetime
-            let disk_info = disk_info.clone();
-            let directory_reader_clone = Arc::clone(&current_directory_reader);
-            task::spawn(async move {
-                let (files_all, dirs_all, duration, formatted_duration, disk_type, disk_size) =
-                    list_files_and_dirs(path.clone(), &disk_info, directory_reader_clone).await;
-                (
-                    path,
-                    files_all.len(),
-                    dirs_all.len(),
-                    duration,
-                    formatted_duration,
-                    disk_type,
-                    disk_size,
-                )
-            })
+        .map(|drive| {
+            (
+                drive.root_path.to_string_lossy().into_owned(),
+                drive.drive_type,
+                (drive.size_gb * 1_073_741_824.0) as u64,
+            )
+        })
+        .collect();
+
+    let targets = drives
+        .into_iter()
+        .map(|drive| ScanTarget {
+            rotational: drive.drive_type.is_rotational(),
+            root_path: drive.root_path,
+            directory_reader: drive.directory_reader,
+            disks: drive.disks,
         })
         .collect();
 
-    // Await all tasks concurrently
-    let results_futures = join_all(tasks).await;
-
-    // Process results
-    for (path, files_len, dirs_len, duration, formatted_duration, disk_type, disk_size) in
-        results_futures.into_iter().filter_map(Result::ok)
-    {
-        total_files += files_len;
-        total_dirs += dirs_len;
-        total_size += disk_size;
-
-        results.push((
-            path.to_str().unwrap().to_string(),
-            disk_type,
-            disk_size,
-            files_len,
-            dirs_len,
-            duration.as_secs_f64(),
-            formatted_duration,
-        ));
+    let exports = ScanExports::start(output);
+    let volumes = scan_disks(targets, &disk_info, output.keeps_entries(), &exports.sink).await;
+
+    for volume in &volumes {
+        history.record(
+            volume.root(),
+            volume.reader(),
+            volume.num_files() as u64,
+            volume.num_dirs() as u64,
+            volume.duration().as_secs_f64(),
+        );
     }
 
-    let total_duration = start.elapsed();
+    let scan = ScanResult::new(volumes, start.elapsed());
+    exports.finish(&scan);
+    render_scan(scan, output);
+}
 
-    let total_formatted_duration = format_duration(total_duration);
+// The exports written from the listings of the readers while the volumes are read, instead of
+// from the arenas after the scan
+#[derive(Default)]
+struct ScanExports {
+    sink: ListingSink,
+    sqlite: Option<(PathBuf, SqliteExport)>,
+    parquet: Option<(PathBuf, ParquetExport)>,
+}
 
-    let longest_path_length = results
-        .iter()
-        .map(|(path, _, _, _, _, _, _)| path.len())
-        .max()
-        .unwrap_or(0);
-    let path_length = longest_path_length + 2;
-    let type_length = 12;
-    let size_length = 15;
-    let files_length = 12;
-    let dirs_length = 12;
-    let time_seconds_length = 10;
-    let time_length = 8;
-
-    println!("\n");
-
-    // Header
-    println!(
-        "{:<path_length$} {:<type_length$} {:>size_length$} {:>files_length$} {:>dirs_length$} {:>time_seconds_length$} {:>time_length$}",
-        "Path".bold().underline().blue(),
-        "Type".bold().underline().blue(),
-        "Size (GB / TB)".bold().underline().blue(),
-        "Files".bold().underline().blue(),
-        "Dirs".bold().underline().blue(),
-        "Time (s)".bold().underline().blue(),
-        "Time".bold().underline().blue(),
-    );
+impl ScanExports {
+    fn start(output: &OutputOptions) -> Self {
+        let mut exports = ScanExports::default();
+        if let Some(path) = &output.sqlite {
+            match SqliteExport::start(path, exports.sink.subscribe()) {
+                Ok(export) => exports.sqlite = Some((path.clone(), export)),
+                Err(e) => error!("Failed to export {}: {}", path.display(), e),
+            }
+        }
+        if let Some(path) = &output.parquet {
+            match ParquetExport::start(path, exports.sink.subscribe()) {
+                Ok(export) => exports.parquet = Some((path.clone(), export)),
+                Err(e) => error!("Failed to export {}: {}", path.display(), e),
+            }
+        }
+        exports
+    }
 
-    // Separator
-    let separator = format!(
-        "{}",
-        "-".repeat(
-            path_length
-                + type_length
-                + size_length
-                + files_length
-                + dirs_length
-                + time_seconds_length
-                + time_length
-                + 6
-        )
-        .green()
-    );
-    println!("{}", separator);
-
-    // Data rows
-    for (path, disk_type, size, files, dirs, duration, formatted_duration) in results {
-        println!(
-            "{:<path_length$} {:<type_length$} {:>size_length$} {:>files_length$} {:>dirs_length$} {:>time_seconds_length$.3} {:>time_length$}",
-            path,
-            disk_type,
-            format_size(size),
-            format_number(files, files_length),
-            format_number(dirs, dirs_length),
-            duration,
-            formatted_duration,
-        );
+    fn finish(self, scan: &ScanResult) {
+        // The writers see the end of the listings once the last sender is gone
+        drop(self.sink);
+        if let Some((path, export)) = self.sqlite {
+            log_export(&path, export.finish(scan));
+        }
+        if let Some((path, export)) = self.parquet {
+            log_export(&path, export.finish());
+        }
     }
+}
 
-    // Separator
-    println!("{}", separator);
-
-    // Total row
-    println!(
-        "{:<path_length$} {:<type_length$} {:>size_length$} {:>files_length$} {:>dirs_length$} {:>time_seconds_length$.3} {:>time_length$}",
-        "Total".bold().yellow(),
-        "",
-        format_size(total_size),
-        format_number(total_files, files_length),
-        format_number(total_dirs, dirs_length),
-        total_duration.as_secs_f64(),
-        total_formatted_duration,
-    );
+fn log_export(path: &Path, result: Result<usize, UFFSError>) {
+    match result {
+        Ok(written) => info!("Exported {} entries to {}", written, path.display()),
+        Err(e) => error!("Failed to export {}: {}", path.display(), e),
+    }
+}
 
-    println!("\n");
+/// Scans every mounted volume with the default reader and prints the summary, without the
+/// benchmark history or the reader selection of `process_drives`.
+pub async fn process_all_disks() {
+    let default_reader: SharedDirectoryReader = Arc::new(ReadDirectories4::default());
+    let mut history = BenchmarkHistory::default();
+    let drives = discover_drives(&history, &default_reader);
+
+    process_drives(drives, &mut history, &OutputOptions::default()).await;
 }
 
-pub(crate) async fn list_files_and_dirs<T>(
+struct ScanTarget {
     root_path: PathBuf,
-    disk_info: &[(String, String, u64)],
-    directory_reader: Arc<T>,
-) -> (
-    Vec<PathBuf>,
-    Vec<PathBuf>,
-    std::time::Duration,
-    String,
-    String,
-    u64,
-)
+    directory_reader: SharedDirectoryReader,
+    disks: Vec<String>,
+    rotational: bool,
+}
+
+// The entries of the volume are only kept with `keep_entries`, to list them afterwards
+async fn scan_disk(
+    target: ScanTarget,
+    disk_info: Vec<(String, DriveType, u64)>,
+    keep_entries: bool,
+    sink: ListingSink,
+) -> VolumeScanResult {
+    let sink = sink.for_volume(&target.root_path);
+    let mut result =
+        list_files_and_dirs(target.root_path, &disk_info, target.directory_reader, &sink).await;
+    if !keep_entries {
+        result.take_arena();
+    }
+    result
+}
+
+// Volumes on different devices are scanned in parallel. Volumes sharing a spinning disk are
+// scanned one after the other, scanning them at the same time makes the heads seek back and
+// forth between the partitions.
+async fn scan_disks(
+    targets: Vec<ScanTarget>,
+    disk_info: &[(String, DriveType, u64)],
+    keep_entries: bool,
+    sink: &ListingSink,
+) -> Vec<VolumeScanResult> {
+    let groups = group_by_device(targets, |target| (target.disks.clone(), target.rotational));
+
+    let mut tasks = vec![];
+    for group in groups {
+        if group.rotational && group.members.len() > 1 {
+            info!(
+                "Scanning {} volumes on rotational device {} serially",
+                group.members.len(),
+                group.disks.join("+")
+            );
+            let disk_info = disk_info.to_vec();
+            let sink = sink.clone();
+            tasks.push(task::spawn(async move {
+                let mut results = Vec::with_capacity(group.members.len());
+                for target in group.members {
+                    results.push(
+                        scan_disk(target, disk_info.clone(), keep_entries, sink.clone()).await,
+                    );
+                }
+                results
+            }));
+        } else {
+            for target in group.members {
+                let disk_info = disk_info.to_vec();
+                let sink = sink.clone();
+                tasks.push(task::spawn(async move {
+                    vec![scan_disk(target, disk_info, keep_entries, sink).await]
+                }));
+            }
+        }
+    }
+
+    // Await all tasks concurrently, a failed task loses the volumes it was scanning
+    let mut results: Vec<_> = join_all(tasks)
+        .await
+        .into_iter()
+        .filter_map(|result| {
+            result
+                .inspect_err(|e| error!("A volume scan failed: {}", e))
+                .ok()
+        })
+        .flatten()
+        .collect();
+
+    results.sort_by(|a, b| a.root().cmp(b.root()));
+    results
+}
+
+pub(crate) fn render_scan(mut scan: ScanResult, output: &OutputOptions) {
+    // The volumes keep their summary, the entries go on to the reports and exports
+    let arenas = scan.take_arenas();
+
+    // The terminal UI shows the entries itself once the exports are written
+    if !output.interactive {
+        match (&output.usage, &output.search) {
+            (Some(usage), _) => write_usage(output, &usage_rows(&arenas, usage)),
+            (None, Some(search)) => write_matches(output, &arenas, search),
+            (None, None) => write_results(output, &scan, &arenas),
+        }
+    }
+
+    export_entries(output, &arenas);
+
+    if output.interactive {
+        if let Err(e) = run_tui(arenas) {
+            error!("{}", e);
+        }
+    } else if let Some(index) = &output.watch {
+        let scanned_at = SystemTime::now() - scan.duration();
+        let result = if output.serve {
+            serve(arenas, index, output.socket.as_deref(), scanned_at)
+        } else {
+            watch_volumes(arenas, index, scanned_at)
+        };
+        if let Err(e) = result {
+            error!("{}", e);
+        }
+    }
+}
+
+type Exporter = fn(&Path, &[PathArena]) -> Result<usize, UFFSError>;
+
+/// Writes the entries to the file lists and databases requested on the command line.
+pub(crate) fn export_entries(output: &OutputOptions, arenas: &[PathArena]) {
+    let exports: [(&Option<PathBuf>, Exporter); 3] = [
+        (&output.efu, export_efu),
+        (&output.mlocate, write_mlocate_db),
+        (&output.plocate, write_plocate_db),
+    ];
+
+    for (path, export) in exports {
+        let Some(path) = path else { continue };
+        log_export(path, export(path, arenas));
+    }
+}
+
+/// Reads `root_path` and everything below it into a new arena with `directory_reader`.
+pub(crate) async fn read_tree<T>(
+    root_path: &Path,
+    directory_reader: &T,
+) -> (PathArena, std::time::Duration, ReaderStats)
 where
-    T: DirectoryReader + Send + Sync + 'static,
+    T: DirectoryReader + Send + Sync + ?Sized,
 {
-    let files = Arc::new(RwLock::new(Vec::with_capacity(MAX_FILES_ALL)));
-    let dirs = Arc::new(RwLock::new(Vec::with_capacity(MAX_DIRS_ALL)));
-    let paths_queue = Arc::new(RwLock::new(Vec::with_capacity(MAX_DIRS)));
-    let mut timestamp = Local::now().format(LOG_DATE_FORMAT).to_string();
-    // info!("\n\ncurrent_path:\t{:?}\n\n",vec_u16_to_string(&current_path));
+    read_tree_with_sink(root_path, directory_reader, &ListingSink::default()).await
+}
 
-    // println!("START:   {:<18}   at   {}", root_path_string, timestamp);
+/// Like `read_tree`, also sending the entries of each directory to `sink` as they are read.
+pub(crate) async fn read_tree_with_sink<T>(
+    root_path: &Path,
+    directory_reader: &T,
+    sink: &ListingSink,
+) -> (PathArena, std::time::Duration, ReaderStats)
+where
+    T: DirectoryReader + Send + Sync + ?Sized,
+{
+    let mut arena = PathArena::new();
+    let root_id = arena.add_root(root_path);
+    let arena = Arc::new(RwLock::new(arena));
+    let paths_queue = Arc::new(RwLock::new(Vec::with_capacity(MAX_DIRS)));
 
     let start = Instant::now();
-    // info!("Started here: {:?}", root_path);
-    {
-        let mut paths_queue_lock = paths_queue.write().await;
-        paths_queue_lock.push(root_path.clone());
-    }
+    paths_queue
+        .write()
+        .await
+        .push((root_id, root_path.to_path_buf()));
 
-    directory_reader
-        .read_directories(&files, &dirs, &paths_queue)
+    let reader_stats = directory_reader
+        .read_directories(&arena, &paths_queue, sink)
         .await;
 
-    let files_all = Arc::try_unwrap(files)
+    let mut arena = Arc::try_unwrap(arena)
         .expect("Arc has multiple owners")
         .into_inner();
-    let num_files = files_all.len();
+    arena.shrink_to_fit();
 
-    let dirs_all = Arc::try_unwrap(dirs)
-        .expect("Arc has multiple owners")
-        .into_inner();
-    let num_dirs = dirs_all.len();
+    (arena, start.elapsed(), reader_stats)
+}
 
-    let duration = start.elapsed();
-    let formatted_duration = format_duration(duration);
-    timestamp = Local::now().format(LOG_DATE_FORMAT).to_string();
+// The type and size of the mounted volume `root_path` is the root of
+fn disk_info_for<'a>(
+    root_path: &Path,
+    disk_info: &'a [(String, DriveType, u64)],
+) -> Option<&'a (String, DriveType, u64)> {
+    let root_path = root_path.to_string_lossy();
+    disk_info
+        .iter()
+        .find(|(volume, _, _)| same_root(volume, &root_path))
+}
 
-    let components: Vec<_> = root_path.components().collect();
+pub(crate) async fn list_files_and_dirs<T>(
+    root_path: PathBuf,
+    disk_info: &[(String, DriveType, u64)],
+    directory_reader: Arc<T>,
+    sink: &ListingSink,
+) -> VolumeScanResult
+where
+    T: DirectoryReader + Send + Sync + ?Sized + 'static,
+{
+    let (arena, duration, reader_stats) =
+        read_tree_with_sink(&root_path, directory_reader.as_ref(), sink).await;
+    let mut result = VolumeScanResult::new(
+        &root_path,
+        directory_reader.name(),
+        arena,
+        duration,
+        reader_stats,
+    );
+
+    let formatted_duration = format_duration(duration);
 
-    println!(
-        "DONE: {:<18} at {}. FILES: {:>10} DIRS: {:>10} Running TIME: {:<8}",
+    info!(
+        "DONE: {:<18} FILES: {:>10} DIRS: {:>10} MEMORY: {} Running TIME: {:<8}",
         root_path.display(),
-        timestamp,
-        num_files,
-        num_dirs,
+        result.num_files(),
+        result.num_dirs(),
+        format_memory(result.memory_bytes()),
         formatted_duration,
     );
 
-    let drive_letter = components
-        .first()
-        .expect("Could not get drive letter")
-        .as_os_str();
-    let mut drive_letter_path = PathBuf::from(drive_letter);
-    drive_letter_path.push("\\");
-
-    let drive_letter_with_backslash = drive_letter_path
-        .into_os_string()
-        .into_string()
-        .expect("Could not get drive letter with backslash");
-
-    let disk_type = disk_info
-        .iter()
-        .find(|(p, _, _)| p == &drive_letter_with_backslash)
-        .expect("Could not find disk size")
-        .1
-        .clone();
+    if let Some(concurrency) = &result.concurrency {
+        info!("{}: {}", root_path.display(), concurrency);
+    }
+    if !result.errors().is_empty() {
+        warn!(
+            "{}: {} directories could not be read",
+            root_path.display(),
+            result.errors().len()
+        );
+    }
 
-    let disk_size = disk_info
-        .iter()
-        .find(|(p, _, _)| p == &drive_letter_with_backslash)
-        .expect("Could not find disk size")
-        .2;
+    match disk_info_for(&root_path, disk_info) {
+        Some((_, disk_type, disk_size)) => {
+            result.drive_type = Some(disk_type.to_string());
+            result.size_bytes = Some(*disk_size);
+        }
+        None => warn!(
+            "{}: not a mounted volume, its size is unknown",
+            root_path.display()
+        ),
+    }
 
-    (
-        files_all,
-        dirs_all,
-        duration,
-        formatted_duration,
-        disk_type,
-        disk_size,
-    )
+    result
 }
 
 // Refactored get_file_dir_len function
-pub(crate) async fn get_file_dir_len(root_path: &PathBuf) -> Result<(u64, u64), UFFSError> {
+pub(crate) async fn get_file_dir_len(root_path: &Path) -> Result<(u64, u64), UFFSError> {
     let start = Instant::now();
-    let timestamp = Local::now().format(LOG_DATE_FORMAT).to_string();
-    // println!("START:   {:<18}   at   {}", root_path.display(), timestamp);
 
-    if let Some(drive_letter) = get_drive_letter(&root_path) {
-        // info!("Processing drive: {}", drive_letter);
-    } else {
+    if get_drive_letter(root_path).is_none() {
         error!("Drive letter not found");
         return Err(UFFSError::DriveLetterNotFound);
     }
@@ -359,15 +510,19 @@ pub(crate) async fn get_file_dir_len(root_path: &PathBuf) -> Result<(u64, u64),
     let mut num_files = 0u64;
     let mut num_dirs = 0u64;
 
-    count_all_disk_entries(root_path, &mut num_files, &mut num_dirs).await?;
+    count_all_disk_entries(
+        root_path,
+        &IgnoreFilter::default(),
+        &mut num_files,
+        &mut num_dirs,
+    )
+    .await?;
 
     let duration = start.elapsed();
     let formatted_duration = format_duration(duration);
-    let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S").to_string();
-    println!(
-        "DONE: {:<18} at {}. FILES: {:>10} DIRS: {:>10} Running TIME: {:<8}",
+    info!(
+        "DONE: {:<18} FILES: {:>10} DIRS: {:>10} Running TIME: {:<8}",
         root_path.display(),
-        timestamp,
         num_files,
         num_dirs,
         formatted_duration,
@@ -375,3 +530,22 @@ pub(crate) async fn get_file_dir_len(root_path: &PathBuf) -> Result<(u64, u64),
 
     Ok((num_files, num_dirs))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disk_info_is_found_by_root_path() {
+        let disk_info = vec![
+            ("/".to_string(), DriveType::Ssd, 100),
+            ("/mnt/data/".to_string(), DriveType::Hdd, 200),
+        ];
+
+        let found = |root: &str| disk_info_for(Path::new(root), &disk_info).map(|d| d.2);
+        assert_eq!(found("/"), Some(100));
+        assert_eq!(found("/mnt/data"), Some(200));
+        assert_eq!(found("/mnt"), None);
+        assert_eq!(found("C:\\"), None);
+    }
+}
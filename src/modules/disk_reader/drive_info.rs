@@ -1,30 +1,83 @@
 use crate::modules::directory_reader::DirectoryReader;
 use std::fmt;
 use std::path::PathBuf;
+use std::sync::Arc;
+use sysinfo::DiskKind;
 
-pub(crate) struct DriveInfo {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DriveType {
+    Ssd,
+    Hdd,
+    Unknown,
+}
+
+impl DriveType {
+    pub(crate) fn from_rotational(rotational: bool) -> Self {
+        if rotational {
+            DriveType::Hdd
+        } else {
+            DriveType::Ssd
+        }
+    }
+
+    pub(crate) fn is_rotational(self) -> bool {
+        self == DriveType::Hdd
+    }
+}
+
+impl From<DiskKind> for DriveType {
+    fn from(kind: DiskKind) -> Self {
+        match kind {
+            DiskKind::SSD => DriveType::Ssd,
+            DiskKind::HDD => DriveType::Hdd,
+            DiskKind::Unknown(_) => DriveType::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for DriveType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DriveType::Ssd => "SSD",
+            DriveType::Hdd => "HDD",
+            DriveType::Unknown => "Unknown",
+        };
+        // Pad like a str so the type lines up in the result table
+        f.pad(name)
+    }
+}
+
+pub struct DriveInfo {
     pub(crate) root_path: PathBuf,
-    pub(crate) drive_type: String, // "SSD" or "HDD"
+    pub(crate) drive_type: DriveType,
+    // Physical disks the volume lives on, shared by all partitions of a disk
+    pub(crate) disks: Vec<String>,
+    pub(crate) file_system: String,
     pub(crate) size_gb: f64,
     pub(crate) num_files: u64,
     pub(crate) num_dirs: u64,
     pub(crate) time_seconds: f64,
-    pub(crate) directory_reader: Box<dyn DirectoryReader + Send + Sync + 'static>,
+    pub(crate) directory_reader: Arc<dyn DirectoryReader + Send + Sync + 'static>,
 }
 
 impl DriveInfo {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         root_path: PathBuf,
-        drive_type: String,
+        drive_type: DriveType,
+        disks: Vec<String>,
+        file_system: String,
         size_gb: f64,
         num_files: u64,
         num_dirs: u64,
         time_seconds: f64,
-        directory_reader: Box<dyn DirectoryReader + Send + Sync + 'static>,
+        directory_reader: Arc<dyn DirectoryReader + Send + Sync + 'static>,
     ) -> Self {
         Self {
             root_path,
             drive_type,
+            disks,
+            file_system,
             size_gb,
             num_files,
             num_dirs,
@@ -37,13 +90,16 @@ impl fmt::Display for DriveInfo {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "DriveInfo {{\n  root_path: {:?},\n  drive_type: {},\n  size_gb: {:.2},\n  num_files: {},\n  num_dirs: {},\n  time_seconds: {:.2}\n}}",
+            "DriveInfo {{\n  root_path: {:?},\n  drive_type: {},\n  disks: {},\n  file_system: {},\n  size_gb: {:.2},\n  num_files: {},\n  num_dirs: {},\n  time_seconds: {:.2},\n  directory_reader: {}\n}}",
             self.root_path,
             self.drive_type,
+            self.disks.join("+"),
+            self.file_system,
             self.size_gb,
             self.num_files,
             self.num_dirs,
-            self.time_seconds
+            self.time_seconds,
+            self.directory_reader.name()
         )
     }
 }
@@ -53,10 +109,13 @@ impl fmt::Debug for DriveInfo {
         f.debug_struct("DriveInfo")
             .field("root_path", &self.root_path)
             .field("drive_type", &self.drive_type)
+            .field("disks", &self.disks)
+            .field("file_system", &self.file_system)
             .field("size_gb", &format!("{:.2}", self.size_gb))
             .field("num_files", &self.num_files)
             .field("num_dirs", &self.num_dirs)
             .field("time_seconds", &format!("{:.2}", self.time_seconds))
+            .field("directory_reader", &self.directory_reader.name())
             .finish()
     }
 }
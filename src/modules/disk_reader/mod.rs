@@ -1,10 +1,13 @@
+mod block_device;
 pub mod disk_reader_impl;
-mod drive_info;
+pub mod drive_info;
 
-pub(crate) use disk_reader_impl::get_file_dir_len;
-pub(crate) use disk_reader_impl::init_drives;
+pub(crate) use disk_reader_impl::discover_drives;
+pub(crate) use disk_reader_impl::export_entries;
 pub(crate) use disk_reader_impl::list_files_and_dirs;
-pub(crate) use disk_reader_impl::process_all_disks;
-pub(crate) use disk_reader_impl::Disks;
+pub(crate) use disk_reader_impl::process_drives;
+pub(crate) use disk_reader_impl::read_tree;
+pub(crate) use disk_reader_impl::render_scan;
 
 pub(crate) use drive_info::DriveInfo;
+pub(crate) use drive_info::DriveType;
@@ -0,0 +1,292 @@
+use std::borrow::{Borrow, Cow};
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::modules::efu::file_time::{file_attributes, to_filetime, FILE_ATTRIBUTE_DIRECTORY};
+use crate::modules::errors::UFFSError;
+use crate::modules::path_arena::PathArena;
+
+pub(crate) const EFU_HEADER: &str = "Filename,Size,Date Modified,Date Created,Attributes";
+
+/// One line of an Everything file list. Dates are Windows FILETIMEs, attributes the Windows
+/// `FILE_ATTRIBUTE_*` flags.
+#[derive(Debug, Clone)]
+pub(crate) struct EfuEntry {
+    pub(crate) path: OsString,
+    pub(crate) size: Option<u64>,
+    pub(crate) date_modified: Option<u64>,
+    pub(crate) date_created: Option<u64>,
+    pub(crate) attributes: u32,
+}
+
+impl EfuEntry {
+    pub(crate) fn is_dir(&self) -> bool {
+        self.attributes & FILE_ATTRIBUTE_DIRECTORY != 0
+    }
+
+    // Lists written on Windows separate with backslashes, lists from our own scans may not.
+    // For matching only, the invalid parts are replaced.
+    pub(crate) fn name(&self) -> String {
+        let path = self.path.to_string_lossy();
+        path.trim_end_matches(['\\', '/'])
+            .rsplit(['\\', '/'])
+            .next()
+            .unwrap_or(&path)
+            .to_string()
+    }
+
+    // Size, dates and attributes are read from the file system, an entry that vanished since the
+    // scan keeps only its name
+    fn from_path(path: &Path, name: &OsStr) -> EfuEntry {
+        let mut entry = EfuEntry {
+            path: path.as_os_str().to_owned(),
+            size: None,
+            date_modified: None,
+            date_created: None,
+            attributes: 0,
+        };
+
+        if let Ok(metadata) = fs::symlink_metadata(path) {
+            entry.size = (!metadata.is_dir()).then_some(metadata.len());
+            entry.date_modified = metadata.modified().ok().map(to_filetime);
+            entry.date_created = metadata.created().ok().map(to_filetime);
+            entry.attributes = file_attributes(&metadata, name);
+        }
+
+        entry
+    }
+
+    fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        let optional = |value: Option<u64>| value.map(|v| v.to_string()).unwrap_or_default();
+
+        // Everything quotes every file name, doubling the quotes inside. The name is written as
+        // is, UTF-8 when it is valid.
+        out.write_all(b"\"")?;
+        for (i, part) in self
+            .path
+            .as_encoded_bytes()
+            .split(|&b| b == b'"')
+            .enumerate()
+        {
+            if i > 0 {
+                out.write_all(b"\"\"")?;
+            }
+            out.write_all(part)?;
+        }
+        writeln!(
+            out,
+            "\",{},{},{},{}",
+            optional(self.size),
+            optional(self.date_modified),
+            optional(self.date_created),
+            self.attributes,
+        )
+    }
+}
+
+/// A file list loaded from an EFU file, searched instead of scanning the disks.
+#[derive(Debug, Default)]
+pub(crate) struct EfuList {
+    entries: Vec<EfuEntry>,
+}
+
+impl EfuList {
+    pub(crate) fn load(path: &Path) -> Result<EfuList, UFFSError> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        // Everything writes UTF-8, with a byte order mark depending on the version
+        if reader.fill_buf()?.starts_with(b"\xEF\xBB\xBF") {
+            reader.consume(3);
+        }
+
+        EfuList::read(reader, path)
+    }
+
+    fn read<R: Read>(reader: R, path: &Path) -> Result<EfuList, UFFSError> {
+        let invalid = |line: u64, message: String| UFFSError::InvalidFileList {
+            path: path.display().to_string(),
+            line,
+            message,
+        };
+
+        let mut csv_reader = csv::ReaderBuilder::new().flexible(true).from_reader(reader);
+
+        // The columns are looked up by name, only Filename is required
+        let headers = csv_reader
+            .headers()
+            .map_err(|e| invalid(1, e.to_string()))?
+            .clone();
+        let column = |name: &str| {
+            headers
+                .iter()
+                .position(|h| h.trim().eq_ignore_ascii_case(name))
+        };
+        let filename = column("Filename")
+            .ok_or_else(|| invalid(1, "missing the Filename column".to_string()))?;
+        let size = column("Size");
+        let date_modified = column("Date Modified");
+        let date_created = column("Date Created");
+        let attributes = column("Attributes");
+
+        let mut entries = vec![];
+        for record in csv_reader.byte_records() {
+            let record =
+                record.map_err(|e| invalid(e.position().map_or(0, |p| p.line()), e.to_string()))?;
+            let line = record.position().map_or(0, |p| p.line());
+
+            let number = |index: Option<usize>| -> Result<Option<u64>, UFFSError> {
+                let value = index
+                    .and_then(|i| record.get(i))
+                    .map(String::from_utf8_lossy);
+                match value.as_deref().map(str::trim) {
+                    None | Some("") => Ok(None),
+                    Some(value) => value
+                        .parse()
+                        .map(Some)
+                        .map_err(|_| invalid(line, format!("{:?} is not a number", value))),
+                }
+            };
+
+            let Some(path) = record.get(filename).filter(|p| !p.is_empty()) else {
+                return Err(invalid(line, "empty Filename".to_string()));
+            };
+
+            let attributes = number(attributes)?.unwrap_or(0);
+            let attributes = u32::try_from(attributes)
+                .map_err(|_| invalid(line, format!("{} are not file attributes", attributes)))?;
+
+            entries.push(EfuEntry {
+                path: os_str(path).into_owned(),
+                size: number(size)?,
+                date_modified: number(date_modified)?,
+                date_created: number(date_created)?,
+                attributes,
+            });
+        }
+
+        Ok(EfuList { entries })
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// The entries whose name contains `pattern`, ignoring case. An empty pattern matches all.
+    pub(crate) fn search<'a>(&'a self, pattern: &str) -> impl Iterator<Item = &'a EfuEntry> + 'a {
+        let pattern = pattern.to_lowercase();
+        self.entries.iter().filter(move |entry| {
+            pattern.is_empty() || entry.name().to_lowercase().contains(&pattern)
+        })
+    }
+}
+
+// Lists written on Unix hold the raw bytes of the names, Everything writes UTF-8
+#[cfg(unix)]
+fn os_str(bytes: &[u8]) -> Cow<'_, OsStr> {
+    use std::os::unix::ffi::OsStrExt;
+
+    Cow::Borrowed(OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn os_str(bytes: &[u8]) -> Cow<'_, OsStr> {
+    match String::from_utf8_lossy(bytes) {
+        Cow::Borrowed(name) => Cow::Borrowed(OsStr::new(name)),
+        Cow::Owned(name) => Cow::Owned(name.into()),
+    }
+}
+
+/// Writes `entries` to `path` as an Everything file list and returns the number written.
+pub(crate) fn write_efu<I>(path: &Path, entries: I) -> Result<usize, UFFSError>
+where
+    I: IntoIterator,
+    I::Item: Borrow<EfuEntry>,
+{
+    let mut out = BufWriter::new(File::create(path)?);
+    writeln!(out, "{}", EFU_HEADER)?;
+
+    let mut written = 0;
+    for entry in entries {
+        entry.borrow().write(&mut out)?;
+        written += 1;
+    }
+
+    out.flush()?;
+    Ok(written)
+}
+
+/// Exports every entry of `arenas` to `path` as an Everything file list.
+pub(crate) fn export_efu(path: &Path, arenas: &[PathArena]) -> Result<usize, UFFSError> {
+    let entries = arenas.iter().flat_map(|arena| {
+        arena
+            .dirs()
+            .iter()
+            .chain(arena.files())
+            .map(|&id| EfuEntry::from_path(&arena.path(id), arena.name(id)))
+    });
+
+    write_efu(path, entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: impl Into<OsString>, attributes: u32) -> EfuEntry {
+        EfuEntry {
+            path: path.into(),
+            size: Some(42),
+            date_modified: Some(133_000_000_000_000_000),
+            date_created: None,
+            attributes,
+        }
+    }
+
+    fn round_trip(entries: &[EfuEntry]) -> EfuList {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("list.efu");
+        assert_eq!(write_efu(&path, entries).unwrap(), entries.len());
+        EfuList::load(&path).unwrap()
+    }
+
+    #[test]
+    fn lists_round_trip() {
+        let entries = [
+            entry(r"C:\dir", FILE_ATTRIBUTE_DIRECTORY),
+            entry(r#"C:\dir\say "hi", then.txt"#, 0x20),
+        ];
+        let list = round_trip(&entries);
+
+        assert_eq!(list.len(), 2);
+        for (read, written) in list.search("").zip(&entries) {
+            assert_eq!(read.path, written.path);
+            assert_eq!(read.size, written.size);
+            assert_eq!(read.date_modified, written.date_modified);
+            assert_eq!(read.date_created, written.date_created);
+            assert_eq!(read.attributes, written.attributes);
+        }
+        assert!(list.search("DIR").next().unwrap().is_dir());
+        assert_eq!(list.search("then").count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn names_are_kept_byte_for_byte() {
+        use std::os::unix::ffi::OsStringExt;
+
+        let name = OsString::from_vec(b"/tmp/caf\xe9 \"1\".txt".to_vec());
+        let list = round_trip(&[entry(name.clone(), 0x80)]);
+
+        assert_eq!(list.search("caf").next().unwrap().path, name);
+    }
+
+    #[test]
+    fn attributes_out_of_range_are_rejected() {
+        let list = format!("{}\n\"a\",,,,{}\n", EFU_HEADER, u64::from(u32::MAX) + 1);
+
+        let error = EfuList::read(list.as_bytes(), Path::new("list.efu")).unwrap_err();
+        assert!(matches!(error, UFFSError::InvalidFileList { line: 2, .. }));
+    }
+}
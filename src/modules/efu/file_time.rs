@@ -0,0 +1,93 @@
+use std::ffi::OsStr;
+use std::fs::Metadata;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// FILETIME counts 100 ns intervals since 1601-01-01 UTC
+const FILETIME_TICKS_PER_SECOND: u64 = 10_000_000;
+const FILETIME_UNIX_EPOCH: u64 = 11_644_473_600 * FILETIME_TICKS_PER_SECOND;
+
+pub(crate) const FILE_ATTRIBUTE_DIRECTORY: u32 = 0x10;
+
+fn to_ticks(duration: Duration) -> u64 {
+    duration.as_secs() * FILETIME_TICKS_PER_SECOND + u64::from(duration.subsec_nanos()) / 100
+}
+
+pub(crate) fn to_filetime(time: SystemTime) -> u64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(since_epoch) => FILETIME_UNIX_EPOCH + to_ticks(since_epoch),
+        Err(e) => FILETIME_UNIX_EPOCH.saturating_sub(to_ticks(e.duration())),
+    }
+}
+
+#[cfg(windows)]
+pub(crate) fn file_attributes(metadata: &Metadata, _name: &OsStr) -> u32 {
+    use std::os::windows::fs::MetadataExt;
+
+    metadata.file_attributes()
+}
+
+// Unix has no attributes, they are derived from the file type, permissions and name the way
+// Samba maps them
+#[cfg(not(windows))]
+pub(crate) fn file_attributes(metadata: &Metadata, name: &OsStr) -> u32 {
+    const FILE_ATTRIBUTE_READONLY: u32 = 0x1;
+    const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+    const FILE_ATTRIBUTE_NORMAL: u32 = 0x80;
+    const FILE_ATTRIBUTE_REPARSE_POINT: u32 = 0x400;
+
+    let mut attributes = 0;
+    if metadata.is_dir() {
+        attributes |= FILE_ATTRIBUTE_DIRECTORY;
+    }
+    if metadata.file_type().is_symlink() {
+        attributes |= FILE_ATTRIBUTE_REPARSE_POINT;
+    }
+    if metadata.permissions().readonly() {
+        attributes |= FILE_ATTRIBUTE_READONLY;
+    }
+    if name.as_encoded_bytes().starts_with(b".") {
+        attributes |= FILE_ATTRIBUTE_HIDDEN;
+    }
+
+    if attributes == 0 {
+        FILE_ATTRIBUTE_NORMAL
+    } else {
+        attributes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_filetime(filetime: u64) -> SystemTime {
+        let nanos = |ticks: u64| Duration::from_nanos(ticks * 100);
+        match filetime.checked_sub(FILETIME_UNIX_EPOCH) {
+            Some(ticks) => UNIX_EPOCH + nanos(ticks),
+            None => UNIX_EPOCH - nanos(FILETIME_UNIX_EPOCH - filetime),
+        }
+    }
+
+    #[test]
+    fn the_unix_epoch_is_a_known_filetime() {
+        assert_eq!(to_filetime(UNIX_EPOCH), 116_444_736_000_000_000);
+    }
+
+    #[test]
+    fn filetimes_round_trip() {
+        let times = [
+            UNIX_EPOCH + Duration::new(1_700_000_000, 123_456_700),
+            UNIX_EPOCH - Duration::new(86_400, 500),
+            UNIX_EPOCH - Duration::from_secs(11_644_473_600),
+        ];
+        for time in times {
+            assert_eq!(from_filetime(to_filetime(time)), time);
+        }
+    }
+
+    #[test]
+    fn filetimes_count_whole_ticks() {
+        let time = UNIX_EPOCH + Duration::new(1, 199);
+        assert_eq!(to_filetime(time), FILETIME_UNIX_EPOCH + 10_000_001);
+    }
+}
@@ -0,0 +1,6 @@
+pub mod efu_impl;
+pub(crate) mod file_time;
+
+pub(crate) use efu_impl::export_efu;
+pub(crate) use efu_impl::write_efu;
+pub(crate) use efu_impl::EfuList;
@@ -1,30 +1,109 @@
+use miette::Diagnostic;
 use std::io;
 use thiserror::Error;
-use miette::{Diagnostic, SourceSpan};
 
 #[derive(Error, Debug, Diagnostic)]
 pub(crate) enum UFFSError {
     #[error("IO error: {0}")]
-    #[diagnostic(code(uff::io_error), help("Check if the file path is correct and you have the necessary permissions."))]
+    #[diagnostic(
+        code(uff::io_error),
+        help("Check if the file path is correct and you have the necessary permissions.")
+    )]
     Io(#[from] io::Error),
 
-    #[error("Drive information is empty.")]
-    #[diagnostic(code(uff::empty_drive_info), help("Ensure that the drive is properly connected and contains data."))]
-    EmptyDriveInfo,
-
     #[error("Drive letter not found.")]
-    #[diagnostic(code(uff::drive_letter_not_found), help("Verify that the drive letter is correct and accessible."))]
+    #[diagnostic(
+        code(uff::drive_letter_not_found),
+        help("Verify that the drive letter is correct and accessible.")
+    )]
     DriveLetterNotFound,
 
-    #[error("Failed to read directory entries.")]
-    #[diagnostic(code(uff::directory_read_error), help("Check the directory path and your access permissions."))]
-    DirectoryReadError,
+    #[error("Unknown directory reader: {name}")]
+    #[diagnostic(code(uff::unknown_reader), help("Available readers: {available}"))]
+    UnknownReader { name: String, available: String },
+
+    #[error("Invalid file list {path}, line {line}: {message}")]
+    #[diagnostic(
+        code(uff::invalid_file_list),
+        help("Check that the file was exported completely and in the expected format.")
+    )]
+    InvalidFileList {
+        path: String,
+        line: u64,
+        message: String,
+    },
+
+    #[cfg(feature = "sqlite")]
+    #[error("SQLite error: {0}")]
+    #[diagnostic(
+        code(uff::sqlite_error),
+        help("Check that the database isn't locked by another program.")
+    )]
+    Sqlite(#[from] rusqlite::Error),
+
+    #[cfg(feature = "parquet")]
+    #[error("Parquet error: {0}")]
+    #[diagnostic(code(uff::parquet_error))]
+    Parquet(#[from] parquet::errors::ParquetError),
+
+    #[cfg(any(not(feature = "sqlite"), not(feature = "parquet")))]
+    #[error("UFFS was built without the {feature} feature.")]
+    #[diagnostic(
+        code(uff::feature_disabled),
+        help("Rebuild with `cargo build --release --features {feature}`.")
+    )]
+    FeatureDisabled { feature: String },
+
+    // Only the platforms without the watch or daemon support construct these
+    #[cfg_attr(target_os = "linux", allow(dead_code))]
+    #[error("{feature} is only supported on {platform}.")]
+    #[diagnostic(code(uff::unsupported))]
+    Unsupported { feature: String, platform: String },
+
+    #[cfg_attr(not(unix), allow(dead_code))]
+    #[error("Index daemon error: {message}")]
+    #[diagnostic(code(uff::daemon_error))]
+    Daemon { message: String },
 
     #[error("Configuration error: {0}")]
     #[diagnostic(code(uff::config_error))]
     ConfigError(String),
+}
+
+/// Why a `Scanner` could not start. Errors reading single directories during a scan don't stop
+/// it, the directories are left out of the result.
+#[derive(Error, Debug, Diagnostic)]
+#[non_exhaustive]
+pub enum ScanError {
+    #[error("Nothing to scan, no root was given.")]
+    #[diagnostic(
+        code(uff::no_roots),
+        help("Add the directories to scan with `Scanner::root`.")
+    )]
+    NoRoots,
+
+    #[error("Cannot scan {path}: {source}")]
+    #[diagnostic(
+        code(uff::invalid_root),
+        help("Check that the directory exists and you may read it.")
+    )]
+    InvalidRoot {
+        path: std::path::PathBuf,
+        source: io::Error,
+    },
+
+    #[error("Unknown directory reader: {name}")]
+    #[diagnostic(code(uff::unknown_reader), help("Available readers: {available}"))]
+    UnknownReader { name: String, available: String },
+
+    #[error("Failed to start the scan runtime: {0}")]
+    #[diagnostic(code(uff::runtime_error))]
+    Runtime(io::Error),
 
-    #[error("Custom error with data: {message}, data: {data}")]
-    #[diagnostic(code(uff::custom_error))]
-    CustomError { message: String, data: usize },
+    #[error("Invalid exclude pattern: {0}")]
+    #[diagnostic(
+        code(uff::invalid_exclude),
+        help("Exclude patterns are written as in a .gitignore, e.g. `target/` or `*.tmp`.")
+    )]
+    InvalidExclude(ignore::Error),
 }
@@ -1,3 +1,4 @@
 mod errors_impl;
 
+pub use errors_impl::ScanError;
 pub(crate) use errors_impl::UFFSError;
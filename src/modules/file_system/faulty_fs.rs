@@ -0,0 +1,183 @@
+use async_trait::async_trait;
+use globset::{GlobBuilder, GlobMatcher};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::modules::file_system::file_system_impl::{
+    DirEntry, DirTime, FileSystem, SharedFileSystem,
+};
+
+/// What goes wrong when `FaultyFileSystem` reads a directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// EACCES, `ERROR_ACCESS_DENIED` on Windows
+    PermissionDenied,
+    /// ENOENT, the directory was removed between being listed and being read
+    Vanished,
+    /// EIO, e.g. a failing disk or a dropped network share
+    Io,
+    /// ENAMETOOLONG, `ERROR_FILENAME_EXCED_RANGE` on Windows
+    NameTooLong,
+    /// The read succeeds, after the delay
+    Slow(Duration),
+}
+
+impl Fault {
+    /// The error the read fails with, `None` for `Slow`. On Windows it carries the Win32 code,
+    /// which `raw_os_error` gives back e.g. for `handle_find_error`.
+    #[cfg(windows)]
+    pub fn to_error(self) -> Option<io::Error> {
+        use winapi::shared::winerror::{
+            ERROR_ACCESS_DENIED, ERROR_FILENAME_EXCED_RANGE, ERROR_IO_DEVICE, ERROR_PATH_NOT_FOUND,
+        };
+
+        let code = match self {
+            Fault::PermissionDenied => ERROR_ACCESS_DENIED,
+            Fault::Vanished => ERROR_PATH_NOT_FOUND,
+            Fault::Io => ERROR_IO_DEVICE,
+            Fault::NameTooLong => ERROR_FILENAME_EXCED_RANGE,
+            Fault::Slow(_) => return None,
+        };
+        Some(io::Error::from_raw_os_error(code as i32))
+    }
+
+    #[cfg(not(windows))]
+    pub fn to_error(self) -> Option<io::Error> {
+        let (kind, message) = match self {
+            Fault::PermissionDenied => (io::ErrorKind::PermissionDenied, "permission denied"),
+            Fault::Vanished => (io::ErrorKind::NotFound, "no such file or directory"),
+            Fault::Io => (io::ErrorKind::Other, "input/output error"),
+            Fault::NameTooLong => (io::ErrorKind::InvalidFilename, "file name too long"),
+            Fault::Slow(_) => return None,
+        };
+        Some(io::Error::new(kind, message))
+    }
+}
+
+/// A fault `FaultyFileSystem` injected, to check what the readers reported against.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InjectedFault {
+    pub path: PathBuf,
+    pub fault: Fault,
+}
+
+struct Rule {
+    // None for every path
+    pattern: Option<GlobMatcher>,
+    probability: f64,
+    fault: Fault,
+}
+
+/// Wraps another file system and makes some of its reads fail or slow down, to exercise the
+/// error paths of the readers. The rules apply in the order they were added: every matching
+/// `Slow` adds its delay and the first matching error ends the read.
+///
+/// The random faults come from a seeded generator, so a sequential reader sees the same faults
+/// on every run. Concurrent readers read in a different order each time.
+pub struct FaultyFileSystem {
+    inner: SharedFileSystem,
+    rules: Vec<Rule>,
+    rng: Mutex<StdRng>,
+    injected: Mutex<Vec<InjectedFault>>,
+}
+
+impl FaultyFileSystem {
+    pub fn new(inner: SharedFileSystem) -> Self {
+        Self {
+            inner,
+            rules: vec![],
+            rng: Mutex::new(StdRng::seed_from_u64(0)),
+            injected: Mutex::new(vec![]),
+        }
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.rng = Mutex::new(StdRng::seed_from_u64(seed));
+        self
+    }
+
+    /// Injects `fault` into every read of a directory matching `pattern`, a glob over the whole
+    /// path in which `*` stays within one name and `**` spans any number of them, e.g.
+    /// `**/private` or `/data/*/cache`.
+    pub fn fail_matching(mut self, pattern: &str, fault: Fault) -> Result<Self, globset::Error> {
+        let matcher = GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()?
+            .compile_matcher();
+        self.rules.push(Rule {
+            pattern: Some(matcher),
+            probability: 1.0,
+            fault,
+        });
+        Ok(self)
+    }
+
+    /// Injects `fault` into any read with the given probability, from 0 to 1.
+    pub fn fail_randomly(mut self, probability: f64, fault: Fault) -> Self {
+        self.rules.push(Rule {
+            pattern: None,
+            probability: probability.clamp(0.0, 1.0),
+            fault,
+        });
+        self
+    }
+
+    /// The faults injected so far, in the order of the reads.
+    pub fn injected(&self) -> Vec<InjectedFault> {
+        self.injected.lock().unwrap().clone()
+    }
+
+    // Picks the faults of one read and records them
+    fn faults_for(&self, path: &Path) -> Vec<Fault> {
+        let mut faults = vec![];
+        for rule in &self.rules {
+            if rule
+                .pattern
+                .as_ref()
+                .is_some_and(|pattern| !pattern.is_match(path))
+            {
+                continue;
+            }
+            if rule.probability < 1.0 && !self.rng.lock().unwrap().gen_bool(rule.probability) {
+                continue;
+            }
+            faults.push(rule.fault);
+            if !matches!(rule.fault, Fault::Slow(_)) {
+                break;
+            }
+        }
+
+        let mut injected = self.injected.lock().unwrap();
+        injected.extend(faults.iter().map(|&fault| InjectedFault {
+            path: path.to_path_buf(),
+            fault,
+        }));
+        faults
+    }
+}
+
+#[async_trait]
+impl FileSystem for FaultyFileSystem {
+    async fn read_dir(&self, path: &Path) -> io::Result<Vec<DirEntry>> {
+        for fault in self.faults_for(path) {
+            match fault.to_error() {
+                Some(error) => return Err(error),
+                None => {
+                    if let Fault::Slow(delay) = fault {
+                        tokio::time::sleep(delay).await;
+                    }
+                }
+            }
+        }
+        self.inner.read_dir(path).await
+    }
+
+    // The faults are of the reads, the times come from the wrapped file system
+    async fn dir_time(&self, path: &Path) -> io::Result<DirTime> {
+        self.inner.dir_time(path).await
+    }
+}
@@ -0,0 +1,281 @@
+use async_trait::async_trait;
+use std::ffi::OsString;
+use std::fs::{self, FileType, Metadata};
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use crate::modules::efu::file_time::file_attributes;
+use crate::modules::path_arena::EntrySize;
+
+/// Kind of a directory entry. Symbolic links are not followed, the readers count them as files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    File,
+    Dir,
+    Symlink,
+}
+
+impl From<FileType> for FileKind {
+    fn from(file_type: FileType) -> Self {
+        if file_type.is_dir() {
+            FileKind::Dir
+        } else if file_type.is_symlink() {
+            FileKind::Symlink
+        } else {
+            FileKind::File
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirEntry {
+    pub name: OsString,
+    pub kind: FileKind,
+    /// Only listed by the file systems asked to, like `modified` and `attributes`.
+    pub size: Option<EntrySize>,
+    pub modified: Option<SystemTime>,
+    /// Windows file attributes, derived from the file type and permissions elsewhere.
+    pub attributes: Option<u32>,
+}
+
+impl DirEntry {
+    /// The entry `name` at `path` with what its metadata, symlinks not followed, tells about
+    /// it. An entry whose metadata could not be read keeps only its name and kind.
+    pub(crate) fn listed(
+        name: OsString,
+        kind: FileKind,
+        path: &Path,
+        metadata: Option<&Metadata>,
+    ) -> Self {
+        DirEntry {
+            size: metadata.map(|metadata| entry_size(path, metadata)),
+            modified: metadata.and_then(|metadata| metadata.modified().ok()),
+            attributes: metadata.map(|metadata| file_attributes(metadata, &name)),
+            name,
+            kind,
+        }
+    }
+}
+
+/// What the directory readers need from a file system, so that they can read an in-memory tree
+/// (see `MemoryFileSystem`) as well as the disk.
+#[async_trait]
+pub trait FileSystem: Send + Sync {
+    /// The entries of the directory at `path` in no particular order, without `.` and `..`.
+    async fn read_dir(&self, path: &Path) -> io::Result<Vec<DirEntry>>;
+
+    /// When the directory at `path` last changed, symlinks not followed. Fails for anything
+    /// but a directory.
+    async fn dir_time(&self, path: &Path) -> io::Result<DirTime>;
+}
+
+pub type SharedFileSystem = Arc<dyn FileSystem>;
+
+/// The file system of the machine, through `tokio::fs`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OsFileSystem {
+    sizes: bool,
+}
+
+impl OsFileSystem {
+    pub fn shared() -> SharedFileSystem {
+        Arc::new(OsFileSystem::default())
+    }
+
+    /// Lists the sizes of the entries with their names, for the usage reports. Free on Windows,
+    /// where the listing holds them, a call per entry elsewhere.
+    pub fn listing_sizes() -> SharedFileSystem {
+        Arc::new(OsFileSystem { sizes: true })
+    }
+}
+
+#[async_trait]
+impl FileSystem for OsFileSystem {
+    async fn read_dir(&self, path: &Path) -> io::Result<Vec<DirEntry>> {
+        if self.sizes {
+            let path = path.to_path_buf();
+            return tokio::task::spawn_blocking(move || read_dir_with_sizes(&path))
+                .await
+                .map_err(io::Error::other)?;
+        }
+
+        let mut read_dir = tokio::fs::read_dir(path).await?;
+        let mut entries = vec![];
+        while let Some(entry) = read_dir.next_entry().await? {
+            entries.push(DirEntry {
+                kind: entry.file_type().await?.into(),
+                name: entry.file_name(),
+                size: None,
+                modified: None,
+                attributes: None,
+            });
+        }
+        Ok(entries)
+    }
+
+    async fn dir_time(&self, path: &Path) -> io::Result<DirTime> {
+        let metadata = tokio::fs::symlink_metadata(path).await?;
+        if !metadata.is_dir() {
+            return Err(io::ErrorKind::NotADirectory.into());
+        }
+        Ok(directory_time(&metadata))
+    }
+}
+
+/// When a directory last changed, in seconds and nanoseconds since the epoch. Adding, removing
+/// or renaming an entry changes it, changes below its subdirectories don't.
+pub(crate) type DirTime = (u64, u32);
+
+// mlocate keeps the later of the change and modification time to detect changes
+#[cfg(unix)]
+pub(crate) fn directory_time(metadata: &Metadata) -> DirTime {
+    use std::os::unix::fs::MetadataExt;
+
+    let ctime = (metadata.ctime(), metadata.ctime_nsec());
+    let mtime = (metadata.mtime(), metadata.mtime_nsec());
+    let (seconds, nanos) = ctime.max(mtime);
+    (seconds.max(0) as u64, nanos as u32)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn directory_time(metadata: &Metadata) -> DirTime {
+    use std::time::UNIX_EPOCH;
+
+    metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|since| (since.as_secs(), since.subsec_nanos()))
+        .unwrap_or((0, 0))
+}
+
+fn read_dir_with_sizes(path: &Path) -> io::Result<Vec<DirEntry>> {
+    let mut entries = vec![];
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        // An entry removed since the listing is kept, without sizes
+        let metadata = entry.metadata().ok();
+        entries.push(DirEntry::listed(
+            entry.file_name(),
+            entry.file_type()?.into(),
+            &entry.path(),
+            metadata.as_ref(),
+        ));
+    }
+    Ok(entries)
+}
+
+/// The sizes of the entry at `path`, whose metadata, symlinks not followed, is `metadata`.
+pub(crate) fn entry_size(path: &Path, metadata: &Metadata) -> EntrySize {
+    EntrySize {
+        len: metadata.len(),
+        allocated: allocated_size(path, metadata),
+        link: link_id(path, metadata),
+    }
+}
+
+#[cfg(unix)]
+fn link_id(_path: &Path, metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    (metadata.is_file() && metadata.nlink() > 1).then(|| (metadata.dev(), metadata.ino()))
+}
+
+// The link count and file index are only known from an open handle. Opening without access
+// reads the file record and leaves the contents alone.
+#[cfg(windows)]
+fn link_id(path: &Path, metadata: &Metadata) -> Option<(u64, u64)> {
+    use std::mem;
+    use std::os::windows::fs::OpenOptionsExt;
+    use std::os::windows::io::AsRawHandle;
+    use winapi::um::fileapi::{GetFileInformationByHandle, BY_HANDLE_FILE_INFORMATION};
+    use winapi::um::winbase::{FILE_FLAG_BACKUP_SEMANTICS, FILE_FLAG_OPEN_REPARSE_POINT};
+
+    if !metadata.is_file() {
+        return None;
+    }
+    let file = fs::OpenOptions::new()
+        .access_mode(0)
+        .custom_flags(FILE_FLAG_BACKUP_SEMANTICS | FILE_FLAG_OPEN_REPARSE_POINT)
+        .open(path)
+        .ok()?;
+
+    // SAFETY: the handle stays open while `file` lives and `info` is written by the call
+    let mut info: BY_HANDLE_FILE_INFORMATION = unsafe { mem::zeroed() };
+    if unsafe { GetFileInformationByHandle(file.as_raw_handle().cast(), &mut info) } == 0 {
+        return None;
+    }
+    let index = (u64::from(info.nFileIndexHigh) << 32) | u64::from(info.nFileIndexLow);
+    (info.nNumberOfLinks > 1).then(|| (u64::from(info.dwVolumeSerialNumber), index))
+}
+
+#[cfg(not(any(unix, windows)))]
+fn link_id(_path: &Path, _metadata: &Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+#[cfg(unix)]
+fn allocated_size(_path: &Path, metadata: &Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+
+    // st_blocks counts 512 byte units whatever the block size of the file system
+    metadata.blocks() * 512
+}
+
+// The size rounded up to the clusters of the volume, compressed and sparse files take less
+#[cfg(not(unix))]
+fn allocated_size(path: &Path, metadata: &Metadata) -> u64 {
+    let cluster = cluster_bytes(path);
+    metadata.len().div_ceil(cluster) * cluster
+}
+
+// Of the volume `path` is on, asked once per volume
+#[cfg(windows)]
+fn cluster_bytes(path: &Path) -> u64 {
+    use std::collections::HashMap;
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::{Component, PathBuf};
+    use std::sync::{LazyLock, Mutex};
+    use winapi::um::fileapi::GetDiskFreeSpaceW;
+
+    static CLUSTERS: LazyLock<Mutex<HashMap<PathBuf, u64>>> = LazyLock::new(Default::default);
+
+    // `C:\` or `\\server\share\`, with the separator the call requires
+    let root: PathBuf = path
+        .components()
+        .take_while(|c| matches!(c, Component::Prefix(_) | Component::RootDir))
+        .collect();
+    *CLUSTERS
+        .lock()
+        .unwrap()
+        .entry(root)
+        .or_insert_with_key(|root| {
+            let name: Vec<u16> = root.as_os_str().encode_wide().chain([0]).collect();
+            let (mut sectors, mut sector_bytes, mut free, mut total) = (0, 0, 0, 0);
+            // SAFETY: `name` is NUL terminated and the counts are written by the call
+            let ok = unsafe {
+                GetDiskFreeSpaceW(
+                    name.as_ptr(),
+                    &mut sectors,
+                    &mut sector_bytes,
+                    &mut free,
+                    &mut total,
+                )
+            };
+            match u64::from(sectors) * u64::from(sector_bytes) {
+                cluster if ok != 0 && cluster > 0 => cluster,
+                _ => DEFAULT_CLUSTER_BYTES,
+            }
+        })
+}
+
+#[cfg(not(any(unix, windows)))]
+fn cluster_bytes(_path: &Path) -> u64 {
+    DEFAULT_CLUSTER_BYTES
+}
+
+// Of NTFS, for the volumes that can't be asked
+#[cfg(not(unix))]
+const DEFAULT_CLUSTER_BYTES: u64 = 4096;
@@ -0,0 +1,258 @@
+use async_trait::async_trait;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+
+use crate::modules::file_system::file_system_impl::{DirEntry, DirTime, FileKind, FileSystem};
+
+// Like the limit of Linux, to give up on symlink loops
+const MAX_SYMLINK_HOPS: usize = 40;
+
+#[derive(Debug)]
+enum Node {
+    File,
+    Dir {
+        entries: BTreeMap<OsString, Node>,
+        readable: bool,
+        time: DirTime,
+    },
+    Symlink(PathBuf),
+}
+
+impl Node {
+    fn dir(time: DirTime) -> Self {
+        Node::Dir {
+            entries: BTreeMap::new(),
+            readable: true,
+            time,
+        }
+    }
+
+    fn kind(&self) -> FileKind {
+        match self {
+            Node::File => FileKind::File,
+            Node::Dir { .. } => FileKind::Dir,
+            Node::Symlink(_) => FileKind::Symlink,
+        }
+    }
+}
+
+/// A tree of directories, files and symlinks held in memory, for running the readers without a
+/// disk. Paths are taken from the root of the tree: a prefix or root is ignored, so `C:\a\b`,
+/// `/a/b` and `a/b` are the same directory.
+///
+/// The times of the directories count the changes to the tree instead of seconds: every entry
+/// added or removed sets the time of its directory to the next count.
+#[derive(Debug)]
+pub struct MemoryFileSystem {
+    root: RwLock<Node>,
+    changes: AtomicU64,
+}
+
+impl Default for MemoryFileSystem {
+    fn default() -> Self {
+        Self {
+            root: RwLock::new(Node::dir((0, 0))),
+            changes: AtomicU64::new(0),
+        }
+    }
+}
+
+impl MemoryFileSystem {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates the directory and its missing parents, like `std::fs::create_dir_all`.
+    pub fn create_dir_all(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let now = self.now();
+        let mut root = self.root.write().unwrap();
+        dir_entries(&mut root, &names(path.as_ref()), Some(now))?;
+        Ok(())
+    }
+
+    /// Adds an empty file, creating the missing parent directories.
+    pub fn add_file(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.insert(path.as_ref(), Node::File)
+    }
+
+    /// Adds a symlink to `target`, which is taken from the directory of the link when relative.
+    /// The readers don't follow symlinks, but reading a directory through one does.
+    pub fn add_symlink(&self, path: impl AsRef<Path>, target: impl AsRef<Path>) -> io::Result<()> {
+        self.insert(path.as_ref(), Node::Symlink(target.as_ref().to_path_buf()))
+    }
+
+    /// Makes reading the directory fail with `PermissionDenied`, or succeed again.
+    pub fn set_readable(&self, path: impl AsRef<Path>, readable: bool) -> io::Result<()> {
+        let mut root = self.root.write().unwrap();
+        match get_mut(&mut root, &names(path.as_ref()))? {
+            Node::Dir { readable: r, .. } => {
+                *r = readable;
+                Ok(())
+            }
+            _ => Err(io::ErrorKind::NotADirectory.into()),
+        }
+    }
+
+    /// Removes the entry, with everything below it for a directory.
+    pub fn remove(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut names = names(path.as_ref());
+        let name = names.pop().ok_or(io::ErrorKind::InvalidInput)?;
+        let now = self.now();
+        let mut root = self.root.write().unwrap();
+        let (entries, time) = dir_entries(&mut root, &names, None)?;
+        entries.remove(&name).ok_or(io::ErrorKind::NotFound)?;
+        *time = now;
+        Ok(())
+    }
+
+    fn insert(&self, path: &Path, node: Node) -> io::Result<()> {
+        let mut names = names(path);
+        let name = names.pop().ok_or(io::ErrorKind::AlreadyExists)?;
+        let now = self.now();
+        let mut root = self.root.write().unwrap();
+        let (entries, time) = dir_entries(&mut root, &names, Some(now))?;
+        if entries.contains_key(&name) {
+            return Err(io::ErrorKind::AlreadyExists.into());
+        }
+        entries.insert(name, node);
+        *time = now;
+        Ok(())
+    }
+
+    fn now(&self) -> DirTime {
+        (self.changes.fetch_add(1, Ordering::Relaxed) + 1, 0)
+    }
+}
+
+#[async_trait]
+impl FileSystem for MemoryFileSystem {
+    async fn read_dir(&self, path: &Path) -> io::Result<Vec<DirEntry>> {
+        let root = self.root.read().unwrap();
+        match resolve(&root, path)? {
+            Node::Dir {
+                readable: false, ..
+            } => Err(io::ErrorKind::PermissionDenied.into()),
+            Node::Dir { entries, .. } => Ok(entries
+                .iter()
+                .map(|(name, node)| DirEntry {
+                    name: name.clone(),
+                    kind: node.kind(),
+                    size: None,
+                    modified: None,
+                    attributes: None,
+                })
+                .collect()),
+            _ => Err(io::ErrorKind::NotADirectory.into()),
+        }
+    }
+
+    async fn dir_time(&self, path: &Path) -> io::Result<DirTime> {
+        let root = self.root.read().unwrap();
+        let mut names = names(path);
+        let node = match names.pop() {
+            // The last name is looked up without following a symlink
+            Some(name) => match resolve(&root, &names.iter().collect::<PathBuf>())? {
+                Node::Dir { entries, .. } => entries.get(&name).ok_or(io::ErrorKind::NotFound)?,
+                _ => return Err(io::ErrorKind::NotADirectory.into()),
+            },
+            None => &*root,
+        };
+        match node {
+            Node::Dir { time, .. } => Ok(*time),
+            _ => Err(io::ErrorKind::NotADirectory.into()),
+        }
+    }
+}
+
+// Appends the names of `path` to `names`, with `..` removing the last one
+fn push_names(names: &mut Vec<OsString>, path: &Path) {
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => names.push(name.to_os_string()),
+            Component::ParentDir => {
+                names.pop();
+            }
+            Component::Prefix(_) | Component::RootDir | Component::CurDir => {}
+        }
+    }
+}
+
+fn names(path: &Path) -> Vec<OsString> {
+    let mut names = vec![];
+    push_names(&mut names, path);
+    names
+}
+
+// The node at `names` without following symlinks, for the changes to the tree
+fn get_mut<'a>(root: &'a mut Node, names: &[OsString]) -> io::Result<&'a mut Node> {
+    let mut node = root;
+    for name in names {
+        node = match node {
+            Node::Dir { entries, .. } => entries.get_mut(name).ok_or(io::ErrorKind::NotFound)?,
+            _ => return Err(io::ErrorKind::NotADirectory.into()),
+        };
+    }
+    Ok(node)
+}
+
+// The entries and time of the directory at `names`, the missing directories are created at the
+// time `create` if given
+fn dir_entries<'a>(
+    root: &'a mut Node,
+    names: &[OsString],
+    create: Option<DirTime>,
+) -> io::Result<(&'a mut BTreeMap<OsString, Node>, &'a mut DirTime)> {
+    let mut node = root;
+    for name in names {
+        let Node::Dir { entries, time, .. } = node else {
+            return Err(io::ErrorKind::NotADirectory.into());
+        };
+        node = match create {
+            Some(now) if !entries.contains_key(name) => {
+                *time = now;
+                entries.entry(name.clone()).or_insert(Node::dir(now))
+            }
+            _ => entries.get_mut(name).ok_or(io::ErrorKind::NotFound)?,
+        };
+    }
+    match node {
+        Node::Dir { entries, time, .. } => Ok((entries, time)),
+        _ => Err(io::ErrorKind::NotADirectory.into()),
+    }
+}
+
+// The node at `path`, following the symlinks on the way and at the end
+fn resolve<'a>(root: &'a Node, path: &Path) -> io::Result<&'a Node> {
+    let mut pending: Vec<OsString> = names(path).into_iter().rev().collect();
+    let mut node = root;
+    let mut resolved = vec![];
+    let mut hops = 0;
+    while let Some(name) = pending.pop() {
+        let child = match node {
+            Node::Dir { entries, .. } => entries.get(&name).ok_or(io::ErrorKind::NotFound)?,
+            _ => return Err(io::ErrorKind::NotADirectory.into()),
+        };
+        match child {
+            Node::Symlink(target) => {
+                hops += 1;
+                if hops > MAX_SYMLINK_HOPS {
+                    return Err(io::Error::other("too many levels of symbolic links"));
+                }
+                let mut target_names = if target.has_root() { vec![] } else { resolved };
+                push_names(&mut target_names, target);
+                pending.extend(target_names.into_iter().rev());
+                node = root;
+                resolved = vec![];
+            }
+            _ => {
+                node = child;
+                resolved.push(name);
+            }
+        }
+    }
+    Ok(node)
+}
@@ -0,0 +1,12 @@
+pub mod faulty_fs;
+pub mod file_system_impl;
+pub mod memory_fs;
+
+pub(crate) use file_system_impl::directory_time;
+pub(crate) use file_system_impl::entry_size;
+pub(crate) use file_system_impl::DirEntry;
+pub(crate) use file_system_impl::DirTime;
+pub(crate) use file_system_impl::FileKind;
+pub(crate) use file_system_impl::FileSystem;
+pub(crate) use file_system_impl::OsFileSystem;
+pub(crate) use file_system_impl::SharedFileSystem;
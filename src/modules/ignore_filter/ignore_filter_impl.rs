@@ -0,0 +1,160 @@
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use serde::Deserialize;
+use std::path::Path;
+use std::sync::Arc;
+use tracing::warn;
+
+use crate::modules::file_system::DirEntry;
+
+/// The ignore files read in every directory, a later file overrides an earlier one as in git.
+pub const IGNORE_FILE_NAMES: [&str; 3] = [".gitignore", ".ignore", ".uffsignore"];
+
+/// Which entries a scan leaves out, read from the `[ignore]` section of `uffs.toml`, e.g.
+///
+/// ```text
+/// [ignore]
+/// ignore_files = true
+/// exclude = ["target/", "node_modules/"]
+/// skip_hidden = true
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct IgnoreOptions {
+    /// Honor the `IGNORE_FILE_NAMES` found below the scanned roots
+    pub ignore_files: bool,
+    /// Gitignore patterns applied everywhere, e.g. `target/` or `*.tmp`. Patterns with a slash
+    /// other than at the end match the full path.
+    pub exclude: Vec<String>,
+    /// Leave out the names starting with a dot
+    pub skip_hidden: bool,
+}
+
+#[derive(Debug)]
+struct Rules {
+    ignore_files: bool,
+    skip_hidden: bool,
+    exclude: Gitignore,
+}
+
+// The ignore files of one directory, with those of the directories above it
+#[derive(Debug)]
+struct Level {
+    ignore: Gitignore,
+    parent: Option<Arc<Level>>,
+}
+
+/// Decides which entries of a directory are left out of a scan. A filter is entered for every
+/// directory read, taking up its ignore files for the entries below it. The default filter
+/// leaves nothing out and costs nothing.
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreFilter {
+    rules: Option<Arc<Rules>>,
+    levels: Option<Arc<Level>>,
+}
+
+impl IgnoreFilter {
+    pub fn new(options: &IgnoreOptions) -> Result<Self, ignore::Error> {
+        if *options == IgnoreOptions::default() {
+            return Ok(Self::default());
+        }
+
+        let mut exclude = GitignoreBuilder::new("");
+        for pattern in &options.exclude {
+            exclude.add_line(None, pattern)?;
+        }
+        Ok(Self {
+            rules: Some(Arc::new(Rules {
+                ignore_files: options.ignore_files,
+                skip_hidden: options.skip_hidden,
+                exclude: exclude.build()?,
+            })),
+            levels: None,
+        })
+    }
+
+    /// Whether the filter leaves out anything, the readers skip the filtering otherwise.
+    pub fn is_active(&self) -> bool {
+        self.rules.is_some()
+    }
+
+    /// The filter for the entries of `dir`, with the ignore files `dir` holds. The ignore files
+    /// that cannot be read are skipped with a warning.
+    pub fn enter(&self, dir: &Path) -> IgnoreFilter {
+        if !self.rules.as_ref().is_some_and(|rules| rules.ignore_files) {
+            return self.clone();
+        }
+
+        let mut builder = GitignoreBuilder::new(dir);
+        let mut found = false;
+        for name in IGNORE_FILE_NAMES {
+            let path = dir.join(name);
+            if !path.is_file() {
+                continue;
+            }
+            found = true;
+            if let Some(e) = builder.add(&path) {
+                warn!("Failed to read {}: {}", path.display(), e);
+            }
+        }
+        if !found {
+            return self.clone();
+        }
+
+        match builder.build() {
+            Ok(ignore) if !ignore.is_empty() => IgnoreFilter {
+                rules: self.rules.clone(),
+                levels: Some(Arc::new(Level {
+                    ignore,
+                    parent: self.levels.clone(),
+                })),
+            },
+            Ok(_) => self.clone(),
+            Err(e) => {
+                warn!(
+                    "Failed to read the ignore files of {}: {}",
+                    dir.display(),
+                    e
+                );
+                self.clone()
+            }
+        }
+    }
+
+    /// Whether the entry at `path`, in a directory this filter was entered for, is left out.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let Some(rules) = &self.rules else {
+            return false;
+        };
+        if rules.skip_hidden
+            && path
+                .file_name()
+                .is_some_and(|name| name.as_encoded_bytes().starts_with(b"."))
+        {
+            return true;
+        }
+        if rules.exclude.matched(path, is_dir).is_ignore() {
+            return true;
+        }
+
+        // The closest ignore file with a rule for the path decides, as in git
+        let mut level = self.levels.as_deref();
+        while let Some(current) = level {
+            match current.ignore.matched(path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+                Match::None => level = current.parent.as_deref(),
+            }
+        }
+        false
+    }
+
+    /// Drops the ignored entries from a listing of `dir`.
+    pub(crate) fn retain(&self, dir: &Path, files: &mut Vec<DirEntry>, dirs: &mut Vec<DirEntry>) {
+        if !self.is_active() {
+            return;
+        }
+        files.retain(|entry| !self.is_ignored(&dir.join(&entry.name), false));
+        dirs.retain(|entry| !self.is_ignored(&dir.join(&entry.name), true));
+    }
+}
@@ -0,0 +1,4 @@
+pub mod ignore_filter_impl;
+
+pub(crate) use ignore_filter_impl::IgnoreFilter;
+pub(crate) use ignore_filter_impl::IgnoreOptions;
@@ -0,0 +1,696 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use std::slice;
+
+use tracing::warn;
+
+use crate::modules::errors::UFFSError;
+use crate::modules::file_system::{directory_time, DirTime};
+use crate::modules::path_arena::{EntryId, EntrySize, PathArena};
+
+const MLOCATE_MAGIC: &[u8; 8] = b"\0mlocate";
+const PLOCATE_MAGIC: &[u8; 8] = b"\0plocate";
+const MLOCATE_VERSION: u8 = 0;
+
+// The sizes file next to a database, see `write_sizes`
+const SIZES_MAGIC: &[u8; 8] = b"\0uffssiz";
+const NO_SIZE: u8 = 0;
+const SIZE: u8 = 1;
+const LINKED_SIZE: u8 = 2;
+
+const ENTRY_FILE: u8 = 0;
+const ENTRY_DIR: u8 = 1;
+const ENTRY_END: u8 = 2;
+
+// The variables updatedb stores, sorted by name. Nothing was pruned from our scans.
+const MLOCATE_CONFIGURATION: &[(&str, &[&str])] = &[
+    ("prune_bind_mounts", &["0"]),
+    ("prunefs", &[]),
+    ("prunenames", &[]),
+    ("prunepaths", &[]),
+];
+
+/// Writes the entries of `arenas` to `path` as an mlocate database, as `updatedb` would, and
+/// returns the number of entries written. Volumes scanned more than once (a mount point below
+/// another scanned root) are written once. The sizes the scan listed are kept next to it, see
+/// `sizes_path`.
+///
+/// The database is written next to `path` and renamed when complete, so `locate` never sees a
+/// partial database.
+pub(crate) fn write_mlocate_db(path: &Path, arenas: &[PathArena]) -> Result<usize, UFFSError> {
+    write_database(path, arenas, &disk_time)
+}
+
+fn disk_time(_dir: EntryId, dir_path: &Path) -> DirTime {
+    fs::metadata(dir_path)
+        .map(|metadata| directory_time(&metadata))
+        .unwrap_or((0, 0))
+}
+
+fn write_database(
+    path: &Path,
+    arenas: &[PathArena],
+    dir_time: DirTimeFn<'_>,
+) -> Result<usize, UFFSError> {
+    let mut sizes = vec![];
+    let written = write_atomically(path, |temp_path| {
+        write_mlocate_file(temp_path, arenas, dir_time, &mut sizes)
+    })?;
+    write_sizes(path, &sizes)?;
+    Ok(written)
+}
+
+/// The file the sizes of the entries of the database at `path` are kept in, mlocate databases
+/// have no place for them.
+pub(crate) fn sizes_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".sizes");
+    PathBuf::from(name)
+}
+
+// The sizes of the entries in the order of the database, the directory of each record then its
+// entries, after the length of the database to notice when it was written without them
+fn write_sizes(path: &Path, sizes: &[Option<EntrySize>]) -> Result<(), UFFSError> {
+    let sizes_path = sizes_path(path);
+    if sizes.iter().all(Option::is_none) {
+        return match fs::remove_file(&sizes_path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(UFFSError::Io(e)),
+            _ => Ok(()),
+        };
+    }
+
+    let database_len = fs::metadata(path)?.len();
+    write_atomically(&sizes_path, |temp_path| {
+        let mut out = BufWriter::new(File::create(temp_path)?);
+        out.write_all(SIZES_MAGIC)?;
+        out.write_all(&database_len.to_be_bytes())?;
+        out.write_all(&(sizes.len() as u64).to_be_bytes())?;
+        for size in sizes {
+            let Some(size) = size else {
+                out.write_all(&[NO_SIZE])?;
+                continue;
+            };
+            out.write_all(&[if size.link.is_some() {
+                LINKED_SIZE
+            } else {
+                SIZE
+            }])?;
+            out.write_all(&size.len.to_be_bytes())?;
+            out.write_all(&size.allocated.to_be_bytes())?;
+            if let Some((device, file)) = size.link {
+                out.write_all(&device.to_be_bytes())?;
+                out.write_all(&file.to_be_bytes())?;
+            }
+        }
+        out.flush()?;
+        Ok(sizes.len())
+    })?;
+    Ok(())
+}
+
+// None when the sizes file is missing or was written for another database
+fn read_sizes(path: &Path, entries: usize) -> io::Result<Option<Vec<Option<EntrySize>>>> {
+    let file = match File::open(sizes_path(path)) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let mut reader = BufReader::new(file);
+    let magic = read_u64(&mut reader)?.to_be_bytes();
+    let database_len = read_u64(&mut reader)?;
+    let count = read_u64(&mut reader)?;
+    if &magic != SIZES_MAGIC || database_len != fs::metadata(path)?.len() || count != entries as u64
+    {
+        return Ok(None);
+    }
+
+    let mut sizes = Vec::with_capacity(entries);
+    for _ in 0..entries {
+        let mut kind = [0u8; 1];
+        reader.read_exact(&mut kind)?;
+        let size = match kind[0] {
+            NO_SIZE => None,
+            SIZE | LINKED_SIZE => {
+                let len = read_u64(&mut reader)?;
+                let allocated = read_u64(&mut reader)?;
+                let link = match kind[0] {
+                    LINKED_SIZE => Some((read_u64(&mut reader)?, read_u64(&mut reader)?)),
+                    _ => None,
+                };
+                Some(EntrySize {
+                    len,
+                    allocated,
+                    link,
+                })
+            }
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "unknown size kind",
+                ))
+            }
+        };
+        sizes.push(size);
+    }
+    Ok(Some(sizes))
+}
+
+fn write_atomically<F>(path: &Path, write: F) -> Result<usize, UFFSError>
+where
+    F: FnOnce(&Path) -> Result<usize, UFFSError>,
+{
+    let temp_path = path.with_extension("uffs-tmp");
+    let result = write(&temp_path);
+
+    match result {
+        Ok(written) => {
+            fs::rename(&temp_path, path)?;
+            Ok(written)
+        }
+        Err(e) => {
+            let _ = fs::remove_file(&temp_path);
+            Err(e)
+        }
+    }
+}
+
+/// Writes a plocate database by converting an mlocate database with `plocate-build`, which
+/// comes with plocate.
+pub(crate) fn write_plocate_db(path: &Path, arenas: &[PathArena]) -> Result<usize, UFFSError> {
+    let mlocate_path = path.with_extension("mlocate-tmp");
+    // plocate keeps no sizes
+    let written = write_atomically(&mlocate_path, |temp_path| {
+        write_mlocate_file(temp_path, arenas, &disk_time, &mut vec![])
+    })?;
+
+    let status = Command::new("plocate-build")
+        .arg(&mlocate_path)
+        .arg(path)
+        .status();
+    let _ = fs::remove_file(&mlocate_path);
+
+    match status {
+        Ok(status) if status.success() => Ok(written),
+        Ok(status) => Err(UFFSError::Io(io::Error::other(format!(
+            "plocate-build failed with {}",
+            status
+        )))),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Err(UFFSError::Io(io::Error::new(
+            e.kind(),
+            "plocate-build not found, install plocate to write plocate databases",
+        ))),
+        Err(e) => Err(UFFSError::Io(e)),
+    }
+}
+
+// The time stored for a directory, by id and path
+type DirTimeFn<'a> = &'a dyn Fn(EntryId, &Path) -> DirTime;
+
+// Collects the sizes of the entries in the order they are written into `sizes`
+fn write_mlocate_file(
+    path: &Path,
+    arenas: &[PathArena],
+    dir_time: DirTimeFn<'_>,
+    sizes: &mut Vec<Option<EntrySize>>,
+) -> Result<usize, UFFSError> {
+    let mut out = BufWriter::new(File::create(path)?);
+
+    // Every directory with its arena, in path order so that parents come before their children
+    let mut directories: Vec<(PathBuf, usize, EntryId)> = vec![];
+    let mut children: Vec<Vec<(EntryId, EntryId)>> = Vec::with_capacity(arenas.len());
+    for (index, arena) in arenas.iter().enumerate() {
+        // (parent, child) pairs, sorted by parent to find the children of a directory
+        let mut by_parent: Vec<(EntryId, EntryId)> = arena
+            .dirs()
+            .iter()
+            .chain(arena.files())
+            .filter_map(|&id| arena.parent(id).map(|parent| (parent, id)))
+            .collect();
+        by_parent.sort_unstable();
+
+        for root in arena.roots() {
+            directories.push((arena.path(root), index, root));
+        }
+        for &dir in arena.dirs() {
+            directories.push((arena.path(dir), index, dir));
+        }
+        children.push(by_parent);
+    }
+    directories.sort_by(|a, b| a.0.cmp(&b.0));
+    directories.dedup_by(|a, b| a.0 == b.0);
+
+    let database_root = common_root(directories.iter().map(|(path, _, _)| path.as_path()));
+
+    write_header(&mut out, &database_root)?;
+
+    let mut written = 0;
+    for (dir_path, index, dir) in &directories {
+        let arena = &arenas[*index];
+        let by_parent = &children[*index];
+
+        let (seconds, nanos) = dir_time(*dir, dir_path);
+        out.write_all(&seconds.to_be_bytes())?;
+        out.write_all(&nanos.to_be_bytes())?;
+        out.write_all(&[0; 4])?;
+        write_c_string(&mut out, dir_path.as_os_str())?;
+        sizes.push(arena.size(*dir));
+
+        let start = by_parent.partition_point(|&(parent, _)| parent < *dir);
+        let mut entries: Vec<(&OsStr, u8, EntryId)> = by_parent[start..]
+            .iter()
+            .take_while(|&&(parent, _)| parent == *dir)
+            .map(|&(_, id)| {
+                let kind = if arena.is_dir(id) {
+                    ENTRY_DIR
+                } else {
+                    ENTRY_FILE
+                };
+                (arena.name(id), kind, id)
+            })
+            .collect();
+        entries.sort_unstable_by(|a, b| a.0.as_encoded_bytes().cmp(b.0.as_encoded_bytes()));
+
+        for (name, kind, id) in entries {
+            out.write_all(&[kind])?;
+            write_c_string(&mut out, name)?;
+            sizes.push(arena.size(id));
+            written += 1;
+        }
+        out.write_all(&[ENTRY_END])?;
+    }
+
+    out.flush()?;
+    Ok(written)
+}
+
+fn write_header(out: &mut impl Write, database_root: &Path) -> io::Result<()> {
+    let mut configuration = vec![];
+    for (name, values) in MLOCATE_CONFIGURATION {
+        configuration.extend_from_slice(name.as_bytes());
+        configuration.push(0);
+        for value in *values {
+            configuration.extend_from_slice(value.as_bytes());
+            configuration.push(0);
+        }
+        configuration.push(0);
+    }
+
+    out.write_all(MLOCATE_MAGIC)?;
+    out.write_all(&(configuration.len() as u32).to_be_bytes())?;
+    // Version, then require_visibility: locate only shows entries the user may access
+    out.write_all(&[MLOCATE_VERSION, 1, 0, 0])?;
+    write_c_string(out, database_root.as_os_str())?;
+    out.write_all(&configuration)
+}
+
+fn write_c_string(out: &mut impl Write, value: &OsStr) -> io::Result<()> {
+    out.write_all(value.as_encoded_bytes())?;
+    out.write_all(&[0])
+}
+
+// The deepest directory containing all scanned directories
+fn common_root<'a>(mut paths: impl Iterator<Item = &'a Path>) -> PathBuf {
+    let Some(first) = paths.next() else {
+        return PathBuf::from(Component::RootDir.as_os_str());
+    };
+
+    let mut root: Vec<Component> = first.components().collect();
+    for path in paths {
+        let common = root
+            .iter()
+            .zip(path.components())
+            .take_while(|(a, b)| *a == b)
+            .count();
+        root.truncate(common);
+    }
+    root.iter().collect()
+}
+
+/// A locate database loaded as a search source.
+#[derive(Debug, Default)]
+pub(crate) struct LocateDb {
+    arena: PathArena,
+    // Of every directory with its own record, the roots included
+    times: HashMap<EntryId, DirTime>,
+}
+
+impl LocateDb {
+    pub(crate) fn new(arena: PathArena, times: HashMap<EntryId, DirTime>) -> Self {
+        Self { arena, times }
+    }
+
+    pub(crate) fn load(path: &Path) -> Result<LocateDb, UFFSError> {
+        let invalid = |message: &str| UFFSError::InvalidFileList {
+            path: path.display().to_string(),
+            line: 0,
+            message: message.to_string(),
+        };
+        let truncated = |e: io::Error| match e.kind() {
+            io::ErrorKind::UnexpectedEof => invalid("the database is truncated"),
+            _ => UFFSError::Io(e),
+        };
+
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let mut header = [0u8; 16];
+        reader.read_exact(&mut header).map_err(truncated)?;
+        if &header[..8] == PLOCATE_MAGIC {
+            return Err(invalid(
+                "plocate databases can't be read, import the mlocate database it was built from",
+            ));
+        }
+        if &header[..8] != MLOCATE_MAGIC {
+            return Err(invalid("not an mlocate database"));
+        }
+        if header[12] != MLOCATE_VERSION {
+            return Err(invalid("unsupported mlocate database version"));
+        }
+        let configuration_size = u32::from_be_bytes([header[8], header[9], header[10], header[11]]);
+
+        let _database_root = read_c_string(&mut reader).map_err(truncated)?;
+        io::copy(
+            &mut reader.by_ref().take(u64::from(configuration_size)),
+            &mut io::sink(),
+        )?;
+
+        let mut arena = PathArena::new();
+        let mut times = HashMap::new();
+        // Directories seen as entries, to attach their own listing to them
+        let mut dir_ids: HashMap<Vec<u8>, EntryId> = HashMap::new();
+        // The ids in the order of the database, that of the sizes
+        let mut order: Vec<EntryId> = vec![];
+
+        // Directory records until the end of the file
+        while !reader.fill_buf()?.is_empty() {
+            let mut directory_header = [0u8; 16];
+            reader
+                .read_exact(&mut directory_header)
+                .map_err(truncated)?;
+            let dir_path = read_c_string(&mut reader).map_err(truncated)?;
+
+            let parent = match dir_ids.get(&dir_path) {
+                Some(&id) => id,
+                None => arena.add_root(Path::new(&*os_str(&dir_path))),
+            };
+            let seconds = u64::from_be_bytes(directory_header[..8].try_into().unwrap());
+            let nanos = u32::from_be_bytes(directory_header[8..12].try_into().unwrap());
+            times.insert(parent, (seconds, nanos));
+            order.push(parent);
+
+            loop {
+                let mut kind = [0u8; 1];
+                reader.read_exact(&mut kind).map_err(truncated)?;
+                match kind[0] {
+                    ENTRY_END => break,
+                    ENTRY_FILE => {
+                        let name = read_c_string(&mut reader).map_err(truncated)?;
+                        order.push(arena.add_file(parent, &os_str(&name)));
+                    }
+                    ENTRY_DIR => {
+                        let name = read_c_string(&mut reader).map_err(truncated)?;
+                        let id = arena.add_dir(parent, &os_str(&name));
+                        order.push(id);
+
+                        let mut child_path = dir_path.clone();
+                        if !child_path.ends_with(b"/") {
+                            child_path.push(b'/');
+                        }
+                        child_path.extend_from_slice(&name);
+                        dir_ids.insert(child_path, id);
+                    }
+                    _ => return Err(invalid("unknown entry type")),
+                }
+            }
+        }
+
+        // Only for the databases uffs wrote, they are left out otherwise
+        match read_sizes(path, order.len()) {
+            Ok(Some(sizes)) => {
+                for (id, size) in order.into_iter().zip(sizes) {
+                    if let Some(size) = size {
+                        arena.set_size(id, size);
+                    }
+                }
+            }
+            Ok(None) => {}
+            Err(e) => warn!("Could not read the sizes of {}: {}", path.display(), e),
+        }
+
+        arena.shrink_to_fit();
+        Ok(LocateDb { arena, times })
+    }
+
+    /// Writes the database to `path`, with the directory times it was loaded or refreshed with
+    /// rather than the current ones: a directory changed since it was read must be read again.
+    pub(crate) fn write(&self, path: &Path) -> Result<usize, UFFSError> {
+        write_database(path, slice::from_ref(&self.arena), &|dir, _| {
+            self.dir_time(dir).unwrap_or((0, 0))
+        })
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.arena.num_files() + self.arena.num_dirs()
+    }
+
+    /// The entries whose name contains `pattern`, ignoring case. An empty pattern matches all.
+    pub(crate) fn search<'a>(
+        &'a self,
+        pattern: &str,
+    ) -> impl Iterator<Item = (PathBuf, &'static str)> + 'a {
+        self.arena
+            .search(pattern)
+            .map(|(id, kind)| (self.arena.path(id), kind))
+    }
+
+    pub(crate) fn arena(&self) -> &PathArena {
+        &self.arena
+    }
+
+    pub(crate) fn dir_time(&self, dir: EntryId) -> Option<DirTime> {
+        self.times.get(&dir).copied()
+    }
+
+    pub(crate) fn into_arena(self) -> PathArena {
+        self.arena
+    }
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_be_bytes(bytes))
+}
+
+fn read_c_string(reader: &mut impl BufRead) -> io::Result<Vec<u8>> {
+    let mut value = vec![];
+    reader.read_until(0, &mut value)?;
+    if value.pop() != Some(0) {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+    Ok(value)
+}
+
+// Locate databases hold the raw bytes of Unix file names
+#[cfg(unix)]
+fn os_str(bytes: &[u8]) -> Cow<'_, OsStr> {
+    use std::os::unix::ffi::OsStrExt;
+
+    Cow::Borrowed(OsStr::from_bytes(bytes))
+}
+
+#[cfg(not(unix))]
+fn os_str(bytes: &[u8]) -> Cow<'_, OsStr> {
+    match String::from_utf8_lossy(bytes) {
+        Cow::Borrowed(name) => Cow::Borrowed(OsStr::new(name)),
+        Cow::Owned(name) => Cow::Owned(name.into()),
+    }
+}
+
+// The databases join the paths with `/`, as on the systems locate runs on
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    // Of every entry, whether it is a directory, and the times of the directories, by path
+    type Listing = (BTreeMap<PathBuf, bool>, BTreeMap<PathBuf, DirTime>);
+
+    fn listing(database: &LocateDb) -> Listing {
+        let arena = database.arena();
+        let entries = arena
+            .dirs()
+            .iter()
+            .chain(arena.files())
+            .map(|&id| (arena.path(id), arena.is_dir(id)))
+            .collect();
+        let times = arena
+            .roots()
+            .chain(arena.dirs().iter().copied())
+            .filter_map(|id| Some((arena.path(id), database.dir_time(id)?)))
+            .collect();
+        (entries, times)
+    }
+
+    // /data with docs/{report.pdf, old/}, notes.txt and a time per directory
+    fn database() -> LocateDb {
+        let mut arena = PathArena::new();
+        let root = arena.add_root(Path::new("/data"));
+        let docs = arena.add_dir(root, "docs".as_ref());
+        arena.add_file(docs, "report.pdf".as_ref());
+        let old = arena.add_dir(docs, "old".as_ref());
+        arena.add_file(root, "notes.txt".as_ref());
+
+        let times = HashMap::from([
+            (root, (1_700_000_000, 5)),
+            (docs, (1_700_000_100, 999_999_999)),
+            (old, (u64::from(u32::MAX) + 1, 0)),
+        ]);
+        LocateDb::new(arena, times)
+    }
+
+    #[test]
+    fn databases_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("index.db");
+        let database = database();
+
+        assert_eq!(database.write(&path).unwrap(), 4);
+        let loaded = LocateDb::load(&path).unwrap();
+        assert_eq!(loaded.len(), 4);
+        assert_eq!(listing(&loaded), listing(&database));
+        assert!(!path.with_extension("uffs-tmp").exists());
+    }
+
+    #[test]
+    fn header_is_the_one_updatedb_writes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("index.db");
+        database().write(&path).unwrap();
+        let bytes = fs::read(&path).unwrap();
+
+        assert_eq!(&bytes[..8], MLOCATE_MAGIC);
+        let configuration_size = u32::from_be_bytes(bytes[8..12].try_into().unwrap()) as usize;
+        // Version 0, require_visibility, padding
+        assert_eq!(&bytes[12..16], &[0, 1, 0, 0]);
+        let rest = &bytes[16..];
+        assert_eq!(&rest[..b"/data\0".len()], b"/data\0");
+
+        let configuration = &rest[b"/data\0".len()..][..configuration_size];
+        assert_eq!(
+            configuration,
+            b"prune_bind_mounts\x000\0\0prunefs\0\0prunenames\0\0prunepaths\0\0"
+        );
+
+        // The first directory record, the root: seconds and nanoseconds big endian, padding
+        let record = &rest[b"/data\0".len() + configuration_size..];
+        assert_eq!(&record[..8], &1_700_000_000u64.to_be_bytes());
+        assert_eq!(&record[8..12], &5u32.to_be_bytes());
+        assert_eq!(&record[12..16], &[0; 4]);
+        assert_eq!(&record[16..22], b"/data\0");
+        // Entries sorted by name
+        assert_eq!(&record[22..24], &[ENTRY_DIR, b'd']);
+    }
+
+    #[test]
+    fn directory_times_are_those_of_the_disk() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(root.join("sub").join("file"), b"").unwrap();
+        let mut arena = PathArena::new();
+        let root_id = arena.add_root(&root);
+        let sub = arena.add_dir(root_id, "sub".as_ref());
+        arena.add_file(sub, "file".as_ref());
+
+        let path = dir.path().join("index.db");
+        assert_eq!(write_mlocate_db(&path, &[arena]).unwrap(), 2);
+        let (entries, times) = listing(&LocateDb::load(&path).unwrap());
+        assert_eq!(entries.len(), 2);
+        for dir in [root.clone(), root.join("sub")] {
+            let expected = directory_time(&fs::metadata(&dir).unwrap());
+            assert_eq!(times[&dir], expected);
+        }
+    }
+
+    #[test]
+    fn names_are_kept_byte_for_byte() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let name = OsStr::from_bytes(b"caf\xe9 \xff.txt");
+        let mut arena = PathArena::new();
+        let root = arena.add_root(Path::new("/data"));
+        arena.add_file(root, name);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("index.db");
+        LocateDb::new(arena, HashMap::new()).write(&path).unwrap();
+        let loaded = LocateDb::load(&path).unwrap();
+        assert_eq!(loaded.arena().name(loaded.arena().files()[0]), name);
+    }
+
+    #[test]
+    fn sizes_are_kept_next_to_the_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("index.db");
+        let mut sized_database = database();
+        let sized = |len| EntrySize {
+            len,
+            allocated: 4096,
+            link: (len == 7).then_some((1, 2)),
+        };
+        let mut expected = BTreeMap::new();
+        let arena = &mut sized_database.arena;
+        for (i, id) in arena.files().to_vec().into_iter().enumerate() {
+            arena.set_size(id, sized(i as u64 + 7));
+            expected.insert(arena.path(id), sized(i as u64 + 7));
+        }
+
+        sized_database.write(&path).unwrap();
+        assert!(sizes_path(&path).exists());
+        let loaded = LocateDb::load(&path).unwrap();
+        let arena = loaded.arena();
+        let sizes: BTreeMap<PathBuf, EntrySize> = (0..arena.len() as EntryId)
+            .filter_map(|id| Some((arena.path(id), arena.size(id)?)))
+            .collect();
+        assert_eq!(sizes, expected);
+
+        // Written by another tool since, the sizes are left out
+        let mut bytes = fs::read(&path).unwrap();
+        bytes.extend_from_slice(&[0; 16]);
+        bytes.extend_from_slice(b"/other\0");
+        bytes.push(ENTRY_END);
+        fs::write(&path, bytes).unwrap();
+        assert!(!LocateDb::load(&path).unwrap().arena().has_sizes());
+
+        // Written without sizes
+        database().write(&path).unwrap();
+        assert!(!sizes_path(&path).exists());
+    }
+
+    #[test]
+    fn broken_databases_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("index.db");
+        database().write(&path).unwrap();
+        let bytes = fs::read(&path).unwrap();
+
+        let invalid = |bytes: &[u8]| {
+            fs::write(&path, bytes).unwrap();
+            matches!(
+                LocateDb::load(&path),
+                Err(UFFSError::InvalidFileList { .. })
+            )
+        };
+        assert!(invalid(&bytes[..bytes.len() - 1]));
+        assert!(invalid(b"\0notlocate\0\0\0\0\0\0\0"));
+        assert!(invalid(b"\0plocate\0\0\0\0\0\0\0\0"));
+        let mut version = bytes.clone();
+        version[12] = 1;
+        assert!(invalid(&version));
+    }
+}
@@ -0,0 +1,7 @@
+pub mod locate_impl;
+mod refresh;
+
+pub(crate) use locate_impl::write_mlocate_db;
+pub(crate) use locate_impl::write_plocate_db;
+pub(crate) use locate_impl::LocateDb;
+pub(crate) use refresh::refresh_index;
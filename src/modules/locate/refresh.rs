@@ -0,0 +1,284 @@
+use futures::stream::{self, StreamExt};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::config::constants::MAX_CONCURRENT_READS;
+use crate::modules::file_system::{DirEntry, DirTime, FileKind, SharedFileSystem};
+use crate::modules::ignore_filter::IgnoreFilter;
+use crate::modules::locate::locate_impl::LocateDb;
+use crate::modules::path_arena::{EntryId, PathArena};
+
+/// What `refresh_index` did.
+#[derive(Debug, Default)]
+pub(crate) struct RefreshStats {
+    // Directories whose listing was read, the new ones included
+    pub(crate) dirs_read: usize,
+    // Directories whose entries were kept from the previous index
+    pub(crate) dirs_unchanged: usize,
+    pub(crate) duration: Duration,
+}
+
+// A directory to fill: its id in the previous index if it was there, its id in the new one and
+// the ignore filter of its parent
+struct Pending {
+    old: Option<EntryId>,
+    new: EntryId,
+    path: PathBuf,
+    filter: IgnoreFilter,
+}
+
+struct Refresh<'a> {
+    previous: &'a LocateDb,
+    fs: &'a SharedFileSystem,
+    // (parent, child) pairs of the previous index, sorted by parent
+    by_parent: Vec<(EntryId, EntryId)>,
+    // The time of every directory of the previous index now, None once it's gone
+    current: HashMap<EntryId, Option<DirTime>>,
+    arena: PathArena,
+    times: HashMap<EntryId, DirTime>,
+    pending: Vec<Pending>,
+    stats: RefreshStats,
+}
+
+/// Brings the entries of a previous index up to date, reading the listing of the directories
+/// changed since it was written only. A change doesn't show in the times of the directories
+/// above it, so every directory is still checked, but with a stat instead of a listing.
+///
+/// The listings are read from `fs` and filtered with `ignore`, like those of a scan.
+pub(crate) async fn refresh_index(
+    previous: &LocateDb,
+    fs: &SharedFileSystem,
+    ignore: &IgnoreFilter,
+) -> (LocateDb, RefreshStats) {
+    let started = Instant::now();
+    let old = previous.arena();
+
+    let mut by_parent: Vec<(EntryId, EntryId)> = old
+        .dirs()
+        .iter()
+        .chain(old.files())
+        .filter_map(|&id| old.parent(id).map(|parent| (parent, id)))
+        .collect();
+    by_parent.sort_unstable();
+
+    let dirs: Vec<EntryId> = old.roots().chain(old.dirs().iter().copied()).collect();
+    let current = stream::iter(dirs)
+        .map(|dir| async move { (dir, fs.dir_time(&old.path(dir)).await.ok()) })
+        .buffer_unordered(MAX_CONCURRENT_READS)
+        .collect()
+        .await;
+
+    let mut refresh = Refresh {
+        previous,
+        fs,
+        by_parent,
+        current,
+        arena: PathArena::with_capacity(old.len(), 0),
+        times: HashMap::new(),
+        pending: vec![],
+        stats: RefreshStats::default(),
+    };
+    for root in old.roots() {
+        let path = old.path(root);
+        let id = refresh.arena.add_root(&path);
+        refresh.pending.push(Pending {
+            old: Some(root),
+            new: id,
+            path,
+            filter: ignore.clone(),
+        });
+    }
+    while let Some(dir) = refresh.pending.pop() {
+        refresh.refresh_dir(dir).await;
+    }
+
+    refresh.arena.shrink_to_fit();
+    let mut stats = refresh.stats;
+    stats.duration = started.elapsed();
+    (LocateDb::new(refresh.arena, refresh.times), stats)
+}
+
+impl Refresh<'_> {
+    fn children(&self, old: EntryId) -> impl Iterator<Item = EntryId> + '_ {
+        let start = self.by_parent.partition_point(|&(parent, _)| parent < old);
+        self.by_parent[start..]
+            .iter()
+            .take_while(move |&&(parent, _)| parent == old)
+            .map(|&(_, child)| child)
+    }
+
+    // Fills the directory, queueing its subdirectories
+    async fn refresh_dir(&mut self, dir: Pending) {
+        let time = match dir.old {
+            Some(old) => self.current.get(&old).copied().flatten(),
+            None => self.fs.dir_time(&dir.path).await.ok(),
+        };
+        // Deleted since its parent was read
+        let Some(time) = time else {
+            return;
+        };
+        self.times.insert(dir.new, time);
+
+        let filter = dir.filter.enter(&dir.path);
+        match dir.old {
+            Some(old) if self.previous.dir_time(old) == Some(time) => {
+                self.stats.dirs_unchanged += 1;
+                self.copy_listing(old, dir.new, &dir.path, filter);
+            }
+            _ => {
+                self.stats.dirs_read += 1;
+                self.read_listing(dir.old, dir.new, &dir.path, filter).await;
+            }
+        }
+    }
+
+    fn copy_listing(&mut self, old: EntryId, new: EntryId, path: &Path, filter: IgnoreFilter) {
+        let arena = self.previous.arena();
+        // Only the filter options can have changed since, the ignore files in the directory not
+        let kept = |&child: &EntryId| {
+            !filter.is_active()
+                || !filter.is_ignored(&path.join(arena.name(child)), arena.is_dir(child))
+        };
+        let (dirs, files): (Vec<EntryId>, Vec<EntryId>) = self
+            .children(old)
+            .filter(kept)
+            .partition(|&child| arena.is_dir(child));
+        let file_names: Vec<&OsStr> = files.iter().map(|&file| arena.name(file)).collect();
+        let dir_names: Vec<&OsStr> = dirs.iter().map(|&dir| arena.name(dir)).collect();
+
+        let ids = self.arena.add_listing(new, &file_names, &dir_names);
+        for ((old_dir, name), id) in dirs.iter().zip(dir_names).zip(ids) {
+            self.pending.push(Pending {
+                old: Some(*old_dir),
+                new: id,
+                path: path.join(name),
+                filter: filter.clone(),
+            });
+        }
+    }
+
+    async fn read_listing(
+        &mut self,
+        old: Option<EntryId>,
+        new: EntryId,
+        path: &Path,
+        filter: IgnoreFilter,
+    ) {
+        let Ok(entries) = self.fs.read_dir(path).await else {
+            return;
+        };
+        let (mut dirs, mut files): (Vec<DirEntry>, Vec<DirEntry>) = entries
+            .into_iter()
+            .partition(|entry| entry.kind == FileKind::Dir);
+        filter.retain(path, &mut files, &mut dirs);
+
+        // The directories already in the previous index keep their unchanged subtrees
+        let arena = self.previous.arena();
+        let known: HashMap<&OsStr, EntryId> = old
+            .into_iter()
+            .flat_map(|old| self.children(old))
+            .filter(|&child| arena.is_dir(child))
+            .map(|child| (arena.name(child), child))
+            .collect();
+        let old_dirs: Vec<Option<EntryId>> = dirs
+            .iter()
+            .map(|dir| known.get(dir.name.as_os_str()).copied())
+            .collect();
+
+        let file_names: Vec<&OsStr> = files.iter().map(|file| file.name.as_os_str()).collect();
+        let dir_names: Vec<&OsStr> = dirs.iter().map(|dir| dir.name.as_os_str()).collect();
+        let ids = self.arena.add_listing(new, &file_names, &dir_names);
+        for ((dir, old_dir), id) in dirs.iter().zip(old_dirs).zip(ids) {
+            self.pending.push(Pending {
+                old: old_dir,
+                new: id,
+                path: path.join(&dir.name),
+                filter: filter.clone(),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::file_system::memory_fs::MemoryFileSystem;
+    use crate::modules::ignore_filter::IgnoreOptions;
+    use std::collections::BTreeSet;
+    use std::sync::Arc;
+
+    const ROOT: &str = "/tree";
+
+    fn tree() -> Arc<MemoryFileSystem> {
+        let fs = Arc::new(MemoryFileSystem::new());
+        for file in [
+            "a/one.txt",
+            "a/b/two.txt",
+            "c/three.txt",
+            "keep/deep/x",
+            ".hidden/y",
+        ] {
+            fs.add_file(Path::new(ROOT).join(file)).unwrap();
+        }
+        fs
+    }
+
+    // An index of the root alone, refreshing it reads the whole tree
+    fn empty_index() -> LocateDb {
+        let mut arena = PathArena::new();
+        arena.add_root(Path::new(ROOT));
+        LocateDb::new(arena, HashMap::new())
+    }
+
+    fn paths(database: &LocateDb) -> BTreeSet<PathBuf> {
+        let arena = database.arena();
+        arena.file_paths().chain(arena.dir_paths()).collect()
+    }
+
+    #[tokio::test]
+    async fn only_the_changed_directories_are_read() {
+        let memory = tree();
+        let fs: SharedFileSystem = memory.clone();
+        let none = IgnoreFilter::default();
+        let (first, stats) = refresh_index(&empty_index(), &fs, &none).await;
+        assert_eq!((stats.dirs_read, stats.dirs_unchanged), (7, 0));
+        assert_eq!(paths(&first).len(), 11);
+
+        // Changes /tree/a/b, then /tree twice
+        memory.add_file("/tree/a/b/new.txt").unwrap();
+        memory.remove("/tree/c").unwrap();
+        memory.add_file("/tree/d/e.txt").unwrap();
+
+        let (second, stats) = refresh_index(&first, &fs, &none).await;
+        let (full, _) = refresh_index(&empty_index(), &fs, &none).await;
+        assert_eq!(paths(&second), paths(&full));
+        assert!(paths(&second).contains(Path::new("/tree/a/b/new.txt")));
+        assert!(!paths(&second).contains(Path::new("/tree/c")));
+        // The root, b and the new d are read, a, keep, deep and .hidden are kept
+        assert_eq!((stats.dirs_read, stats.dirs_unchanged), (3, 4));
+    }
+
+    #[tokio::test]
+    async fn the_ignore_filter_applies_to_kept_and_read_listings() {
+        let memory = tree();
+        let fs: SharedFileSystem = memory.clone();
+        let (first, _) = refresh_index(&empty_index(), &fs, &IgnoreFilter::default()).await;
+
+        memory.add_file("/tree/a/b/new.txt").unwrap();
+        let ignore = IgnoreFilter::new(&IgnoreOptions {
+            exclude: vec!["*.txt".to_string()],
+            skip_hidden: true,
+            ..IgnoreOptions::default()
+        })
+        .unwrap();
+        let (second, _) = refresh_index(&first, &fs, &ignore).await;
+
+        let expected: BTreeSet<PathBuf> = ["a", "a/b", "c", "keep", "keep/deep", "keep/deep/x"]
+            .iter()
+            .map(|path| Path::new(ROOT).join(path))
+            .collect();
+        assert_eq!(paths(&second), expected);
+    }
+}
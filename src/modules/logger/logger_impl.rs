@@ -22,10 +22,10 @@
 // ------------------------------------------------------------------------------
 
 use dirs_next::home_dir;
-use std::io::stdout;
+use std::io::stderr;
 use std::path::PathBuf;
 use std::{env, fs};
-use tracing::{debug, info};
+use tracing::debug;
 use tracing_appender::non_blocking::{NonBlocking, WorkerGuard};
 use tracing_appender::rolling::{RollingFileAppender, Rotation};
 use tracing_subscriber::fmt::time::UtcTime;
@@ -34,6 +34,8 @@ use tracing_subscriber::registry::Registry;
 use tracing_subscriber::EnvFilter;
 use tracing_subscriber::{fmt, Layer};
 
+use crate::modules::output::color_enabled;
+
 // Function to get the logging level from the environment variable or default to LevelFilter::Info
 fn get_log_level() -> EnvFilter {
     EnvFilter::new(env::var("LOG_LEVEL").unwrap_or_else(|_| "info".to_string()))
@@ -72,12 +74,12 @@ pub fn init_logger() -> WorkerGuard {
     // Create a format for the timer
     let timer = UtcTime::rfc_3339();
 
-    // Create the terminal layer
+    // Create the terminal layer, on stderr to keep stdout for the results
     let terminal_layer = fmt::layer()
-        .with_writer(stdout)
+        .with_writer(stderr)
         .with_timer(timer.clone())
-        .with_ansi(true)
-        .with_filter(terminal_filter); // Enable ANSI colors
+        .with_ansi(color_enabled(&stderr()))
+        .with_filter(terminal_filter); // ANSI colors unless NO_COLOR or redirected
 
     // Create the file layer
     let file_layer = fmt::layer()
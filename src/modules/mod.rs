@@ -1,10 +1,28 @@
 // Module declarations
 pub mod algo_selector;
+pub mod cli;
+pub mod daemon;
 pub mod directory_reader;
 pub mod disk_reader;
+pub mod efu;
 pub mod errors;
+pub mod file_system;
+pub mod ignore_filter;
+pub mod locate;
 pub mod logger;
+pub mod output;
+pub mod parquet_export;
+pub mod path_arena;
 pub mod path_reader;
 pub mod process;
+pub mod raw_path;
 pub mod runtime;
+pub mod scanner;
+pub mod sqlite;
+pub mod tree_gen;
+pub mod tui;
+pub mod tuning;
+pub mod usage;
 pub mod utils;
+pub mod verify;
+pub mod watch;
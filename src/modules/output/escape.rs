@@ -0,0 +1,330 @@
+use serde::Serializer;
+use std::borrow::Cow;
+use std::ffi::OsStr;
+use std::path::Path;
+
+use crate::modules::output::output_impl::Quoting;
+use crate::modules::raw_path::raw_path_impl::PathUnit;
+use crate::modules::raw_path::RawPath;
+
+// A piece of a name: a character, or what doesn't decode to one on this platform
+enum Unit {
+    Char(char),
+    // Not part of valid UTF-8
+    #[cfg(unix)]
+    Byte(u8),
+    // Unpaired in the UTF-16 of Windows
+    #[cfg(windows)]
+    Surrogate(u16),
+}
+
+#[cfg(unix)]
+fn units(name: &OsStr) -> impl Iterator<Item = Unit> + '_ {
+    name.as_encoded_bytes().utf8_chunks().flat_map(|chunk| {
+        let chars = chunk.valid().chars().map(Unit::Char);
+        chars.chain(chunk.invalid().iter().map(|&byte| Unit::Byte(byte)))
+    })
+}
+
+#[cfg(windows)]
+fn units(name: &OsStr) -> impl Iterator<Item = Unit> + '_ {
+    use std::os::windows::ffi::OsStrExt;
+
+    char::decode_utf16(name.encode_wide()).map(|unit| match unit {
+        Ok(c) => Unit::Char(c),
+        Err(e) => Unit::Surrogate(e.unpaired_surrogate()),
+    })
+}
+
+// C-style escape of what can't be printed as it is, None for the printable characters
+fn escape_unit(unit: &Unit) -> Option<String> {
+    match *unit {
+        Unit::Char('\n') => Some("\\n".to_string()),
+        Unit::Char('\t') => Some("\\t".to_string()),
+        Unit::Char('\r') => Some("\\r".to_string()),
+        Unit::Char(c) if c.is_ascii_control() => Some(format!("\\x{:02X}", c as u32)),
+        Unit::Char(c) if c.is_control() => Some(format!("\\u{:04X}", c as u32)),
+        Unit::Char(_) => None,
+        #[cfg(unix)]
+        Unit::Byte(byte) => Some(format!("\\x{:02X}", byte)),
+        #[cfg(windows)]
+        Unit::Surrogate(surrogate) => Some(format!("\\u{:04X}", surrogate)),
+    }
+}
+
+// Characters that never need quotes in a POSIX shell
+fn is_shell_safe(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "_@%+=:,./-".contains(c)
+}
+
+/// Writes `name` in the quoting style of the text renderers. None of them replaces what isn't
+/// valid Unicode, and the C and shell styles can be read back unambiguously.
+pub(crate) fn quote(name: &OsStr, quoting: Quoting) -> Cow<'_, str> {
+    match quoting {
+        Quoting::Escape => escape(name),
+        Quoting::C => Cow::Owned(c_quote(name)),
+        Quoting::Shell => shell_quote(name),
+    }
+}
+
+pub(crate) fn quote_path(path: &Path, quoting: Quoting) -> Cow<'_, str> {
+    quote(path.as_os_str(), quoting)
+}
+
+// The escapes without quotes, valid names are kept as they are, backslashes included
+fn escape(name: &OsStr) -> Cow<'_, str> {
+    escape_units(name, false)
+}
+
+/// The escapes of a TSV field: those of `Quoting::Escape` with the backslashes doubled, so tabs
+/// and newlines in names can't be taken for the separators and every field reads back.
+pub(crate) fn tsv_escape(name: &OsStr) -> Cow<'_, str> {
+    escape_units(name, true)
+}
+
+fn escape_units(name: &OsStr, double_backslashes: bool) -> Cow<'_, str> {
+    if let Some(text) = name.to_str() {
+        if !text
+            .chars()
+            .any(|c| c.is_control() || (double_backslashes && c == '\\'))
+        {
+            return Cow::Borrowed(text);
+        }
+    }
+
+    let mut quoted = String::with_capacity(name.len());
+    for unit in units(name) {
+        match (escape_unit(&unit), unit) {
+            (Some(escaped), _) => quoted.push_str(&escaped),
+            (None, Unit::Char('\\')) if double_backslashes => quoted.push_str("\\\\"),
+            (None, Unit::Char(c)) => quoted.push(c),
+            (None, _) => {}
+        }
+    }
+    Cow::Owned(quoted)
+}
+
+// Like `ls --quoting-style=c`: in double quotes, with the quotes and backslashes escaped too
+fn c_quote(name: &OsStr) -> String {
+    let mut quoted = String::with_capacity(name.len() + 2);
+    quoted.push('"');
+    for unit in units(name) {
+        match (escape_unit(&unit), unit) {
+            (Some(escaped), _) => quoted.push_str(&escaped),
+            (None, Unit::Char(c @ ('"' | '\\'))) => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            (None, Unit::Char(c)) => quoted.push(c),
+            (None, _) => {}
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+// Single quotes for the printable parts and bash's $'...' for the rest, to paste into a shell
+fn shell_quote(name: &OsStr) -> Cow<'_, str> {
+    if let Some(text) = name.to_str() {
+        if !text.is_empty() && text.chars().all(is_shell_safe) {
+            return Cow::Borrowed(text);
+        }
+    }
+
+    #[derive(PartialEq)]
+    enum Open {
+        None,
+        Single,
+        AnsiC,
+    }
+
+    let mut quoted = String::with_capacity(name.len() + 2);
+    let mut open = Open::None;
+    for unit in units(name) {
+        match (escape_unit(&unit), unit) {
+            (Some(escaped), _) => {
+                if open != Open::AnsiC {
+                    if open != Open::None {
+                        quoted.push('\'');
+                    }
+                    quoted.push_str("$'");
+                    open = Open::AnsiC;
+                }
+                quoted.push_str(&escaped);
+            }
+            (None, Unit::Char(c)) => {
+                if open != Open::Single {
+                    if open != Open::None {
+                        quoted.push('\'');
+                    }
+                    quoted.push('\'');
+                    open = Open::Single;
+                }
+                if c == '\'' {
+                    quoted.push_str("'\\''");
+                } else {
+                    quoted.push(c);
+                }
+            }
+            (None, _) => {}
+        }
+    }
+    if open == Open::None {
+        quoted.push_str("''");
+    } else {
+        quoted.push('\'');
+    }
+    Cow::Owned(quoted)
+}
+
+/// The native code units of `path` when it isn't valid Unicode, for the `path_raw` field the
+/// JSON renderers add next to the text of such paths.
+pub(crate) fn raw_units(path: &Path) -> Option<Vec<PathUnit>> {
+    match path.to_str() {
+        Some(_) => None,
+        None => Some(RawPath::from(path).as_units().to_vec()),
+    }
+}
+
+/// Serializes a path as text, with the parts that aren't valid Unicode replaced. Goes with a
+/// `path_raw` field from `raw_units`.
+pub(crate) fn serialize_lossy<S: Serializer>(path: &Path, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_str(&path.to_string_lossy())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::iter::Peekable;
+    use std::os::unix::ffi::OsStrExt;
+    use std::str::Chars;
+
+    fn names() -> Vec<&'static [u8]> {
+        vec![
+            b"plain.txt",
+            b"",
+            b"with space",
+            b"it's",
+            b"say \"hi\"",
+            b"back\\slash",
+            b"tab\there",
+            b"new\nline\r",
+            b"bell\x07",
+            b"caf\xe9",
+            b"caf\xc3\xa9",
+            b"next\xc2\x85line",
+            b"\xff\xfe'\n",
+            b"-dash",
+        ]
+    }
+
+    fn hex(chars: &mut Peekable<Chars>, digits: usize) -> u32 {
+        let text: String = chars.take(digits).collect();
+        u32::from_str_radix(&text, 16).unwrap()
+    }
+
+    // Reads the escape after a backslash back into `out`
+    fn unescape_one(chars: &mut Peekable<Chars>, out: &mut Vec<u8>) {
+        match chars.next().unwrap() {
+            'n' => out.push(b'\n'),
+            't' => out.push(b'\t'),
+            'r' => out.push(b'\r'),
+            'x' => out.push(hex(chars, 2) as u8),
+            'u' => {
+                let c = char::from_u32(hex(chars, 4)).unwrap();
+                out.extend_from_slice(c.to_string().as_bytes());
+            }
+            c => out.extend_from_slice(c.to_string().as_bytes()),
+        }
+    }
+
+    fn unescape(text: &str) -> Vec<u8> {
+        let mut out = vec![];
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => unescape_one(&mut chars, &mut out),
+                c => out.extend_from_slice(c.to_string().as_bytes()),
+            }
+        }
+        out
+    }
+
+    fn unquote_c(text: &str) -> Vec<u8> {
+        let inner = text.strip_prefix('"').unwrap().strip_suffix('"').unwrap();
+        unescape(inner)
+    }
+
+    // The words a POSIX shell with bash's $'...' would make of `text`
+    fn unquote_shell(text: &str) -> Vec<u8> {
+        let mut out = vec![];
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\'' => {
+                    for c in chars.by_ref().take_while(|&c| c != '\'') {
+                        out.extend_from_slice(c.to_string().as_bytes());
+                    }
+                }
+                '$' if chars.peek() == Some(&'\'') => {
+                    chars.next();
+                    while let Some(c) = chars.next() {
+                        match c {
+                            '\'' => break,
+                            '\\' => unescape_one(&mut chars, &mut out),
+                            c => out.extend_from_slice(c.to_string().as_bytes()),
+                        }
+                    }
+                }
+                '\\' => unescape_one(&mut chars, &mut out),
+                c => out.extend_from_slice(c.to_string().as_bytes()),
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn escaped_names_round_trip() {
+        // Backslashes are kept as they are, a name with one can't be told from an escape
+        for name in names().into_iter().filter(|name| !name.contains(&b'\\')) {
+            let quoted = quote(OsStr::from_bytes(name), Quoting::Escape);
+            assert_eq!(unescape(&quoted), name, "{}", quoted);
+        }
+    }
+
+    #[test]
+    fn tsv_fields_round_trip() {
+        for name in names() {
+            let escaped = tsv_escape(OsStr::from_bytes(name));
+            assert!(!escaped.contains(['\t', '\n', '\r']), "{}", escaped);
+            assert_eq!(unescape(&escaped), name, "{}", escaped);
+        }
+    }
+
+    #[test]
+    fn c_quoted_names_round_trip() {
+        for name in names() {
+            let quoted = quote(OsStr::from_bytes(name), Quoting::C);
+            assert_eq!(unquote_c(&quoted), name, "{}", quoted);
+        }
+    }
+
+    #[test]
+    fn shell_quoted_names_round_trip() {
+        for name in names() {
+            let quoted = quote(OsStr::from_bytes(name), Quoting::Shell);
+            assert_eq!(unquote_shell(&quoted), name, "{}", quoted);
+        }
+    }
+
+    #[test]
+    fn printable_names_are_kept() {
+        let name = OsStr::new("caf\u{e9} 1.txt");
+        assert_eq!(quote(name, Quoting::Escape), "caf\u{e9} 1.txt");
+        assert_eq!(quote(OsStr::new("a\\b"), Quoting::Escape), "a\\b");
+        assert_eq!(quote(OsStr::new("a\\b"), Quoting::C), "\"a\\\\b\"");
+        assert_eq!(tsv_escape(OsStr::new("a\\b")), "a\\\\b");
+        assert_eq!(quote(OsStr::new("a.txt"), Quoting::Shell), "a.txt");
+        assert_eq!(quote(OsStr::new("it's"), Quoting::Shell), "'it'\\''s'");
+    }
+}
@@ -0,0 +1,19 @@
+mod escape;
+pub mod output_impl;
+mod renderers;
+
+pub(crate) use escape::quote;
+pub(crate) use escape::quote_path;
+pub(crate) use escape::raw_units;
+pub(crate) use escape::serialize_lossy;
+
+pub(crate) use output_impl::color_enabled;
+pub(crate) use output_impl::configure_colors;
+pub(crate) use output_impl::write_entries;
+pub(crate) use output_impl::write_matches;
+pub(crate) use output_impl::write_results;
+pub(crate) use output_impl::write_usage;
+pub(crate) use output_impl::OutputFormat;
+pub(crate) use output_impl::OutputOptions;
+pub(crate) use output_impl::Quoting;
+pub(crate) use output_impl::SearchOptions;
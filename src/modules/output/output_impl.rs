@@ -0,0 +1,230 @@
+use clap::ValueEnum;
+use serde::Serialize;
+use std::env;
+use std::io::{self, BufWriter, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use tracing::error;
+
+use crate::modules::output::escape::{raw_units, serialize_lossy};
+use crate::modules::output::renderers::{
+    CsvRenderer, JsonLinesRenderer, JsonRenderer, NulRenderer, TableRenderer, TsvRenderer,
+};
+use crate::modules::path_arena::PathArena;
+use crate::modules::raw_path::raw_path_impl::PathUnit;
+use crate::modules::scanner::ScanResult;
+use crate::modules::usage::{UsageOptions, UsageRow};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Aligned table for the terminal
+    #[default]
+    Table,
+    /// A single JSON array
+    Json,
+    /// One JSON object per line
+    #[value(name = "jsonl")]
+    JsonLines,
+    /// Comma separated values with a header row
+    Csv,
+    /// Tab separated values with a header row
+    Tsv,
+    /// Paths only, each terminated by a NUL byte (for `xargs -0`)
+    Nul,
+}
+
+/// How the table and CSV output write paths. TSV escapes the backslashes, tabs and newlines of
+/// the names as they are, JSON keeps the native code units of paths that aren't valid Unicode in
+/// a `path_raw` field instead, NUL separated output the bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Quoting {
+    /// As they are, with C-style escapes for invalid encodings and control characters only
+    #[default]
+    Escape,
+    /// In double quotes with C-style escapes, like `ls --quoting-style=c`
+    C,
+    /// Quoted to paste into a POSIX shell, with bash's $'...' for what can't be printed
+    Shell,
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct OutputOptions {
+    pub(crate) format: OutputFormat,
+    pub(crate) quoting: Quoting,
+    // Every file and directory instead of the per-volume summary
+    pub(crate) list_entries: bool,
+    // Everything file list to export the entries to
+    pub(crate) efu: Option<PathBuf>,
+    // mlocate and plocate databases to write the entries to
+    pub(crate) mlocate: Option<PathBuf>,
+    pub(crate) plocate: Option<PathBuf>,
+    // Parquet file to write the entries to
+    pub(crate) parquet: Option<PathBuf>,
+    // SQLite database the scan run is added to
+    pub(crate) sqlite: Option<PathBuf>,
+    // Disk usage report instead of the per-volume summary
+    pub(crate) usage: Option<UsageOptions>,
+    // Browse the entries in the terminal UI after the scan
+    pub(crate) interactive: bool,
+    // Index kept up to date with the changes after the scan
+    pub(crate) watch: Option<PathBuf>,
+    // Answer queries about the watched index on `socket`, its default location if None
+    pub(crate) serve: bool,
+    pub(crate) socket: Option<PathBuf>,
+    // Only the entries matching a search instead of the per-volume summary
+    pub(crate) search: Option<SearchOptions>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SearchOptions {
+    // Part of the name, ignoring case
+    pub(crate) pattern: String,
+    pub(crate) limit: Option<usize>,
+}
+
+impl OutputOptions {
+    /// Whether the entries of the scanned volumes are needed after the scan. The SQLite and
+    /// Parquet exports are written during the scan.
+    pub(crate) fn keeps_entries(&self) -> bool {
+        self.list_entries
+            || self.efu.is_some()
+            || self.mlocate.is_some()
+            || self.plocate.is_some()
+            || self.usage.is_some()
+            || self.interactive
+            || self.watch.is_some()
+            || self.search.is_some()
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub(crate) struct EntryRow<'a> {
+    #[serde(serialize_with = "serialize_lossy")]
+    pub(crate) path: &'a Path,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) path_raw: Option<Vec<PathUnit>>,
+    #[serde(rename = "type")]
+    pub(crate) kind: &'static str,
+}
+
+impl<'a> EntryRow<'a> {
+    pub(crate) fn new(path: &'a Path, kind: &'static str) -> Self {
+        Self {
+            path,
+            path_raw: raw_units(path),
+            kind,
+        }
+    }
+}
+
+// The directories of a volume followed by its files, with their kind
+pub(crate) fn entry_paths(arena: &PathArena) -> impl Iterator<Item = (PathBuf, &'static str)> + '_ {
+    let dirs = arena.dirs().iter().map(|&id| (id, "dir"));
+    let files = arena.files().iter().map(|&id| (id, "file"));
+    dirs.chain(files).map(|(id, kind)| (arena.path(id), kind))
+}
+
+pub(crate) type EntryIter<'a> = &'a mut dyn Iterator<Item = (PathBuf, &'static str)>;
+
+pub(crate) trait Renderer {
+    /// Writes the summary of a scan, one row per volume.
+    fn volumes(&mut self, out: &mut dyn Write, scan: &ScanResult) -> io::Result<()>;
+
+    /// Writes files and directories with their kind ("file" or "dir"), called once per volume
+    /// or file list.
+    fn entries(&mut self, out: &mut dyn Write, entries: EntryIter<'_>) -> io::Result<()>;
+
+    /// Writes a disk usage report, the rows of a volume and section follow each other.
+    fn usage(&mut self, out: &mut dyn Write, rows: &[UsageRow]) -> io::Result<()>;
+
+    /// Closes the output after the last call.
+    fn finish(&mut self, _out: &mut dyn Write) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+pub(crate) fn create_renderer(format: OutputFormat, quoting: Quoting) -> Box<dyn Renderer> {
+    match format {
+        OutputFormat::Table => Box::new(TableRenderer { quoting }),
+        OutputFormat::Json => Box::new(JsonRenderer::default()),
+        OutputFormat::JsonLines => Box::new(JsonLinesRenderer),
+        OutputFormat::Csv => Box::new(CsvRenderer::new(quoting)),
+        OutputFormat::Tsv => Box::new(TsvRenderer::default()),
+        OutputFormat::Nul => Box::new(NulRenderer),
+    }
+}
+
+/// Whether to color what goes to `stream`: only terminals get colors, `NO_COLOR` turns them off
+/// and `CLICOLOR_FORCE` on regardless of the stream (see no-color.org).
+pub(crate) fn color_enabled<S: IsTerminal>(stream: &S) -> bool {
+    let is_set = |name: &str| env::var_os(name).is_some_and(|v| !v.is_empty() && v != "0");
+
+    if env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty()) {
+        false
+    } else if is_set("CLICOLOR_FORCE") {
+        true
+    } else {
+        stream.is_terminal()
+    }
+}
+
+/// Colors the results on stdout. The progress goes to stderr through tracing, which checks
+/// stderr itself (see `init_logger`).
+pub(crate) fn configure_colors(format: OutputFormat) {
+    colored::control::set_override(format == OutputFormat::Table && color_enabled(&io::stdout()));
+}
+
+/// Renders the scan results to stdout, the entries in `arenas` with `list_entries` and the
+/// per-volume summary of `scan` otherwise.
+pub(crate) fn write_results(output: &OutputOptions, scan: &ScanResult, arenas: &[PathArena]) {
+    render(output, |renderer, out| {
+        if output.list_entries {
+            arenas
+                .iter()
+                .try_for_each(|arena| renderer.entries(out, &mut entry_paths(arena)))
+        } else {
+            renderer.volumes(out, scan)
+        }
+    });
+}
+
+/// Renders entries that don't come from a scan, e.g. from an imported file list, to stdout.
+pub(crate) fn write_entries(output: &OutputOptions, entries: EntryIter<'_>) {
+    render(output, |renderer, out| renderer.entries(out, entries));
+}
+
+/// Renders the entries of `arenas` matching `search` to stdout.
+pub(crate) fn write_matches(output: &OutputOptions, arenas: &[PathArena], search: &SearchOptions) {
+    let mut matches = arenas
+        .iter()
+        .flat_map(|arena| {
+            arena
+                .search(&search.pattern)
+                .map(|(id, kind)| (arena.path(id), kind))
+        })
+        .take(search.limit.unwrap_or(usize::MAX));
+    write_entries(output, &mut matches);
+}
+
+/// Renders a disk usage report to stdout.
+pub(crate) fn write_usage(output: &OutputOptions, rows: &[UsageRow]) {
+    render(output, |renderer, out| renderer.usage(out, rows));
+}
+
+fn render<F>(output: &OutputOptions, write: F)
+where
+    F: FnOnce(&mut dyn Renderer, &mut dyn Write) -> io::Result<()>,
+{
+    let mut renderer = create_renderer(output.format, output.quoting);
+    let mut out = BufWriter::new(io::stdout().lock());
+
+    let result = write(renderer.as_mut(), &mut out)
+        .and_then(|_| renderer.finish(&mut out))
+        .and_then(|_| out.flush());
+
+    match result {
+        // The reading end of the pipe is gone, e.g. `uffs --list | head`
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {}
+        Err(e) => error!("Failed to write the results: {}", e),
+        Ok(()) => {}
+    }
+}
@@ -0,0 +1,637 @@
+use colored::*;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::ffi::OsStr;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::modules::output::escape::{quote_path, tsv_escape};
+use crate::modules::output::output_impl::{EntryIter, EntryRow, Quoting, Renderer};
+use crate::modules::scanner::{ScanResult, VolumeScanResult};
+use crate::modules::usage::UsageRow;
+use crate::modules::utils::{format_duration, format_memory, format_number, format_size};
+
+const VOLUME_COLUMNS: [&str; 8] = [
+    "path",
+    "drive_type",
+    "size_bytes",
+    "reader",
+    "files",
+    "dirs",
+    "memory_bytes",
+    "seconds",
+];
+
+const ENTRY_COLUMNS: [&str; 2] = ["path", "type"];
+
+const USAGE_COLUMNS: [&str; 7] = [
+    "section",
+    "volume",
+    "path",
+    "type",
+    "size_bytes",
+    "allocated_bytes",
+    "entries",
+];
+
+pub(crate) struct TableRenderer {
+    pub(crate) quoting: Quoting,
+}
+
+impl Renderer for TableRenderer {
+    fn volumes(&mut self, out: &mut dyn Write, scan: &ScanResult) -> io::Result<()> {
+        let volumes = scan.volumes();
+        let total = scan.summary();
+
+        let total_formatted_duration = format_duration(total.duration);
+
+        let roots: Vec<_> = volumes
+            .iter()
+            .map(|v| quote_path(v.root(), self.quoting))
+            .collect();
+        let longest_path_length = roots.iter().map(|root| root.len()).max().unwrap_or(0);
+        let path_length = longest_path_length + 2;
+        let type_length = 12;
+        let size_length = 15;
+        let reader_length = 12;
+        let files_length = 12;
+        let dirs_length = 12;
+        let memory_length = 12;
+        let time_seconds_length = 10;
+        let time_length = 8;
+
+        writeln!(out, "\n")?;
+
+        // Header
+        writeln!(
+            out,
+            "{:<path_length$} {:<type_length$} {:>size_length$} {:<reader_length$} {:>files_length$} {:>dirs_length$} {:>memory_length$} {:>time_seconds_length$} {:>time_length$}",
+            "Path".bold().underline().blue(),
+            "Type".bold().underline().blue(),
+            "Size (GB / TB)".bold().underline().blue(),
+            "Reader".bold().underline().blue(),
+            "Files".bold().underline().blue(),
+            "Dirs".bold().underline().blue(),
+            "Memory".bold().underline().blue(),
+            "Time (s)".bold().underline().blue(),
+            "Time".bold().underline().blue(),
+        )?;
+
+        // Separator
+        let separator = format!(
+            "{}",
+            "-".repeat(
+                path_length
+                    + type_length
+                    + size_length
+                    + reader_length
+                    + files_length
+                    + dirs_length
+                    + memory_length
+                    + time_seconds_length
+                    + time_length
+                    + 8
+            )
+            .green()
+        );
+        writeln!(out, "{}", separator)?;
+
+        // Data rows
+        for (volume, root) in volumes.iter().zip(&roots) {
+            writeln!(
+                out,
+                "{:<path_length$} {:<type_length$} {:>size_length$} {:<reader_length$} {:>files_length$} {:>dirs_length$} {:>memory_length$} {:>time_seconds_length$.3} {:>time_length$}",
+                root,
+                volume.drive_type().unwrap_or(""),
+                format_size(volume.size_bytes().unwrap_or(0)),
+                volume.reader(),
+                format_number(volume.num_files(), files_length),
+                format_number(volume.num_dirs(), dirs_length),
+                format_memory(volume.memory_bytes()),
+                volume.duration().as_secs_f64(),
+                format_duration(volume.duration()),
+            )?;
+        }
+
+        // Separator
+        writeln!(out, "{}", separator)?;
+
+        // Total row
+        writeln!(
+            out,
+            "{:<path_length$} {:<type_length$} {:>size_length$} {:<reader_length$} {:>files_length$} {:>dirs_length$} {:>memory_length$} {:>time_seconds_length$.3} {:>time_length$}",
+            "Total".bold().yellow(),
+            "",
+            format_size(total.size_bytes),
+            "",
+            format_number(total.files, files_length),
+            format_number(total.dirs, dirs_length),
+            format_memory(total.memory_bytes),
+            total.duration.as_secs_f64(),
+            total_formatted_duration,
+        )?;
+
+        // Concurrency of the readers that limit their in-flight reads
+        let concurrency_rows: Vec<_> = volumes
+            .iter()
+            .zip(&roots)
+            .filter_map(|(v, root)| v.concurrency.as_ref().map(|stats| (root, stats)))
+            .collect();
+        if !concurrency_rows.is_empty() {
+            writeln!(out)?;
+            for (path, stats) in concurrency_rows {
+                writeln!(out, "{:<path_length$} {}", path, stats)?;
+            }
+        }
+
+        if total.errors > 0 {
+            writeln!(
+                out,
+                "\n{} directories could not be read",
+                format_number(total.errors, 0)
+            )?;
+        }
+
+        writeln!(out, "\n")
+    }
+
+    fn entries(&mut self, out: &mut dyn Write, entries: EntryIter<'_>) -> io::Result<()> {
+        for (path, _) in entries {
+            writeln!(out, "{}", quote_path(&path, self.quoting))?;
+        }
+        Ok(())
+    }
+
+    fn usage(&mut self, out: &mut dyn Write, rows: &[UsageRow]) -> io::Result<()> {
+        let size_length = 12;
+        let entries_length = 14;
+
+        for (i, row) in rows.iter().enumerate() {
+            let previous = i.checked_sub(1).map(|i| &rows[i]);
+            if previous.is_none_or(|p| p.volume != row.volume || p.section != row.section) {
+                let title = match row.section {
+                    "largest_dirs" => "Largest directories on",
+                    "largest_files" => "Largest files on",
+                    _ => "Usage of",
+                };
+                writeln!(
+                    out,
+                    "\n{} {}",
+                    title.bold().yellow(),
+                    row.volume.bold().yellow()
+                )?;
+                writeln!(
+                    out,
+                    "{:>size_length$} {:>size_length$} {:>entries_length$}  {}",
+                    "On disk".bold().underline().blue(),
+                    "Size".bold().underline().blue(),
+                    "Entries".bold().underline().blue(),
+                    "Path".bold().underline().blue(),
+                )?;
+            }
+
+            writeln!(
+                out,
+                "{:>size_length$} {:>size_length$} {:>entries_length$}  {}",
+                format_memory(row.allocated_bytes),
+                format_memory(row.size_bytes),
+                format_number(row.entries as usize, entries_length),
+                quote_path(&row.path, self.quoting),
+            )?;
+        }
+        writeln!(out)
+    }
+}
+
+/// Writes everything as one JSON array, opened with the first item and closed in `finish`.
+#[derive(Default)]
+pub(crate) struct JsonRenderer {
+    items: usize,
+}
+
+impl JsonRenderer {
+    fn item<T: Serialize>(&mut self, out: &mut dyn Write, value: &T) -> io::Result<()> {
+        out.write_all(if self.items == 0 { b"[\n  " } else { b",\n  " })?;
+        serde_json::to_writer(&mut *out, value)?;
+        self.items += 1;
+        Ok(())
+    }
+}
+
+impl Renderer for JsonRenderer {
+    fn volumes(&mut self, out: &mut dyn Write, scan: &ScanResult) -> io::Result<()> {
+        scan.volumes()
+            .iter()
+            .try_for_each(|volume| self.item(out, volume))
+    }
+
+    fn entries(&mut self, out: &mut dyn Write, entries: EntryIter<'_>) -> io::Result<()> {
+        for (path, kind) in entries {
+            self.item(out, &EntryRow::new(&path, kind))?;
+        }
+        Ok(())
+    }
+
+    fn usage(&mut self, out: &mut dyn Write, rows: &[UsageRow]) -> io::Result<()> {
+        rows.iter().try_for_each(|row| self.item(out, row))
+    }
+
+    fn finish(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        out.write_all(if self.items == 0 { b"[]\n" } else { b"\n]\n" })
+    }
+}
+
+pub(crate) struct JsonLinesRenderer;
+
+impl JsonLinesRenderer {
+    fn line<T: Serialize>(out: &mut dyn Write, value: &T) -> io::Result<()> {
+        serde_json::to_writer(&mut *out, value)?;
+        out.write_all(b"\n")
+    }
+}
+
+impl Renderer for JsonLinesRenderer {
+    fn volumes(&mut self, out: &mut dyn Write, scan: &ScanResult) -> io::Result<()> {
+        scan.volumes()
+            .iter()
+            .try_for_each(|volume| Self::line(out, volume))
+    }
+
+    fn entries(&mut self, out: &mut dyn Write, entries: EntryIter<'_>) -> io::Result<()> {
+        for (path, kind) in entries {
+            Self::line(out, &EntryRow::new(&path, kind))?;
+        }
+        Ok(())
+    }
+
+    fn usage(&mut self, out: &mut dyn Write, rows: &[UsageRow]) -> io::Result<()> {
+        rows.iter().try_for_each(|row| Self::line(out, row))
+    }
+}
+
+// The fields of the delimited formats, the paths and the text escaped by `path` and `text`
+fn volume_fields<'a>(
+    volume: &'a VolumeScanResult,
+    path: impl Fn(&'a Path) -> Cow<'a, str>,
+    text: impl Fn(&'a str) -> Cow<'a, str>,
+) -> [Cow<'a, str>; 8] {
+    [
+        path(volume.root()),
+        text(volume.drive_type().unwrap_or("")),
+        volume.size_bytes().unwrap_or(0).to_string().into(),
+        text(volume.reader()),
+        volume.num_files().to_string().into(),
+        volume.num_dirs().to_string().into(),
+        volume.memory_bytes().to_string().into(),
+        format!("{:.3}", volume.duration().as_secs_f64()).into(),
+    ]
+}
+
+fn usage_fields<'a>(
+    row: &'a UsageRow,
+    path: impl Fn(&'a Path) -> Cow<'a, str>,
+    text: impl Fn(&'a str) -> Cow<'a, str>,
+) -> [Cow<'a, str>; 7] {
+    [
+        text(row.section),
+        text(&row.volume),
+        path(&row.path),
+        text(row.kind),
+        row.size_bytes.to_string().into(),
+        row.allocated_bytes.to_string().into(),
+        row.entries.to_string().into(),
+    ]
+}
+
+/// CSV with the RFC 4180 quoting of the `csv` crate, the paths in the `Quoting` style first.
+pub(crate) struct CsvRenderer {
+    header_written: bool,
+    quoting: Quoting,
+}
+
+impl CsvRenderer {
+    pub(crate) fn new(quoting: Quoting) -> Self {
+        Self {
+            header_written: false,
+            quoting,
+        }
+    }
+
+    // A writer for the records of one call, after the header of the first one
+    fn writer<'a>(
+        &mut self,
+        out: &'a mut dyn Write,
+        columns: &[&str],
+    ) -> io::Result<csv::Writer<&'a mut dyn Write>> {
+        let mut writer = csv::Writer::from_writer(out);
+        if !self.header_written {
+            self.header_written = true;
+            writer.write_record(columns)?;
+        }
+        Ok(writer)
+    }
+}
+
+impl Renderer for CsvRenderer {
+    fn volumes(&mut self, out: &mut dyn Write, scan: &ScanResult) -> io::Result<()> {
+        let quoting = self.quoting;
+        let mut writer = self.writer(out, &VOLUME_COLUMNS)?;
+        for volume in scan.volumes() {
+            let fields = volume_fields(volume, |p| quote_path(p, quoting), Cow::Borrowed);
+            writer.write_record(fields.iter().map(|field| field.as_bytes()))?;
+        }
+        writer.flush()
+    }
+
+    fn entries(&mut self, out: &mut dyn Write, entries: EntryIter<'_>) -> io::Result<()> {
+        let quoting = self.quoting;
+        let mut writer = self.writer(out, &ENTRY_COLUMNS)?;
+        for (path, kind) in entries {
+            writer.write_record([quote_path(&path, quoting).as_bytes(), kind.as_bytes()])?;
+        }
+        writer.flush()
+    }
+
+    fn usage(&mut self, out: &mut dyn Write, rows: &[UsageRow]) -> io::Result<()> {
+        let quoting = self.quoting;
+        let mut writer = self.writer(out, &USAGE_COLUMNS)?;
+        for row in rows {
+            let fields = usage_fields(row, |p| quote_path(p, quoting), Cow::Borrowed);
+            writer.write_record(fields.iter().map(|field| field.as_bytes()))?;
+        }
+        writer.flush()
+    }
+}
+
+/// TSV with backslash escapes, tabs and newlines can't be quoted. The names are escaped as they
+/// are, without the `Quoting` of the paths on top.
+#[derive(Default)]
+pub(crate) struct TsvRenderer {
+    header_written: bool,
+}
+
+impl TsvRenderer {
+    fn path(path: &Path) -> Cow<'_, str> {
+        tsv_escape(path.as_os_str())
+    }
+
+    fn text(text: &str) -> Cow<'_, str> {
+        tsv_escape(OsStr::new(text))
+    }
+
+    // The fields are escaped already
+    fn record(out: &mut dyn Write, fields: &[Cow<'_, str>]) -> io::Result<()> {
+        for (i, field) in fields.iter().enumerate() {
+            if i > 0 {
+                out.write_all(b"\t")?;
+            }
+            out.write_all(field.as_bytes())?;
+        }
+        out.write_all(b"\n")
+    }
+
+    fn header(&mut self, out: &mut dyn Write, columns: &[&str]) -> io::Result<()> {
+        if !self.header_written {
+            self.header_written = true;
+            let columns: Vec<_> = columns.iter().map(|column| Self::text(column)).collect();
+            Self::record(out, &columns)?;
+        }
+        Ok(())
+    }
+}
+
+impl Renderer for TsvRenderer {
+    fn volumes(&mut self, out: &mut dyn Write, scan: &ScanResult) -> io::Result<()> {
+        self.header(out, &VOLUME_COLUMNS)?;
+        for volume in scan.volumes() {
+            Self::record(out, &volume_fields(volume, Self::path, Self::text))?;
+        }
+        Ok(())
+    }
+
+    fn entries(&mut self, out: &mut dyn Write, entries: EntryIter<'_>) -> io::Result<()> {
+        self.header(out, &ENTRY_COLUMNS)?;
+        for (path, kind) in entries {
+            Self::record(out, &[Self::path(&path), Self::text(kind)])?;
+        }
+        Ok(())
+    }
+
+    fn usage(&mut self, out: &mut dyn Write, rows: &[UsageRow]) -> io::Result<()> {
+        self.header(out, &USAGE_COLUMNS)?;
+        for row in rows {
+            Self::record(out, &usage_fields(row, Self::path, Self::text))?;
+        }
+        Ok(())
+    }
+}
+
+/// Paths only, written as they are stored, so names that aren't valid UTF-8 survive.
+pub(crate) struct NulRenderer;
+
+impl Renderer for NulRenderer {
+    fn volumes(&mut self, out: &mut dyn Write, scan: &ScanResult) -> io::Result<()> {
+        for volume in scan.volumes() {
+            out.write_all(volume.root().as_os_str().as_encoded_bytes())?;
+            out.write_all(b"\0")?;
+        }
+        Ok(())
+    }
+
+    fn entries(&mut self, out: &mut dyn Write, entries: EntryIter<'_>) -> io::Result<()> {
+        for (path, _) in entries {
+            out.write_all(path.as_os_str().as_encoded_bytes())?;
+            out.write_all(b"\0")?;
+        }
+        Ok(())
+    }
+
+    fn usage(&mut self, out: &mut dyn Write, rows: &[UsageRow]) -> io::Result<()> {
+        for row in rows {
+            out.write_all(row.path.as_os_str().as_encoded_bytes())?;
+            out.write_all(b"\0")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::directory_reader::ReaderStats;
+    use crate::modules::output::output_impl::{create_renderer, OutputFormat};
+    use crate::modules::path_arena::PathArena;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    const NAMES: [&str; 5] = [
+        "/r/plain",
+        "/r/a,b",
+        "/r/say \"hi\"",
+        "/r/tab\there",
+        "/r/new\nline",
+    ];
+
+    fn render(
+        format: OutputFormat,
+        write: impl FnOnce(&mut dyn Renderer, &mut dyn Write) -> io::Result<()>,
+    ) -> String {
+        colored::control::set_override(false);
+        let mut renderer = create_renderer(format, Quoting::Escape);
+        let mut out = vec![];
+        write(renderer.as_mut(), &mut out).unwrap();
+        renderer.finish(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn render_entries(format: OutputFormat, paths: &[&str]) -> String {
+        let mut entries = paths.iter().map(|path| (PathBuf::from(path), "file"));
+        render(format, |renderer, out| renderer.entries(out, &mut entries))
+    }
+
+    fn render_volumes(format: OutputFormat) -> String {
+        let mut volume = VolumeScanResult::new(
+            Path::new("/r/a,b"),
+            "semaphore",
+            PathArena::default(),
+            Duration::from_millis(1_500),
+            ReaderStats::default(),
+        );
+        volume.drive_type = Some("SSD".to_string());
+        volume.size_bytes = Some(1_000);
+        (volume.num_files, volume.num_dirs, volume.memory_bytes) = (3, 2, 1_024);
+        let scan = ScanResult::new(vec![volume], Duration::from_secs(2));
+        render(format, |renderer, out| renderer.volumes(out, &scan))
+    }
+
+    #[test]
+    fn table() {
+        assert_eq!(
+            render_entries(OutputFormat::Table, &NAMES),
+            "/r/plain\n/r/a,b\n/r/say \"hi\"\n/r/tab\\there\n/r/new\\nline\n"
+        );
+        assert_eq!(render_entries(OutputFormat::Table, &[]), "");
+
+        // The columns are padded to their widths, compare the fields of the lines
+        let table = render_volumes(OutputFormat::Table);
+        let lines: Vec<Vec<&str>> = table
+            .lines()
+            .filter(|line| !line.is_empty() && !line.starts_with('-'))
+            .map(|line| line.split_whitespace().collect())
+            .collect();
+        assert_eq!(
+            lines,
+            [
+                vec![
+                    "Path", "Type", "Size", "(GB", "/", "TB)", "Reader", "Files", "Dirs", "Memory",
+                    "Time", "(s)", "Time"
+                ],
+                vec![
+                    "/r/a,b",
+                    "SSD",
+                    "0.00",
+                    "GB",
+                    "semaphore",
+                    "3",
+                    "2",
+                    "0.00",
+                    "MB",
+                    "1.500",
+                    "1",
+                    "s",
+                    "500",
+                    "ms"
+                ],
+                vec!["Total", "0.00", "GB", "3", "2", "0.00", "MB", "2.000", "2", "s", "0", "ms"],
+            ]
+        );
+    }
+
+    #[test]
+    fn csv() {
+        assert_eq!(
+            render_entries(OutputFormat::Csv, &NAMES),
+            "path,type\n\
+             /r/plain,file\n\
+             \"/r/a,b\",file\n\
+             \"/r/say \"\"hi\"\"\",file\n\
+             /r/tab\\there,file\n\
+             /r/new\\nline,file\n"
+        );
+        assert_eq!(render_entries(OutputFormat::Csv, &[]), "path,type\n");
+        assert_eq!(
+            render_volumes(OutputFormat::Csv),
+            "path,drive_type,size_bytes,reader,files,dirs,memory_bytes,seconds\n\
+             \"/r/a,b\",SSD,1000,semaphore,3,2,1024,1.500\n"
+        );
+    }
+
+    #[test]
+    fn tsv() {
+        assert_eq!(
+            render_entries(OutputFormat::Tsv, &NAMES),
+            "path\ttype\n\
+             /r/plain\tfile\n\
+             /r/a,b\tfile\n\
+             /r/say \"hi\"\tfile\n\
+             /r/tab\\there\tfile\n\
+             /r/new\\nline\tfile\n"
+        );
+        assert_eq!(render_entries(OutputFormat::Tsv, &[]), "path\ttype\n");
+        assert_eq!(
+            render_volumes(OutputFormat::Tsv),
+            "path\tdrive_type\tsize_bytes\treader\tfiles\tdirs\tmemory_bytes\tseconds\n\
+             /r/a,b\tSSD\t1000\tsemaphore\t3\t2\t1024\t1.500\n"
+        );
+    }
+
+    #[test]
+    fn tsv_escapes_the_names_once() {
+        for quoting in [Quoting::Escape, Quoting::C, Quoting::Shell] {
+            let mut entries = [(PathBuf::from("a\\b\tc"), "file")].into_iter();
+            let mut renderer = create_renderer(OutputFormat::Tsv, quoting);
+            let mut out = vec![];
+            renderer.entries(&mut out, &mut entries).unwrap();
+            assert_eq!(out, b"path\ttype\na\\\\b\\tc\tfile\n");
+        }
+    }
+
+    #[test]
+    fn json() {
+        assert_eq!(
+            render_entries(OutputFormat::Json, &NAMES),
+            "[\n  \
+             {\"path\":\"/r/plain\",\"type\":\"file\"},\n  \
+             {\"path\":\"/r/a,b\",\"type\":\"file\"},\n  \
+             {\"path\":\"/r/say \\\"hi\\\"\",\"type\":\"file\"},\n  \
+             {\"path\":\"/r/tab\\there\",\"type\":\"file\"},\n  \
+             {\"path\":\"/r/new\\nline\",\"type\":\"file\"}\n]\n"
+        );
+        assert_eq!(render_entries(OutputFormat::Json, &[]), "[]\n");
+        assert_eq!(
+            render_volumes(OutputFormat::Json),
+            "[\n  {\"path\":\"/r/a,b\",\"drive_type\":\"SSD\",\"size_bytes\":1000,\
+             \"reader\":\"semaphore\",\"files\":3,\"dirs\":2,\"memory_bytes\":1024,\
+             \"seconds\":1.5}\n]\n"
+        );
+    }
+
+    #[test]
+    fn json_lines() {
+        assert_eq!(
+            render_entries(OutputFormat::JsonLines, &NAMES),
+            "{\"path\":\"/r/plain\",\"type\":\"file\"}\n\
+             {\"path\":\"/r/a,b\",\"type\":\"file\"}\n\
+             {\"path\":\"/r/say \\\"hi\\\"\",\"type\":\"file\"}\n\
+             {\"path\":\"/r/tab\\there\",\"type\":\"file\"}\n\
+             {\"path\":\"/r/new\\nline\",\"type\":\"file\"}\n"
+        );
+        assert_eq!(render_entries(OutputFormat::JsonLines, &[]), "");
+        assert_eq!(
+            render_volumes(OutputFormat::JsonLines),
+            "{\"path\":\"/r/a,b\",\"drive_type\":\"SSD\",\"size_bytes\":1000,\
+             \"reader\":\"semaphore\",\"files\":3,\"dirs\":2,\"memory_bytes\":1024,\
+             \"seconds\":1.5}\n"
+        );
+    }
+}
@@ -0,0 +1,9 @@
+#[cfg(not(feature = "parquet"))]
+mod parquet_disabled;
+#[cfg(feature = "parquet")]
+pub mod parquet_export_impl;
+
+#[cfg(not(feature = "parquet"))]
+pub(crate) use parquet_disabled::ParquetExport;
+#[cfg(feature = "parquet")]
+pub(crate) use parquet_export_impl::ParquetExport;
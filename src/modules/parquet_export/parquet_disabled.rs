@@ -0,0 +1,23 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::modules::directory_reader::Listing;
+use crate::modules::errors::UFFSError;
+
+// Stands in for the export when UFFS is built without Parquet, it never starts
+pub(crate) enum ParquetExport {}
+
+impl ParquetExport {
+    pub(crate) fn start(
+        _path: &Path,
+        _listings: flume::Receiver<Arc<Listing>>,
+    ) -> Result<Self, UFFSError> {
+        Err(UFFSError::FeatureDisabled {
+            feature: "parquet".to_string(),
+        })
+    }
+
+    pub(crate) fn finish(self) -> Result<usize, UFFSError> {
+        match self {}
+    }
+}
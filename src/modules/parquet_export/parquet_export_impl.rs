@@ -0,0 +1,244 @@
+use arrow_array::builder::{
+    BooleanBuilder, StringBuilder, TimestampMicrosecondBuilder, UInt32Builder, UInt64Builder,
+};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::errors::ParquetError;
+use parquet::file::properties::WriterProperties;
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::UNIX_EPOCH;
+
+use crate::config::constants::PARQUET_ROW_GROUP_ROWS;
+use crate::modules::directory_reader::Listing;
+use crate::modules::errors::UFFSError;
+use crate::modules::file_system::{DirEntry, FileKind};
+
+fn schema() -> SchemaRef {
+    let timestamp = DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into()));
+    Arc::new(Schema::new(vec![
+        Field::new("volume", DataType::Utf8, false),
+        Field::new("path", DataType::Utf8, false),
+        Field::new("name", DataType::Utf8, false),
+        Field::new("extension", DataType::Utf8, true),
+        Field::new("is_dir", DataType::Boolean, false),
+        Field::new("size", DataType::UInt64, true),
+        Field::new("mtime", timestamp, true),
+        Field::new("attributes", DataType::UInt32, true),
+    ]))
+}
+
+/// The columns of one row group under construction.
+struct RowGroup {
+    schema: SchemaRef,
+    rows: usize,
+    volume: StringBuilder,
+    path: StringBuilder,
+    name: StringBuilder,
+    extension: StringBuilder,
+    is_dir: BooleanBuilder,
+    size: UInt64Builder,
+    mtime: TimestampMicrosecondBuilder,
+    attributes: UInt32Builder,
+}
+
+impl RowGroup {
+    fn new(schema: SchemaRef) -> Self {
+        Self {
+            schema,
+            rows: 0,
+            volume: StringBuilder::new(),
+            path: StringBuilder::new(),
+            name: StringBuilder::new(),
+            extension: StringBuilder::new(),
+            is_dir: BooleanBuilder::new(),
+            size: UInt64Builder::new(),
+            mtime: TimestampMicrosecondBuilder::new().with_timezone("UTC"),
+            attributes: UInt32Builder::new(),
+        }
+    }
+
+    // The entry of the directory at `dir_path` with what its listing recorded
+    fn append(&mut self, volume: &str, dir_path: &Path, entry: &DirEntry) {
+        let is_dir = entry.kind == FileKind::Dir;
+
+        self.volume.append_value(volume);
+        self.path
+            .append_value(dir_path.join(&entry.name).to_string_lossy());
+        self.name.append_value(entry.name.to_string_lossy());
+        self.extension.append_option(
+            (!is_dir)
+                .then(|| Path::new(&entry.name).extension())
+                .flatten()
+                .map(|extension| extension.to_string_lossy().to_lowercase()),
+        );
+        self.is_dir.append_value(is_dir);
+        self.size
+            .append_option(entry.size.filter(|_| !is_dir).map(|size| size.len));
+        self.mtime.append_option(
+            entry
+                .modified
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|since| since.as_micros() as i64),
+        );
+        self.attributes.append_option(entry.attributes);
+
+        self.rows += 1;
+    }
+
+    // Hands out the finished columns, leaving the builders empty for the next row group
+    fn finish(&mut self) -> Result<RecordBatch, UFFSError> {
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(self.volume.finish()),
+            Arc::new(self.path.finish()),
+            Arc::new(self.name.finish()),
+            Arc::new(self.extension.finish()),
+            Arc::new(self.is_dir.finish()),
+            Arc::new(self.size.finish()),
+            Arc::new(self.mtime.finish()),
+            Arc::new(self.attributes.finish()),
+        ];
+        self.rows = 0;
+
+        RecordBatch::try_new(self.schema.clone(), columns)
+            .map_err(|e| UFFSError::Parquet(ParquetError::from(e)))
+    }
+}
+
+/// Writes the entries to a Parquet file from the listings of the readers, a row group at a
+/// time as the volumes are read.
+pub(crate) struct ParquetExport {
+    writer: JoinHandle<Result<usize, UFFSError>>,
+}
+
+impl ParquetExport {
+    /// Creates the file at `path` and starts writing the entries of `listings` to it, in row
+    /// groups of `PARQUET_ROW_GROUP_ROWS` entries.
+    pub(crate) fn start(
+        path: &Path,
+        listings: flume::Receiver<Arc<Listing>>,
+    ) -> Result<Self, UFFSError> {
+        Self::with_row_groups(File::create(path)?, listings, PARQUET_ROW_GROUP_ROWS)
+    }
+
+    fn with_row_groups(
+        file: File,
+        listings: flume::Receiver<Arc<Listing>>,
+        row_group_rows: usize,
+    ) -> Result<Self, UFFSError> {
+        let schema = schema();
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .set_max_row_group_size(row_group_rows)
+            .build();
+        let mut writer = ArrowWriter::try_new(file, schema.clone(), Some(properties))?;
+
+        let writer = thread::spawn(move || {
+            let mut row_group = RowGroup::new(schema);
+            let mut written = 0;
+            for listing in listings {
+                let volume = listing.volume.to_string_lossy();
+                for (_, entry) in &listing.entries {
+                    row_group.append(&volume, &listing.dir_path, entry);
+                    if row_group.rows == row_group_rows {
+                        written += row_group.rows;
+                        writer.write(&row_group.finish()?)?;
+                        writer.flush()?;
+                    }
+                }
+            }
+
+            if row_group.rows > 0 {
+                written += row_group.rows;
+                writer.write(&row_group.finish()?)?;
+            }
+            writer.close()?;
+            Ok(written)
+        });
+        Ok(Self { writer })
+    }
+
+    /// Waits for the listings of the scan to be written and returns the number of entries
+    /// written.
+    pub(crate) fn finish(self) -> Result<usize, UFFSError> {
+        self.writer
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::directory_reader::{ListingSink, ReadDirectories2};
+    use crate::modules::disk_reader::disk_reader_impl::read_tree_with_sink;
+    use crate::modules::file_system::OsFileSystem;
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::UInt64Type;
+    use arrow_array::Array;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use std::collections::BTreeMap;
+    use std::fs;
+    use std::path::PathBuf;
+
+    #[tokio::test]
+    async fn a_scanned_tree_reads_back_in_row_groups() {
+        let root = tempfile::tempdir().unwrap();
+        let tree = root.path().join("tree");
+        fs::create_dir_all(tree.join("docs/old")).unwrap();
+        fs::write(tree.join("docs/report.TXT"), b"12345").unwrap();
+        fs::write(tree.join("docs/old/notes.txt"), b"123").unwrap();
+        fs::write(tree.join("image.png"), b"1234567890").unwrap();
+        let file = root.path().join("entries.parquet");
+
+        let mut sink = ListingSink::default();
+        let export =
+            ParquetExport::with_row_groups(File::create(&file).unwrap(), sink.subscribe(), 2)
+                .unwrap();
+        let reader = ReadDirectories2::default().with_file_system(OsFileSystem::listing_sizes());
+        read_tree_with_sink(&tree, &reader, &sink.for_volume(&tree)).await;
+        drop(sink);
+        assert_eq!(export.finish().unwrap(), 5);
+
+        let builder = ParquetRecordBatchReaderBuilder::try_new(File::open(&file).unwrap()).unwrap();
+        assert_eq!(builder.schema().fields(), schema().fields());
+        assert_eq!(builder.metadata().num_row_groups(), 3);
+        assert_eq!(builder.metadata().file_metadata().num_rows(), 5);
+
+        let mut sizes = BTreeMap::new();
+        for batch in builder.build().unwrap() {
+            let batch = batch.unwrap();
+            let volume = batch.column(0).as_string::<i32>();
+            let path = batch.column(1).as_string::<i32>();
+            let extension = batch.column(3).as_string::<i32>();
+            let is_dir = batch.column(4).as_boolean();
+            let size = batch.column(5).as_primitive::<UInt64Type>();
+            let mtime = batch.column(6);
+            for row in 0..batch.num_rows() {
+                assert_eq!(volume.value(row), tree.to_string_lossy());
+                assert!(mtime.is_valid(row));
+                assert_eq!(is_dir.value(row), size.is_null(row));
+                let relative = Path::new(path.value(row)).strip_prefix(&tree).unwrap();
+                let extension = extension.is_valid(row).then(|| extension.value(row));
+                let size = size.is_valid(row).then(|| size.value(row));
+                sizes.insert(relative.to_path_buf(), (extension.map(String::from), size));
+            }
+        }
+
+        let file_entry = |extension: &str, size| (Some(extension.to_string()), Some(size));
+        assert_eq!(
+            sizes,
+            BTreeMap::from([
+                (PathBuf::from("docs"), (None, None)),
+                (PathBuf::from("docs/old"), (None, None)),
+                (PathBuf::from("docs/old/notes.txt"), file_entry("txt", 3)),
+                (PathBuf::from("docs/report.TXT"), file_entry("txt", 5)),
+                (PathBuf::from("image.png"), file_entry("png", 10)),
+            ])
+        );
+    }
+}
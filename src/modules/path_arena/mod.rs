@@ -0,0 +1,6 @@
+pub mod path_arena_impl;
+
+pub(crate) use path_arena_impl::name_contains;
+pub(crate) use path_arena_impl::EntryId;
+pub(crate) use path_arena_impl::EntrySize;
+pub(crate) use path_arena_impl::PathArena;
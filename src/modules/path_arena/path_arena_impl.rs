@@ -0,0 +1,353 @@
+use std::ffi::OsStr;
+use std::fmt;
+use std::mem;
+use std::path::{Path, PathBuf};
+
+use crate::config::constants::{ARENA_INITIAL_ENTRIES, ARENA_INITIAL_NAME_BYTES};
+
+/// Index of an entry in a `PathArena`.
+pub type EntryId = u32;
+
+const NO_PARENT: EntryId = EntryId::MAX;
+
+/// Whether `name` contains `pattern`, which is in lowercase, ignoring case. ASCII patterns are
+/// matched byte-wise, so names that aren't valid Unicode are found by their valid parts.
+pub(crate) fn name_contains(name: &OsStr, pattern: &str) -> bool {
+    if pattern.is_empty() {
+        return true;
+    }
+    let bytes = name.as_encoded_bytes();
+    if pattern.is_ascii() {
+        let pattern = pattern.as_bytes();
+        return bytes
+            .windows(pattern.len())
+            .any(|window| window.eq_ignore_ascii_case(pattern));
+    }
+    match name.to_str() {
+        Some(name) => name.to_lowercase().contains(pattern),
+        None => bytes
+            .windows(pattern.len())
+            .any(|window| window == pattern.as_bytes()),
+    }
+}
+
+/// Sizes of an entry, as listed with its directory.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EntrySize {
+    /// Apparent size in bytes, what `du --apparent-size` shows.
+    pub len: u64,
+    /// Space taken on disk in bytes, what `du` shows.
+    pub allocated: u64,
+    /// Identifies a file with several hard links, the same for all of them. `None` for entries
+    /// with a single link.
+    pub link: Option<(u64, u64)>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    name_start: usize,
+    name_len: u32,
+    parent: EntryId,
+}
+
+/// Compact storage for the entries of a scan. Every entry is its parent's index plus its name,
+/// the names of all entries share one byte buffer. A root entry holds its whole path as name.
+///
+/// Full paths are only built on request, so a scan of millions of entries costs about 16 bytes
+/// plus the name length per entry instead of a heap allocated `PathBuf` with the full path.
+///
+/// The arenas are filled by the scans, outside this crate they can only be read.
+#[derive(Default)]
+pub struct PathArena {
+    names: Vec<u8>,
+    entries: Vec<Entry>,
+    files: Vec<EntryId>,
+    dirs: Vec<EntryId>,
+    // By entry id, empty unless the scan listed the sizes
+    sizes: Vec<Option<EntrySize>>,
+}
+
+impl PathArena {
+    pub(crate) fn new() -> Self {
+        Self::with_capacity(ARENA_INITIAL_ENTRIES, ARENA_INITIAL_NAME_BYTES)
+    }
+
+    pub(crate) fn with_capacity(entries: usize, name_bytes: usize) -> Self {
+        Self {
+            names: Vec::with_capacity(name_bytes),
+            entries: Vec::with_capacity(entries),
+            files: vec![],
+            dirs: vec![],
+            sizes: vec![],
+        }
+    }
+
+    /// Adds the directory a scan starts at. Roots are neither counted as files nor as dirs.
+    pub(crate) fn add_root(&mut self, path: &Path) -> EntryId {
+        self.push(NO_PARENT, path.as_os_str())
+    }
+
+    pub(crate) fn add_file(&mut self, parent: EntryId, name: &OsStr) -> EntryId {
+        let id = self.push(parent, name);
+        self.files.push(id);
+        id
+    }
+
+    pub(crate) fn add_dir(&mut self, parent: EntryId, name: &OsStr) -> EntryId {
+        let id = self.push(parent, name);
+        // `is_dir` searches the ids in order
+        debug_assert!(self.dirs.last().is_none_or(|&last| last < id));
+        self.dirs.push(id);
+        id
+    }
+
+    /// Adds the entries read from the directory `parent`, returns the ids of the new directories
+    /// in the order of `dirs`.
+    pub(crate) fn add_listing<S: AsRef<OsStr>>(
+        &mut self,
+        parent: EntryId,
+        files: &[S],
+        dirs: &[S],
+    ) -> Vec<EntryId> {
+        for name in files {
+            self.add_file(parent, name.as_ref());
+        }
+        dirs.iter()
+            .map(|name| self.add_dir(parent, name.as_ref()))
+            .collect()
+    }
+
+    fn push(&mut self, parent: EntryId, name: &OsStr) -> EntryId {
+        let id = EntryId::try_from(self.entries.len())
+            .ok()
+            .filter(|&id| id != NO_PARENT)
+            .expect("Path arena is full");
+        let bytes = name.as_encoded_bytes();
+        let name_len = u32::try_from(bytes.len()).expect("Name too long for the path arena");
+
+        self.entries.push(Entry {
+            name_start: self.names.len(),
+            name_len,
+            parent,
+        });
+        self.names.extend_from_slice(bytes);
+
+        id
+    }
+
+    pub fn name(&self, id: EntryId) -> &OsStr {
+        let entry = &self.entries[id as usize];
+        let bytes = &self.names[entry.name_start..entry.name_start + entry.name_len as usize];
+        // SAFETY: the bytes were copied whole from `OsStr::as_encoded_bytes` in `push`
+        unsafe { OsStr::from_encoded_bytes_unchecked(bytes) }
+    }
+
+    pub fn parent(&self, id: EntryId) -> Option<EntryId> {
+        let parent = self.entries[id as usize].parent;
+        (parent != NO_PARENT).then_some(parent)
+    }
+
+    /// Records the sizes listed for an entry.
+    pub(crate) fn set_size(&mut self, id: EntryId, size: EntrySize) {
+        let index = id as usize;
+        if self.sizes.len() <= index {
+            self.sizes.resize(index + 1, None);
+        }
+        self.sizes[index] = Some(size);
+    }
+
+    /// The sizes listed for an entry, `None` when the scan listed none.
+    pub fn size(&self, id: EntryId) -> Option<EntrySize> {
+        self.sizes.get(id as usize).copied().flatten()
+    }
+
+    /// Whether the scan listed the sizes of the entries.
+    pub fn has_sizes(&self) -> bool {
+        !self.sizes.is_empty()
+    }
+
+    /// Rebuilds the full path of an entry from the names of its ancestors.
+    pub fn path(&self, id: EntryId) -> PathBuf {
+        let mut ancestors = vec![id];
+        let mut current = id;
+        while let Some(parent) = self.parent(current) {
+            ancestors.push(parent);
+            current = parent;
+        }
+
+        let mut path = PathBuf::new();
+        for ancestor in ancestors.into_iter().rev() {
+            path.push(self.name(ancestor));
+        }
+        path
+    }
+
+    /// Number of entries, roots included. Ids run from 0 to `len() - 1`.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The entries added with `add_root`.
+    pub fn roots(&self) -> impl Iterator<Item = EntryId> + '_ {
+        (0..self.len() as EntryId).filter(|&id| self.parent(id).is_none())
+    }
+
+    pub fn is_dir(&self, id: EntryId) -> bool {
+        // Ids are handed out in ascending order
+        self.dirs.binary_search(&id).is_ok()
+    }
+
+    pub fn files(&self) -> &[EntryId] {
+        &self.files
+    }
+
+    pub fn dirs(&self) -> &[EntryId] {
+        &self.dirs
+    }
+
+    pub fn num_files(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn num_dirs(&self) -> usize {
+        self.dirs.len()
+    }
+
+    /// The directories, then the files whose name contains `pattern`, ignoring case, with their
+    /// type as "dir" or "file". An empty pattern matches all.
+    pub fn search<'a>(
+        &'a self,
+        pattern: &str,
+    ) -> impl Iterator<Item = (EntryId, &'static str)> + 'a {
+        let pattern = pattern.to_lowercase();
+        let dirs = self.dirs.iter().map(|&id| (id, "dir"));
+        let files = self.files.iter().map(|&id| (id, "file"));
+        dirs.chain(files)
+            .filter(move |&(id, _)| name_contains(self.name(id), &pattern))
+    }
+
+    pub fn file_paths(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        self.files.iter().map(|&id| self.path(id))
+    }
+
+    pub fn dir_paths(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        self.dirs.iter().map(|&id| self.path(id))
+    }
+
+    /// Heap memory held by the arena, in bytes.
+    pub fn memory_usage(&self) -> usize {
+        self.names.capacity()
+            + self.entries.capacity() * mem::size_of::<Entry>()
+            + (self.files.capacity() + self.dirs.capacity()) * mem::size_of::<EntryId>()
+            + self.sizes.capacity() * mem::size_of::<Option<EntrySize>>()
+    }
+
+    /// Releases the spare capacity left over from growing the buffers.
+    pub(crate) fn shrink_to_fit(&mut self) {
+        self.names.shrink_to_fit();
+        self.entries.shrink_to_fit();
+        self.files.shrink_to_fit();
+        self.dirs.shrink_to_fit();
+        self.sizes.shrink_to_fit();
+    }
+}
+
+impl fmt::Debug for PathArena {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PathArena")
+            .field("entries", &self.entries.len())
+            .field("files", &self.files.len())
+            .field("dirs", &self.dirs.len())
+            .field("name_bytes", &self.names.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Two roots, the second with a nested directory
+    fn arena() -> (PathArena, [EntryId; 6]) {
+        let mut arena = PathArena::new();
+        let first = arena.add_root(Path::new("/first"));
+        let readme = arena.add_file(first, OsStr::new("README"));
+        let second = arena.add_root(Path::new("/second/root"));
+        let src = arena.add_dir(second, OsStr::new("src"));
+        let lib = arena.add_file(src, OsStr::new("lib.rs"));
+        let empty = arena.add_dir(src, OsStr::new("empty"));
+        (arena, [first, readme, second, src, lib, empty])
+    }
+
+    #[test]
+    fn paths_are_rebuilt_from_the_names() {
+        let (arena, [first, readme, second, src, lib, empty]) = arena();
+
+        assert_eq!(arena.path(first), Path::new("/first"));
+        assert_eq!(arena.path(readme), Path::new("/first/README"));
+        assert_eq!(arena.path(second), Path::new("/second/root"));
+        assert_eq!(arena.path(lib), Path::new("/second/root/src/lib.rs"));
+        assert_eq!(arena.path(empty), Path::new("/second/root/src/empty"));
+        assert_eq!(arena.name(src), "src");
+        assert_eq!(arena.name(second), "/second/root");
+    }
+
+    #[test]
+    fn entries_know_their_parents() {
+        let (arena, [first, readme, second, src, lib, _]) = arena();
+
+        assert_eq!(arena.parent(first), None);
+        assert_eq!(arena.parent(second), None);
+        assert_eq!(arena.parent(readme), Some(first));
+        assert_eq!(arena.parent(src), Some(second));
+        assert_eq!(arena.parent(lib), Some(src));
+    }
+
+    #[test]
+    fn roots_are_neither_files_nor_dirs() {
+        let (arena, [first, readme, second, src, lib, empty]) = arena();
+
+        assert_eq!(arena.roots().collect::<Vec<_>>(), [first, second]);
+        assert_eq!(arena.files(), [readme, lib]);
+        assert_eq!(arena.dirs(), [src, empty]);
+        assert_eq!(arena.len(), 6);
+        assert!(arena.is_dir(src) && arena.is_dir(empty));
+        assert!(!arena.is_dir(first) && !arena.is_dir(lib));
+    }
+
+    #[test]
+    fn listings_are_searched_by_name() {
+        let mut arena = PathArena::new();
+        let root = arena.add_root(Path::new("/root"));
+        let dirs = arena.add_listing(root, &["Cargo.toml", "cargo.lock"], &["CARGO", "src"]);
+
+        let found: Vec<_> = arena
+            .search("Cargo")
+            .map(|(id, _)| arena.name(id))
+            .collect();
+        assert_eq!(found, ["CARGO", "Cargo.toml", "cargo.lock"]);
+        assert_eq!(arena.search("").count(), 4);
+        assert_eq!(arena.path(dirs[1]), Path::new("/root/src"));
+    }
+
+    #[test]
+    fn sizes_are_kept_by_entry() {
+        let (mut arena, [_, readme, _, _, lib, _]) = arena();
+        assert!(!arena.has_sizes());
+
+        let size = EntrySize {
+            len: 10,
+            allocated: 4096,
+            link: None,
+        };
+        arena.set_size(readme, size);
+
+        assert!(arena.has_sizes());
+        assert_eq!(arena.size(readme), Some(size));
+        assert_eq!(arena.size(lib), None);
+    }
+}
@@ -1,22 +1,84 @@
-use crate::modules::directory_reader::ReadDirectories4;
-use crate::modules::disk_reader::process_all_disks;
-use colored::Colorize;
-use std::sync::Arc;
+use crate::modules::algo_selector::algo_selector_impl::{same_root, DEFAULT_READER};
+use crate::modules::algo_selector::{
+    select_algorithm, BenchmarkHistory, ReaderOverrides, ReaderRegistry,
+};
+use crate::modules::disk_reader::{discover_drives, process_drives};
+use crate::modules::errors::UFFSError;
+use crate::modules::output::OutputOptions;
+use crate::modules::tuning::TunedSettings;
+use std::path::PathBuf;
 use tokio::time::Instant;
+use tracing::{error, info, warn};
 
-pub(crate) async fn run_directory_processing() -> tokio::time::Duration {
+pub(crate) async fn run_directory_processing(
+    registry: &ReaderRegistry,
+    overrides: &ReaderOverrides,
+    tuned_settings: &TunedSettings,
+    volumes: &[PathBuf],
+    output: &OutputOptions,
+) -> Result<tokio::time::Duration, UFFSError> {
     let start = Instant::now();
 
-    let separator1 = "=".repeat(50).green().to_string();
-    let separator2 = "-".repeat(50).red().to_string();
+    let history_path = BenchmarkHistory::default_path();
+    let mut history = match &history_path {
+        Ok(path) => BenchmarkHistory::load(path),
+        Err(_) => BenchmarkHistory::default(),
+    };
 
-    println!("{}", separator1);
-    println!("\nReadDirectories4\n");
-    println!("{}", separator2);
+    let default_reader = registry.get(DEFAULT_READER)?;
+    let mut drives = discover_drives(&history, &default_reader);
+    if !volumes.is_empty() {
+        for volume in volumes {
+            let volume = volume.to_string_lossy();
+            if !drives
+                .iter()
+                .any(|d| same_root(&d.root_path.to_string_lossy(), &volume))
+            {
+                warn!("{} is not a mounted volume, skipping it", volume);
+            }
+        }
+        drives.retain(|drive| {
+            let root = drive.root_path.to_string_lossy();
+            volumes
+                .iter()
+                .any(|volume| same_root(&volume.to_string_lossy(), &root))
+        });
+    }
 
-    let directory_reader = Arc::new(ReadDirectories4);
+    // Progress is logged to stderr, stdout only gets the results
+    info!("Reading {} volumes:", drives.len());
+    for drive in drives.iter_mut() {
+        match select_algorithm(drive, registry, &history, overrides) {
+            Ok(directory_reader) => drive.directory_reader = directory_reader,
+            Err(e) => error!("{}: {}", drive.root_path.display(), e),
+        }
 
-    process_all_disks(directory_reader).await;
+        let tuning = tuned_settings.get(&drive.root_path.to_string_lossy());
+        if let Some(max_concurrent_reads) = tuning.and_then(|t| t.max_concurrent_reads) {
+            if let Some(directory_reader) = drive
+                .directory_reader
+                .with_max_concurrent_reads(max_concurrent_reads)
+            {
+                drive.directory_reader = directory_reader;
+            }
+        }
+        info!(
+            "{:<18} {}",
+            drive.root_path.display(),
+            drive.directory_reader.name()
+        );
+    }
 
-    Instant::now() - start
+    process_drives(drives, &mut history, output).await;
+
+    match history_path {
+        Ok(path) => {
+            if let Err(e) = history.save(&path) {
+                warn!("Could not save benchmark history {:?}: {}", path, e);
+            }
+        }
+        Err(e) => warn!("Could not locate benchmark history: {}", e),
+    }
+
+    Ok(Instant::now() - start)
 }
@@ -0,0 +1,5 @@
+pub mod raw_path_impl;
+
+#[cfg(windows)]
+pub(crate) use raw_path_impl::is_dot_entry;
+pub(crate) use raw_path_impl::RawPath;
@@ -0,0 +1,258 @@
+use std::borrow::Cow;
+use std::ffi::{OsStr, OsString};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+#[cfg(unix)]
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+#[cfg(windows)]
+use std::os::windows::ffi::{OsStrExt, OsStringExt};
+
+/// A code unit of the native path encoding, UTF-16 on Windows and bytes on Unix.
+#[cfg(windows)]
+pub type PathUnit = u16;
+#[cfg(unix)]
+pub type PathUnit = u8;
+
+#[cfg(windows)]
+const SEPARATOR: PathUnit = b'\\' as PathUnit;
+#[cfg(unix)]
+const SEPARATOR: PathUnit = b'/';
+
+#[cfg(windows)]
+const DOT: PathUnit = b'.' as PathUnit;
+const WILDCARD: PathUnit = b'*' as PathUnit;
+
+fn is_separator(unit: PathUnit) -> bool {
+    // Windows takes both
+    unit == SEPARATOR || unit == b'/' as PathUnit
+}
+
+/// A path in the native encoding, as the system calls take and return it. Names that are not
+/// valid Unicode, unpaired surrogates on Windows or any bytes on Unix, go through unchanged.
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RawPath {
+    // Without a terminating NUL
+    units: Vec<PathUnit>,
+}
+
+impl RawPath {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_units(units: Vec<PathUnit>) -> Self {
+        Self { units }
+    }
+
+    /// The units up to the first NUL, e.g. of the fixed size name buffer in `WIN32_FIND_DATAW`.
+    pub fn from_nul_terminated(units: &[PathUnit]) -> Self {
+        let len = units
+            .iter()
+            .position(|&unit| unit == 0)
+            .unwrap_or(units.len());
+        Self::from_units(units[..len].to_vec())
+    }
+
+    pub fn from_os_str(path: &OsStr) -> Self {
+        #[cfg(windows)]
+        let units = path.encode_wide().collect();
+        #[cfg(unix)]
+        let units = path.as_bytes().to_vec();
+        Self { units }
+    }
+
+    pub fn as_units(&self) -> &[PathUnit] {
+        &self.units
+    }
+
+    pub fn len(&self) -> usize {
+        self.units.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.units.is_empty()
+    }
+
+    /// Appends `name` with a single separator in between, none if the path already ends with
+    /// one (e.g. `C:\` or `/`) or is empty.
+    pub fn push(&mut self, name: &[PathUnit]) {
+        if self.units.last().is_some_and(|&unit| !is_separator(unit)) {
+            self.units.push(SEPARATOR);
+        }
+        self.units.extend_from_slice(name);
+    }
+
+    pub fn join(&self, name: &[PathUnit]) -> Self {
+        let mut joined = Vec::with_capacity(self.units.len() + name.len() + 1);
+        joined.extend_from_slice(&self.units);
+        let mut joined = Self::from_units(joined);
+        joined.push(name);
+        joined
+    }
+
+    /// The path followed by a NUL, for the system calls taking a C string.
+    pub fn to_nul_terminated(&self) -> Vec<PathUnit> {
+        let mut units = Vec::with_capacity(self.units.len() + 1);
+        units.extend_from_slice(&self.units);
+        units.push(0);
+        units
+    }
+
+    /// The NUL terminated pattern matching every entry of the directory, e.g. `C:\dir\*` for
+    /// `FindFirstFileW`.
+    pub fn to_search_pattern(&self) -> Vec<PathUnit> {
+        self.join(&[WILDCARD]).to_nul_terminated()
+    }
+
+    /// Borrowed on Unix, converted on Windows where `OsStr` isn't UTF-16.
+    pub fn as_os_str(&self) -> Cow<'_, OsStr> {
+        #[cfg(windows)]
+        return Cow::Owned(OsString::from_wide(&self.units));
+        #[cfg(unix)]
+        return Cow::Borrowed(OsStr::from_bytes(&self.units));
+    }
+
+    /// Takes over the buffer on Unix.
+    pub fn into_os_string(self) -> OsString {
+        #[cfg(windows)]
+        return OsString::from_wide(&self.units);
+        #[cfg(unix)]
+        return OsString::from_vec(self.units);
+    }
+
+    pub fn into_path_buf(self) -> PathBuf {
+        PathBuf::from(self.into_os_string())
+    }
+
+    /// For messages only, the invalid parts are replaced.
+    pub fn to_string_lossy(&self) -> String {
+        self.as_os_str().to_string_lossy().into_owned()
+    }
+}
+
+/// Whether `name` is `.` or `..`, which `FindFirstFileW` returns along with the entries.
+#[cfg(windows)]
+pub(crate) fn is_dot_entry(name: &[PathUnit]) -> bool {
+    matches!(name, [DOT] | [DOT, DOT])
+}
+
+impl From<&OsStr> for RawPath {
+    fn from(path: &OsStr) -> Self {
+        Self::from_os_str(path)
+    }
+}
+
+impl From<&Path> for RawPath {
+    fn from(path: &Path) -> Self {
+        Self::from_os_str(path.as_os_str())
+    }
+}
+
+impl From<OsString> for RawPath {
+    fn from(path: OsString) -> Self {
+        #[cfg(windows)]
+        return Self::from_os_str(&path);
+        #[cfg(unix)]
+        return Self::from_units(path.into_vec());
+    }
+}
+
+impl From<PathBuf> for RawPath {
+    fn from(path: PathBuf) -> Self {
+        Self::from(path.into_os_string())
+    }
+}
+
+impl From<RawPath> for OsString {
+    fn from(path: RawPath) -> Self {
+        path.into_os_string()
+    }
+}
+
+impl From<RawPath> for PathBuf {
+    fn from(path: RawPath) -> Self {
+        path.into_path_buf()
+    }
+}
+
+// The escapes of `OsStr`'s Debug keep invalid names apart, unlike `to_string_lossy`
+impl fmt::Debug for RawPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.as_os_str(), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn units(text: &str) -> Vec<PathUnit> {
+        RawPath::from_os_str(OsStr::new(text)).as_units().to_vec()
+    }
+
+    #[test]
+    fn names_are_joined_with_one_separator() {
+        let dir = RawPath::from(Path::new("dir"));
+        assert_eq!(
+            dir.join(&units("file")).into_path_buf(),
+            Path::new("dir").join("file")
+        );
+
+        let with_separator = RawPath::from_units(units("dir/"));
+        assert_eq!(
+            with_separator.join(&units("file")).as_units(),
+            units("dir/file")
+        );
+
+        let empty = RawPath::new();
+        assert_eq!(empty.join(&units("file")).as_units(), units("file"));
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn nul_terminated_names_stop_at_the_nul() {
+        let mut buffer = units("name");
+        buffer.extend([0, b'x' as PathUnit, 0]);
+        let name = RawPath::from_nul_terminated(&buffer);
+
+        assert_eq!(name.as_units(), units("name"));
+        assert_eq!(name.to_nul_terminated(), [units("name"), vec![0]].concat());
+    }
+
+    #[test]
+    fn valid_paths_round_trip() {
+        let path = Path::new("dir").join("caf\u{e9} 1.txt");
+        let raw = RawPath::from(path.clone());
+
+        assert_eq!(raw.to_string_lossy(), path.to_string_lossy());
+        assert_eq!(OsString::from(raw.clone()), path.as_os_str());
+        assert_eq!(format!("{:?}", raw), format!("{:?}", path.as_os_str()));
+        assert_eq!(PathBuf::from(raw), path);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn invalid_names_round_trip() {
+        let name = OsString::from_vec(b"caf\xe9".to_vec());
+        let raw = RawPath::from(name.clone());
+
+        assert_eq!(raw.as_units(), b"caf\xe9");
+        assert_eq!(raw.to_string_lossy(), "caf\u{fffd}");
+        assert_eq!(format!("{:?}", raw), "\"caf\\xE9\"");
+        assert_eq!(raw.into_os_string(), name);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn invalid_names_round_trip() {
+        let units = [b'c' as u16, 0xD800, b'f' as u16];
+        let name = OsString::from_wide(&units);
+        let raw = RawPath::from(name.clone());
+
+        assert_eq!(raw.as_units(), units);
+        assert_eq!(raw.to_string_lossy(), "c\u{fffd}f");
+        assert_eq!(format!("{:?}", raw), "\"c\\u{d800}f\"");
+        assert_eq!(raw.into_os_string(), name);
+    }
+}
@@ -0,0 +1,11 @@
+mod scan_result;
+pub mod scanner_impl;
+
+pub use scan_result::Entry;
+pub use scan_result::EntryMetadata;
+pub use scan_result::MetadataMask;
+pub use scan_result::ReadError;
+pub use scan_result::ScanResult;
+pub use scan_result::ScanSummary;
+pub use scan_result::VolumeScanResult;
+pub use scanner_impl::Scanner;
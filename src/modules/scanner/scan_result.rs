@@ -0,0 +1,498 @@
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs;
+use std::mem;
+use std::ops::{BitOr, BitOrAssign};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::modules::directory_reader::{ConcurrencyStats, ReaderStats};
+use crate::modules::path_arena::{EntryId, PathArena};
+
+/// The metadata a `Scanner` reads for every entry it returns. Reading metadata costs a call per
+/// entry after the directories are read, so none is read by default.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct MetadataMask(u8);
+
+impl MetadataMask {
+    pub const NONE: Self = Self(0);
+    pub const SIZE: Self = Self(1);
+    pub const MODIFIED: Self = Self(1 << 1);
+    pub const CREATED: Self = Self(1 << 2);
+    pub const ACCESSED: Self = Self(1 << 3);
+    pub const READONLY: Self = Self(1 << 4);
+    pub const ALL: Self = Self(0b1_1111);
+
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl BitOr for MetadataMask {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for MetadataMask {
+    fn bitor_assign(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+}
+
+/// Metadata of an entry, the fields not in the `MetadataMask` of the scan are `None`, as are
+/// those the file system doesn't keep or that could not be read.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct EntryMetadata {
+    /// Size in bytes, files only.
+    pub size: Option<u64>,
+    pub modified: Option<SystemTime>,
+    pub created: Option<SystemTime>,
+    pub accessed: Option<SystemTime>,
+    pub readonly: Option<bool>,
+}
+
+impl EntryMetadata {
+    pub(crate) fn read(path: &Path, mask: MetadataMask) -> Self {
+        let Ok(metadata) = fs::symlink_metadata(path) else {
+            return Self::default();
+        };
+        let wanted = |field: MetadataMask| mask.contains(field);
+
+        Self {
+            size: (wanted(MetadataMask::SIZE) && !metadata.is_dir()).then_some(metadata.len()),
+            modified: metadata
+                .modified()
+                .ok()
+                .filter(|_| wanted(MetadataMask::MODIFIED)),
+            created: metadata
+                .created()
+                .ok()
+                .filter(|_| wanted(MetadataMask::CREATED)),
+            accessed: metadata
+                .accessed()
+                .ok()
+                .filter(|_| wanted(MetadataMask::ACCESSED)),
+            readonly: wanted(MetadataMask::READONLY).then(|| metadata.permissions().readonly()),
+        }
+    }
+}
+
+/// An entry of a `VolumeScanResult`.
+#[derive(Debug, Clone, Copy)]
+pub struct Entry<'a> {
+    arena: &'a PathArena,
+    id: EntryId,
+    is_dir: bool,
+    metadata: Option<&'a EntryMetadata>,
+}
+
+impl<'a> Entry<'a> {
+    /// Id of the entry in `VolumeScanResult::arena`.
+    pub fn id(&self) -> EntryId {
+        self.id
+    }
+
+    pub fn name(&self) -> &'a OsStr {
+        self.arena.name(self.id)
+    }
+
+    /// The full path, built on each call.
+    pub fn path(&self) -> PathBuf {
+        self.arena.path(self.id)
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.is_dir
+    }
+
+    /// `None` when the scan read no metadata.
+    pub fn metadata(&self) -> Option<&'a EntryMetadata> {
+        self.metadata
+    }
+}
+
+/// A directory that could not be read during a scan, its entries are missing from the result.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReadError {
+    pub path: PathBuf,
+    pub message: String,
+}
+
+impl ReadError {
+    pub(crate) fn new(path: &Path, error: &dyn fmt::Display) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            message: error.to_string(),
+        }
+    }
+}
+
+// The entries passing the filters of a scan, in id order
+#[derive(Debug, Default)]
+pub(crate) struct Selection {
+    pub(crate) dirs: Vec<EntryId>,
+    pub(crate) files: Vec<EntryId>,
+}
+
+// Durations are written as seconds, like the rest of the reports
+mod seconds {
+    use serde::{Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub(super) fn serialize<S: Serializer>(duration: &Duration, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_f64(duration.as_secs_f64())
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<Duration, D::Error> {
+        let seconds = f64::deserialize(d)?;
+        Duration::try_from_secs_f64(seconds).map_err(serde::de::Error::custom)
+    }
+}
+
+/// The entries found below one root of a scan, with what is known about the volume and how it
+/// was read.
+///
+/// Serializing writes the summary of the volume only, the entries are left out.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VolumeScanResult {
+    #[serde(rename = "path")]
+    pub(crate) root: PathBuf,
+    // Only known for the volumes the drive discovery found
+    #[serde(default)]
+    pub(crate) drive_type: Option<String>,
+    #[serde(default)]
+    pub(crate) size_bytes: Option<u64>,
+    pub(crate) reader: Cow<'static, str>,
+    #[serde(rename = "files")]
+    pub(crate) num_files: usize,
+    #[serde(rename = "dirs")]
+    pub(crate) num_dirs: usize,
+    // Of the arena when the scan finished, kept once the entries are dropped
+    pub(crate) memory_bytes: u64,
+    #[serde(rename = "seconds", with = "seconds")]
+    pub(crate) duration: Duration,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) concurrency: Option<ConcurrencyStats>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) errors: Vec<ReadError>,
+    #[serde(skip)]
+    pub(crate) arena: PathArena,
+    // None when every entry of the arena is part of the result
+    #[serde(skip)]
+    pub(crate) selection: Option<Selection>,
+    // Of the directories then the files, empty without a metadata mask
+    #[serde(skip)]
+    pub(crate) metadata: Vec<EntryMetadata>,
+}
+
+impl VolumeScanResult {
+    /// The result of reading the tree below `root` into `arena`, every entry included.
+    pub(crate) fn new(
+        root: &Path,
+        reader: &'static str,
+        arena: PathArena,
+        duration: Duration,
+        stats: ReaderStats,
+    ) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            drive_type: None,
+            size_bytes: None,
+            reader: Cow::Borrowed(reader),
+            num_files: arena.num_files(),
+            num_dirs: arena.num_dirs(),
+            memory_bytes: arena.memory_usage() as u64,
+            duration,
+            concurrency: stats.concurrency,
+            errors: stats.errors,
+            arena,
+            selection: None,
+            metadata: vec![],
+        }
+    }
+
+    /// Narrows the result down to the entries in `selection`, `metadata` being theirs.
+    pub(crate) fn select(&mut self, selection: Selection, metadata: Vec<EntryMetadata>) {
+        self.num_dirs = selection.dirs.len();
+        self.num_files = selection.files.len();
+        self.selection = Some(selection);
+        self.metadata = metadata;
+    }
+
+    /// Hands out the entries, the counts and the rest of the summary stay.
+    pub(crate) fn take_arena(&mut self) -> PathArena {
+        self.selection = None;
+        self.metadata = vec![];
+        mem::take(&mut self.arena)
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The kind of drive the root is on, e.g. "SSD", when known.
+    pub fn drive_type(&self) -> Option<&str> {
+        self.drive_type.as_deref()
+    }
+
+    /// Size of the volume the root is on, when known.
+    pub fn size_bytes(&self) -> Option<u64> {
+        self.size_bytes
+    }
+
+    /// Name of the directory reader that scanned the root.
+    pub fn reader(&self) -> &str {
+        &self.reader
+    }
+
+    /// Time spent reading the directories, without reading the metadata.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Number of files passing the filters.
+    pub fn num_files(&self) -> usize {
+        self.num_files
+    }
+
+    /// Number of directories passing the filters, the root not included.
+    pub fn num_dirs(&self) -> usize {
+        self.num_dirs
+    }
+
+    /// Memory the entries took when the scan finished.
+    pub fn memory_bytes(&self) -> u64 {
+        self.memory_bytes
+    }
+
+    /// The directories that could not be read.
+    pub fn errors(&self) -> &[ReadError] {
+        &self.errors
+    }
+
+    /// The directories then the files passing the filters.
+    pub fn entries(&self) -> impl Iterator<Item = Entry<'_>> + '_ {
+        let (dirs, files): (&[EntryId], &[EntryId]) = match &self.selection {
+            Some(selection) => (&selection.dirs, &selection.files),
+            None => (self.arena.dirs(), self.arena.files()),
+        };
+        let dirs = dirs.iter().map(|&id| (id, true));
+        let files = files.iter().map(|&id| (id, false));
+        dirs.chain(files)
+            .enumerate()
+            .map(move |(index, (id, is_dir))| Entry {
+                arena: &self.arena,
+                id,
+                is_dir,
+                metadata: self.metadata.get(index),
+            })
+    }
+
+    /// Every entry found, filtered or not, with the paths leading to them.
+    pub fn arena(&self) -> &PathArena {
+        &self.arena
+    }
+
+    pub fn into_arena(self) -> PathArena {
+        self.arena
+    }
+}
+
+/// Totals over the volumes of a scan, as shown below the per-volume rows of a report.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[non_exhaustive]
+pub struct ScanSummary {
+    pub volumes: usize,
+    pub files: usize,
+    pub dirs: usize,
+    /// Of the volumes whose size is known.
+    pub size_bytes: u64,
+    pub memory_bytes: u64,
+    /// Directories that could not be read.
+    pub errors: usize,
+    #[serde(rename = "seconds", with = "seconds")]
+    pub duration: Duration,
+}
+
+/// The result of a scan, one `VolumeScanResult` per root. `Scanner::scan` keeps the order the
+/// roots were added in.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ScanResult {
+    pub(crate) volumes: Vec<VolumeScanResult>,
+    #[serde(rename = "seconds", with = "seconds")]
+    pub(crate) duration: Duration,
+}
+
+impl ScanResult {
+    pub(crate) fn new(volumes: Vec<VolumeScanResult>, duration: Duration) -> Self {
+        Self { volumes, duration }
+    }
+
+    pub fn volumes(&self) -> &[VolumeScanResult] {
+        &self.volumes
+    }
+
+    pub fn into_volumes(self) -> Vec<VolumeScanResult> {
+        self.volumes
+    }
+
+    /// Wall time of the whole scan, the metadata included.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    pub fn num_files(&self) -> usize {
+        self.volumes.iter().map(VolumeScanResult::num_files).sum()
+    }
+
+    pub fn num_dirs(&self) -> usize {
+        self.volumes.iter().map(VolumeScanResult::num_dirs).sum()
+    }
+
+    /// The directories that could not be read, on all volumes.
+    pub fn errors(&self) -> impl Iterator<Item = &ReadError> + '_ {
+        self.volumes.iter().flat_map(VolumeScanResult::errors)
+    }
+
+    /// The entries of all volumes.
+    pub fn entries(&self) -> impl Iterator<Item = Entry<'_>> + '_ {
+        self.volumes.iter().flat_map(VolumeScanResult::entries)
+    }
+
+    pub fn summary(&self) -> ScanSummary {
+        ScanSummary {
+            volumes: self.volumes.len(),
+            files: self.num_files(),
+            dirs: self.num_dirs(),
+            size_bytes: self.volumes.iter().filter_map(|v| v.size_bytes).sum(),
+            memory_bytes: self.volumes.iter().map(|v| v.memory_bytes).sum(),
+            errors: self.volumes.iter().map(|v| v.errors.len()).sum(),
+            duration: self.duration,
+        }
+    }
+
+    /// Adds the volumes of a scan run after this one, the durations add up.
+    pub fn merge(&mut self, other: ScanResult) {
+        self.volumes.extend(other.volumes);
+        self.duration += other.duration;
+    }
+
+    /// Hands out the entries of every volume, in the order of `volumes`. The volumes that were
+    /// scanned without keeping their entries give an empty arena.
+    pub(crate) fn take_arenas(&mut self) -> Vec<PathArena> {
+        self.volumes
+            .iter_mut()
+            .map(VolumeScanResult::take_arena)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn volume(
+        root: &str,
+        files: usize,
+        size_bytes: Option<u64>,
+        errors: usize,
+    ) -> VolumeScanResult {
+        let mut volume = VolumeScanResult::new(
+            Path::new(root),
+            "sequential",
+            PathArena::default(),
+            Duration::from_millis(250),
+            ReaderStats::default(),
+        );
+        volume.num_files = files;
+        volume.num_dirs = files / 2;
+        volume.memory_bytes = 100;
+        volume.size_bytes = size_bytes;
+        volume.errors = (0..errors)
+            .map(|i| ReadError::new(&Path::new(root).join(i.to_string()), &"denied"))
+            .collect();
+        volume
+    }
+
+    fn roots(scan: &ScanResult) -> Vec<&Path> {
+        scan.volumes().iter().map(VolumeScanResult::root).collect()
+    }
+
+    #[test]
+    fn the_summary_adds_up_the_volumes() {
+        let scan = ScanResult::new(
+            vec![volume("/a", 10, Some(1_000), 1), volume("/b", 4, None, 2)],
+            Duration::from_secs(3),
+        );
+        let summary = scan.summary();
+        assert_eq!(
+            summary,
+            ScanSummary {
+                volumes: 2,
+                files: 14,
+                dirs: 7,
+                size_bytes: 1_000,
+                memory_bytes: 200,
+                errors: 3,
+                duration: Duration::from_secs(3),
+            }
+        );
+        assert_eq!((scan.num_files(), scan.num_dirs()), (14, 7));
+        assert_eq!(scan.errors().count(), 3);
+        assert_eq!(ScanResult::default().summary(), ScanSummary::default());
+    }
+
+    #[test]
+    fn merged_results_keep_the_volumes_in_order() {
+        let mut scan = ScanResult::new(vec![volume("/a", 10, None, 0)], Duration::from_secs(1));
+        scan.merge(ScanResult::new(
+            vec![volume("/c", 2, None, 1), volume("/b", 4, Some(50), 0)],
+            Duration::from_millis(500),
+        ));
+        assert_eq!(
+            roots(&scan),
+            [Path::new("/a"), Path::new("/c"), Path::new("/b")]
+        );
+
+        let summary = scan.summary();
+        assert_eq!((summary.volumes, summary.files, summary.dirs), (3, 16, 8));
+        assert_eq!((summary.size_bytes, summary.errors), (50, 1));
+        assert_eq!(summary.duration, Duration::from_millis(1_500));
+    }
+
+    #[test]
+    fn durations_are_written_as_seconds() {
+        let scan = ScanResult::new(
+            vec![volume("/a", 10, Some(1_000), 1)],
+            Duration::from_millis(1_250),
+        );
+        let json = serde_json::to_value(&scan).unwrap();
+        assert_eq!(json["seconds"], 1.25);
+        assert_eq!(json["volumes"][0]["seconds"], 0.25);
+        assert_eq!(
+            serde_json::to_value(scan.summary()).unwrap()["seconds"],
+            1.25
+        );
+
+        let read: ScanResult = serde_json::from_value(json).unwrap();
+        assert_eq!(read.duration(), scan.duration());
+        assert_eq!(roots(&read), roots(&scan));
+        let (volume, read_volume) = (&scan.volumes()[0], &read.volumes()[0]);
+        assert_eq!(read_volume.duration(), volume.duration());
+        assert_eq!(read_volume.num_files(), volume.num_files());
+        assert_eq!(read_volume.errors(), volume.errors());
+
+        let negative = serde_json::json!({ "volumes": [], "seconds": -1.0 });
+        assert!(serde_json::from_value::<ScanResult>(negative).is_err());
+    }
+}
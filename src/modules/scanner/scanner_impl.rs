@@ -0,0 +1,360 @@
+use futures::stream::{self, Stream, StreamExt};
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::runtime::Builder;
+use tokio::task::{self, JoinError};
+
+use crate::modules::algo_selector::algo_selector_impl::DEFAULT_READER;
+use crate::modules::algo_selector::{ReaderOptions, ReaderRegistry, SharedDirectoryReader};
+use crate::modules::directory_reader::ReaderStats;
+use crate::modules::disk_reader::read_tree;
+use crate::modules::errors::ScanError;
+use crate::modules::ignore_filter::{IgnoreFilter, IgnoreOptions};
+use crate::modules::path_arena::{name_contains, EntryId, PathArena};
+use crate::modules::scanner::scan_result::{
+    EntryMetadata, MetadataMask, ReadError, ScanResult, Selection, VolumeScanResult,
+};
+
+type EntryFilter = Arc<dyn Fn(&Path, bool) -> bool + Send + Sync>;
+
+#[derive(Clone)]
+enum Filter {
+    // Part of the name, lowercase
+    NameContains(String),
+    Custom(EntryFilter),
+}
+
+impl Filter {
+    fn matches(&self, arena: &PathArena, id: EntryId, is_dir: bool) -> bool {
+        match self {
+            Filter::NameContains(pattern) => name_contains(arena.name(id), pattern),
+            Filter::Custom(filter) => filter(&arena.path(id), is_dir),
+        }
+    }
+}
+
+impl fmt::Debug for Filter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Filter::NameContains(pattern) => f.debug_tuple("NameContains").field(pattern).finish(),
+            Filter::Custom(_) => f.write_str("Custom"),
+        }
+    }
+}
+
+/// Builds and runs a scan of one or more directory trees.
+///
+/// ```no_run
+/// use UltraFastFileSearch_library::{MetadataMask, Scanner};
+///
+/// let result = Scanner::new()
+///     .root("/home")
+///     .name_contains(".rs")
+///     .metadata(MetadataMask::SIZE)
+///     .scan()?;
+/// for entry in result.entries() {
+///     println!("{} {:?}", entry.path().display(), entry.metadata());
+/// }
+/// # Ok::<(), UltraFastFileSearch_library::ScanError>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct Scanner {
+    roots: Vec<PathBuf>,
+    reader: Option<String>,
+    max_concurrent_reads: Option<usize>,
+    parallel_roots: usize,
+    filters: Vec<Filter>,
+    ignore: IgnoreOptions,
+    metadata: MetadataMask,
+}
+
+impl Default for Scanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scanner {
+    pub fn new() -> Self {
+        Self {
+            roots: vec![],
+            reader: None,
+            max_concurrent_reads: None,
+            parallel_roots: usize::MAX,
+            filters: vec![],
+            ignore: IgnoreOptions::default(),
+            metadata: MetadataMask::NONE,
+        }
+    }
+
+    /// Names of the directory readers to choose from with `reader`.
+    pub fn readers() -> Vec<&'static str> {
+        ReaderRegistry::default().names()
+    }
+
+    /// Adds a directory to scan, with everything below it.
+    pub fn root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.roots.push(root.into());
+        self
+    }
+
+    pub fn roots<I>(mut self, roots: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<PathBuf>,
+    {
+        self.roots.extend(roots.into_iter().map(Into::into));
+        self
+    }
+
+    /// The directory reader to scan with, one of `Scanner::readers`.
+    pub fn reader(mut self, name: impl Into<String>) -> Self {
+        self.reader = Some(name.into());
+        self
+    }
+
+    /// Limits the directories read at the same time below a root, for the readers with such a
+    /// limit.
+    pub fn max_concurrent_reads(mut self, max_concurrent_reads: usize) -> Self {
+        self.max_concurrent_reads = Some(max_concurrent_reads.max(1));
+        self
+    }
+
+    /// Limits the roots scanned at the same time, all of them by default.
+    pub fn parallel_roots(mut self, parallel_roots: usize) -> Self {
+        self.parallel_roots = parallel_roots.max(1);
+        self
+    }
+
+    /// Only returns the entries whose name contains `pattern`, ignoring case.
+    pub fn name_contains(mut self, pattern: &str) -> Self {
+        self.filters
+            .push(Filter::NameContains(pattern.to_lowercase()));
+        self
+    }
+
+    /// Only returns the entries for which `filter`, called with the path and whether the entry
+    /// is a directory, returns true. Filters add up, an entry has to pass all of them.
+    /// A filter that panics leaves its volume without entries, with the panic in `errors`.
+    pub fn filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&Path, bool) -> bool + Send + Sync + 'static,
+    {
+        self.filters.push(Filter::Custom(Arc::new(filter)));
+        self
+    }
+
+    /// Honors the `.gitignore`, `.ignore` and `.uffsignore` files below the roots. Unlike the
+    /// filters, the ignored directories are not read at all.
+    pub fn ignore_files(mut self, ignore_files: bool) -> Self {
+        self.ignore.ignore_files = ignore_files;
+        self
+    }
+
+    /// Leaves out the entries matching `pattern`, written as in a `.gitignore`, e.g.
+    /// `node_modules/`, and everything below them.
+    pub fn exclude(mut self, pattern: impl Into<String>) -> Self {
+        self.ignore.exclude.push(pattern.into());
+        self
+    }
+
+    /// Leaves out the names starting with a dot and everything below them.
+    pub fn skip_hidden(mut self, skip_hidden: bool) -> Self {
+        self.ignore.skip_hidden = skip_hidden;
+        self
+    }
+
+    /// The metadata to read for the returned entries.
+    pub fn metadata(mut self, mask: MetadataMask) -> Self {
+        self.metadata = mask;
+        self
+    }
+
+    /// Runs the scan on a runtime of its own and waits for it. Use `scan_async` or `stream`
+    /// from within a Tokio runtime.
+    pub fn scan(&self) -> Result<ScanResult, ScanError> {
+        let runtime = Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .map_err(ScanError::Runtime)?;
+        runtime.block_on(self.scan_async())
+    }
+
+    pub async fn scan_async(&self) -> Result<ScanResult, ScanError> {
+        let start = Instant::now();
+        let volumes = self.stream_ordered()?.collect().await;
+
+        Ok(ScanResult::new(volumes, start.elapsed()))
+    }
+
+    /// The results of the roots as each scan completes, which is not necessarily the order the
+    /// roots were added in. Has to be polled within a Tokio runtime.
+    pub fn stream(&self) -> Result<impl Stream<Item = VolumeScanResult> + Send + '_, ScanError> {
+        let reader = self.prepare()?;
+        Ok(stream::iter(&self.roots)
+            .map(move |root| self.scan_root(root, Arc::clone(&reader)))
+            .buffer_unordered(self.parallel_roots))
+    }
+
+    fn stream_ordered(
+        &self,
+    ) -> Result<impl Stream<Item = VolumeScanResult> + Send + '_, ScanError> {
+        let reader = self.prepare()?;
+        Ok(stream::iter(&self.roots)
+            .map(move |root| self.scan_root(root, Arc::clone(&reader)))
+            .buffered(self.parallel_roots))
+    }
+
+    // Checks the roots and picks the reader before anything is read
+    fn prepare(&self) -> Result<SharedDirectoryReader, ScanError> {
+        if self.roots.is_empty() {
+            return Err(ScanError::NoRoots);
+        }
+        for root in &self.roots {
+            let invalid = |source| ScanError::InvalidRoot {
+                path: root.clone(),
+                source,
+            };
+            let metadata = root.metadata().map_err(invalid)?;
+            if !metadata.is_dir() {
+                return Err(invalid(io::Error::other("not a directory")));
+            }
+        }
+
+        let ignore = IgnoreFilter::new(&self.ignore).map_err(ScanError::InvalidExclude)?;
+        let registry = ReaderRegistry::new(ReaderOptions {
+            ignore,
+            ..ReaderOptions::default()
+        });
+        let name = self.reader.as_deref().unwrap_or(DEFAULT_READER);
+        let reader = registry.get(name).map_err(|_| ScanError::UnknownReader {
+            name: name.to_string(),
+            available: registry.names().join(", "),
+        })?;
+
+        Ok(self
+            .max_concurrent_reads
+            .and_then(|max| reader.with_max_concurrent_reads(max))
+            .unwrap_or(reader))
+    }
+
+    async fn scan_root(&self, root: &Path, reader: SharedDirectoryReader) -> VolumeScanResult {
+        let (arena, duration, stats) = read_tree(root, reader.as_ref()).await;
+        let mut result = VolumeScanResult::new(root, reader.name(), arena, duration, stats);
+        if self.filters.is_empty() && self.metadata.is_empty() {
+            return result;
+        }
+
+        // A filter that panics loses the entries, the volume is returned with the error
+        let stats = ReaderStats {
+            concurrency: result.concurrency.clone(),
+            errors: result.errors.clone(),
+        };
+        let duration = result.duration;
+
+        let filters = self.filters.clone();
+        let mask = self.metadata;
+        // Filtering and reading metadata are blocking work, spread over the rayon pool
+        task::spawn_blocking(move || {
+            let arena = &result.arena;
+            let select = |ids: &[EntryId], is_dir: bool| -> Vec<EntryId> {
+                let mut selected: Vec<EntryId> = ids
+                    .into_par_iter()
+                    .copied()
+                    .filter(|&id| filters.iter().all(|f| f.matches(arena, id, is_dir)))
+                    .collect();
+                selected.sort_unstable();
+                selected
+            };
+            let selection = Selection {
+                dirs: select(arena.dirs(), true),
+                files: select(arena.files(), false),
+            };
+
+            let metadata = if mask.is_empty() {
+                vec![]
+            } else {
+                selection
+                    .dirs
+                    .iter()
+                    .chain(&selection.files)
+                    .collect::<Vec<_>>()
+                    .into_par_iter()
+                    .map(|&id| EntryMetadata::read(&arena.path(id), mask))
+                    .collect()
+            };
+            result.select(selection, metadata);
+            result
+        })
+        .await
+        .unwrap_or_else(|e| {
+            let mut failed =
+                VolumeScanResult::new(root, reader.name(), PathArena::default(), duration, stats);
+            failed
+                .errors
+                .push(ReadError::new(root, &filtering_error(e)));
+            failed
+        })
+    }
+}
+
+fn filtering_error(error: JoinError) -> String {
+    if !error.is_panic() {
+        return format!("Filtering the entries failed: {}", error);
+    }
+    let panic = error.into_panic();
+    let message = panic
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| panic.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("no message");
+    format!("Filtering the entries panicked: {}", message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn tree() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src/lib.rs"), "").unwrap();
+        fs::write(dir.path().join("README.md"), "").unwrap();
+        dir
+    }
+
+    #[test]
+    fn filters_narrow_the_entries() {
+        let dir = tree();
+        let result = Scanner::new()
+            .root(dir.path())
+            .filter(|path, is_dir| !is_dir && path.extension().is_some_and(|e| e == "rs"))
+            .scan()
+            .unwrap();
+
+        let paths: Vec<_> = result.entries().map(|entry| entry.path()).collect();
+        assert_eq!(paths, [dir.path().join("src/lib.rs")]);
+        assert_eq!((result.num_files(), result.num_dirs()), (1, 0));
+    }
+
+    #[test]
+    fn a_panicking_filter_is_reported_with_the_volume() {
+        let dir = tree();
+        let result = Scanner::new()
+            .root(dir.path())
+            .filter(|_, _| panic!("broken filter"))
+            .scan()
+            .unwrap();
+
+        let volume = &result.volumes()[0];
+        assert_eq!(volume.root(), dir.path());
+        assert_eq!(volume.entries().count(), 0);
+        assert_eq!(volume.errors().len(), 1);
+        assert!(volume.errors()[0].message.contains("broken filter"));
+    }
+}
@@ -0,0 +1,9 @@
+#[cfg(not(feature = "sqlite"))]
+mod sqlite_disabled;
+#[cfg(feature = "sqlite")]
+pub mod sqlite_impl;
+
+#[cfg(not(feature = "sqlite"))]
+pub(crate) use sqlite_disabled::SqliteExport;
+#[cfg(feature = "sqlite")]
+pub(crate) use sqlite_impl::SqliteExport;
@@ -0,0 +1,24 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::modules::directory_reader::Listing;
+use crate::modules::errors::UFFSError;
+use crate::modules::scanner::ScanResult;
+
+// Stands in for the export when UFFS is built without SQLite, it never starts
+pub(crate) enum SqliteExport {}
+
+impl SqliteExport {
+    pub(crate) fn start(
+        _path: &Path,
+        _listings: flume::Receiver<Arc<Listing>>,
+    ) -> Result<Self, UFFSError> {
+        Err(UFFSError::FeatureDisabled {
+            feature: "sqlite".to_string(),
+        })
+    }
+
+    pub(crate) fn finish(self, _scan: &ScanResult) -> Result<usize, UFFSError> {
+        match self {}
+    }
+}
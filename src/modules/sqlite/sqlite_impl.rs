@@ -0,0 +1,365 @@
+use chrono::Local;
+use rusqlite::{params, Connection};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::UNIX_EPOCH;
+
+use crate::config::constants::{LOG_DATE_FORMAT, SQLITE_BATCH_ROWS};
+use crate::modules::directory_reader::Listing;
+use crate::modules::errors::UFFSError;
+use crate::modules::file_system::FileKind;
+use crate::modules::path_arena::EntryId;
+use crate::modules::scanner::ScanResult;
+
+// Every export adds a scan run, earlier runs stay in the database for comparison
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS scan_runs (
+        id INTEGER PRIMARY KEY,
+        started_at TEXT NOT NULL,
+        duration_seconds REAL NOT NULL,
+        uffs_version TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS volumes (
+        id INTEGER PRIMARY KEY,
+        scan_run_id INTEGER NOT NULL REFERENCES scan_runs(id),
+        path TEXT NOT NULL,
+        drive_type TEXT NOT NULL,
+        size_bytes INTEGER NOT NULL,
+        reader TEXT NOT NULL,
+        files INTEGER NOT NULL,
+        dirs INTEGER NOT NULL,
+        seconds REAL NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS directories (
+        id INTEGER PRIMARY KEY,
+        volume_id INTEGER NOT NULL REFERENCES volumes(id),
+        parent_id INTEGER REFERENCES directories(id),
+        path TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS entries (
+        id INTEGER PRIMARY KEY,
+        directory_id INTEGER NOT NULL REFERENCES directories(id),
+        name TEXT NOT NULL,
+        extension TEXT,
+        is_dir INTEGER NOT NULL,
+        size INTEGER,
+        mtime INTEGER
+    );
+    CREATE INDEX IF NOT EXISTS volumes_scan_run ON volumes(scan_run_id);
+    CREATE INDEX IF NOT EXISTS directories_volume ON directories(volume_id);
+    CREATE INDEX IF NOT EXISTS directories_path ON directories(path);
+    CREATE INDEX IF NOT EXISTS entries_directory ON entries(directory_id);
+    CREATE INDEX IF NOT EXISTS entries_name ON entries(name);
+    CREATE INDEX IF NOT EXISTS entries_extension ON entries(extension);
+    CREATE INDEX IF NOT EXISTS entries_size ON entries(size);
+    CREATE INDEX IF NOT EXISTS entries_mtime ON entries(mtime);
+";
+
+/// Adds a scan run to a SQLite database from the listings of the readers, as the volumes are
+/// read. The summaries of the volumes are added by `finish` once the scan is done.
+pub(crate) struct SqliteExport {
+    writer: JoinHandle<Result<SqliteRun, UFFSError>>,
+}
+
+impl SqliteExport {
+    /// Creates the database at `path` if needed and starts writing the entries of `listings`
+    /// to it, in transactions of `SQLITE_BATCH_ROWS` rows.
+    pub(crate) fn start(
+        path: &Path,
+        listings: flume::Receiver<Arc<Listing>>,
+    ) -> Result<Self, UFFSError> {
+        let connection = Connection::open(path)?;
+        connection.execute_batch("PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL;")?;
+        Self::with_connection(connection, listings)
+    }
+
+    fn with_connection(
+        connection: Connection,
+        listings: flume::Receiver<Arc<Listing>>,
+    ) -> Result<Self, UFFSError> {
+        connection.execute_batch(SCHEMA)?;
+        connection.execute(
+            "INSERT INTO scan_runs (started_at, duration_seconds, uffs_version) \
+             VALUES (?1, 0, ?2)",
+            params![
+                Local::now().format(LOG_DATE_FORMAT).to_string(),
+                env!("CARGO_PKG_VERSION"),
+            ],
+        )?;
+        let mut run = SqliteRun {
+            scan_run_id: connection.last_insert_rowid(),
+            connection,
+            volumes: HashMap::new(),
+            rows: 0,
+            entries: 0,
+        };
+
+        let writer = thread::spawn(move || {
+            run.connection.execute_batch("BEGIN")?;
+            for listing in listings {
+                run.insert_listing(&listing)?;
+            }
+            run.connection.execute_batch("COMMIT")?;
+            Ok(run)
+        });
+        Ok(Self { writer })
+    }
+
+    /// Waits for the listings of the scan to be written, adds the volumes of `scan` and returns
+    /// the number of entries written.
+    pub(crate) fn finish(self, scan: &ScanResult) -> Result<usize, UFFSError> {
+        self.close(scan).map(|run| run.entries)
+    }
+
+    fn close(self, scan: &ScanResult) -> Result<SqliteRun, UFFSError> {
+        let run = self
+            .writer
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))?;
+
+        run.connection.execute(
+            "UPDATE scan_runs SET duration_seconds = ?1 WHERE id = ?2",
+            params![scan.duration().as_secs_f64(), run.scan_run_id],
+        )?;
+        for volume in scan.volumes() {
+            // A volume whose root could not be read has no rows yet
+            let volume_id = match run.volumes.get(volume.root()) {
+                Some(rows) => rows.id,
+                None => insert_volume(&run.connection, run.scan_run_id, volume.root())?,
+            };
+            run.connection
+                .prepare_cached(
+                    "UPDATE volumes SET drive_type = ?1, size_bytes = ?2, reader = ?3, \
+                     files = ?4, dirs = ?5, seconds = ?6 WHERE id = ?7",
+                )?
+                .execute(params![
+                    volume.drive_type().unwrap_or_default(),
+                    volume.size_bytes().unwrap_or(0) as i64,
+                    volume.reader(),
+                    volume.num_files() as i64,
+                    volume.num_dirs() as i64,
+                    volume.duration().as_secs_f64(),
+                    volume_id,
+                ])?;
+        }
+        Ok(run)
+    }
+}
+
+// The rows of one volume. A directory's row is added with the listing it appears in, its id
+// is only needed until its own listing comes.
+struct VolumeRows {
+    id: i64,
+    directories: HashMap<EntryId, i64>,
+}
+
+struct SqliteRun {
+    connection: Connection,
+    scan_run_id: i64,
+    volumes: HashMap<Arc<Path>, VolumeRows>,
+    // Rows in the open transaction: one transaction per row is slow, one for the whole scan
+    // keeps a huge journal
+    rows: usize,
+    entries: usize,
+}
+
+impl SqliteRun {
+    fn insert_listing(&mut self, listing: &Listing) -> Result<(), UFFSError> {
+        let mut insert_directory = self.connection.prepare_cached(
+            "INSERT INTO directories (volume_id, parent_id, path) VALUES (?1, ?2, ?3)",
+        )?;
+        let mut insert_entry = self.connection.prepare_cached(
+            "INSERT INTO entries (directory_id, name, extension, is_dir, size, mtime) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )?;
+
+        let volume = match self.volumes.entry(Arc::clone(&listing.volume)) {
+            Entry::Occupied(volume) => volume.into_mut(),
+            Entry::Vacant(volume) => {
+                let id = insert_volume(&self.connection, self.scan_run_id, &listing.volume)?;
+                volume.insert(VolumeRows {
+                    id,
+                    directories: HashMap::new(),
+                })
+            }
+        };
+
+        // Only the root of the volume is listed without having a row already
+        let directory_id = match volume.directories.remove(&listing.dir) {
+            Some(directory_id) => directory_id,
+            None => {
+                insert_directory.execute(params![
+                    volume.id,
+                    None::<i64>,
+                    listing.dir_path.to_string_lossy(),
+                ])?;
+                self.rows += 1;
+                self.connection.last_insert_rowid()
+            }
+        };
+
+        for (id, entry) in &listing.entries {
+            let is_dir = entry.kind == FileKind::Dir;
+            if is_dir {
+                insert_directory.execute(params![
+                    volume.id,
+                    directory_id,
+                    listing.dir_path.join(&entry.name).to_string_lossy(),
+                ])?;
+                volume
+                    .directories
+                    .insert(*id, self.connection.last_insert_rowid());
+                self.rows += 1;
+            }
+
+            let extension = (!is_dir)
+                .then(|| Path::new(&entry.name).extension())
+                .flatten()
+                .map(|extension| extension.to_string_lossy().to_lowercase());
+            let size = entry.size.filter(|_| !is_dir).map(|size| size.len as i64);
+            let mtime = entry
+                .modified
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|since| since.as_secs() as i64);
+            insert_entry.execute(params![
+                directory_id,
+                entry.name.to_string_lossy(),
+                extension,
+                is_dir,
+                size,
+                mtime,
+            ])?;
+            self.rows += 1;
+        }
+        self.entries += listing.entries.len();
+
+        if self.rows >= SQLITE_BATCH_ROWS {
+            self.connection.execute_batch("COMMIT; BEGIN")?;
+            self.rows = 0;
+        }
+        Ok(())
+    }
+}
+
+// The row of a volume, its summary is only filled in once the scan is done
+fn insert_volume(connection: &Connection, scan_run_id: i64, root: &Path) -> Result<i64, UFFSError> {
+    connection
+        .prepare_cached(
+            "INSERT INTO volumes (scan_run_id, path, drive_type, size_bytes, reader, files, \
+             dirs, seconds) VALUES (?1, ?2, '', 0, '', 0, 0, 0)",
+        )?
+        .execute(params![scan_run_id, root.to_string_lossy()])?;
+    Ok(connection.last_insert_rowid())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::directory_reader::{DirectoryReader, ListingSink, ReadDirectories2};
+    use crate::modules::disk_reader::disk_reader_impl::read_tree_with_sink;
+    use crate::modules::file_system::OsFileSystem;
+    use crate::modules::scanner::VolumeScanResult;
+    use std::fs;
+    use std::time::Duration;
+
+    // Scans `root` into an in-memory database the way a scan with `--sqlite` does
+    async fn export_scan(root: &Path) -> SqliteRun {
+        let mut sink = ListingSink::default();
+        let connection = Connection::open_in_memory().unwrap();
+        let export = SqliteExport::with_connection(connection, sink.subscribe()).unwrap();
+
+        let reader = ReadDirectories2::default().with_file_system(OsFileSystem::listing_sizes());
+        let (arena, duration, stats) =
+            read_tree_with_sink(root, &reader, &sink.for_volume(root)).await;
+        drop(sink);
+
+        let volume = VolumeScanResult::new(root, reader.name(), arena, duration, stats);
+        let scan = ScanResult::new(vec![volume], Duration::from_secs(2));
+        export.close(&scan).unwrap()
+    }
+
+    fn count(connection: &Connection, table: &str) -> i64 {
+        connection
+            .query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+                row.get(0)
+            })
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn the_scan_is_written_with_its_sizes_and_times() {
+        let root = tempfile::tempdir().unwrap();
+        fs::create_dir_all(root.path().join("docs/old")).unwrap();
+        fs::write(root.path().join("docs/report.TXT"), b"12345").unwrap();
+        fs::write(root.path().join("docs/old/notes.txt"), b"123").unwrap();
+        fs::write(root.path().join("image.png"), b"1234567890").unwrap();
+
+        let run = export_scan(root.path()).await;
+        let connection = &run.connection;
+        assert_eq!(run.entries, 5);
+
+        let tables: Vec<String> = connection
+            .prepare("SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(tables, ["directories", "entries", "scan_runs", "volumes"]);
+
+        assert_eq!(count(connection, "scan_runs"), 1);
+        assert_eq!(count(connection, "volumes"), 1);
+        // The root and the two directories below it
+        assert_eq!(count(connection, "directories"), 3);
+        assert_eq!(count(connection, "entries"), 5);
+
+        let duration: f64 = connection
+            .query_row("SELECT duration_seconds FROM scan_runs", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(duration, 2.0);
+        let volume: (String, String, i64, i64) = connection
+            .query_row("SELECT path, reader, files, dirs FROM volumes", [], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })
+            .unwrap();
+        let root_path = root.path().to_string_lossy().into_owned();
+        assert_eq!(volume, (root_path, "sequential".to_string(), 3, 2));
+
+        let text_files: Vec<(String, String, i64)> = connection
+            .prepare(
+                "SELECT directories.path, entries.name, entries.size FROM entries \
+                 JOIN directories ON directories.id = entries.directory_id \
+                 WHERE entries.extension = 'txt' ORDER BY entries.size",
+            )
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let dir = |path: &str| root.path().join(path).to_string_lossy().into_owned();
+        assert_eq!(
+            text_files,
+            [
+                (dir("docs/old"), "notes.txt".to_string(), 3),
+                (dir("docs"), "report.TXT".to_string(), 5),
+            ]
+        );
+
+        // Only the files have a size, every entry has the time the listing recorded
+        let sizes: i64 = connection
+            .query_row("SELECT COUNT(size) FROM entries WHERE is_dir", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(sizes, 0);
+        let times: i64 = connection
+            .query_row("SELECT COUNT(mtime) FROM entries", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(times, 5);
+    }
+}
@@ -0,0 +1,44 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// How a count or size of the generated tree is drawn, written in the spec as e.g.
+/// `{ kind = "uniform", min = 0, max = 8 }`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Distribution {
+    Fixed {
+        value: u64,
+    },
+    /// Any value from `min` to `max`, both included
+    Uniform {
+        min: u64,
+        max: u64,
+    },
+    /// Mostly values around `median` with a long tail of larger ones, like the sizes of real
+    /// files and directories. Capped at `max`.
+    LogNormal {
+        median: f64,
+        sigma: f64,
+        max: u64,
+    },
+}
+
+impl Distribution {
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> u64 {
+        match *self {
+            Distribution::Fixed { value } => value,
+            Distribution::Uniform { min, max } => rng.gen_range(min..=max.max(min)),
+            Distribution::LogNormal { median, sigma, max } => {
+                let value = median * (sigma * standard_normal(rng)).exp();
+                (value.round() as u64).min(max)
+            }
+        }
+    }
+}
+
+// Box-Muller, rand has the normal distribution only in the separate rand_distr
+fn standard_normal<R: Rng>(rng: &mut R) -> f64 {
+    let u1: f64 = 1.0 - rng.gen::<f64>();
+    let u2: f64 = rng.gen();
+    (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos()
+}
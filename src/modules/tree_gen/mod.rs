@@ -0,0 +1,4 @@
+pub mod distribution;
+pub mod tree_gen_impl;
+
+pub(crate) use tree_gen_impl::TreeSpec;
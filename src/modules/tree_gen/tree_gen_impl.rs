@@ -0,0 +1,386 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use tracing::warn;
+
+use crate::modules::errors::UFFSError;
+use crate::modules::file_system::memory_fs::MemoryFileSystem;
+use crate::modules::tree_gen::distribution::Distribution;
+
+// Long enough to push deep paths past MAX_PATH, short enough for the 255 bytes most file
+// systems allow per name even with the Unicode stems
+const LONG_NAME_LEN: usize = 200;
+
+// Scripts, combining accents, emoji and right-to-left text
+const UNICODE_STEMS: &[&str] = &[
+    "données",
+    "Ünïcödé",
+    "e\u{301}te\u{301}",
+    "Ελληνικά",
+    "Привет",
+    "日本語のファイル",
+    "中文 目录",
+    "עברית",
+    "🎉 party",
+];
+
+/// Layout of a generated tree, read from a TOML file such as
+///
+/// ```text
+/// seed = 7
+/// depth = 6
+/// fan_out = { kind = "uniform", min = 1, max = 4 }
+/// files_per_dir = { kind = "log_normal", median = 20.0, sigma = 1.2, max = 5000 }
+/// unicode_names = 0.1
+/// ```
+///
+/// The missing settings keep their defaults.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TreeSpec {
+    /// The same spec and seed give the same tree
+    pub seed: u64,
+    /// Levels of directories below the root
+    pub depth: u32,
+    /// Subdirectories per directory
+    pub fan_out: Distribution,
+    pub files_per_dir: Distribution,
+    /// In bytes, the files are sparse where the file system allows it
+    pub file_size: Distribution,
+    /// Share of the names `LONG_NAME_LEN` characters long
+    pub long_names: f64,
+    /// Share of the names with characters outside ASCII
+    pub unicode_names: f64,
+    /// Share of the files that are symlinks to an earlier entry
+    pub symlinks: f64,
+    /// Share of the files that are hard links to an earlier file
+    pub hard_links: f64,
+    /// Stops adding entries past this many, whatever the distributions draw
+    pub max_entries: u64,
+}
+
+impl Default for TreeSpec {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            depth: 4,
+            fan_out: Distribution::Uniform { min: 0, max: 6 },
+            files_per_dir: Distribution::LogNormal {
+                median: 8.0,
+                sigma: 1.0,
+                max: 2_000,
+            },
+            file_size: Distribution::LogNormal {
+                median: 4096.0,
+                sigma: 2.0,
+                max: 64 * 1024 * 1024,
+            },
+            long_names: 0.01,
+            unicode_names: 0.05,
+            symlinks: 0.01,
+            hard_links: 0.01,
+            max_entries: 100_000,
+        }
+    }
+}
+
+impl TreeSpec {
+    pub(crate) fn load(path: &Path) -> Result<TreeSpec, UFFSError> {
+        let content = fs::read_to_string(path)?;
+        toml::from_str(&content)
+            .map_err(|e| UFFSError::ConfigError(format!("{}: {}", path.display(), e)))
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Draws the entries of the tree, nothing is written yet.
+    pub fn generate(&self) -> GeneratedTree {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut entries: Vec<TreeEntry> = vec![];
+        // Indexes of the regular files, the targets of the hard links
+        let mut files: Vec<usize> = vec![];
+        let mut queue = VecDeque::from([(PathBuf::new(), 0)]);
+
+        'generate: while let Some((dir, level)) = queue.pop_front() {
+            for _ in 0..self.files_per_dir.sample(&mut rng) {
+                if entries.len() as u64 >= self.max_entries {
+                    break 'generate;
+                }
+                let name = self.entry_name(&mut rng, "file", entries.len(), ".txt");
+                let kind = if !entries.is_empty() && chance(&mut rng, self.symlinks) {
+                    let target = &entries[rng.gen_range(0..entries.len())].path;
+                    TreeEntryKind::Symlink {
+                        target: relative_to(&dir, target),
+                    }
+                } else if !files.is_empty() && chance(&mut rng, self.hard_links) {
+                    TreeEntryKind::HardLink {
+                        target: entries[files[rng.gen_range(0..files.len())]].path.clone(),
+                    }
+                } else {
+                    files.push(entries.len());
+                    TreeEntryKind::File {
+                        size: self.file_size.sample(&mut rng),
+                    }
+                };
+                entries.push(TreeEntry {
+                    path: dir.join(name),
+                    kind,
+                });
+            }
+
+            if level >= self.depth {
+                continue;
+            }
+            for _ in 0..self.fan_out.sample(&mut rng) {
+                if entries.len() as u64 >= self.max_entries {
+                    break 'generate;
+                }
+                let path = dir.join(self.entry_name(&mut rng, "dir", entries.len(), ""));
+                entries.push(TreeEntry {
+                    path: path.clone(),
+                    kind: TreeEntryKind::Dir,
+                });
+                queue.push_back((path, level + 1));
+            }
+        }
+
+        GeneratedTree { entries }
+    }
+
+    // Unique through `index`, the position of the entry in the tree
+    fn entry_name(&self, rng: &mut StdRng, stem: &str, index: usize, extension: &str) -> String {
+        let stem = if chance(rng, self.unicode_names) {
+            UNICODE_STEMS[rng.gen_range(0..UNICODE_STEMS.len())]
+        } else {
+            stem
+        };
+        let mut name = format!("{}_{}", stem, index);
+        if chance(rng, self.long_names) {
+            let padding = LONG_NAME_LEN.saturating_sub(name.chars().count() + extension.len());
+            name.extend(std::iter::repeat_n('x', padding));
+        }
+        name.push_str(extension);
+        name
+    }
+}
+
+fn chance(rng: &mut StdRng, probability: f64) -> bool {
+    rng.gen_bool(probability.clamp(0.0, 1.0))
+}
+
+// `target` as seen from `dir`, both relative to the root
+fn relative_to(dir: &Path, target: &Path) -> PathBuf {
+    let mut relative: PathBuf = dir.components().map(|_| Component::ParentDir).collect();
+    relative.push(target);
+    relative
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TreeEntryKind {
+    Dir,
+    File {
+        size: u64,
+    },
+    /// `target` is relative to the directory of the link
+    Symlink {
+        target: PathBuf,
+    },
+    /// `target` is relative to the root
+    HardLink {
+        target: PathBuf,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeEntry {
+    /// Relative to the root of the tree
+    pub path: PathBuf,
+    pub kind: TreeEntryKind,
+}
+
+/// Counts of a generated tree, or of what `GeneratedTree::write_to` created.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct TreeStats {
+    pub dirs: u64,
+    pub files: u64,
+    pub symlinks: u64,
+    pub hard_links: u64,
+    pub bytes: u64,
+    /// Links the file system refused, e.g. symlinks on Windows without the privilege
+    pub skipped_links: u64,
+}
+
+impl TreeStats {
+    fn count(&mut self, kind: &TreeEntryKind) {
+        match kind {
+            TreeEntryKind::Dir => self.dirs += 1,
+            TreeEntryKind::File { size } => {
+                self.files += 1;
+                self.bytes += size;
+            }
+            TreeEntryKind::Symlink { .. } => self.symlinks += 1,
+            TreeEntryKind::HardLink { .. } => self.hard_links += 1,
+        }
+    }
+}
+
+/// The entries of a generated tree, every directory before what it contains.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GeneratedTree {
+    entries: Vec<TreeEntry>,
+}
+
+impl GeneratedTree {
+    pub fn entries(&self) -> &[TreeEntry] {
+        &self.entries
+    }
+
+    pub fn stats(&self) -> TreeStats {
+        let mut stats = TreeStats::default();
+        for entry in &self.entries {
+            stats.count(&entry.kind);
+        }
+        stats
+    }
+
+    /// Creates the tree below `root`, which must be missing or empty. The links the file
+    /// system refuses are skipped with a warning.
+    pub fn write_to(&self, root: &Path) -> io::Result<TreeStats> {
+        fs::create_dir_all(root)?;
+        if fs::read_dir(root)?.next().is_some() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} is not empty", root.display()),
+            ));
+        }
+
+        let mut stats = TreeStats::default();
+        for entry in &self.entries {
+            let path = root.join(&entry.path);
+            let linked = match &entry.kind {
+                TreeEntryKind::Dir => fs::create_dir(&path),
+                TreeEntryKind::File { size } => File::create(&path)?.set_len(*size),
+                TreeEntryKind::Symlink { target } => symlink(target, &path),
+                TreeEntryKind::HardLink { target } => fs::hard_link(root.join(target), &path),
+            };
+            match linked {
+                Ok(()) => stats.count(&entry.kind),
+                Err(e) if matches!(entry.kind, TreeEntryKind::Dir | TreeEntryKind::File { .. }) => {
+                    return Err(e)
+                }
+                Err(e) => {
+                    warn!("Skipped the link {}: {}", path.display(), e);
+                    stats.skipped_links += 1;
+                }
+            }
+        }
+        Ok(stats)
+    }
+
+    /// Adds the tree below `root` to `fs`, hard links become plain files.
+    pub fn write_to_memory(&self, fs: &MemoryFileSystem, root: &Path) -> io::Result<()> {
+        fs.create_dir_all(root)?;
+        for entry in &self.entries {
+            let path = root.join(&entry.path);
+            match &entry.kind {
+                TreeEntryKind::Dir => fs.create_dir_all(&path)?,
+                TreeEntryKind::File { .. } | TreeEntryKind::HardLink { .. } => {
+                    fs.add_file(&path)?
+                }
+                TreeEntryKind::Symlink { target } => fs.add_symlink(&path, target)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+// Windows tells links to directories apart, which needs the target to exist already
+#[cfg(windows)]
+fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    let resolved = link.parent().unwrap_or(link).join(target);
+    if resolved.is_dir() {
+        std::os::windows::fs::symlink_dir(target, link)
+    } else {
+        std::os::windows::fs::symlink_file(target, link)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    fn spec(seed: u64) -> TreeSpec {
+        TreeSpec {
+            max_entries: 2_000,
+            ..TreeSpec::default()
+        }
+        .with_seed(seed)
+    }
+
+    // The paths below `root` with whether each is a directory and the size of the files
+    fn listing(root: &Path) -> BTreeSet<(PathBuf, bool, u64)> {
+        let mut listing = BTreeSet::new();
+        let mut pending = vec![root.to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            for entry in fs::read_dir(&dir).unwrap() {
+                let path = entry.unwrap().path();
+                let metadata = fs::symlink_metadata(&path).unwrap();
+                if metadata.is_dir() {
+                    pending.push(path.clone());
+                }
+                let len = if metadata.is_file() {
+                    metadata.len()
+                } else {
+                    0
+                };
+                let relative = path.strip_prefix(root).unwrap().to_path_buf();
+                listing.insert((relative, metadata.is_dir(), len));
+            }
+        }
+        listing
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_tree() {
+        let tree = spec(7).generate();
+        assert!(tree.entries().len() > 100);
+        assert_eq!(spec(7).generate(), tree);
+        assert_ne!(spec(8).generate(), tree);
+
+        let first = tempfile::tempdir().unwrap();
+        let second = tempfile::tempdir().unwrap();
+        let stats = tree.write_to(first.path()).unwrap();
+        assert_eq!(spec(7).generate().write_to(second.path()).unwrap(), stats);
+        assert_eq!(listing(first.path()), listing(second.path()));
+    }
+
+    #[test]
+    fn specs_are_loaded_with_their_seed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("spec.toml");
+        fs::write(&path, "seed = 7\nmax_entries = 2000\n").unwrap();
+
+        let loaded = TreeSpec::load(&path).unwrap();
+        assert_eq!(loaded, spec(7));
+        assert_eq!(loaded.generate(), spec(7).generate());
+
+        fs::write(&path, "seed = \"seven\"\n").unwrap();
+        assert!(matches!(
+            TreeSpec::load(&path),
+            Err(UFFSError::ConfigError(_))
+        ));
+    }
+}
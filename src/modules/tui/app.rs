@@ -0,0 +1,505 @@
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::widgets::ListState;
+use std::ffi::OsStr;
+use std::path::PathBuf;
+
+use crate::config::constants::TUI_MAX_RESULTS;
+use crate::modules::path_arena::{name_contains, EntryId, PathArena};
+use crate::modules::usage::{listed_usage, Usage};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Mode {
+    Search,
+    Browse,
+}
+
+/// An entry of one of the scanned volumes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Item {
+    pub(crate) arena: usize,
+    pub(crate) id: EntryId,
+}
+
+/// What the event loop does for a key besides updating the state.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum Action {
+    CopyPath(PathBuf),
+    RevealPath(PathBuf),
+}
+
+// Built the first time a volume is browsed: finding the children of a directory and summing the
+// sizes the scan listed take a pass over the whole volume
+struct Tree {
+    by_parent: Vec<(EntryId, EntryId)>,
+    usage: Vec<Usage>,
+}
+
+impl Tree {
+    fn new(arena: &PathArena) -> Self {
+        let mut by_parent: Vec<(EntryId, EntryId)> = arena
+            .dirs()
+            .iter()
+            .chain(arena.files())
+            .filter_map(|&id| arena.parent(id).map(|parent| (parent, id)))
+            .collect();
+        by_parent.sort_unstable();
+
+        Self {
+            by_parent,
+            usage: listed_usage(arena),
+        }
+    }
+
+    fn children(&self, dir: EntryId) -> impl Iterator<Item = EntryId> + '_ {
+        let start = self.by_parent.partition_point(|&(parent, _)| parent < dir);
+        self.by_parent[start..]
+            .iter()
+            .take_while(move |&&(parent, _)| parent == dir)
+            .map(|&(_, id)| id)
+    }
+}
+
+pub(crate) struct App {
+    arenas: Vec<PathArena>,
+    trees: Vec<Option<Tree>>,
+    pub(crate) mode: Mode,
+    pub(crate) query: String,
+    pub(crate) results: Vec<Item>,
+    // More entries matched than are shown
+    pub(crate) truncated: bool,
+    // The directory being browsed, None for the list of volumes
+    pub(crate) current: Option<Item>,
+    pub(crate) listing: Vec<Item>,
+    pub(crate) list_state: ListState,
+    pub(crate) status: Option<String>,
+    pub(crate) quit: bool,
+}
+
+impl App {
+    pub(crate) fn new(arenas: Vec<PathArena>) -> Self {
+        let trees = arenas.iter().map(|_| None).collect();
+        let mut app = Self {
+            arenas,
+            trees,
+            mode: Mode::Search,
+            query: String::new(),
+            results: vec![],
+            truncated: false,
+            current: None,
+            listing: vec![],
+            list_state: ListState::default(),
+            status: None,
+            quit: false,
+        };
+        app.search();
+        app
+    }
+
+    pub(crate) fn items(&self) -> &[Item] {
+        match self.mode {
+            Mode::Search => &self.results,
+            Mode::Browse => &self.listing,
+        }
+    }
+
+    pub(crate) fn selected(&self) -> Option<Item> {
+        self.list_state
+            .selected()
+            .and_then(|i| self.items().get(i).copied())
+    }
+
+    pub(crate) fn path(&self, item: Item) -> PathBuf {
+        self.arenas[item.arena].path(item.id)
+    }
+
+    pub(crate) fn name(&self, item: Item) -> &OsStr {
+        self.arenas[item.arena].name(item.id)
+    }
+
+    pub(crate) fn is_dir(&self, item: Item) -> bool {
+        let arena = &self.arenas[item.arena];
+        arena.is_dir(item.id) || arena.parent(item.id).is_none()
+    }
+
+    /// Recursive sizes, known once the volume was browsed if the scan or index listed them.
+    pub(crate) fn usage(&self, item: Item) -> Option<Usage> {
+        self.trees[item.arena]
+            .as_ref()
+            .filter(|_| self.arenas[item.arena].has_sizes())
+            .map(|tree| tree.usage[item.id as usize])
+    }
+
+    pub(crate) fn num_entries(&self) -> usize {
+        self.arenas
+            .iter()
+            .map(|arena| arena.num_files() + arena.num_dirs())
+            .sum()
+    }
+
+    fn select_first(&mut self) {
+        let first = (!self.items().is_empty()).then_some(0);
+        self.list_state = ListState::default().with_selected(first);
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        let len = self.items().len();
+        if len == 0 {
+            return;
+        }
+        let current = self.list_state.selected().unwrap_or(0) as isize;
+        let selected = (current + delta).clamp(0, len as isize - 1) as usize;
+        self.list_state.select(Some(selected));
+    }
+
+    /// Finds the entries whose name contains the query, ignoring case, up to `TUI_MAX_RESULTS`.
+    fn search(&mut self) {
+        let query = self.query.to_lowercase();
+        self.results.clear();
+        self.truncated = false;
+
+        'arenas: for (index, arena) in self.arenas.iter().enumerate() {
+            for &id in arena.dirs().iter().chain(arena.files()) {
+                if !name_contains(arena.name(id), &query) {
+                    continue;
+                }
+                if self.results.len() == TUI_MAX_RESULTS {
+                    self.truncated = true;
+                    break 'arenas;
+                }
+                self.results.push(Item { arena: index, id });
+            }
+        }
+
+        self.select_first();
+    }
+
+    /// Lists the directory `dir`, largest entries first, or the volumes for None.
+    fn browse(&mut self, dir: Option<Item>, select: Option<Item>) {
+        self.mode = Mode::Browse;
+        self.current = dir;
+
+        self.listing = match dir {
+            Some(dir) => {
+                let arena = &self.arenas[dir.arena];
+                let tree = self.trees[dir.arena].get_or_insert_with(|| Tree::new(arena));
+                let mut listing: Vec<Item> = tree
+                    .children(dir.id)
+                    .map(|id| Item {
+                        arena: dir.arena,
+                        id,
+                    })
+                    .collect();
+                listing.sort_by(|a, b| {
+                    let allocated = |item: &Item| tree.usage[item.id as usize].allocated;
+                    allocated(b)
+                        .cmp(&allocated(a))
+                        .then_with(|| arena.name(a.id).cmp(arena.name(b.id)))
+                });
+                listing
+            }
+            None => self
+                .arenas
+                .iter()
+                .enumerate()
+                .flat_map(|(index, arena)| arena.roots().map(move |id| Item { arena: index, id }))
+                .collect(),
+        };
+
+        self.select_first();
+        if let Some(position) = select.and_then(|s| self.listing.iter().position(|&i| i == s)) {
+            self.list_state.select(Some(position));
+        }
+    }
+
+    fn parent(&self, item: Item) -> Option<Item> {
+        self.arenas[item.arena]
+            .parent(item.id)
+            .map(|id| Item { id, ..item })
+    }
+
+    // Into a directory, or to the directory of a file with the file selected
+    fn open(&mut self, item: Item) {
+        if self.is_dir(item) {
+            self.browse(Some(item), None);
+        } else {
+            self.browse(self.parent(item), Some(item));
+        }
+    }
+
+    pub(crate) fn handle_key(&mut self, key: KeyEvent) -> Option<Action> {
+        self.status = None;
+        let control = key.modifiers.contains(KeyModifiers::CONTROL);
+
+        match key.code {
+            KeyCode::Char('c') if control => self.quit = true,
+            KeyCode::Char('y') if control => {
+                return self
+                    .selected()
+                    .map(|item| Action::CopyPath(self.path(item)));
+            }
+            KeyCode::Char('o') if control => {
+                return self
+                    .selected()
+                    .map(|item| Action::RevealPath(self.path(item)));
+            }
+            KeyCode::Esc => match self.mode {
+                Mode::Browse => {
+                    self.mode = Mode::Search;
+                    self.select_first();
+                }
+                Mode::Search if !self.query.is_empty() => {
+                    self.query.clear();
+                    self.search();
+                }
+                Mode::Search => self.quit = true,
+            },
+            KeyCode::Tab => match self.mode {
+                Mode::Search => match self.selected() {
+                    Some(item) => self.open(item),
+                    None => self.browse(self.current, None),
+                },
+                Mode::Browse => {
+                    self.mode = Mode::Search;
+                    self.select_first();
+                }
+            },
+            KeyCode::Up => self.move_selection(-1),
+            KeyCode::Down => self.move_selection(1),
+            KeyCode::PageUp => self.move_selection(-20),
+            KeyCode::PageDown => self.move_selection(20),
+            KeyCode::Home => self.move_selection(isize::MIN / 2),
+            KeyCode::End => self.move_selection(isize::MAX / 2),
+            KeyCode::Enter | KeyCode::Right => {
+                if let Some(item) = self.selected() {
+                    if self.mode == Mode::Search || self.is_dir(item) {
+                        self.open(item);
+                    }
+                }
+            }
+            KeyCode::Left if self.mode == Mode::Browse => self.up(),
+            KeyCode::Backspace if self.mode == Mode::Browse => self.up(),
+            KeyCode::Backspace => {
+                self.query.pop();
+                self.search();
+            }
+            KeyCode::Char(c) if !control => {
+                if self.mode == Mode::Browse {
+                    self.mode = Mode::Search;
+                }
+                self.query.push(c);
+                self.search();
+            }
+            _ => {}
+        }
+
+        None
+    }
+
+    // To the parent directory, with the directory we came from selected
+    fn up(&mut self) {
+        if let Some(current) = self.current {
+            self.browse(self.parent(current), Some(current));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::path_arena::EntrySize;
+    use std::path::Path;
+
+    // /data with the sizes a scan lists, docs is the largest directory
+    fn volume() -> PathArena {
+        let mut arena = PathArena::new();
+        let root = arena.add_root(Path::new("/data"));
+        let docs = arena.add_dir(root, OsStr::new("docs"));
+        let photos = arena.add_dir(root, OsStr::new("photos"));
+        let old = arena.add_dir(docs, OsStr::new("old"));
+        for (parent, name, allocated) in [
+            (root, "readme.md", 4_096),
+            (docs, "report.txt", 4_096),
+            (docs, "big.iso", 1_003_520),
+            (old, "notes.txt", 4_096),
+            (photos, "beach.jpg", 200_704),
+        ] {
+            let id = arena.add_file(parent, OsStr::new(name));
+            let size = EntrySize {
+                len: allocated,
+                allocated,
+                link: None,
+            };
+            arena.set_size(id, size);
+        }
+        arena
+    }
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    fn control(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL)
+    }
+
+    fn type_text(app: &mut App, text: &str) {
+        for c in text.chars() {
+            assert_eq!(app.handle_key(key(KeyCode::Char(c))), None);
+        }
+    }
+
+    fn names(app: &App, items: &[Item]) -> Vec<String> {
+        items
+            .iter()
+            .map(|&item| app.name(item).to_string_lossy().into_owned())
+            .collect()
+    }
+
+    fn selected_name(app: &App) -> Option<String> {
+        app.selected()
+            .map(|item| app.name(item).to_string_lossy().into_owned())
+    }
+
+    #[test]
+    fn the_results_follow_the_query_as_it_is_typed() {
+        let mut app = App::new(vec![volume()]);
+        assert_eq!(app.results.len(), 8);
+        assert_eq!(app.num_entries(), 8);
+
+        type_text(&mut app, "O");
+        assert_eq!(
+            names(&app, &app.results),
+            [
+                "docs",
+                "photos",
+                "old",
+                "report.txt",
+                "big.iso",
+                "notes.txt"
+            ]
+        );
+        type_text(&mut app, "t");
+        assert_eq!(names(&app, &app.results), ["photos", "notes.txt"]);
+        assert_eq!(selected_name(&app).as_deref(), Some("photos"));
+
+        app.handle_key(key(KeyCode::Down));
+        assert_eq!(selected_name(&app).as_deref(), Some("notes.txt"));
+        app.handle_key(key(KeyCode::PageDown));
+        assert_eq!(selected_name(&app).as_deref(), Some("notes.txt"));
+        app.handle_key(key(KeyCode::Home));
+        assert_eq!(selected_name(&app).as_deref(), Some("photos"));
+
+        app.handle_key(key(KeyCode::Backspace));
+        assert_eq!(app.query, "O");
+        assert_eq!(app.results.len(), 6);
+        assert_eq!(selected_name(&app).as_deref(), Some("docs"));
+
+        type_text(&mut app, "zz");
+        assert!(app.results.is_empty());
+        assert_eq!(app.selected(), None);
+
+        // Esc clears the query first, then quits
+        app.handle_key(key(KeyCode::Esc));
+        assert_eq!(app.query, "");
+        assert_eq!(app.results.len(), 8);
+        assert!(!app.quit);
+        app.handle_key(key(KeyCode::Esc));
+        assert!(app.quit);
+    }
+
+    #[test]
+    fn the_results_stop_at_the_limit() {
+        let mut arena = PathArena::new();
+        let root = arena.add_root(Path::new("/many"));
+        for i in 0..TUI_MAX_RESULTS + 5 {
+            arena.add_file(root, OsStr::new(&format!("file{}.txt", i)));
+        }
+        let mut app = App::new(vec![arena, volume()]);
+        assert_eq!(app.results.len(), TUI_MAX_RESULTS);
+        assert!(app.truncated);
+        assert!(app.results.iter().all(|item| item.arena == 0));
+
+        type_text(&mut app, "file1");
+        assert!(app.results.len() < TUI_MAX_RESULTS);
+        assert!(!app.truncated);
+    }
+
+    #[test]
+    fn browsing_drills_down_the_largest_entries_first() {
+        let mut app = App::new(vec![volume()]);
+        type_text(&mut app, "docs");
+
+        app.handle_key(key(KeyCode::Tab));
+        assert_eq!(app.mode, Mode::Browse);
+        let docs = app.current.unwrap();
+        assert_eq!(app.path(docs), Path::new("/data/docs"));
+        assert_eq!(app.usage(docs).unwrap().allocated, 1_011_712);
+        // Equal sizes are listed by name
+        assert_eq!(names(&app, &app.listing), ["big.iso", "old", "report.txt"]);
+
+        app.handle_key(key(KeyCode::Down));
+        app.handle_key(key(KeyCode::Enter));
+        assert_eq!(app.path(app.current.unwrap()), Path::new("/data/docs/old"));
+        assert_eq!(names(&app, &app.listing), ["notes.txt"]);
+
+        // Files are not entered
+        app.handle_key(key(KeyCode::Enter));
+        assert_eq!(app.path(app.current.unwrap()), Path::new("/data/docs/old"));
+
+        // Back up with the directory we came from selected
+        app.handle_key(key(KeyCode::Left));
+        assert_eq!(app.current, Some(docs));
+        assert_eq!(selected_name(&app).as_deref(), Some("old"));
+
+        app.handle_key(key(KeyCode::Backspace));
+        assert_eq!(app.path(app.current.unwrap()), Path::new("/data"));
+        assert_eq!(names(&app, &app.listing), ["docs", "photos", "readme.md"]);
+        app.handle_key(key(KeyCode::Left));
+        assert_eq!(app.current, None);
+        assert_eq!(names(&app, &app.listing), ["/data"]);
+
+        // Typing searches again, Tab on nothing selected browses where we were
+        type_text(&mut app, "x");
+        assert_eq!(app.mode, Mode::Search);
+        app.handle_key(key(KeyCode::Tab));
+        assert_eq!(app.mode, Mode::Browse);
+        assert_eq!(app.current, None);
+    }
+
+    #[test]
+    fn a_file_found_opens_in_its_directory() {
+        let mut app = App::new(vec![volume()]);
+        type_text(&mut app, "notes");
+        app.handle_key(key(KeyCode::Enter));
+
+        assert_eq!(app.mode, Mode::Browse);
+        assert_eq!(app.path(app.current.unwrap()), Path::new("/data/docs/old"));
+        assert_eq!(selected_name(&app).as_deref(), Some("notes.txt"));
+        assert_eq!(app.usage(app.selected().unwrap()).unwrap().allocated, 4_096);
+
+        // Esc goes back to the results, which kept the query
+        app.handle_key(key(KeyCode::Esc));
+        assert_eq!(app.mode, Mode::Search);
+        assert_eq!(names(&app, &app.results), ["notes.txt"]);
+    }
+
+    #[test]
+    fn keys_copy_reveal_and_quit() {
+        let mut app = App::new(vec![volume()]);
+        type_text(&mut app, "beach");
+
+        let path = PathBuf::from("/data/photos/beach.jpg");
+        assert_eq!(
+            app.handle_key(control('y')),
+            Some(Action::CopyPath(path.clone()))
+        );
+        assert_eq!(app.handle_key(control('o')), Some(Action::RevealPath(path)));
+        assert_eq!(app.query, "beach");
+
+        type_text(&mut app, "zz");
+        assert_eq!(app.handle_key(control('y')), None);
+        assert!(!app.quit);
+        assert_eq!(app.handle_key(control('c')), None);
+        assert!(app.quit);
+    }
+}
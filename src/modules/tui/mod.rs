@@ -0,0 +1,6 @@
+mod app;
+pub mod tui_impl;
+mod view;
+
+pub(crate) use tui_impl::default_index_path;
+pub(crate) use tui_impl::run_tui;
@@ -0,0 +1,100 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ratatui::crossterm::event::{self, Event, KeyEventKind};
+use ratatui::DefaultTerminal;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use crate::modules::errors::UFFSError;
+use crate::modules::path_arena::PathArena;
+use crate::modules::tui::app::{Action, App};
+use crate::modules::tui::view::draw;
+use crate::modules::utils::get_config_dir;
+
+pub(crate) const INDEX_FILE_NAME: &str = "index.db";
+
+/// The mlocate database the entries of the last `uffs tui` scan are kept in.
+pub(crate) fn default_index_path() -> Result<PathBuf, UFFSError> {
+    Ok(get_config_dir()?.join(INDEX_FILE_NAME))
+}
+
+/// Runs the full-screen browser over the scanned entries until the user quits.
+pub(crate) fn run_tui(arenas: Vec<PathArena>) -> Result<(), UFFSError> {
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, App::new(arenas));
+    ratatui::restore();
+    result
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, mut app: App) -> Result<(), UFFSError> {
+    while !app.quit {
+        terminal.draw(|frame| draw(frame, &mut app))?;
+
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        // Windows also reports key releases
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+
+        let status = match app.handle_key(key) {
+            Some(Action::CopyPath(path)) => match copy_to_clipboard(&path) {
+                Ok(()) => format!("Copied {}", path.display()),
+                Err(e) => format!("Could not copy the path: {}", e),
+            },
+            Some(Action::RevealPath(path)) => match reveal(&path) {
+                Ok(()) => format!("Opened the folder of {}", path.display()),
+                Err(e) => format!("Could not open the folder: {}", e),
+            },
+            None => continue,
+        };
+        app.status = Some(status);
+    }
+
+    Ok(())
+}
+
+// OSC 52 has the terminal set the clipboard, which also works over SSH
+fn copy_to_clipboard(path: &Path) -> io::Result<()> {
+    let text = path.to_string_lossy();
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", STANDARD.encode(text.as_bytes()))?;
+    stdout.flush()
+}
+
+// Opens the file manager on the folder containing `path`, with `path` selected where supported
+fn reveal(path: &Path) -> io::Result<()> {
+    let mut command = reveal_command(path);
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map(|_| ())
+}
+
+#[cfg(windows)]
+fn reveal_command(path: &Path) -> Command {
+    use std::os::windows::process::CommandExt;
+
+    // Explorer only understands the path quoted after the comma
+    let mut command = Command::new("explorer");
+    command.raw_arg(format!("/select,\"{}\"", path.display()));
+    command
+}
+
+#[cfg(target_os = "macos")]
+fn reveal_command(path: &Path) -> Command {
+    let mut command = Command::new("open");
+    command.arg("-R").arg(path);
+    command
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn reveal_command(path: &Path) -> Command {
+    let mut command = Command::new("xdg-open");
+    command.arg(path.parent().unwrap_or(path));
+    command
+}
@@ -0,0 +1,183 @@
+use chrono::{DateTime, Local};
+use ratatui::layout::{Constraint, Layout, Position, Rect};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, ListItem, Paragraph, Wrap};
+use ratatui::Frame;
+use std::borrow::Cow;
+use std::ffi::OsStr;
+use std::fs;
+
+use crate::config::constants::LOG_DATE_FORMAT;
+use crate::modules::output::{quote, Quoting};
+use crate::modules::tui::app::{App, Item, Mode};
+use crate::modules::utils::{format_memory, format_number};
+
+const HELP: &str = "type to search  ↑↓ select  Enter open  ←/Backspace up  Tab browse/search  \
+                    Ctrl+Y copy path  Ctrl+O open folder  Esc back/quit";
+
+pub(crate) fn draw(frame: &mut Frame, app: &mut App) {
+    let [search_area, main_area, footer_area] = Layout::vertical([
+        Constraint::Length(3),
+        Constraint::Min(1),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    let [list_area, preview_area] =
+        Layout::horizontal([Constraint::Percentage(65), Constraint::Percentage(35)])
+            .areas(main_area);
+
+    draw_search(frame, app, search_area);
+    draw_list(frame, app, list_area);
+    draw_preview(frame, app, preview_area);
+
+    let footer = match &app.status {
+        Some(status) => Line::from(status.as_str()).yellow(),
+        None => Line::from(HELP).dark_gray(),
+    };
+    frame.render_widget(footer, footer_area);
+}
+
+fn block(title: String, active: bool) -> Block<'static> {
+    let block = Block::bordered().title(title);
+    if active {
+        block.border_style(Style::new().fg(Color::Yellow))
+    } else {
+        block
+    }
+}
+
+fn draw_search(frame: &mut Frame, app: &App, area: Rect) {
+    let active = app.mode == Mode::Search;
+    let title = format!(" Search {} entries ", format_number(app.num_entries(), 0));
+    frame.render_widget(
+        Paragraph::new(app.query.as_str()).block(block(title, active)),
+        area,
+    );
+
+    if active {
+        let cursor = area.x + 1 + app.query.chars().count() as u16;
+        frame.set_cursor_position(Position::new(cursor.min(area.right() - 2), area.y + 1));
+    }
+}
+
+// Names that aren't valid Unicode or hold control characters are shown escaped
+fn shown(name: &OsStr) -> Cow<'_, str> {
+    quote(name, Quoting::Escape)
+}
+
+fn draw_list(frame: &mut Frame, app: &mut App, area: Rect) {
+    let title = match app.mode {
+        Mode::Search if app.truncated => format!(" First {} matches ", app.results.len()),
+        Mode::Search => format!(" {} matches ", app.results.len()),
+        Mode::Browse => match app.current {
+            Some(dir) => format!(" {} ", shown(app.path(dir).as_os_str())),
+            None => " Volumes ".to_string(),
+        },
+    };
+
+    let items: Vec<ListItem> = app
+        .items()
+        .iter()
+        .map(|&item| ListItem::new(list_line(app, item)))
+        .collect();
+    let list = List::new(items)
+        .block(block(title, app.mode == Mode::Browse))
+        .highlight_style(Style::new().reversed())
+        .highlight_symbol("> ");
+
+    frame.render_stateful_widget(list, area, &mut app.list_state);
+}
+
+// Search results show the full path, a browsed directory the names with their size
+fn list_line(app: &App, item: Item) -> Line<'static> {
+    let suffix = if app.is_dir(item) { "/" } else { "" };
+    match app.mode {
+        Mode::Search => Line::from(format!("{}{}", shown(app.path(item).as_os_str()), suffix)),
+        Mode::Browse => {
+            let size = app
+                .usage(item)
+                .map(|usage| format_memory(usage.allocated))
+                .unwrap_or_default();
+            let name = format!("{}{}", shown(app.name(item)), suffix);
+            let name = if app.is_dir(item) {
+                Span::from(name).bold().blue()
+            } else {
+                Span::from(name)
+            };
+            Line::from(vec![
+                Span::from(format!("{:>12}  ", size)).dark_gray(),
+                name,
+            ])
+        }
+    }
+}
+
+fn draw_preview(frame: &mut Frame, app: &App, area: Rect) {
+    let mut lines = vec![];
+
+    if let Some(item) = app.selected() {
+        let path = app.path(item);
+        let field = |name: &str, value: String| {
+            Line::from(vec![
+                Span::from(format!("{:<10}", name)).bold(),
+                Span::from(value),
+            ])
+        };
+
+        lines.push(field("Path", shown(path.as_os_str()).into_owned()));
+        lines.push(field(
+            "Type",
+            if app.is_dir(item) {
+                "Directory"
+            } else {
+                "File"
+            }
+            .to_string(),
+        ));
+
+        match fs::symlink_metadata(&path) {
+            Ok(metadata) => {
+                if metadata.is_symlink() {
+                    let target = fs::read_link(&path)
+                        .map(|target| shown(target.as_os_str()).into_owned())
+                        .unwrap_or_default();
+                    lines.push(field("Link to", target));
+                }
+                if !metadata.is_dir() {
+                    lines.push(field("Size", format!("{} bytes", metadata.len())));
+                }
+                if let Ok(modified) = metadata.modified() {
+                    let modified: DateTime<Local> = modified.into();
+                    lines.push(field(
+                        "Modified",
+                        modified.format(LOG_DATE_FORMAT).to_string(),
+                    ));
+                }
+                if metadata.permissions().readonly() {
+                    lines.push(field("Access", "read-only".to_string()));
+                }
+            }
+            Err(e) => lines.push(field("Error", e.to_string())),
+        }
+
+        if let Some(usage) = app.usage(item).filter(|_| app.is_dir(item)) {
+            lines.push(field(
+                "On disk",
+                format_memory(usage.allocated).trim().to_string(),
+            ));
+            lines.push(field(
+                "Contents",
+                format_memory(usage.size).trim().to_string(),
+            ));
+            lines.push(field("Entries", format_number(usage.entries as usize, 0)));
+        }
+    }
+
+    frame.render_widget(
+        Paragraph::new(lines)
+            .block(Block::bordered().title(" Details "))
+            .wrap(Wrap { trim: false }),
+        area,
+    );
+}
@@ -0,0 +1,6 @@
+mod tuned_settings;
+pub mod tuning_impl;
+
+pub(crate) use tuned_settings::TunedSettings;
+
+pub(crate) use tuning_impl::tune_volumes;
@@ -0,0 +1,129 @@
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+use crate::config::constants::LOG_DATE_FORMAT;
+use crate::modules::algo_selector::algo_selector_impl::same_root;
+use crate::modules::errors::UFFSError;
+use crate::modules::utils::get_config_dir;
+
+pub(crate) const TUNED_SETTINGS_FILE_NAME: &str = "tuning.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct VolumeTuning {
+    pub(crate) root_path: String,
+    pub(crate) worker_threads: usize,
+    pub(crate) blocking_threads: usize,
+    // Only for the readers that limit their in-flight reads
+    #[serde(default)]
+    pub(crate) max_concurrent_reads: Option<usize>,
+    pub(crate) seconds: f64,
+    pub(crate) timestamp: String,
+}
+
+/// Best settings found by `uffs tune`, per volume, persisted as JSON in the config directory.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct TunedSettings {
+    volumes: Vec<VolumeTuning>,
+}
+
+impl TunedSettings {
+    pub(crate) fn default_path() -> Result<PathBuf, UFFSError> {
+        Ok(get_config_dir()?.join(TUNED_SETTINGS_FILE_NAME))
+    }
+
+    /// Loads the settings from `path`. A missing or unreadable file means nothing is tuned yet.
+    pub(crate) fn load(path: &Path) -> TunedSettings {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(_) => return TunedSettings::default(),
+        };
+
+        serde_json::from_str(&content).unwrap_or_else(|e| {
+            warn!("Ignoring unreadable tuning settings {:?}: {}", path, e);
+            TunedSettings::default()
+        })
+    }
+
+    pub(crate) fn load_default() -> TunedSettings {
+        TunedSettings::default_path()
+            .map(|path| TunedSettings::load(&path))
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn save(&self, path: &Path) -> Result<(), UFFSError> {
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|e| UFFSError::ConfigError(e.to_string()))?;
+        fs::write(path, content)?;
+        Ok(())
+    }
+
+    pub(crate) fn set(
+        &mut self,
+        root_path: &Path,
+        worker_threads: usize,
+        blocking_threads: usize,
+        max_concurrent_reads: Option<usize>,
+        seconds: f64,
+    ) {
+        let root_path = root_path.to_string_lossy().into_owned();
+        self.volumes
+            .retain(|v| !same_root(&v.root_path, &root_path));
+        self.volumes.push(VolumeTuning {
+            root_path,
+            worker_threads,
+            blocking_threads,
+            max_concurrent_reads,
+            seconds,
+            timestamp: Local::now().format(LOG_DATE_FORMAT).to_string(),
+        });
+    }
+
+    pub(crate) fn get(&self, root_path: &str) -> Option<&VolumeTuning> {
+        self.volumes
+            .iter()
+            .find(|v| same_root(&v.root_path, root_path))
+    }
+
+    /// The thread counts of the runtime. All volumes are scanned on one runtime, so it gets the
+    /// largest counts tuned for any volume, and the volumes tuned for fewer threads run with
+    /// more than they were tuned for.
+    pub(crate) fn runtime_threads(&self) -> Option<(usize, usize)> {
+        let worker_threads = self.volumes.iter().map(|v| v.worker_threads).max()?;
+        let blocking_threads = self.volumes.iter().map(|v| v.blocking_threads).max()?;
+        Some((worker_threads, blocking_threads))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(TUNED_SETTINGS_FILE_NAME);
+
+        let mut settings = TunedSettings::default();
+        settings.set(Path::new("/"), 8, 16, Some(64), 1.5);
+        settings.set(Path::new("/data"), 4, 32, None, 2.0);
+        settings.save(&path).unwrap();
+
+        let settings = TunedSettings::load(&path);
+        assert_eq!(settings.get("/").unwrap().max_concurrent_reads, Some(64));
+        assert_eq!(settings.get("/data").unwrap().max_concurrent_reads, None);
+        assert_eq!(settings.runtime_threads(), Some((8, 32)));
+    }
+
+    #[test]
+    fn volumes_are_tuned_once() {
+        let mut settings = TunedSettings::default();
+        settings.set(Path::new("/"), 8, 16, Some(64), 1.5);
+        settings.set(Path::new("/"), 2, 4, None, 1.0);
+
+        assert_eq!(settings.volumes.len(), 1);
+        assert_eq!(settings.runtime_threads(), Some((2, 4)));
+    }
+}
@@ -0,0 +1,215 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+use crate::config::constants::{
+    MAX_CONCURRENT_READS, MIN_TUNING_CONCURRENT_READS, MIN_TUNING_THREADS,
+};
+use crate::modules::algo_selector::algo_selector_impl::{same_root, DEFAULT_READER};
+use crate::modules::algo_selector::{
+    select_algorithm, BenchmarkHistory, ReaderOverrides, ReaderRegistry, SharedDirectoryReader,
+};
+use crate::modules::directory_reader::ListingSink;
+use crate::modules::disk_reader::{discover_drives, list_files_and_dirs, DriveType};
+use crate::modules::errors::UFFSError;
+use crate::modules::runtime::build_runtime;
+use crate::modules::tuning::TunedSettings;
+use crate::modules::utils::{format_duration, optimize_parameter, set_threads_count};
+
+pub(crate) fn run_with_configuration(
+    worker_threads: usize,
+    blocking_threads: usize,
+    root_path: &Path,
+    disk_info: &[(String, DriveType, u64)],
+    directory_reader: SharedDirectoryReader,
+) -> Duration {
+    // Configure Tokio runtime with the settings under test
+    let runtime = build_runtime(worker_threads, blocking_threads);
+
+    runtime.block_on(async {
+        let start = Instant::now();
+        list_files_and_dirs(
+            root_path.to_path_buf(),
+            disk_info,
+            directory_reader,
+            &ListingSink::default(),
+        )
+        .await;
+        start.elapsed()
+    })
+}
+
+pub(crate) fn find_best_configuration<F>(
+    configurations: Vec<(usize, usize)>,
+    run_with_configuration: F,
+) -> (usize, usize)
+where
+    F: Fn(usize, usize) -> Duration,
+{
+    let mut best_duration = Duration::MAX;
+    let mut best_config = (0, 0);
+
+    for (worker_threads, blocking_threads) in configurations {
+        let duration = run_with_configuration(worker_threads, blocking_threads);
+        info!(
+            "Configuration with {} worker threads and {} blocking threads took {}",
+            worker_threads,
+            blocking_threads,
+            format_duration(duration)
+        );
+
+        if duration < best_duration {
+            best_duration = duration;
+            best_config = (worker_threads, blocking_threads);
+        }
+    }
+
+    best_config
+}
+
+/// Searches the worker threads, blocking threads and concurrent reads that scan each volume the
+/// fastest and saves them. An empty `roots` tunes every mounted volume.
+///
+/// Every step of the search is a full scan of the volume, so tuning a large volume takes a while.
+pub(crate) fn tune_volumes(
+    roots: &[PathBuf],
+    registry: &ReaderRegistry,
+    overrides: &ReaderOverrides,
+    history: &BenchmarkHistory,
+) -> Result<TunedSettings, UFFSError> {
+    let settings_path = TunedSettings::default_path()?;
+    let mut settings = TunedSettings::load(&settings_path);
+
+    let default_reader = registry.get(DEFAULT_READER)?;
+    let drives: Vec<_> = discover_drives(history, &default_reader)
+        .into_iter()
+        .filter(|drive| {
+            roots.is_empty()
+                || roots.iter().any(|root| {
+                    same_root(&root.to_string_lossy(), &drive.root_path.to_string_lossy())
+                })
+        })
+        .collect();
+
+    for root in roots {
+        if !drives
+            .iter()
+            .any(|d| same_root(&d.root_path.to_string_lossy(), &root.to_string_lossy()))
+        {
+            warn!("{} is not a mounted volume, skipping it", root.display());
+        }
+    }
+
+    for drive in &drives {
+        let directory_reader = select_algorithm(drive, registry, history, overrides)?;
+        let disk_info = vec![(
+            drive.root_path.to_string_lossy().into_owned(),
+            drive.drive_type,
+            (drive.size_gb * 1_073_741_824.0) as u64,
+        )];
+
+        let scan = |worker_threads: usize,
+                    blocking_threads: usize,
+                    directory_reader: SharedDirectoryReader| {
+            run_with_configuration(
+                worker_threads,
+                blocking_threads,
+                &drive.root_path,
+                &disk_info,
+                directory_reader,
+            )
+        };
+
+        info!(
+            "Tuning {} with reader '{}'",
+            drive.root_path.display(),
+            directory_reader.name()
+        );
+
+        // Warm up the file system cache, otherwise the first measurement is off
+        let (default_worker_threads, default_blocking_threads) = set_threads_count();
+        scan(
+            default_worker_threads,
+            default_blocking_threads,
+            Arc::clone(&directory_reader),
+        );
+
+        let worker_threads = optimize_parameter(MIN_TUNING_THREADS, |worker_threads| {
+            scan(
+                worker_threads,
+                worker_threads * 2,
+                Arc::clone(&directory_reader),
+            );
+            Ok(())
+        });
+
+        let blocking_threads = optimize_parameter(worker_threads, |blocking_threads| {
+            scan(
+                worker_threads,
+                blocking_threads.max(1),
+                Arc::clone(&directory_reader),
+            );
+            Ok(())
+        })
+        .max(1);
+
+        // The search can get lost in the noise, keep the defaults unless the result beats them
+        let (worker_threads, blocking_threads) = find_best_configuration(
+            vec![
+                (default_worker_threads, default_blocking_threads),
+                (worker_threads, blocking_threads),
+            ],
+            |worker_threads, blocking_threads| {
+                scan(
+                    worker_threads,
+                    blocking_threads,
+                    Arc::clone(&directory_reader),
+                )
+            },
+        );
+
+        // Only the readers that limit their in-flight reads, the semaphore reader, get a limit
+        let limited =
+            |max_concurrent_reads| directory_reader.with_max_concurrent_reads(max_concurrent_reads);
+        let max_concurrent_reads = limited(MAX_CONCURRENT_READS).is_some().then(|| {
+            optimize_parameter(MIN_TUNING_CONCURRENT_READS, |max_concurrent_reads| {
+                if let Some(directory_reader) = limited(max_concurrent_reads) {
+                    scan(worker_threads, blocking_threads, directory_reader);
+                }
+                Ok(())
+            })
+            .max(1)
+        });
+
+        let duration = scan(
+            worker_threads,
+            blocking_threads,
+            max_concurrent_reads
+                .and_then(limited)
+                .unwrap_or_else(|| Arc::clone(&directory_reader)),
+        );
+
+        println!(
+            "Tuned {}: worker threads {}, blocking threads {}, concurrent reads {} ({})",
+            drive.root_path.display(),
+            worker_threads,
+            blocking_threads,
+            max_concurrent_reads.map_or("unlimited".to_string(), |max| max.to_string()),
+            format_duration(duration)
+        );
+
+        settings.set(
+            &drive.root_path,
+            worker_threads,
+            blocking_threads,
+            max_concurrent_reads,
+            duration.as_secs_f64(),
+        );
+
+        // Save after every volume so an interrupted run keeps what it found
+        settings.save(&settings_path)?;
+    }
+
+    Ok(settings)
+}
@@ -0,0 +1,7 @@
+pub mod usage_impl;
+
+pub(crate) use usage_impl::listed_usage;
+pub(crate) use usage_impl::usage_rows;
+pub(crate) use usage_impl::Usage;
+pub(crate) use usage_impl::UsageOptions;
+pub(crate) use usage_impl::UsageRow;
@@ -0,0 +1,272 @@
+use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::modules::file_system::entry_size;
+use crate::modules::output::{raw_units, serialize_lossy};
+use crate::modules::path_arena::{EntryId, EntrySize, PathArena};
+use crate::modules::raw_path::raw_path_impl::PathUnit;
+
+/// What `uffs usage` reports: the `top` largest directories and files of every volume, or with
+/// `depth` every directory down to that depth, like `du -d`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct UsageOptions {
+    pub(crate) top: usize,
+    pub(crate) depth: Option<usize>,
+}
+
+/// Recursive usage of an entry. For a file its own sizes and no entries.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Usage {
+    // Apparent size, what `du --apparent-size` shows
+    pub(crate) size: u64,
+    // Space taken on disk, what `du` shows
+    pub(crate) allocated: u64,
+    // Files and directories below a directory
+    pub(crate) entries: u64,
+}
+
+impl Usage {
+    fn add(&mut self, child: &Usage) {
+        self.size += child.size;
+        self.allocated += child.allocated;
+        self.entries += child.entries + 1;
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct UsageRow {
+    // "largest_dirs", "largest_files" or "depth"
+    pub(crate) section: &'static str,
+    pub(crate) volume: String,
+    #[serde(serialize_with = "serialize_lossy")]
+    pub(crate) path: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) path_raw: Option<Vec<PathUnit>>,
+    #[serde(rename = "type")]
+    pub(crate) kind: &'static str,
+    pub(crate) size_bytes: u64,
+    pub(crate) allocated_bytes: u64,
+    pub(crate) entries: u64,
+}
+
+/// Sums the sizes of every directory in `arena` over everything below it, indexed by entry id.
+/// The sizes are those the scan listed. The entries listed without, the roots and those of an
+/// index, are measured on disk, those that vanished since count as empty.
+pub(crate) fn aggregate_usage(arena: &PathArena) -> Vec<Usage> {
+    let unlisted: Vec<EntryId> = (0..arena.len() as EntryId)
+        .filter(|&id| arena.size(id).is_none())
+        .collect();
+    let measured: HashMap<EntryId, EntrySize> = unlisted
+        .par_iter()
+        .filter_map(|&id| {
+            let path = arena.path(id);
+            let metadata = fs::symlink_metadata(&path).ok()?;
+            Some((id, entry_size(&path, &metadata)))
+        })
+        .collect();
+
+    sum_usage(arena, &measured)
+}
+
+/// Like `aggregate_usage` with the listed sizes only, the disk is left alone. The entries listed
+/// without count as empty.
+pub(crate) fn listed_usage(arena: &PathArena) -> Vec<Usage> {
+    sum_usage(arena, &HashMap::new())
+}
+
+fn sum_usage(arena: &PathArena, measured: &HashMap<EntryId, EntrySize>) -> Vec<Usage> {
+    let mut totals = vec![Usage::default(); arena.len()];
+    let mut seen_links = HashSet::new();
+    for id in 0..arena.len() as EntryId {
+        let Some(size) = arena.size(id).or_else(|| measured.get(&id).copied()) else {
+            continue;
+        };
+        // Only the first link found to a file counts
+        if size.link.is_some_and(|link| !seen_links.insert(link)) {
+            continue;
+        }
+        totals[id as usize].size = size.len;
+        totals[id as usize].allocated = size.allocated;
+    }
+
+    // Children always come after their parent, so walking backwards adds up every subtree
+    // before it is added to its parent
+    for id in (0..arena.len() as EntryId).rev() {
+        if let Some(parent) = arena.parent(id) {
+            let child = totals[id as usize];
+            totals[parent as usize].add(&child);
+        }
+    }
+
+    totals
+}
+
+fn depths(arena: &PathArena) -> Vec<usize> {
+    let mut depths = vec![0; arena.len()];
+    for id in 0..arena.len() as EntryId {
+        if let Some(parent) = arena.parent(id) {
+            depths[id as usize] = depths[parent as usize] + 1;
+        }
+    }
+    depths
+}
+
+// The `n` ids with the most allocated space, largest first
+fn largest(mut ids: Vec<EntryId>, n: usize, totals: &[Usage]) -> Vec<EntryId> {
+    let allocated = |id: &EntryId| std::cmp::Reverse(totals[*id as usize].allocated);
+    if n < ids.len() {
+        ids.select_nth_unstable_by_key(n, allocated);
+        ids.truncate(n);
+    }
+    ids.sort_unstable_by_key(allocated);
+    ids
+}
+
+/// The usage report of every volume in `arenas`.
+pub(crate) fn usage_rows(arenas: &[PathArena], options: &UsageOptions) -> Vec<UsageRow> {
+    let mut rows = vec![];
+
+    for arena in arenas {
+        let Some(root) = arena.roots().next() else {
+            continue;
+        };
+        let volume = arena.path(root).to_string_lossy().into_owned();
+        let totals = aggregate_usage(arena);
+
+        let row = |section: &'static str, id: EntryId| {
+            let usage = &totals[id as usize];
+            let path = arena.path(id);
+            UsageRow {
+                section,
+                volume: volume.clone(),
+                path_raw: raw_units(&path),
+                path,
+                kind: if arena.is_dir(id) || id == root {
+                    "dir"
+                } else {
+                    "file"
+                },
+                size_bytes: usage.size,
+                allocated_bytes: usage.allocated,
+                entries: usage.entries,
+            }
+        };
+
+        match options.depth {
+            Some(max_depth) => {
+                let depths = depths(arena);
+                let mut summary: Vec<UsageRow> = arena
+                    .roots()
+                    .chain(arena.dirs().iter().copied())
+                    .filter(|&id| depths[id as usize] <= max_depth)
+                    .map(|id| row("depth", id))
+                    .collect();
+                // Component-wise, so a directory is followed by its subdirectories
+                summary.sort_by(|a, b| a.path.cmp(&b.path));
+                rows.extend(summary);
+            }
+            None => {
+                for id in largest(arena.dirs().to_vec(), options.top, &totals) {
+                    rows.push(row("largest_dirs", id));
+                }
+                for id in largest(arena.files().to_vec(), options.top, &totals) {
+                    rows.push(row("largest_files", id));
+                }
+            }
+        }
+    }
+
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::algo_selector::{ReaderOptions, ReaderRegistry};
+    use crate::modules::disk_reader::read_tree;
+    use std::path::Path;
+
+    fn size(len: u64, link: Option<(u64, u64)>) -> EntrySize {
+        EntrySize {
+            len,
+            allocated: len.div_ceil(4096) * 4096,
+            link,
+        }
+    }
+
+    #[test]
+    fn listed_sizes_are_summed_and_links_counted_once() {
+        // Not on disk, the listed sizes are all there is
+        let mut arena = PathArena::new();
+        let root = arena.add_root(Path::new("/nowhere"));
+        arena.set_size(root, size(0, None));
+        let docs = arena.add_dir(root, "docs".as_ref());
+        arena.set_size(docs, size(0, None));
+        let report = arena.add_file(docs, "report.pdf".as_ref());
+        arena.set_size(report, size(5000, Some((1, 7))));
+        let link = arena.add_file(root, "report link".as_ref());
+        arena.set_size(link, size(5000, Some((1, 7))));
+        let notes = arena.add_file(root, "notes.txt".as_ref());
+        arena.set_size(notes, size(10, None));
+
+        let totals = aggregate_usage(&arena);
+        assert_eq!(totals[docs as usize].size, 5000);
+        assert_eq!(totals[docs as usize].allocated, 8192);
+        assert_eq!(totals[docs as usize].entries, 1);
+        assert_eq!(totals[link as usize].size, 0);
+        assert_eq!(totals[root as usize].size, 5010);
+        assert_eq!(totals[root as usize].allocated, 8192 + 4096);
+        assert_eq!(totals[root as usize].entries, 4);
+    }
+
+    #[test]
+    fn only_the_unlisted_sizes_are_measured() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("unlisted"), b"on disk").unwrap();
+        let mut arena = PathArena::new();
+        let root = arena.add_root(dir.path());
+        arena.set_size(root, size(0, None));
+        let unlisted = arena.add_file(root, "unlisted".as_ref());
+
+        assert_eq!(aggregate_usage(&arena)[unlisted as usize].size, 7);
+        assert_eq!(listed_usage(&arena)[unlisted as usize].size, 0);
+    }
+
+    #[tokio::test]
+    async fn readers_list_the_sizes() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        fs::create_dir(root.join("docs")).unwrap();
+        fs::write(root.join("docs").join("report.pdf"), vec![0; 10_000]).unwrap();
+        fs::hard_link(root.join("docs").join("report.pdf"), root.join("link")).unwrap();
+        fs::write(root.join("notes.txt"), b"notes").unwrap();
+
+        let registry = ReaderRegistry::new(ReaderOptions {
+            sizes: true,
+            ..ReaderOptions::default()
+        });
+        for name in registry.names() {
+            let (arena, _, _) = read_tree(root, registry.get(name).unwrap().as_ref()).await;
+            assert_eq!(arena.num_files(), 3, "{}", name);
+            for &id in arena.files().iter().chain(arena.dirs()) {
+                let listed = arena.size(id).unwrap();
+                let metadata = fs::symlink_metadata(arena.path(id)).unwrap();
+                assert_eq!(listed.len, metadata.len(), "{}", name);
+            }
+
+            let totals = aggregate_usage(&arena);
+            let root_id = arena.roots().next().unwrap();
+            let dirs: u64 = arena
+                .dirs()
+                .iter()
+                .chain([&root_id])
+                .map(|&id| fs::symlink_metadata(arena.path(id)).unwrap().len())
+                .sum();
+            // The second link to the report takes no space of its own
+            assert_eq!(totals[root_id as usize].size, dirs + 10_005, "{}", name);
+        }
+    }
+}
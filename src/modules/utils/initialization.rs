@@ -1,20 +1,36 @@
-use crate::config::worker_threads::CURRENT_BLOCKING_THREADS;
-use crate::config::worker_threads::CURRENT_WORKER_THREADS;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::slice;
+use std::time::Instant;
 
-use std::sync::RwLock;
-
-use crate::config::{BLOCKING_THREADS, WORKER_THREADS};
+use crate::config::{UserConfig, BLOCKING_THREADS, WORKER_THREADS};
+use crate::modules::algo_selector::{
+    BenchmarkHistory, ReaderOptions, ReaderOverrides, ReaderRegistry,
+};
+use crate::modules::cli::{Cli, Command};
+use crate::modules::daemon::{daemon_arenas, daemon_scan, run_daemon_command, search_daemon};
+use crate::modules::disk_reader::{export_entries, render_scan};
+use crate::modules::efu::{write_efu, EfuList};
+use crate::modules::errors::UFFSError;
+use crate::modules::ignore_filter::IgnoreFilter;
+use crate::modules::locate::{refresh_index, LocateDb};
 use crate::modules::logger::init_logger;
+use crate::modules::output::{configure_colors, write_entries, OutputOptions, SearchOptions};
 use crate::modules::process::run_directory_processing;
 use crate::modules::runtime::build_runtime;
-use crate::modules::utils::{format_duration, get_number_of_cpu_cores};
-use tracing::{debug, error, info};
+use crate::modules::tree_gen::TreeSpec;
+use crate::modules::tui::{default_index_path, run_tui};
+use crate::modules::tuning::{tune_volumes, TunedSettings};
+use crate::modules::usage::UsageOptions;
+use crate::modules::utils::{format_duration, format_memory, get_number_of_cpu_cores};
+use crate::modules::verify::{memory_readers, verify_generated, verify_root};
+use tracing::{debug, error, info, warn};
 
 pub fn initialize_app() {
     let _guard = init_logger();
 
     info!("Application started...");
-
 }
 
 pub fn set_threads_count() -> (usize, usize) {
@@ -25,9 +41,423 @@ pub fn set_threads_count() -> (usize, usize) {
     (worker_threads, blocking_threads)
 }
 
-pub fn run_app() {
+fn load_user_config(cli: &Cli) -> Result<UserConfig, UFFSError> {
+    match &cli.config {
+        Some(path) if !path.exists() => Err(UFFSError::ConfigError(format!(
+            "Config file not found: {}",
+            path.display()
+        ))),
+        Some(path) => UserConfig::load(path),
+        None => UserConfig::load(&UserConfig::default_path()?),
+    }
+}
+
+// The ignore options of the config file with those of the command line added
+fn ignore_filter(cli: &Cli, config: &UserConfig) -> Result<IgnoreFilter, UFFSError> {
+    let mut options = config.ignore.clone();
+    options.ignore_files |= cli.ignore_files;
+    options.skip_hidden |= cli.skip_hidden;
+    options.exclude.extend(cli.exclude.iter().cloned());
+    IgnoreFilter::new(&options)
+        .map_err(|e| UFFSError::ConfigError(format!("Invalid exclude pattern: {}", e)))
+}
+
+// Searches an imported file list, the matches can be exported to another list with `--efu`
+fn search_efu(file: &Path, pattern: &str, output: &OutputOptions) {
+    let list = match EfuList::load(file) {
+        Ok(list) => list,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+    info!("Loaded {} entries from {}", list.len(), file.display());
+
+    let mut entries = list.search(pattern).map(|entry| {
+        (
+            PathBuf::from(&entry.path),
+            if entry.is_dir() { "dir" } else { "file" },
+        )
+    });
+    write_entries(output, &mut entries);
+
+    if let Some(path) = &output.efu {
+        if let Err(e) = write_efu(path, list.search(pattern)) {
+            error!("Failed to export {}: {}", path.display(), e);
+        }
+    }
+}
+
+// Searches a database written by `updatedb`, as a replacement for `locate`
+fn search_locate(file: &Path, pattern: &str, output: &OutputOptions) {
+    let database = match LocateDb::load(file) {
+        Ok(database) => database,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+    info!("Loaded {} entries from {}", database.len(), file.display());
+
+    write_entries(output, &mut database.search(pattern));
+}
+
+// Writes a synthetic tree to benchmark the readers on
+fn generate_tree(dir: &Path, spec: Option<&Path>, seed: Option<u64>) {
+    let spec = match spec
+        .map(TreeSpec::load)
+        .unwrap_or_else(|| Ok(TreeSpec::default()))
+    {
+        Ok(spec) => spec,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+    let spec = match seed {
+        Some(seed) => spec.with_seed(seed),
+        None => spec,
+    };
+
+    let start = Instant::now();
+    match spec.generate().write_to(dir) {
+        Ok(stats) => info!(
+            "Created {} directories, {} files, {} symlinks and {} hard links of {} in {} in {}",
+            stats.dirs,
+            stats.files,
+            stats.symlinks,
+            stats.hard_links,
+            format_memory(stats.bytes).trim(),
+            dir.display(),
+            format_duration(start.elapsed())
+        ),
+        Err(e) => error!("Failed to create the tree in {}: {}", dir.display(), e),
+    }
+}
+
+enum VerifyTarget {
+    Roots(Vec<PathBuf>),
+    Generated {
+        count: u64,
+        spec: Option<PathBuf>,
+        faults: f64,
+    },
+}
+
+// Compares the readers and prints the reports, true when they all agree
+fn verify(
+    target: VerifyTarget,
+    names: &[String],
+    registry: &ReaderRegistry,
+    ignore: &IgnoreFilter,
+    output: &OutputOptions,
+) -> Result<bool, UFFSError> {
+    let runtime = build_runtime(WORKER_THREADS, BLOCKING_THREADS);
+    let reports = match target {
+        VerifyTarget::Roots(roots) => {
+            let names = match names {
+                [] => registry.names(),
+                names => names.iter().map(String::as_str).collect(),
+            };
+            let readers = names
+                .into_iter()
+                .map(|name| registry.get(name))
+                .collect::<Result<Vec<_>, _>>()?;
+            runtime.block_on(async {
+                let mut reports = vec![];
+                for root in &roots {
+                    reports.push(verify_root(root, &readers, ignore).await);
+                }
+                reports
+            })
+        }
+        VerifyTarget::Generated {
+            count,
+            spec,
+            faults,
+        } => {
+            let readers = memory_readers(names)?;
+            let spec = match spec {
+                Some(path) => TreeSpec::load(&path)?,
+                None => TreeSpec::default(),
+            };
+            runtime.block_on(verify_generated(&spec, 0..count, &readers, ignore, faults))
+        }
+    };
+
+    let mut stdout = io::stdout().lock();
+    for report in &reports {
+        report.write(&mut stdout, output.quoting)?;
+    }
+    Ok(reports.iter().all(|report| report.is_consistent()))
+}
+
+// Updates an index in place, the directories unchanged since it was written keep their entries
+fn refresh_locate(
+    index: &Path,
+    registry: &ReaderRegistry,
+    ignore: &IgnoreFilter,
+    output: &OutputOptions,
+) {
+    if !index.exists() {
+        error!(
+            "No index at {}, write one with `uffs --mlocate {}` first",
+            index.display(),
+            index.display()
+        );
+        return;
+    }
+    let previous = match LocateDb::load(index) {
+        Ok(previous) => previous,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+
+    let runtime = build_runtime(WORKER_THREADS, BLOCKING_THREADS);
+    let fs = registry.file_system();
+    let (refreshed, stats) = runtime.block_on(refresh_index(&previous, &fs, ignore));
+    info!(
+        "Read {} directories again and kept {} unchanged in {}",
+        stats.dirs_read,
+        stats.dirs_unchanged,
+        format_duration(stats.duration)
+    );
+    match refreshed.write(index) {
+        Ok(written) => info!("Updated {} with {} entries", index.display(), written),
+        Err(e) => error!("Failed to update {}: {}", index.display(), e),
+    }
+
+    if output.list_entries {
+        write_entries(output, &mut refreshed.search(""));
+    }
+    export_entries(output, slice::from_ref(refreshed.arena()));
+    for path in [&output.sqlite, &output.parquet].into_iter().flatten() {
+        warn!(
+            "{} is only written by a scan, the index has no sizes or times",
+            path.display()
+        );
+    }
+}
+
+// Reports and exports the volumes from the index of the running daemon. False when there is
+// none and the volumes have to be scanned.
+fn serve_from_daemon(socket: Option<&Path>, volumes: &[PathBuf], output: &OutputOptions) -> bool {
+    match daemon_scan(socket, volumes) {
+        Some(scan) => {
+            render_scan(scan, output);
+            true
+        }
+        None => false,
+    }
+}
+
+pub fn run_app(cli: Cli) {
+    configure_colors(cli.format);
+    let mut output = OutputOptions {
+        format: cli.format,
+        quoting: cli.quoting,
+        list_entries: cli.list,
+        efu: cli.efu.clone(),
+        mlocate: cli.mlocate.clone(),
+        plocate: cli.plocate.clone(),
+        parquet: cli.parquet.clone(),
+        sqlite: cli.sqlite.clone(),
+        usage: None,
+        interactive: false,
+        watch: None,
+        serve: false,
+        socket: cli.socket.clone(),
+        search: None,
+    };
+
+    let config = match load_user_config(&cli) {
+        Ok(config) => config,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+
+    let ignore = match ignore_filter(&cli, &config) {
+        Ok(ignore) => ignore,
+        Err(e) => {
+            error!("{}", e);
+            return;
+        }
+    };
+    let registry = ReaderRegistry::new(ReaderOptions {
+        adaptive_concurrency: cli.adaptive_concurrency || config.readers.adaptive_concurrency,
+        // The reports on the space taken, the terminal UI shows it too. The SQLite and Parquet
+        // exports take the sizes and times from the listings.
+        sizes: matches!(
+            cli.command,
+            Some(Command::Usage { .. } | Command::Tui { .. })
+        ) || cli.sqlite.is_some()
+            || cli.parquet.is_some(),
+        ignore: ignore.clone(),
+    });
+    let overrides = ReaderOverrides::new(&config, &cli.reader_overrides);
+    if let Err(e) = overrides.validate(&registry) {
+        error!("{}", e);
+        return;
+    }
+
+    // The index of a running daemon is current and has the entries any reader would find, unless
+    // a reader is picked or entries are ignored. It has no sizes or times to export.
+    let use_daemon = cli.reader_overrides.is_empty()
+        && !ignore.is_active()
+        && cli.sqlite.is_none()
+        && cli.parquet.is_none();
+
+    // Volumes to scan, all if empty
+    let mut volumes = vec![];
+    match cli.command.unwrap_or_default() {
+        Command::Scan => {
+            if use_daemon && serve_from_daemon(cli.socket.as_deref(), &volumes, &output) {
+                return;
+            }
+        }
+        Command::Usage { roots, top, depth } => {
+            output.usage = Some(UsageOptions { top, depth });
+            volumes = roots;
+            if use_daemon && serve_from_daemon(cli.socket.as_deref(), &volumes, &output) {
+                return;
+            }
+        }
+        Command::Readers => {
+            for name in registry.names() {
+                println!("{}", name);
+            }
+            return;
+        }
+        Command::Tune { roots } => {
+            let history = BenchmarkHistory::default_path()
+                .map(|path| BenchmarkHistory::load(&path))
+                .unwrap_or_default();
+            if let Err(e) = tune_volumes(&roots, &registry, &overrides, &history) {
+                error!("Tuning failed: {}", e);
+            }
+            return;
+        }
+        Command::Efu { file, pattern } => {
+            search_efu(&file, pattern.as_deref().unwrap_or(""), &output);
+            return;
+        }
+        Command::Tui { index, rescan } => {
+            let index = match index.map(Ok).unwrap_or_else(default_index_path) {
+                Ok(index) => index,
+                Err(e) => {
+                    error!("{}", e);
+                    return;
+                }
+            };
+
+            if !rescan && use_daemon {
+                if let Some(arenas) = daemon_arenas(cli.socket.as_deref()) {
+                    if let Err(e) = run_tui(arenas) {
+                        error!("{}", e);
+                    }
+                    return;
+                }
+            }
+            if !rescan && index.exists() {
+                match LocateDb::load(&index) {
+                    Ok(database) => {
+                        if let Err(e) = run_tui(vec![database.into_arena()]) {
+                            error!("{}", e);
+                        }
+                        return;
+                    }
+                    Err(e) => warn!("Could not open the index, scanning instead: {}", e),
+                }
+            }
+
+            // The scan is kept as index for the next start
+            output.interactive = true;
+            output.mlocate = Some(index);
+        }
+        Command::Watch { roots, index } => {
+            match index.map(Ok).unwrap_or_else(default_index_path) {
+                Ok(index) => output.watch = Some(index),
+                Err(e) => {
+                    error!("{}", e);
+                    return;
+                }
+            }
+            volumes = roots;
+        }
+        Command::Refresh { index } => {
+            match index.map(Ok).unwrap_or_else(default_index_path) {
+                Ok(index) => refresh_locate(&index, &registry, &ignore, &output),
+                Err(e) => error!("{}", e),
+            }
+            return;
+        }
+        Command::Serve { roots, index } => {
+            match index.map(Ok).unwrap_or_else(default_index_path) {
+                Ok(index) => output.watch = Some(index),
+                Err(e) => {
+                    error!("{}", e);
+                    return;
+                }
+            }
+            output.serve = true;
+            volumes = roots;
+        }
+        Command::Search { pattern, limit } => {
+            // Answered from the index of the daemon in milliseconds, a scan otherwise
+            if search_daemon(cli.socket.as_deref(), &pattern, limit, &output) {
+                return;
+            }
+            output.search = Some(SearchOptions { pattern, limit });
+        }
+        Command::Daemon { command } => {
+            if let Err(e) = run_daemon_command(cli.socket.as_deref(), command) {
+                error!("{}", e);
+            }
+            return;
+        }
+        Command::Locate { file, pattern } => {
+            search_locate(&file, pattern.as_deref().unwrap_or(""), &output);
+            return;
+        }
+        Command::GenTree { dir, spec, seed } => {
+            generate_tree(&dir, spec.as_deref(), seed);
+            return;
+        }
+        Command::Verify {
+            roots,
+            readers,
+            generated,
+            spec,
+            faults,
+        } => {
+            let target = match generated {
+                Some(count) => VerifyTarget::Generated {
+                    count,
+                    spec,
+                    faults,
+                },
+                None => VerifyTarget::Roots(roots),
+            };
+            match verify(target, &readers, &registry, &ignore, &output) {
+                Ok(true) => {}
+                Ok(false) => exit(1),
+                Err(e) => {
+                    error!("{}", e);
+                    exit(2);
+                }
+            }
+            return;
+        }
+    }
 
-    let (worker_threads, blocking_threads) = set_threads_count();
+    let tuned_settings = TunedSettings::load_default();
+    let (worker_threads, blocking_threads) = tuned_settings
+        .runtime_threads()
+        .unwrap_or_else(set_threads_count);
     debug!(
         "Running with {} worker threads and {} blocking threads",
         worker_threads, blocking_threads
@@ -45,8 +475,12 @@ pub fn run_app() {
 
     // Run the async function using the configured runtime
     runtime.block_on(async {
-        let time_used = run_directory_processing().await;
-        info!("Time used: {:?}", format_duration(time_used));
+        match run_directory_processing(&registry, &overrides, &tuned_settings, &volumes, &output)
+            .await
+        {
+            Ok(time_used) => info!("Time used: {:?}", format_duration(time_used)),
+            Err(e) => error!("{}", e),
+        }
     });
 
     info!("Application finished.");
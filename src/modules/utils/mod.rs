@@ -3,47 +3,23 @@ pub mod temp_files_dirs_impl;
 pub mod tree_printer;
 pub mod utils_impl;
 
-pub(crate) use initialization::initialize_app;
-pub(crate) use initialization::run_app;
 pub(crate) use initialization::set_threads_count;
 
-pub(crate) use temp_files_dirs_impl::create_file;
-pub(crate) use temp_files_dirs_impl::create_file_async;
-pub(crate) use temp_files_dirs_impl::create_temp_dir_with_files_hdd;
-pub(crate) use temp_files_dirs_impl::create_temp_dir_with_files_hdd_tokio;
-pub(crate) use temp_files_dirs_impl::create_temp_dir_with_files_ssd;
-pub(crate) use temp_files_dirs_impl::UffsTempDir;
-
 pub use tree_printer::print_directory_tree;
-pub(crate) use tree_printer::print_directory_tree_recursive;
 
-pub(crate) use utils_impl::add_wildcard;
-pub(crate) use utils_impl::count_disk_entries;
+#[cfg(windows)]
 pub(crate) use utils_impl::count_disk_entries_all_at_once;
-pub(crate) use utils_impl::count_disk_entries_all_at_once_new;
-pub(crate) use utils_impl::create_full_path;
 pub(crate) use utils_impl::format_duration;
+pub(crate) use utils_impl::format_memory;
 pub(crate) use utils_impl::format_number;
 pub(crate) use utils_impl::format_size;
-pub(crate) use utils_impl::generate_fibonacci;
+pub(crate) use utils_impl::get_config_dir;
 pub(crate) use utils_impl::get_drive_letter;
 pub(crate) use utils_impl::get_number_of_cpu_cores;
-pub(crate) use utils_impl::get_path;
-pub(crate) use utils_impl::get_root_path;
-#[cfg(target_family = "unix")]
-pub(crate) use utils_impl::get_unix_path;
+#[cfg(windows)]
 pub(crate) use utils_impl::handle_find_error;
+#[cfg(windows)]
 pub(crate) use utils_impl::handle_find_error_for_reader;
-pub(crate) use utils_impl::hello;
-pub(crate) use utils_impl::measure_time_normal;
-pub(crate) use utils_impl::measure_time_normal_bench;
-pub(crate) use utils_impl::measure_time_tokio;
-pub(crate) use utils_impl::measure_time_tokio_bench;
 pub(crate) use utils_impl::optimize_parameter;
-pub(crate) use utils_impl::optimize_parameter_tokio;
 pub(crate) use utils_impl::read_directory_all_at_once;
 pub(crate) use utils_impl::read_directory_entries;
-pub(crate) use utils_impl::u16_to_string;
-pub(crate) use utils_impl::vec_u16_to_pathbuf;
-pub(crate) use utils_impl::vec_u16_to_string;
-pub(crate) use utils_impl::wait_for_keypress;
@@ -1,4 +1,4 @@
-use crate::config::constants::{MAX_TEMP_FILES, MAX_TEMP_FILES_HDD_BATCH};
+use crate::config::constants::MAX_TEMP_FILES;
 use async_std::io;
 use rand::Rng;
 use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
@@ -1,42 +1,37 @@
 use std::error::Error;
-use std::ffi::{OsStr, OsString};
-use std::fs::File;
 use std::future::Future;
-use std::iter::once;
+#[cfg(windows)]
 use std::mem;
-use std::os::windows::prelude::{OsStrExt, OsStringExt};
 use std::path::{Component, Path, PathBuf, Prefix};
-use std::process::exit;
 use std::time::{Duration, Instant};
 
 use async_std::io;
-use dirs_next::home_dir;
+use dirs_next::config_dir;
 use num_format::{Locale, ToFormattedString};
-use once_cell::sync::Lazy;
-use rayon::prelude::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
-use std::sync::{Arc, Mutex};
-use std::thread;
-use sysinfo::System;
-use tokio::sync::{mpsc, RwLock};
-
-use crate::config::constants::{MAX_TEMP_FILES, MAX_TEMP_FILES_HDD_BATCH};
-use crate::modules::utils::temp_files_dirs_impl::UffsTempDir;
-use tempfile::TempDir;
-use tokio::runtime::Runtime;
-use tokio::task;
-use tokio_stream::wrappers::ReadDirStream;
-use tokio_stream::StreamExt;
-use tracing::{debug, info, warn};
+
+use crate::modules::file_system::{DirEntry, FileKind, FileSystem};
+use crate::modules::ignore_filter::IgnoreFilter;
+#[cfg(windows)]
+use crate::modules::raw_path::is_dot_entry;
+use crate::modules::raw_path::RawPath;
+#[cfg(windows)]
+use tracing::warn;
+use tracing::{debug, info};
+#[cfg(windows)]
 use winapi::shared::minwindef::DWORD;
+#[cfg(windows)]
 use winapi::shared::winerror::{ERROR_ACCESS_DENIED, ERROR_NO_MORE_FILES};
+#[cfg(windows)]
 use winapi::um::errhandlingapi::GetLastError;
+#[cfg(windows)]
 use winapi::um::fileapi::{FindClose, FindFirstFileW, FindNextFileW};
+#[cfg(windows)]
 use winapi::um::handleapi::INVALID_HANDLE_VALUE;
+#[cfg(windows)]
 use winapi::um::minwinbase::WIN32_FIND_DATAW;
+#[cfg(windows)]
 use winapi::um::winnt::FILE_ATTRIBUTE_DIRECTORY;
 
-static SHOULD_PRINT: Lazy<RwLock<bool>> = Lazy::new(|| RwLock::new(true));
-
 pub fn measure_time_normal<F, R>(func: F) -> (R, Duration)
 where
     F: FnOnce() -> R,
@@ -64,8 +59,7 @@ where
 {
     let start = Instant::now();
     let _ = func();
-    let duration = start.elapsed();
-    duration
+    start.elapsed()
 }
 
 pub async fn measure_time_tokio_bench<F, Fut>(func: F) -> Duration
@@ -78,137 +72,41 @@ where
     start.elapsed()
 }
 
+/// Reads the whole listing of `start_path` first, then splits it into files and directories.
 pub async fn read_directory_all_at_once(
-    start_path: &PathBuf,
-) -> Result<(Vec<PathBuf>, Vec<PathBuf>, Vec<PathBuf>), io::Error> {
-    // Create Arc<Mutex<_>> for thread-safe shared data
-    let max_files = 100_000;
-    let max_dirs = 18_000;
-
-    let files = Arc::new(Mutex::new(Vec::with_capacity(max_files)));
-    let dirs = Arc::new(Mutex::new(Vec::with_capacity(max_dirs)));
-    let new_dirs_paths = Arc::new(Mutex::new(Vec::with_capacity(max_dirs)));
-
-    // Read the directory asynchronously
-    let read_dir = tokio::fs::read_dir(start_path).await?;
-    let mut dir_stream = ReadDirStream::new(read_dir);
-    let mut tasks = vec![];
-
-    while let Some(entry) = dir_stream.next().await {
-        let entry = entry?;
-        let file_type = entry.file_type().await?;
-        let path = entry.path();
-
-        let files = Arc::clone(&files);
-        let dirs = Arc::clone(&dirs);
-        let new_dirs_paths = Arc::clone(&new_dirs_paths);
-
-        let task = task::spawn(async move {
-            if file_type.is_dir() {
-                let mut dirs = dirs.lock().unwrap();
-                let mut new_dirs_paths = new_dirs_paths.lock().unwrap();
-                dirs.push(path.clone());
-                new_dirs_paths.push(path);
-            } else {
-                let mut files = files.lock().unwrap();
-                files.push(path);
-            }
-        });
-
-        tasks.push(task);
-    }
-
-    // Await all tasks to complete
-    for task in tasks {
-        task.await.unwrap();
-    }
-
-    // Extract results from Arc<Mutex<_>>
-    let files = Arc::try_unwrap(files).unwrap().into_inner().unwrap();
-    let dirs = Arc::try_unwrap(dirs).unwrap().into_inner().unwrap();
-    let new_dirs_paths = Arc::try_unwrap(new_dirs_paths)
-        .unwrap()
-        .into_inner()
-        .unwrap();
-
-    Ok((files, dirs, new_dirs_paths))
+    fs: &dyn FileSystem,
+    start_path: &Path,
+) -> Result<(Vec<DirEntry>, Vec<DirEntry>), io::Error> {
+    let mut files = vec![];
+    let mut dirs = vec![];
+    read_directory_entries(fs, start_path, &mut files, &mut dirs).await?;
+    Ok((files, dirs))
 }
 
-/// Async function to read directory entries and partition them into local vectors
+/// Async function to read the directory entries and partition them into local vectors.
+/// Symlinks are not followed and count as files.
 pub(crate) async fn read_directory_entries(
-    start_path: &PathBuf,
-    files: &mut Vec<PathBuf>,
-    dirs: &mut Vec<PathBuf>,
-    new_dirs_paths: &mut Vec<PathBuf>,
+    fs: &dyn FileSystem,
+    start_path: &Path,
+    files: &mut Vec<DirEntry>,
+    dirs: &mut Vec<DirEntry>,
 ) -> Result<(), io::Error> {
-    // pub(crate) async fn read_directory_entries(
-    //     path: &Path,
-    //     files: &mut Vec<DirEntry>,
-    //     dirs: &mut Vec<DirEntry>,
-    //     new_dirs_paths: &mut VecDeque<PathBuf>,
-    // ) -> Result<(), io::Error> {
-
-    if let Ok(mut entries) = tokio::fs::read_dir(start_path).await {
-        while let Some(entry) = entries.next_entry().await? {
-            // let entry_path = entry.path();
-            let file_type = entry.file_type().await?;
-            if file_type.is_dir() {
-                dirs.push(entry.path());
-                // dirs.push(entry);
-                new_dirs_paths.push(entry.path())
-            } else {
-                files.push(entry.path());
-                // files.push(entry);
-            }
+    for entry in fs.read_dir(start_path).await? {
+        match entry.kind {
+            FileKind::Dir => dirs.push(entry),
+            FileKind::File | FileKind::Symlink => files.push(entry),
         }
     }
     Ok(())
 }
 
-pub(crate) async fn count_disk_entries(
-    path: &Path,
-    num_files: &mut u64,
-    num_dirs: &mut u64,
-    new_dirs_paths: &mut Vec<PathBuf>,
-) -> Result<(), io::Error> {
-    if let Ok(mut entries) = tokio::fs::read_dir(path).await {
-        while let Some(entry) = entries.next_entry().await? {
-            let file_type = entry.file_type().await?;
-            if file_type.is_dir() {
-                let dir_path = entry.path();
-                new_dirs_paths.push(dir_path);
-                *num_dirs += 1;
-            } else {
-                *num_files += 1;
-            }
-        }
-    }
-    Ok(())
-}
-
-pub(crate) fn add_wildcard(path_wide: &Vec<u16>) -> Vec<u16> {
-    let mut search_path_wide = Vec::with_capacity(path_wide.len() + 1);
-    search_path_wide.extend_from_slice(path_wide);
-    search_path_wide.push(b'*' as u16);
-    search_path_wide.push(0);
-    search_path_wide
-}
-
-pub(crate) fn create_full_path(directory: &[u16], file_name: &[u16]) -> Vec<u16> {
-    let mut full_path = Vec::with_capacity(directory.len() + file_name.len() + 1);
-    full_path.extend_from_slice(directory);
-    full_path.push(b'\\' as u16);
-    full_path.extend_from_slice(file_name);
-    full_path.push(b'\\' as u16);
-    full_path
-}
-
+#[cfg(windows)]
 pub(crate) fn handle_find_error(
     error: DWORD,
     num_files: &mut u64,
     num_dirs: &mut u64,
-    new_dirs_paths: &mut Vec<Vec<u16>>,
-) -> Result<(u64, u64, Vec<Vec<u16>>), io::Error> {
+    new_dirs_paths: &mut Vec<RawPath>,
+) -> Result<(u64, u64, Vec<RawPath>), io::Error> {
     // ) -> Result<(), io::Error> {
     match error {
         winapi::shared::winerror::ERROR_ACCESS_DENIED
@@ -228,6 +126,7 @@ pub(crate) fn handle_find_error(
     }
 }
 
+#[cfg(windows)]
 pub(crate) fn handle_find_error_for_reader(error: DWORD) -> Result<(), io::Error> {
     // ) -> Result<(), io::Error> {
     match error {
@@ -244,41 +143,18 @@ pub(crate) fn handle_find_error_for_reader(error: DWORD) -> Result<(), io::Error
     }
 }
 
-pub(crate) fn vec_u16_to_string(vec: &Vec<u16>) -> String {
-    // Remove the trailing null terminator if present
-    let trimmed_vec: Vec<u16> = vec.iter().cloned().filter(|&c| c != 0).collect();
-    let os_string = OsString::from_wide(&trimmed_vec);
-    os_string.to_string_lossy().into_owned()
-}
-
-// Assuming `vec_u16_to_string` is a function that converts `&[u16]` to `String`.
-pub(crate) fn u16_to_string(vec: &[u16]) -> String {
-    // Your implementation of vec_u16_to_string
-    let mut result = String::new();
-    for &c in vec {
-        if let Some(ch) = std::char::from_u32(c as u32) {
-            result.push(ch);
-        }
-    }
-    result
-}
-
-pub(crate) async fn wait_for_keypress() {
-    println!("Press any key to continue...");
-    let mut input = String::new();
-    io::stdin().read_line(&mut input).await.unwrap();
-}
-
+#[cfg(windows)]
 pub(crate) fn count_disk_entries_all_at_once_new(
-    start_path_wide: &Vec<u16>,
-) -> Result<(u64, u64, Vec<Vec<u16>>), io::Error> {
+    start_path: &RawPath,
+    filter: &IgnoreFilter,
+) -> Result<(u64, u64, Vec<RawPath>), io::Error> {
     // println!("START: count_disk_entries_all_at_once FILES:\t{}", vec_u16_to_string(start_path_wide));
 
     let mut new_num_files = 0u64;
     let mut new_num_dirs = 0u64;
     let mut new_dirs_paths = Vec::new();
 
-    let search_path_wide = add_wildcard(start_path_wide);
+    let search_path_wide = start_path.to_search_pattern();
 
     let mut find_data: WIN32_FIND_DATAW = unsafe { mem::zeroed() };
     let handle = unsafe { FindFirstFileW(search_path_wide.as_ptr(), &mut find_data) };
@@ -300,18 +176,19 @@ pub(crate) fn count_disk_entries_all_at_once_new(
     }
 
     loop {
-        let file_name: Vec<u16> = find_data
-            .cFileName
-            .iter()
-            .take_while(|&&c| c != 0)
-            .cloned()
-            .collect();
-
-        if file_name != [b'.' as u16] && file_name != [b'.' as u16, b'.' as u16] {
-            if (find_data.dwFileAttributes & FILE_ATTRIBUTE_DIRECTORY) != 0 {
+        let file_name = RawPath::from_nul_terminated(&find_data.cFileName);
+
+        let is_dir = (find_data.dwFileAttributes & FILE_ATTRIBUTE_DIRECTORY) != 0;
+        // The ignored entries are left out along with everything below them
+        let ignored = |name: &RawPath| {
+            filter.is_active()
+                && filter.is_ignored(&start_path.join(name.as_units()).into_path_buf(), is_dir)
+        };
+
+        if !is_dot_entry(file_name.as_units()) && !ignored(&file_name) {
+            if is_dir {
                 new_num_dirs += 1;
-                let new_path = create_full_path(start_path_wide, &file_name);
-                new_dirs_paths.push(new_path);
+                new_dirs_paths.push(start_path.join(file_name.as_units()));
             } else {
                 new_num_files += 1;
             }
@@ -324,9 +201,8 @@ pub(crate) fn count_disk_entries_all_at_once_new(
             }
             unsafe { FindClose(handle) };
             warn!(
-                "Encountered error code in LOOP {}: with {}",
-                error,
-                vec_u16_to_string(&search_path_wide)
+                "Encountered error code in LOOP {}: with {:?}",
+                error, start_path
             );
             return handle_find_error(
                 error,
@@ -343,11 +219,66 @@ pub(crate) fn count_disk_entries_all_at_once_new(
     Ok((new_num_files, new_num_dirs, new_dirs_paths))
 }
 
+// The access errors leave the directory out of the counts, as `handle_find_error` does on
+// Windows
+#[cfg(unix)]
+fn handle_read_dir_error(error: io::Error) -> Result<(u64, u64, Vec<RawPath>), io::Error> {
+    match error.kind() {
+        io::ErrorKind::PermissionDenied | io::ErrorKind::NotFound => Ok((0u64, 0u64, Vec::new())),
+        _ => Err(error),
+    }
+}
+
+// `read_dir` takes the file types from the listing itself on most file systems, no entry is
+// looked up on its own
+#[cfg(unix)]
+pub(crate) fn count_disk_entries_all_at_once_new(
+    start_path: &RawPath,
+    filter: &IgnoreFilter,
+) -> Result<(u64, u64, Vec<RawPath>), io::Error> {
+    let mut new_num_files = 0u64;
+    let mut new_num_dirs = 0u64;
+    let mut new_dirs_paths = Vec::new();
+
+    let entries = match std::fs::read_dir(Path::new(&start_path.as_os_str())) {
+        Ok(entries) => entries,
+        Err(e) => return handle_read_dir_error(e),
+    };
+    for entry in entries {
+        let (file_name, is_dir) = match entry.and_then(|entry| {
+            let is_dir = entry.file_type()?.is_dir();
+            Ok((RawPath::from(entry.file_name()), is_dir))
+        }) {
+            Ok(entry) => entry,
+            Err(e) => return handle_read_dir_error(e),
+        };
+
+        // The ignored entries are left out along with everything below them
+        if filter.is_active()
+            && filter.is_ignored(
+                &start_path.join(file_name.as_units()).into_path_buf(),
+                is_dir,
+            )
+        {
+            continue;
+        }
+        if is_dir {
+            new_num_dirs += 1;
+            new_dirs_paths.push(start_path.join(file_name.as_units()));
+        } else {
+            new_num_files += 1;
+        }
+    }
+
+    Ok((new_num_files, new_num_dirs, new_dirs_paths))
+}
+
+#[cfg(windows)]
 pub fn count_disk_entries_all_at_once(
-    start_path_wide: &Vec<u16>,
+    start_path: &RawPath,
     num_files: &mut u64,
     num_dirs: &mut u64,
-    new_dirs_paths: &mut Vec<Vec<u16>>,
+    new_dirs_paths: &mut Vec<RawPath>,
 ) -> Result<(), io::Error> {
     // println!(
     //     "START: count_disk_entries_all_at_once FILES:\t{}",
@@ -357,7 +288,7 @@ pub fn count_disk_entries_all_at_once(
     // println!("ORG Working on:\t{}", vec_u16_to_string(&start_path_wide));
     // println!("ORG Working on VEC:\t{:?}", &start_path_wide);
 
-    let search_path_wide = add_wildcard(&start_path_wide);
+    let search_path_wide = start_path.to_search_pattern();
 
     // println!("Working on:\t{}", vec_u16_to_string(&search_path_wide));
     // println!("Working on VEC:\t{:?}", &search_path_wide);
@@ -379,22 +310,17 @@ pub fn count_disk_entries_all_at_once(
     }
 
     loop {
-        let file_name: Vec<u16> = find_data
-            .cFileName
-            .iter()
-            .take_while(|&&c| c != 0)
-            .cloned()
-            .collect();
+        let file_name = RawPath::from_nul_terminated(&find_data.cFileName);
 
         // Skip "." and ".." entries
-        if file_name != [b'.' as u16] && file_name != [b'.' as u16, b'.' as u16] {
+        if !is_dot_entry(file_name.as_units()) {
             if (find_data.dwFileAttributes & FILE_ATTRIBUTE_DIRECTORY) != 0 {
                 // It's a directory
                 // println!("Directory: {}", vec_u16_to_string(&file_name));
 
                 *num_dirs += 1;
 
-                let new_path = create_full_path(&start_path_wide, &file_name);
+                let new_path = start_path.join(file_name.as_units());
                 // println!(
                 //     "NEW PATH\t{:?}",
                 //     vec_u16_to_string(&new_path)
@@ -419,11 +345,10 @@ pub fn count_disk_entries_all_at_once(
                 break;
             }
             unsafe { FindClose(handle) };
-            println!("PROBLEM File: {}", vec_u16_to_string(&file_name));
-            println!("PROBLEM File VEC: {:?}", &file_name);
             warn!(
-                "Encountered error code {}: Access denied, path not found, or file not found.",
-                error
+                "Encountered error code {} after {}: Access denied, path not found, or file not found.",
+                error,
+                file_name.to_string_lossy()
             );
 
             return match handle_find_error(error, num_files, num_dirs, new_dirs_paths) {
@@ -433,7 +358,7 @@ pub fn count_disk_entries_all_at_once(
         }
     }
     // println!("Done");
-    println!(
+    debug!(
         "STOP: count_disk_entries_all_at_once FILES:\t{}\tDIRS:\t{}",
         num_files, num_dirs
     );
@@ -443,52 +368,16 @@ pub fn count_disk_entries_all_at_once(
     Ok(())
 }
 
-pub(crate) fn get_path() -> Result<PathBuf, io::Error> {
-    #[cfg(target_os = "windows")]
-    let path = {
-        let home_path = home_dir().ok_or(io::Error::new(
+// Directory holding the UFFS configuration and the persisted state (benchmark history, ...)
+pub(crate) fn get_config_dir() -> Result<PathBuf, io::Error> {
+    let path = config_dir()
+        .ok_or(io::Error::new(
             io::ErrorKind::NotFound,
-            "Could not determine home directory",
-        ))?;
-
-        let path = PathBuf::from(home_path).join("GitHub\\UltraFastFileSearch");
-
-        if Path::new(&path).exists() {
-            Ok(path)
-        } else {
-            Err(io::Error::new(
-                io::ErrorKind::NotFound,
-                format!("Path does not exist: {:?}", path),
-            ))
-        }
-    };
-
-    #[cfg(target_family = "unix")]
-    let path = {
-        let path = get_unix_path()?;
-
-        if Path::new(&path).exists() {
-            Ok(path)
-        } else {
-            Err(std::io::Error::new(
-                std::io::ErrorKind::NotFound,
-                format!("Path does not exist: {:?}", path),
-            ))
-        }
-    };
-
-    path
-}
-
-#[cfg(target_family = "unix")]
-pub(crate) fn get_unix_path() -> std::io::Result<PathBuf> {
-    // Construct the full path
-    let path: PathBuf = home_dir()
-        .ok_or(std::io::Error::new(
-            std::io::ErrorKind::NotFound,
-            "Could not determine home directory",
+            "Could not determine config directory",
         ))?
-        .join("GitHub/uffs");
+        .join("uffs");
+
+    std::fs::create_dir_all(&path)?;
 
     Ok(path)
 }
@@ -502,12 +391,12 @@ pub(crate) fn format_size(size: u64) -> String {
     }
 }
 
-pub(crate) fn get_root_path(path: &Path) -> &Path {
-    match path.components().next() {
-        Some(Component::Prefix(prefix)) => Path::new(prefix.as_os_str()),
-        Some(Component::RootDir) => Path::new("/"),
-        Some(Component::Normal(_)) => path.ancestors().find(|p| p.parent().is_none()).unwrap(),
-        _ => path,
+pub(crate) fn format_memory(bytes: u64) -> String {
+    let size_mb = bytes as f64 / (1024.0 * 1024.0);
+    if size_mb >= 1024.0 {
+        format!("{:>8.2} GB", size_mb / 1024.0)
+    } else {
+        format!("{:>8.2} MB", size_mb)
     }
 }
 
@@ -518,7 +407,7 @@ pub(crate) fn format_number(number: usize, width: usize) -> String {
 
 pub(crate) fn get_number_of_cpu_cores() -> usize {
     let mut cpu_cores = num_cpus::get();
-    debug!("{}",cpu_cores);
+    debug!("{}", cpu_cores);
 
     if cpu_cores == 0 {
         cpu_cores = 4
@@ -554,7 +443,7 @@ pub fn format_duration(duration: Duration) -> String {
     }
 }
 
-pub(crate) fn get_drive_letter(path: &PathBuf) -> Option<String> {
+pub(crate) fn get_drive_letter(path: &Path) -> Option<String> {
     if let Some(Component::Prefix(prefix)) = path.components().next() {
         if let Prefix::Disk(disk) = prefix.kind() {
             // Convert the drive number to a drive letter
@@ -578,7 +467,7 @@ where
         format_duration(best_duration)
     );
 
-    let mut step_size = 2;
+    let step_size = 2;
     let mut current_value = best_optimized_value * step_size;
 
     // Exponential growth phase
@@ -673,7 +562,7 @@ where
         format_duration(best_duration)
     );
 
-    let mut step_size = 2;
+    let step_size = 2;
     let mut current_value = best_optimized_value * step_size;
 
     // Exponential growth phase
@@ -773,11 +662,67 @@ pub fn generate_fibonacci(n: usize) -> Vec<usize> {
     fib
 }
 
-pub(crate) fn vec_u16_to_pathbuf(wide: Vec<u16>) -> PathBuf {
-    let os_string = OsString::from_wide(&wide);
-    PathBuf::from(os_string)
-}
-
 pub fn hello() {
     println!("Hello from the library crate!");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::file_system::faulty_fs::Fault;
+
+    // Access errors leave the directory out of the counts, any other error ends the count
+    fn is_skipped(fault: Fault) -> bool {
+        matches!(fault, Fault::PermissionDenied | Fault::Vanished)
+    }
+
+    const FAULTS: [Fault; 4] = [
+        Fault::PermissionDenied,
+        Fault::Vanished,
+        Fault::Io,
+        Fault::NameTooLong,
+    ];
+
+    #[cfg(windows)]
+    #[test]
+    fn find_errors_are_skipped_or_returned() {
+        for fault in FAULTS {
+            let code = fault.to_error().unwrap().raw_os_error().unwrap() as DWORD;
+
+            let (mut files, mut dirs, mut paths) = (3, 2, vec![RawPath::from(Path::new("x"))]);
+            let counted = handle_find_error(code, &mut files, &mut dirs, &mut paths);
+            match counted {
+                Ok(counted) if is_skipped(fault) => {
+                    assert_eq!(counted, (0, 0, vec![]));
+                    assert_eq!((files, dirs, paths.len()), (0, 0, 0));
+                }
+                Err(e) if !is_skipped(fault) => assert_eq!(e.raw_os_error(), Some(code as i32)),
+                other => panic!("{:?}: {:?}", fault, other),
+            }
+            assert_eq!(
+                handle_find_error_for_reader(code).is_ok(),
+                is_skipped(fault)
+            );
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn read_dir_errors_are_skipped_or_returned() {
+        for fault in FAULTS {
+            match handle_read_dir_error(fault.to_error().unwrap()) {
+                Ok(counted) if is_skipped(fault) => assert_eq!(counted, (0, 0, vec![])),
+                Err(_) if !is_skipped(fault) => {}
+                other => panic!("{:?}: {:?}", fault, other),
+            }
+        }
+    }
+
+    #[test]
+    fn counting_skips_a_missing_directory() {
+        let root = tempfile::tempdir().unwrap();
+        let missing = RawPath::from(root.path().join("missing").as_path());
+        let counted = count_disk_entries_all_at_once_new(&missing, &IgnoreFilter::default());
+        assert_eq!(counted.unwrap(), (0, 0, vec![]));
+    }
+}
@@ -0,0 +1,5 @@
+pub mod verify_impl;
+
+pub(crate) use verify_impl::memory_readers;
+pub(crate) use verify_impl::verify_generated;
+pub(crate) use verify_impl::verify_root;
@@ -0,0 +1,652 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::warn;
+
+use crate::modules::algo_selector::SharedDirectoryReader;
+use crate::modules::directory_reader::{
+    count_entries, ReadDirectories1, ReadDirectories2, ReadDirectories4,
+};
+use crate::modules::disk_reader::read_tree;
+use crate::modules::errors::UFFSError;
+use crate::modules::file_system::faulty_fs::{Fault, FaultyFileSystem};
+use crate::modules::file_system::memory_fs::MemoryFileSystem;
+use crate::modules::file_system::SharedFileSystem;
+use crate::modules::ignore_filter::IgnoreFilter;
+use crate::modules::output::{quote_path, Quoting};
+use crate::modules::path_arena::PathArena;
+use crate::modules::scanner::ReadError;
+use crate::modules::tree_gen::tree_gen_impl::{TreeEntryKind, TreeSpec};
+use crate::modules::utils::format_duration;
+
+// Where the generated trees are put in memory
+const GENERATED_ROOT: &str = "/generated";
+
+// Name of the generated tree among the readers it is compared with
+const EXPECTED: &str = "expected";
+
+// The rest of a report is only counted
+const MAX_SHOWN: usize = 50;
+
+/// Every entry below a root, relative to it, with whether it is a directory.
+pub type Listing = BTreeMap<PathBuf, bool>;
+
+/// Where the readers, the counting path or a generated tree disagree. Paths are relative to the
+/// root of the report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Discrepancy {
+    /// Listed by some of the readers only. `below` counts the entries under a missing directory
+    /// that are missing from the same readers, they aren't reported one by one.
+    Missing {
+        path: PathBuf,
+        found_by: Vec<String>,
+        missing_from: Vec<String>,
+        below: u64,
+    },
+    /// A directory for some readers and a file for the others
+    Kind {
+        path: PathBuf,
+        dir_for: Vec<String>,
+        file_for: Vec<String>,
+    },
+    /// A failed read that the reader didn't report, or reported without the others failing
+    Error {
+        path: PathBuf,
+        reader: String,
+        reported: bool,
+    },
+    /// Totals of the counting path other than the ones of a reader, as (files, dirs)
+    Count {
+        reader: String,
+        listed: (u64, u64),
+        counted: (u64, u64),
+    },
+}
+
+impl Discrepancy {
+    fn describe(&self, quoting: Quoting) -> String {
+        match self {
+            Discrepancy::Missing {
+                path,
+                found_by,
+                missing_from,
+                below,
+            } => {
+                let mut text = format!(
+                    "missing  {}: found by {}, missing from {}",
+                    quote_path(path, quoting),
+                    found_by.join(", "),
+                    missing_from.join(", ")
+                );
+                if *below > 0 {
+                    text.push_str(&format!(" (and {} entries below)", below));
+                }
+                text
+            }
+            Discrepancy::Kind {
+                path,
+                dir_for,
+                file_for,
+            } => format!(
+                "kind     {}: a directory for {}, a file for {}",
+                quote_path(path, quoting),
+                dir_for.join(", "),
+                file_for.join(", ")
+            ),
+            Discrepancy::Error {
+                path,
+                reader,
+                reported: false,
+            } => format!(
+                "error    {}: {} did not report the failed read",
+                quote_path(path, quoting),
+                reader
+            ),
+            Discrepancy::Error { path, reader, .. } => format!(
+                "error    {}: only {} failed to read it",
+                quote_path(path, quoting),
+                reader
+            ),
+            Discrepancy::Count {
+                reader,
+                listed,
+                counted,
+            } => format!(
+                "count    {} listed {} files and {} dirs, the counting path {} files and {} dirs",
+                reader, listed.0, listed.1, counted.0, counted.1
+            ),
+        }
+    }
+}
+
+/// What one reader found below the root.
+#[derive(Debug, Clone)]
+pub struct ReaderRun {
+    pub reader: String,
+    pub files: u64,
+    pub dirs: u64,
+    pub errors: Vec<ReadError>,
+    pub duration: Duration,
+}
+
+impl ReaderRun {
+    fn new(reader: &str, listing: &Listing, errors: Vec<ReadError>, duration: Duration) -> Self {
+        let dirs = listing.values().filter(|&&is_dir| is_dir).count() as u64;
+        Self {
+            reader: reader.to_string(),
+            files: listing.len() as u64 - dirs,
+            dirs,
+            errors,
+            duration,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VerifyReport {
+    pub root: PathBuf,
+    /// Seed of the generated tree, `None` for a directory on disk
+    pub seed: Option<u64>,
+    pub runs: Vec<ReaderRun>,
+    /// Files and directories of the counting path
+    pub counted: Option<(u64, u64)>,
+    pub discrepancies: Vec<Discrepancy>,
+}
+
+impl VerifyReport {
+    pub fn is_consistent(&self) -> bool {
+        self.discrepancies.is_empty()
+    }
+
+    pub fn write(&self, out: &mut impl Write, quoting: Quoting) -> io::Result<()> {
+        match self.seed {
+            Some(seed) => writeln!(out, "Generated tree, seed {}", seed)?,
+            None => writeln!(out, "{}", quote_path(&self.root, quoting))?,
+        }
+        for run in &self.runs {
+            writeln!(
+                out,
+                "  {:<12} {:>10} files {:>9} dirs {:>6} errors  {}",
+                run.reader,
+                run.files,
+                run.dirs,
+                run.errors.len(),
+                format_duration(run.duration)
+            )?;
+        }
+        if let Some((files, dirs)) = self.counted {
+            writeln!(
+                out,
+                "  {:<12} {:>10} files {:>9} dirs",
+                "count", files, dirs
+            )?;
+        }
+
+        if self.is_consistent() {
+            return writeln!(out, "  No discrepancies");
+        }
+        writeln!(out, "  {} discrepancies:", self.discrepancies.len())?;
+        for discrepancy in self.discrepancies.iter().take(MAX_SHOWN) {
+            writeln!(out, "    {}", discrepancy.describe(quoting))?;
+        }
+        if self.discrepancies.len() > MAX_SHOWN {
+            writeln!(
+                out,
+                "    ... and {} more",
+                self.discrepancies.len() - MAX_SHOWN
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn relative_to(path: &Path, root: &Path) -> PathBuf {
+    path.strip_prefix(root).unwrap_or(path).to_path_buf()
+}
+
+fn listing(arena: &PathArena, root: &Path) -> Listing {
+    let dirs = arena
+        .dir_paths()
+        .map(|path| (relative_to(&path, root), true));
+    let files = arena
+        .file_paths()
+        .map(|path| (relative_to(&path, root), false));
+    dirs.chain(files).collect()
+}
+
+// The entries not listed by all of `listings` or not of the same kind everywhere
+fn compare_listings(listings: &[(&str, &Listing)]) -> Vec<Discrepancy> {
+    let paths: BTreeSet<&PathBuf> = listings
+        .iter()
+        .flat_map(|(_, listing)| listing.keys())
+        .collect();
+
+    let mut discrepancies = vec![];
+    // The last missing directory, its entries follow it in path order
+    let mut missing_dir: Option<usize> = None;
+    for path in paths {
+        let mut found_by = vec![];
+        let mut missing_from = vec![];
+        let mut dir_for = vec![];
+        let mut file_for = vec![];
+        for (name, listing) in listings {
+            match listing.get(path) {
+                Some(true) => dir_for.push(name.to_string()),
+                Some(false) => file_for.push(name.to_string()),
+                None => missing_from.push(name.to_string()),
+            }
+        }
+
+        if !missing_from.is_empty() {
+            if let Some(Discrepancy::Missing {
+                path: dir,
+                missing_from: missing_from_dir,
+                below,
+                ..
+            }) = missing_dir.map(|index| &mut discrepancies[index])
+            {
+                if path.starts_with(dir) && *missing_from_dir == missing_from {
+                    *below += 1;
+                    continue;
+                }
+            }
+            found_by.extend(dir_for.iter().chain(&file_for).cloned());
+            missing_dir = (!dir_for.is_empty()).then_some(discrepancies.len());
+            discrepancies.push(Discrepancy::Missing {
+                path: path.clone(),
+                found_by,
+                missing_from,
+                below: 0,
+            });
+        } else if !dir_for.is_empty() && !file_for.is_empty() {
+            discrepancies.push(Discrepancy::Kind {
+                path: path.clone(),
+                dir_for,
+                file_for,
+            });
+        }
+    }
+    discrepancies
+}
+
+fn compare_errors(
+    reader: &str,
+    reported: &BTreeSet<PathBuf>,
+    expected: &BTreeSet<PathBuf>,
+) -> Vec<Discrepancy> {
+    let discrepancy = |path: &PathBuf, reported| Discrepancy::Error {
+        path: path.clone(),
+        reader: reader.to_string(),
+        reported,
+    };
+    let unreported = expected
+        .difference(reported)
+        .map(|path| discrepancy(path, false));
+    let unexpected = reported
+        .difference(expected)
+        .map(|path| discrepancy(path, true));
+    unreported.chain(unexpected).collect()
+}
+
+fn error_paths(errors: &[ReadError], root: &Path) -> BTreeSet<PathBuf> {
+    errors
+        .iter()
+        .map(|error| relative_to(&error.path, root))
+        .collect()
+}
+
+/// Reads `root` with each of `readers` one after the other and with the counting path, and
+/// compares what they found. The directory should not change in the meantime, or the changes
+/// show up as discrepancies. `ignore` is the filter the readers were given, the counting path
+/// leaves out the same entries.
+pub(crate) async fn verify_root(
+    root: &Path,
+    readers: &[SharedDirectoryReader],
+    ignore: &IgnoreFilter,
+) -> VerifyReport {
+    let mut runs = vec![];
+    let mut listings = vec![];
+    for reader in readers {
+        let (arena, duration, stats) = read_tree(root, reader.as_ref()).await;
+        let listing = listing(&arena, root);
+        runs.push(ReaderRun::new(
+            reader.name(),
+            &listing,
+            stats.errors,
+            duration,
+        ));
+        listings.push(listing);
+    }
+
+    let named: Vec<_> = runs
+        .iter()
+        .map(|run| run.reader.as_str())
+        .zip(&listings)
+        .collect();
+    let mut discrepancies = compare_listings(&named);
+
+    // A directory one reader couldn't read should have failed for all of them
+    let failed: BTreeSet<PathBuf> = runs
+        .iter()
+        .flat_map(|run| error_paths(&run.errors, root))
+        .collect();
+    for run in &runs {
+        let reported = error_paths(&run.errors, root);
+        discrepancies.extend(compare_errors(&run.reader, &reported, &failed));
+    }
+
+    let counted = match count_entries(root, ignore).await {
+        Ok(counted) => Some(counted),
+        Err(e) => {
+            warn!("Failed to count the entries of {}: {}", root.display(), e);
+            None
+        }
+    };
+    if let Some(counted) = counted {
+        for run in &runs {
+            if (run.files, run.dirs) != counted {
+                discrepancies.push(Discrepancy::Count {
+                    reader: run.reader.clone(),
+                    listed: (run.files, run.dirs),
+                    counted,
+                });
+            }
+        }
+    }
+
+    VerifyReport {
+        root: root.to_path_buf(),
+        seed: None,
+        runs,
+        counted,
+        discrepancies,
+    }
+}
+
+type MemoryReader = fn(SharedFileSystem) -> SharedDirectoryReader;
+
+// The readers that can read from another file system than the disk, by name
+const MEMORY_READERS: [(&str, MemoryReader); 3] = [
+    ("semaphore", |fs| {
+        Arc::new(ReadDirectories1::default().with_file_system(fs))
+    }),
+    ("sequential", |fs| {
+        Arc::new(ReadDirectories2::default().with_file_system(fs))
+    }),
+    ("all_at_once", |fs| {
+        Arc::new(ReadDirectories4::default().with_file_system(fs))
+    }),
+];
+
+/// The readers of `names` that can read the generated trees, all of them when `names` is empty.
+pub(crate) fn memory_readers(names: &[String]) -> Result<Vec<MemoryReader>, UFFSError> {
+    if names.is_empty() {
+        return Ok(MEMORY_READERS.iter().map(|&(_, reader)| reader).collect());
+    }
+    names
+        .iter()
+        .map(|name| {
+            MEMORY_READERS
+                .iter()
+                .find(|(memory_name, _)| memory_name == name)
+                .map(|&(_, reader)| reader)
+                .ok_or_else(|| {
+                    let available: Vec<&str> =
+                        MEMORY_READERS.iter().map(|&(name, _)| name).collect();
+                    UFFSError::ConfigError(format!(
+                        "{} cannot read the generated trees, only {} can",
+                        name,
+                        available.join(", ")
+                    ))
+                })
+        })
+        .collect()
+}
+
+// Whether `ignore` leaves out the entry at `path`, relative to `root`, or a directory above it
+fn is_ignored(ignore: &IgnoreFilter, root: &Path, path: &Path, is_dir: bool) -> bool {
+    ignore.is_active()
+        && path
+            .ancestors()
+            .filter(|ancestor| !ancestor.as_os_str().is_empty())
+            .enumerate()
+            .any(|(level, ancestor)| ignore.is_ignored(&root.join(ancestor), level > 0 || is_dir))
+}
+
+/// Checks `readers`, from `memory_readers`, against the trees generated from `spec` with each
+/// of `seeds`. The readers leave out what `ignore` filters, and so does the expected tree. With
+/// a `fault_probability` above 0 that share of the reads fails, and a reader has to lose exactly
+/// the entries below the directories it couldn't read and report those directories.
+pub(crate) async fn verify_generated(
+    spec: &TreeSpec,
+    seeds: Range<u64>,
+    readers: &[MemoryReader],
+    ignore: &IgnoreFilter,
+    fault_probability: f64,
+) -> Vec<VerifyReport> {
+    let root = Path::new(GENERATED_ROOT);
+    let mut reports = vec![];
+    for seed in seeds {
+        let tree = spec.clone().with_seed(seed).generate();
+        let memory = Arc::new(MemoryFileSystem::new());
+        tree.write_to_memory(&memory, root)
+            .expect("Generated trees have unique names");
+        let generated: Listing = tree
+            .entries()
+            .iter()
+            .map(|entry| (entry.path.clone(), entry.kind == TreeEntryKind::Dir))
+            .filter(|(path, is_dir)| !is_ignored(ignore, root, path, *is_dir))
+            .collect();
+
+        let mut runs = vec![];
+        let mut discrepancies = vec![];
+        for make_reader in readers {
+            let faulty = Arc::new(
+                FaultyFileSystem::new(Arc::clone(&memory) as SharedFileSystem)
+                    .with_seed(seed)
+                    .fail_randomly(fault_probability / 2.0, Fault::PermissionDenied)
+                    .fail_randomly(fault_probability / 2.0, Fault::Vanished),
+            );
+            let reader = make_reader(Arc::clone(&faulty) as SharedFileSystem)
+                .with_ignore_filter(ignore.clone());
+            let (arena, duration, stats) = read_tree(root, reader.as_ref()).await;
+            let listing = listing(&arena, root);
+
+            // Everything below a failed directory is lost, the directory itself is listed
+            let failed: BTreeSet<PathBuf> = faulty
+                .injected()
+                .iter()
+                .map(|injected| relative_to(&injected.path, root))
+                .collect();
+            let expected: Listing = generated
+                .iter()
+                .filter(|(path, _)| !path.ancestors().skip(1).any(|dir| failed.contains(dir)))
+                .map(|(path, &is_dir)| (path.clone(), is_dir))
+                .collect();
+
+            discrepancies.extend(compare_listings(&[
+                (EXPECTED, &expected),
+                (reader.name(), &listing),
+            ]));
+            let reported = error_paths(&stats.errors, root);
+            discrepancies.extend(compare_errors(reader.name(), &reported, &failed));
+            runs.push(ReaderRun::new(
+                reader.name(),
+                &listing,
+                stats.errors,
+                duration,
+            ));
+        }
+
+        reports.push(VerifyReport {
+            root: root.to_path_buf(),
+            seed: Some(seed),
+            runs,
+            counted: None,
+            discrepancies,
+        });
+    }
+    reports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::algo_selector::{ReaderOptions, ReaderRegistry};
+    use crate::modules::ignore_filter::IgnoreOptions;
+    use crate::modules::tree_gen::distribution::Distribution;
+
+    const SEEDS: Range<u64> = 0..8;
+
+    fn spec() -> TreeSpec {
+        TreeSpec {
+            depth: 3,
+            fan_out: Distribution::Uniform { min: 0, max: 4 },
+            files_per_dir: Distribution::Uniform { min: 0, max: 12 },
+            file_size: Distribution::Fixed { value: 0 },
+            unicode_names: 0.2,
+            symlinks: 0.05,
+            hard_links: 0.05,
+            max_entries: 400,
+            ..TreeSpec::default()
+        }
+    }
+
+    fn assert_consistent(reports: &[VerifyReport]) {
+        for report in reports {
+            let mut text = vec![];
+            report.write(&mut text, Quoting::Escape).unwrap();
+            assert!(report.is_consistent(), "{}", String::from_utf8_lossy(&text));
+        }
+    }
+
+    #[tokio::test]
+    async fn readers_and_count_agree_on_generated_trees() {
+        let readers: Vec<SharedDirectoryReader> = {
+            let registry = ReaderRegistry::default();
+            registry
+                .names()
+                .into_iter()
+                .map(|name| registry.get(name).unwrap())
+                .collect()
+        };
+
+        for seed in SEEDS {
+            let root = tempfile::tempdir().unwrap();
+            let tree = spec().with_seed(seed).generate();
+            tree.write_to(root.path()).unwrap();
+
+            let report = verify_root(root.path(), &readers, &IgnoreFilter::default()).await;
+            assert_eq!(report.runs.len(), 4);
+            assert!(report.counted.is_some());
+            assert_consistent(&[report]);
+        }
+    }
+
+    #[tokio::test]
+    async fn readers_and_count_leave_out_the_same_entries() {
+        let temp = tempfile::tempdir().unwrap();
+        // Not the temporary directory itself, its name is hidden
+        let root = temp.path().join("tree");
+        for dir in [".hidden", "build", "src/tmp"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        std::fs::write(root.join(".gitignore"), "*.log\nbuild/\n").unwrap();
+        for file in [
+            ".hidden/a.txt",
+            ".secret.txt",
+            "a.txt",
+            "b.log",
+            "build/c.txt",
+            "src/d.txt",
+            "src/e.log",
+            "src/.cache.txt",
+            "src/tmp/f.txt",
+        ] {
+            std::fs::write(root.join(file), "").unwrap();
+        }
+
+        // The files and directories left for each of the options
+        let cases = [
+            (IgnoreOptions::default(), (10, 4)),
+            (
+                IgnoreOptions {
+                    ignore_files: true,
+                    ..IgnoreOptions::default()
+                },
+                (7, 3),
+            ),
+            (
+                IgnoreOptions {
+                    exclude: vec!["tmp/".to_string(), "d.txt".to_string()],
+                    ..IgnoreOptions::default()
+                },
+                (8, 3),
+            ),
+            (
+                IgnoreOptions {
+                    skip_hidden: true,
+                    ..IgnoreOptions::default()
+                },
+                (6, 3),
+            ),
+        ];
+        for (options, expected) in cases {
+            let ignore = IgnoreFilter::new(&options).unwrap();
+            let registry = ReaderRegistry::new(ReaderOptions {
+                ignore: ignore.clone(),
+                ..ReaderOptions::default()
+            });
+            let readers: Vec<SharedDirectoryReader> = registry
+                .names()
+                .into_iter()
+                .map(|name| registry.get(name).unwrap())
+                .collect();
+
+            let report = verify_root(&root, &readers, &ignore).await;
+            assert_eq!(report.runs.len(), 4);
+            assert_eq!(report.counted, Some(expected), "{:?}", options);
+            assert_consistent(&[report]);
+        }
+    }
+
+    #[tokio::test]
+    async fn memory_readers_agree_on_generated_trees() {
+        let readers = memory_readers(&[]).unwrap();
+        let ignore = IgnoreFilter::default();
+        for faults in [0.0, 0.2] {
+            let reports = verify_generated(&spec(), SEEDS, &readers, &ignore, faults).await;
+            assert_eq!(reports.len(), SEEDS.count());
+            assert_consistent(&reports);
+        }
+    }
+
+    #[tokio::test]
+    async fn generated_trees_are_filtered() {
+        let readers = memory_readers(&["sequential".to_string()]).unwrap();
+        let ignore = IgnoreFilter::new(&IgnoreOptions {
+            exclude: vec!["dir_*/".to_string(), "*_1?.txt".to_string()],
+            ..IgnoreOptions::default()
+        })
+        .unwrap();
+        let spec = TreeSpec {
+            unicode_names: 0.0,
+            ..spec()
+        };
+        let reports = verify_generated(&spec, SEEDS, &readers, &ignore, 0.0).await;
+        assert_consistent(&reports);
+        for report in &reports {
+            // Only the files of the root are left
+            assert_eq!(report.runs[0].dirs, 0);
+        }
+    }
+
+    #[test]
+    fn jwalk_cannot_read_generated_trees() {
+        assert!(memory_readers(&["jwalk".to_string()]).is_err());
+        assert_eq!(memory_readers(&[]).unwrap().len(), MEMORY_READERS.len());
+    }
+}
@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+use std::ffi::{CString, OsStr};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::mem::{self, MaybeUninit};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::time::Duration;
+
+use crate::config::constants::WATCH_BUFFER_BYTES;
+use crate::modules::watch::watch_impl::{wait_readable, Change, ChangeSource};
+
+const EVENTS: u64 = libc::FAN_CREATE
+    | libc::FAN_DELETE
+    | libc::FAN_MOVED_FROM
+    | libc::FAN_MOVED_TO
+    | libc::FAN_ATTRIB
+    | libc::FAN_CLOSE_WRITE
+    | libc::FAN_ONDIR;
+
+// struct fanotify_event_info_header followed by the fsid, then struct file_handle
+const INFO_HEADER_BYTES: usize = 4;
+const FSID_BYTES: usize = 8;
+const FILE_HANDLE_HEADER_BYTES: usize = 8;
+
+type Fsid = [i32; 2];
+
+/// A fanotify mark on every watched file system, reporting the directory and name of each
+/// change. Covers the whole file system with a single mark, but needs CAP_SYS_ADMIN and Linux 5.9.
+pub(crate) struct FanotifySource {
+    fanotify: File,
+    // An open directory of every marked file system, to resolve the directory handles of its
+    // events with open_by_handle_at
+    mounts: HashMap<Fsid, File>,
+    buffer: Vec<u8>,
+}
+
+fn check(result: libc::c_int) -> io::Result<libc::c_int> {
+    if result < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(result)
+    }
+}
+
+fn fsid(file: &File) -> io::Result<Fsid> {
+    let mut stat = MaybeUninit::<libc::statfs>::uninit();
+    // SAFETY: fstatfs fills the struct on success
+    let stat = unsafe {
+        check(libc::fstatfs(file.as_raw_fd(), stat.as_mut_ptr()))?;
+        stat.assume_init()
+    };
+    // SAFETY: fsid_t is two ints, which libc keeps private
+    Ok(unsafe { mem::transmute::<libc::fsid_t, Fsid>(stat.f_fsid) })
+}
+
+fn read_ne<const N: usize>(bytes: &[u8], offset: usize) -> Option<[u8; N]> {
+    bytes.get(offset..offset + N)?.try_into().ok()
+}
+
+impl FanotifySource {
+    /// Marks the file systems `roots` are on.
+    pub(crate) fn new(roots: &[PathBuf]) -> io::Result<Self> {
+        // SAFETY: plain system call, the descriptor is owned by the File below
+        let fanotify = unsafe {
+            let fd = check(libc::fanotify_init(
+                libc::FAN_CLASS_NOTIF
+                    | libc::FAN_CLOEXEC
+                    | libc::FAN_NONBLOCK
+                    | libc::FAN_REPORT_DFID_NAME,
+                (libc::O_RDONLY | libc::O_CLOEXEC) as libc::c_uint,
+            ))?;
+            File::from(OwnedFd::from_raw_fd(fd))
+        };
+
+        let mut mounts = HashMap::new();
+        for root in roots {
+            let dir = File::open(root)?;
+            let fsid = fsid(&dir)?;
+            if mounts.contains_key(&fsid) {
+                continue;
+            }
+
+            let path = CString::new(root.as_os_str().as_bytes())?;
+            // SAFETY: both descriptors and the path are valid for the call
+            check(unsafe {
+                libc::fanotify_mark(
+                    fanotify.as_raw_fd(),
+                    libc::FAN_MARK_ADD | libc::FAN_MARK_FILESYSTEM,
+                    EVENTS,
+                    libc::AT_FDCWD,
+                    path.as_ptr(),
+                )
+            })?;
+            mounts.insert(fsid, dir);
+        }
+
+        Ok(Self {
+            fanotify,
+            mounts,
+            buffer: vec![0; WATCH_BUFFER_BYTES],
+        })
+    }
+
+    // The path of the entry an event is about, from its info records
+    fn event_path(&self, mut records: &[u8]) -> Option<PathBuf> {
+        while records.len() >= INFO_HEADER_BYTES {
+            let info_type = records[0];
+            let len = u16::from_ne_bytes(read_ne(records, 2)?) as usize;
+            if len < INFO_HEADER_BYTES || len > records.len() {
+                return None;
+            }
+            let record = &records[..len];
+            records = &records[len..];
+
+            if info_type != libc::FAN_EVENT_INFO_TYPE_DFID_NAME
+                && info_type != libc::FAN_EVENT_INFO_TYPE_DFID
+            {
+                continue;
+            }
+
+            let fsid = [
+                i32::from_ne_bytes(read_ne(record, INFO_HEADER_BYTES)?),
+                i32::from_ne_bytes(read_ne(record, INFO_HEADER_BYTES + 4)?),
+            ];
+            let handle_start = INFO_HEADER_BYTES + FSID_BYTES;
+            let handle_bytes = u32::from_ne_bytes(read_ne(record, handle_start)?) as usize;
+            let name_start = handle_start + FILE_HANDLE_HEADER_BYTES + handle_bytes;
+            let handle = record.get(handle_start..name_start)?;
+
+            let dir = self.open_by_handle(&fsid, handle).ok()?;
+            // A deleted directory, its own removal is reported to its parent
+            if dir.metadata().ok()?.nlink() == 0 {
+                return None;
+            }
+            let dir_path = fs::read_link(format!("/proc/self/fd/{}", dir.as_raw_fd())).ok()?;
+
+            // The name is null terminated and padded, "." for the directory itself
+            let name = record.get(name_start..).unwrap_or_default();
+            let name = &name[..name.iter().position(|&b| b == 0).unwrap_or(name.len())];
+            return Some(match name {
+                b"" | b"." => dir_path,
+                name => dir_path.join(OsStr::from_bytes(name)),
+            });
+        }
+        None
+    }
+
+    fn open_by_handle(&self, fsid: &Fsid, handle: &[u8]) -> io::Result<File> {
+        let mount = self
+            .mounts
+            .get(fsid)
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))?;
+
+        // The kernel expects a struct file_handle, aligned for its ints
+        let mut aligned = vec![0u32; handle.len().div_ceil(4)];
+        // SAFETY: `aligned` holds at least `handle.len()` bytes, the descriptor is owned below
+        unsafe {
+            ptr::copy_nonoverlapping(handle.as_ptr(), aligned.as_mut_ptr().cast(), handle.len());
+            let fd = libc::syscall(
+                libc::SYS_open_by_handle_at,
+                mount.as_raw_fd(),
+                aligned.as_mut_ptr(),
+                libc::O_PATH | libc::O_CLOEXEC,
+            );
+            check(fd as libc::c_int)?;
+            Ok(File::from(OwnedFd::from_raw_fd(fd as RawFd)))
+        }
+    }
+}
+
+impl ChangeSource for FanotifySource {
+    fn name(&self) -> &'static str {
+        "fanotify"
+    }
+
+    fn read_changes(&mut self, timeout: Duration, changes: &mut Vec<Change>) -> io::Result<bool> {
+        if !wait_readable(self.fanotify.as_raw_fd(), timeout)? {
+            return Ok(false);
+        }
+        let len = match self.fanotify.read(&mut self.buffer) {
+            Ok(len) => len,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(false),
+            Err(e) => return Err(e),
+        };
+
+        let metadata_bytes = mem::size_of::<libc::fanotify_event_metadata>();
+        let mut overflowed = false;
+        let mut events = &self.buffer[..len];
+        while events.len() >= metadata_bytes {
+            // SAFETY: the buffer holds a whole event metadata at its start
+            let metadata: libc::fanotify_event_metadata =
+                unsafe { ptr::read_unaligned(events.as_ptr().cast()) };
+            let event_len = metadata.event_len as usize;
+            if metadata.vers != libc::FANOTIFY_METADATA_VERSION
+                || event_len < metadata_bytes
+                || event_len > events.len()
+            {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "unexpected fanotify event",
+                ));
+            }
+            let event = &events[..event_len];
+            events = &events[event_len..];
+
+            if metadata.mask & libc::FAN_Q_OVERFLOW != 0 {
+                overflowed = true;
+                continue;
+            }
+            let Some(path) = self.event_path(&event[metadata.metadata_len as usize..]) else {
+                continue;
+            };
+
+            // Queued events for the same name are merged, so a creation and a deletion can
+            // arrive as one event. What is on disk now decides which came last.
+            let listing_changes = libc::FAN_CREATE | libc::FAN_DELETE | libc::FAN_MOVE;
+            changes.push(if metadata.mask & listing_changes == 0 {
+                Change::Modified { path }
+            } else {
+                match fs::symlink_metadata(&path) {
+                    Ok(metadata) => Change::Created {
+                        path,
+                        is_dir: metadata.is_dir(),
+                    },
+                    Err(_) => Change::Removed { path },
+                }
+            });
+        }
+
+        Ok(overflowed)
+    }
+
+    // The whole file system is marked already
+    fn dir_added(&mut self, _path: &Path) {}
+
+    fn dir_removed(&mut self, _path: &Path) {}
+}
@@ -0,0 +1,142 @@
+use inotify::{EventMask, Inotify, WatchDescriptor, WatchMask};
+use std::collections::HashMap;
+use std::io;
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{debug, warn};
+
+use crate::config::constants::WATCH_BUFFER_BYTES;
+use crate::modules::watch::watch_impl::{wait_readable, Change, ChangeSource};
+
+/// One inotify watch per directory. Needs no privileges, but the number of watches is limited
+/// by `fs.inotify.max_user_watches`.
+pub(crate) struct InotifySource {
+    inotify: Inotify,
+    dirs: HashMap<WatchDescriptor, PathBuf>,
+    watches: HashMap<PathBuf, WatchDescriptor>,
+    buffer: Vec<u8>,
+    limit_reached: bool,
+}
+
+impl InotifySource {
+    pub(crate) fn new() -> io::Result<Self> {
+        Ok(Self {
+            inotify: Inotify::init()?,
+            dirs: HashMap::new(),
+            watches: HashMap::new(),
+            buffer: vec![0; WATCH_BUFFER_BYTES],
+            limit_reached: false,
+        })
+    }
+
+    /// Number of directories watched.
+    pub(crate) fn len(&self) -> usize {
+        self.dirs.len()
+    }
+}
+
+impl ChangeSource for InotifySource {
+    fn name(&self) -> &'static str {
+        "inotify"
+    }
+
+    fn read_changes(&mut self, timeout: Duration, changes: &mut Vec<Change>) -> io::Result<bool> {
+        if !wait_readable(self.inotify.as_raw_fd(), timeout)? {
+            return Ok(false);
+        }
+        let events = match self.inotify.read_events(&mut self.buffer) {
+            Ok(events) => events,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(false),
+            Err(e) => return Err(e),
+        };
+
+        let mut overflowed = false;
+        for event in events {
+            if event.mask.contains(EventMask::Q_OVERFLOW) {
+                overflowed = true;
+                continue;
+            }
+            // The directory was deleted or its watch removed
+            if event.mask.contains(EventMask::IGNORED) {
+                if let Some(path) = self.dirs.remove(&event.wd) {
+                    if self.watches.get(&path) == Some(&event.wd) {
+                        self.watches.remove(&path);
+                    }
+                }
+                continue;
+            }
+
+            let Some(dir) = self.dirs.get(&event.wd) else {
+                continue;
+            };
+            let path = match event.name {
+                Some(name) => dir.join(name),
+                None => dir.clone(),
+            };
+
+            changes.push(
+                if event
+                    .mask
+                    .intersects(EventMask::CREATE | EventMask::MOVED_TO)
+                {
+                    Change::Created {
+                        path,
+                        is_dir: event.mask.contains(EventMask::ISDIR),
+                    }
+                } else if event
+                    .mask
+                    .intersects(EventMask::DELETE | EventMask::MOVED_FROM)
+                {
+                    Change::Removed { path }
+                } else {
+                    Change::Modified { path }
+                },
+            );
+        }
+
+        Ok(overflowed)
+    }
+
+    fn dir_added(&mut self, path: &Path) {
+        let mask = WatchMask::CREATE
+            | WatchMask::DELETE
+            | WatchMask::MOVED_FROM
+            | WatchMask::MOVED_TO
+            | WatchMask::ATTRIB
+            | WatchMask::CLOSE_WRITE
+            | WatchMask::ONLYDIR
+            | WatchMask::DONT_FOLLOW
+            | WatchMask::EXCL_UNLINK;
+
+        match self.inotify.watches().add(path, mask) {
+            // The same directory seen under another path, e.g. through a bind mount
+            Ok(wd) => {
+                if let Some(previous) = self.dirs.insert(wd.clone(), path.to_path_buf()) {
+                    self.watches.remove(&previous);
+                }
+                self.watches.insert(path.to_path_buf(), wd);
+            }
+            Err(e) if e.raw_os_error() == Some(libc::ENOSPC) => {
+                if !self.limit_reached {
+                    warn!(
+                        "Reached the inotify watch limit at {} directories, changes in the \
+                         others are missed. Raise fs.inotify.max_user_watches or run as root \
+                         to watch with fanotify.",
+                        self.dirs.len()
+                    );
+                    self.limit_reached = true;
+                }
+            }
+            Err(e) => debug!("Could not watch {}: {}", path.display(), e),
+        }
+    }
+
+    fn dir_removed(&mut self, path: &Path) {
+        if let Some(wd) = self.watches.remove(path) {
+            self.dirs.remove(&wd);
+            // Fails for deleted directories, the kernel removed their watch already
+            let _ = self.inotify.watches().remove(wd);
+        }
+    }
+}
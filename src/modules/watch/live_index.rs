@@ -0,0 +1,646 @@
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
+use std::collections::{HashMap, VecDeque};
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::modules::daemon::VolumeStats;
+use crate::modules::path_arena::{EntryId, PathArena};
+use crate::modules::watch::watch_impl::{Change, ChangeSource};
+
+// Directory times come from a clock that can lag `SystemTime::now` by a tick, a directory
+// modified shortly before a resync point counts as modified after it
+const MTIME_SLACK: Duration = Duration::from_secs(1);
+
+/// The lookups needed to apply changes to the arena of one volume. Arenas only grow: a removed
+/// entry is marked as such and stays in the arena until the volume is compacted.
+struct Volume {
+    root: PathBuf,
+    root_id: EntryId,
+    // The entries of every directory still in the index
+    children: HashMap<EntryId, Vec<EntryId>>,
+    // By entry id, false once removed
+    live: Vec<bool>,
+    removed: usize,
+}
+
+impl Volume {
+    fn new(arena: &PathArena, root_id: EntryId) -> Self {
+        let mut children: HashMap<EntryId, Vec<EntryId>> = arena
+            .dirs()
+            .iter()
+            .map(|&dir| (dir, vec![]))
+            .chain([(root_id, vec![])])
+            .collect();
+        for id in 0..arena.len() as EntryId {
+            if let Some(parent) = arena.parent(id) {
+                children.entry(parent).or_default().push(id);
+            }
+        }
+
+        Self {
+            root: arena.path(root_id),
+            root_id,
+            children,
+            live: vec![true; arena.len()],
+            removed: 0,
+        }
+    }
+
+    fn child(&self, arena: &PathArena, dir: EntryId, name: &OsStr) -> Option<EntryId> {
+        self.children
+            .get(&dir)?
+            .iter()
+            .copied()
+            .find(|&id| arena.name(id) == name)
+    }
+
+    fn lookup(&self, arena: &PathArena, path: &Path) -> Option<EntryId> {
+        let mut id = self.root_id;
+        for component in path.strip_prefix(&self.root).ok()?.components() {
+            match component {
+                Component::Normal(name) => id = self.child(arena, id, name)?,
+                _ => return None,
+            }
+        }
+        Some(id)
+    }
+
+    fn add(
+        &mut self,
+        arena: &mut PathArena,
+        parent: EntryId,
+        name: &OsStr,
+        is_dir: bool,
+    ) -> EntryId {
+        let id = if is_dir {
+            let id = arena.add_dir(parent, name);
+            self.children.insert(id, vec![]);
+            id
+        } else {
+            arena.add_file(parent, name)
+        };
+        self.children.entry(parent).or_default().push(id);
+        self.live.push(true);
+        id
+    }
+
+    // Drops the entry and everything below it
+    fn remove(&mut self, arena: &PathArena, id: EntryId, source: &mut dyn ChangeSource) {
+        if let Some(siblings) = arena.parent(id).and_then(|p| self.children.get_mut(&p)) {
+            siblings.retain(|&sibling| sibling != id);
+        }
+
+        let mut pending = vec![id];
+        while let Some(id) = pending.pop() {
+            self.live[id as usize] = false;
+            self.removed += 1;
+            if let Some(children) = self.children.remove(&id) {
+                source.dir_removed(&arena.path(id));
+                pending.extend(children);
+            }
+        }
+    }
+
+    /// Brings the entries of the directory `dir` in line with its listing on disk, directories
+    /// new to the index are read completely. Returns whether anything changed.
+    fn sync_dir(
+        &mut self,
+        arena: &mut PathArena,
+        dir: EntryId,
+        source: &mut dyn ChangeSource,
+    ) -> bool {
+        let path = arena.path(dir);
+        // Gone already, its removal is among the changes still to apply
+        let Ok(read_dir) = fs::read_dir(&path) else {
+            return false;
+        };
+        let mut listing: HashMap<OsString, bool> = read_dir
+            .filter_map(Result::ok)
+            .map(|entry| {
+                let is_dir = entry.file_type().is_ok_and(|kind| kind.is_dir());
+                (entry.file_name(), is_dir)
+            })
+            .collect();
+
+        let mut changed = false;
+        let known = self.children.get(&dir).cloned().unwrap_or_default();
+        for id in known {
+            let name = arena.name(id);
+            if listing.get(name) == Some(&arena.is_dir(id)) {
+                listing.remove(name);
+            } else {
+                // Deleted, or replaced by an entry of the other type which is added below
+                self.remove(arena, id, source);
+                changed = true;
+            }
+        }
+
+        for (name, is_dir) in listing {
+            let id = self.add(arena, dir, &name, is_dir);
+            if is_dir {
+                source.dir_added(&path.join(&name));
+                self.sync_dir(arena, id, source);
+            }
+            changed = true;
+        }
+
+        changed
+    }
+
+    fn create(
+        &mut self,
+        arena: &mut PathArena,
+        path: &Path,
+        is_dir: bool,
+        source: &mut dyn ChangeSource,
+    ) -> bool {
+        let (Some(parent_path), Some(name)) = (path.parent(), path.file_name()) else {
+            return false;
+        };
+        let Some(parent) = self.lookup(arena, parent_path) else {
+            // Missed the parent as well, e.g. a directory filled before it was watched
+            return path
+                .ancestors()
+                .skip(2)
+                .find_map(|ancestor| self.lookup(arena, ancestor))
+                .is_some_and(|ancestor| self.sync_dir(arena, ancestor, source));
+        };
+
+        if let Some(existing) = self.child(arena, parent, name) {
+            // Already added by a resync
+            if arena.is_dir(existing) == is_dir {
+                return false;
+            }
+            self.remove(arena, existing, source);
+        }
+
+        let id = self.add(arena, parent, name, is_dir);
+        if is_dir {
+            source.dir_added(path);
+            self.sync_dir(arena, id, source);
+        }
+        true
+    }
+
+    // Rebuilds the arena from the entries still in the index, breadth first so that parents
+    // keep lower ids than their children
+    fn compact(&mut self, arena: &mut PathArena) {
+        let mut compacted = PathArena::new();
+        let mut children = HashMap::with_capacity(self.children.len());
+        let root_id = compacted.add_root(&self.root);
+
+        let mut queue = VecDeque::from([(self.root_id, root_id)]);
+        while let Some((old, new)) = queue.pop_front() {
+            let mut entries = vec![];
+            for &child in self.children.get(&old).into_iter().flatten() {
+                let id = if arena.is_dir(child) {
+                    let id = compacted.add_dir(new, arena.name(child));
+                    queue.push_back((child, id));
+                    id
+                } else {
+                    compacted.add_file(new, arena.name(child))
+                };
+                entries.push(id);
+            }
+            children.insert(new, entries);
+        }
+        compacted.shrink_to_fit();
+
+        self.live = vec![true; compacted.len()];
+        *arena = compacted;
+        self.children = children;
+        self.root_id = root_id;
+        self.removed = 0;
+    }
+}
+
+/// The entries of the watched volumes, updated with the changes reported by the kernel.
+pub(crate) struct LiveIndex {
+    arenas: Vec<PathArena>,
+    volumes: Vec<Volume>,
+}
+
+impl LiveIndex {
+    pub(crate) fn new(arenas: Vec<PathArena>) -> Self {
+        let (arenas, volumes) = arenas
+            .into_iter()
+            .filter_map(|arena| {
+                let volume = Volume::new(&arena, arena.roots().next()?);
+                Some((arena, volume))
+            })
+            .unzip();
+
+        Self { arenas, volumes }
+    }
+
+    pub(crate) fn arenas(&self) -> &[PathArena] {
+        &self.arenas
+    }
+
+    pub(crate) fn roots(&self) -> Vec<PathBuf> {
+        self.volumes
+            .iter()
+            .map(|volume| volume.root.clone())
+            .collect()
+    }
+
+    /// Every directory in the index, the roots included.
+    pub(crate) fn dir_paths(&self) -> impl Iterator<Item = PathBuf> + '_ {
+        self.arenas
+            .iter()
+            .zip(&self.volumes)
+            .flat_map(|(arena, volume)| volume.children.keys().map(|&dir| arena.path(dir)))
+    }
+
+    // The volume with the longest root containing `path`, mounts can be nested
+    fn volume_of(&self, path: &Path) -> Option<usize> {
+        self.volumes
+            .iter()
+            .enumerate()
+            .filter(|(_, volume)| path.starts_with(&volume.root))
+            .max_by_key(|(_, volume)| volume.root.components().count())
+            .map(|(index, _)| index)
+    }
+
+    /// Applies a change reported by the kernel. Returns whether the index changed.
+    pub(crate) fn apply(&mut self, change: &Change, source: &mut dyn ChangeSource) -> bool {
+        let path = match change {
+            Change::Created { path, .. } | Change::Removed { path } | Change::Modified { path } => {
+                path
+            }
+        };
+        let Some(index) = self.volume_of(path) else {
+            return false;
+        };
+        let (arena, volume) = (&mut self.arenas[index], &mut self.volumes[index]);
+
+        match change {
+            Change::Created { path, is_dir } => volume.create(arena, path, *is_dir, source),
+            Change::Removed { path } => match volume.lookup(arena, path) {
+                Some(id) if id != volume.root_id => {
+                    volume.remove(arena, id, source);
+                    true
+                }
+                _ => false,
+            },
+            // The index keeps the names only, sizes and times are read when it's written. A
+            // change of an entry it doesn't know means its creation was missed.
+            Change::Modified { path } => {
+                if volume.lookup(arena, path).is_some() {
+                    return true;
+                }
+                match fs::symlink_metadata(path) {
+                    Ok(metadata) => volume.create(arena, path, metadata.is_dir(), source),
+                    Err(_) => false,
+                }
+            }
+        }
+    }
+
+    /// Rereads the directories modified since `since`, for changes the kernel didn't report.
+    /// Only the listings of those directories are read again, not the whole volumes.
+    pub(crate) fn resync(&mut self, since: SystemTime, source: &mut dyn ChangeSource) -> bool {
+        let since = since.checked_sub(MTIME_SLACK).unwrap_or(since);
+        self.sync_dirs(
+            |path| {
+                fs::symlink_metadata(path)
+                    .and_then(|metadata| metadata.modified())
+                    .is_ok_and(|modified| modified >= since)
+            },
+            source,
+        )
+    }
+
+    /// Rereads every directory below `under`, or every directory of the index.
+    pub(crate) fn rescan(&mut self, under: Option<&Path>, source: &mut dyn ChangeSource) -> bool {
+        self.sync_dirs(
+            |path| under.is_none_or(|under| path.starts_with(under)),
+            source,
+        )
+    }
+
+    // Syncs the directories whose path passes `filter`, which is evaluated in parallel
+    fn sync_dirs<F>(&mut self, filter: F, source: &mut dyn ChangeSource) -> bool
+    where
+        F: Fn(&Path) -> bool + Sync,
+    {
+        let mut changed = false;
+
+        for (arena, volume) in self.arenas.iter_mut().zip(&mut self.volumes) {
+            let dirs: Vec<(EntryId, PathBuf)> = volume
+                .children
+                .keys()
+                .map(|&dir| (dir, arena.path(dir)))
+                .collect();
+            let mut selected: Vec<EntryId> = dirs
+                .into_par_iter()
+                .filter(|(_, path)| filter(path))
+                .map(|(dir, _)| dir)
+                .collect();
+            selected.sort_unstable();
+
+            for dir in selected {
+                // Skips directories removed while syncing their parent
+                if volume.children.contains_key(&dir) {
+                    changed |= volume.sync_dir(arena, dir, source);
+                }
+            }
+        }
+
+        changed
+    }
+
+    /// The entries whose name contains `pattern`, ignoring case.
+    pub(crate) fn search<'a>(
+        &'a self,
+        pattern: &'a str,
+    ) -> impl Iterator<Item = (PathBuf, &'static str)> + 'a {
+        self.arenas
+            .iter()
+            .zip(&self.volumes)
+            .flat_map(move |(arena, volume)| {
+                arena
+                    .search(pattern)
+                    .filter(|&(id, _)| volume.live[id as usize])
+                    .map(|(id, kind)| (arena.path(id), kind))
+            })
+    }
+
+    pub(crate) fn stats(&self) -> Vec<VolumeStats> {
+        self.arenas
+            .iter()
+            .zip(&self.volumes)
+            .map(|(arena, volume)| {
+                let live =
+                    |ids: &[EntryId]| ids.iter().filter(|&&id| volume.live[id as usize]).count();
+                VolumeStats {
+                    root: volume.root.to_string_lossy().into_owned(),
+                    files: live(arena.files()),
+                    dirs: live(arena.dirs()),
+                }
+            })
+            .collect()
+    }
+
+    /// Drops the removed entries from the arenas.
+    pub(crate) fn compact(&mut self) {
+        for (arena, volume) in self.arenas.iter_mut().zip(&mut self.volumes) {
+            if volume.removed > 0 {
+                volume.compact(arena);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+
+    // Records the directories the index starts and stops watching
+    #[derive(Default)]
+    struct RecordingSource {
+        added: Vec<PathBuf>,
+        removed: Vec<PathBuf>,
+    }
+
+    impl ChangeSource for RecordingSource {
+        fn name(&self) -> &'static str {
+            "test"
+        }
+
+        fn read_changes(
+            &mut self,
+            _timeout: Duration,
+            _changes: &mut Vec<Change>,
+        ) -> io::Result<bool> {
+            Ok(false)
+        }
+
+        fn dir_added(&mut self, path: &Path) {
+            self.added.push(path.to_path_buf());
+        }
+
+        fn dir_removed(&mut self, path: &Path) {
+            self.removed.push(path.to_path_buf());
+        }
+    }
+
+    // A tempdir with `dirs` and `files` below it, and an index of it
+    fn indexed_tree(dirs: &[&str], files: &[&str]) -> (tempfile::TempDir, LiveIndex) {
+        let root = tempfile::tempdir().unwrap();
+        for dir in dirs {
+            fs::create_dir_all(root.path().join(dir)).unwrap();
+        }
+        for file in files {
+            fs::write(root.path().join(file), b"").unwrap();
+        }
+
+        let mut arena = PathArena::new();
+        let root_id = arena.add_root(root.path());
+        add_listing(&mut arena, root_id, root.path());
+        (root, LiveIndex::new(vec![arena]))
+    }
+
+    fn add_listing(arena: &mut PathArena, dir: EntryId, path: &Path) {
+        for entry in fs::read_dir(path).unwrap() {
+            let entry = entry.unwrap();
+            if entry.file_type().unwrap().is_dir() {
+                let id = arena.add_dir(dir, &entry.file_name());
+                add_listing(arena, id, &entry.path());
+            } else {
+                arena.add_file(dir, &entry.file_name());
+            }
+        }
+    }
+
+    // The live entries as "dir/" or "file" relative to the root
+    fn live(index: &LiveIndex, root: &Path) -> Vec<String> {
+        let mut entries: Vec<String> = index
+            .search("")
+            .map(|(path, kind)| {
+                let relative = path.strip_prefix(root).unwrap().to_string_lossy();
+                match kind {
+                    "dir" => format!("{}/", relative),
+                    _ => relative.into_owned(),
+                }
+            })
+            .collect();
+        entries.sort();
+        entries
+    }
+
+    fn created(path: PathBuf, is_dir: bool) -> Change {
+        Change::Created { path, is_dir }
+    }
+
+    #[test]
+    fn created_entries_are_added_with_what_is_below_them() {
+        let (root, mut index) = indexed_tree(&["docs"], &["a.txt"]);
+        let mut source = RecordingSource::default();
+
+        fs::write(root.path().join("docs/new.txt"), b"").unwrap();
+        assert!(index.apply(
+            &created(root.path().join("docs/new.txt"), false),
+            &mut source
+        ));
+        // Seen again, e.g. after a resync added it already
+        assert!(!index.apply(
+            &created(root.path().join("docs/new.txt"), false),
+            &mut source
+        ));
+
+        // Filled before its watch was set up
+        fs::create_dir(root.path().join("photos")).unwrap();
+        fs::write(root.path().join("photos/beach.jpg"), b"").unwrap();
+        assert!(index.apply(&created(root.path().join("photos"), true), &mut source));
+        assert_eq!(source.added, [root.path().join("photos")]);
+
+        // Only the deepest entry was reported, the nearest known directory is read again
+        fs::create_dir_all(root.path().join("deep/er")).unwrap();
+        fs::write(root.path().join("deep/er/file"), b"").unwrap();
+        assert!(index.apply(
+            &created(root.path().join("deep/er/file"), false),
+            &mut source
+        ));
+
+        assert_eq!(
+            live(&index, root.path()),
+            [
+                "a.txt",
+                "deep/",
+                "deep/er/",
+                "deep/er/file",
+                "docs/",
+                "docs/new.txt",
+                "photos/",
+                "photos/beach.jpg",
+            ]
+        );
+        assert_eq!(index.stats()[0].files, 4);
+        assert_eq!(index.stats()[0].dirs, 4);
+    }
+
+    #[test]
+    fn removed_directories_take_their_entries_along() {
+        let (root, mut index) = indexed_tree(
+            &["docs/old", "photos"],
+            &["docs/report.txt", "docs/old/notes.txt", "photos/beach.jpg"],
+        );
+        let mut source = RecordingSource::default();
+
+        fs::remove_dir_all(root.path().join("docs")).unwrap();
+        let removed = Change::Removed {
+            path: root.path().join("docs"),
+        };
+        assert!(index.apply(&removed, &mut source));
+        assert_eq!(live(&index, root.path()), ["photos/", "photos/beach.jpg"]);
+        assert_eq!(
+            source.removed,
+            [root.path().join("docs"), root.path().join("docs/old")]
+        );
+
+        // Gone already, unknown, or the root itself
+        assert!(!index.apply(&removed, &mut source));
+        let unknown = root.path().join("photos/unknown.jpg");
+        assert!(!index.apply(&Change::Removed { path: unknown }, &mut source));
+        let root_removed = Change::Removed {
+            path: root.path().to_path_buf(),
+        };
+        assert!(!index.apply(&root_removed, &mut source));
+
+        assert_eq!(index.stats()[0].files, 1);
+        assert_eq!(index.stats()[0].dirs, 1);
+        index.compact();
+        assert_eq!(index.arenas()[0].len(), 3);
+        assert_eq!(live(&index, root.path()), ["photos/", "photos/beach.jpg"]);
+    }
+
+    #[test]
+    fn renames_move_the_entries_below_them() {
+        let (root, mut index) = indexed_tree(
+            &["docs/old"],
+            &["docs/report.txt", "docs/old/notes.txt", "a.txt"],
+        );
+        let mut source = RecordingSource::default();
+
+        // A rename arrives as the removal of the source and the creation of the destination
+        fs::rename(root.path().join("docs"), root.path().join("papers")).unwrap();
+        fs::rename(root.path().join("a.txt"), root.path().join("papers/a.txt")).unwrap();
+        let changes = [
+            Change::Removed {
+                path: root.path().join("docs"),
+            },
+            created(root.path().join("papers"), true),
+            Change::Removed {
+                path: root.path().join("a.txt"),
+            },
+            created(root.path().join("papers/a.txt"), false),
+        ];
+        for change in &changes {
+            index.apply(change, &mut source);
+        }
+
+        assert_eq!(
+            live(&index, root.path()),
+            [
+                "papers/",
+                "papers/a.txt",
+                "papers/old/",
+                "papers/old/notes.txt",
+                "papers/report.txt",
+            ]
+        );
+        assert_eq!(
+            source.added,
+            [root.path().join("papers"), root.path().join("papers/old")]
+        );
+
+        // The moved entries are found under their new path
+        let modified = Change::Modified {
+            path: root.path().join("papers/old/notes.txt"),
+        };
+        assert!(index.apply(&modified, &mut source));
+        assert_eq!(source.added.len(), 2);
+    }
+
+    #[test]
+    fn a_modified_entry_the_index_missed_is_added() {
+        let (root, mut index) = indexed_tree(&[], &[]);
+        let mut source = RecordingSource::default();
+
+        fs::write(root.path().join("missed"), b"").unwrap();
+        let modified = |name: &str| Change::Modified {
+            path: root.path().join(name),
+        };
+        assert!(index.apply(&modified("missed"), &mut source));
+        assert!(!index.apply(&modified("vanished"), &mut source));
+        assert_eq!(live(&index, root.path()), ["missed"]);
+    }
+
+    #[test]
+    fn a_rescan_replaces_entries_that_changed_type() {
+        let (root, mut index) = indexed_tree(&["box"], &["box/a", "thing", "kept"]);
+        let mut source = RecordingSource::default();
+
+        fs::remove_file(root.path().join("thing")).unwrap();
+        fs::create_dir(root.path().join("thing")).unwrap();
+        fs::write(root.path().join("thing/inner"), b"").unwrap();
+        fs::remove_dir_all(root.path().join("box")).unwrap();
+        fs::write(root.path().join("box"), b"").unwrap();
+
+        assert!(index.rescan(None, &mut source));
+        assert_eq!(
+            live(&index, root.path()),
+            ["box", "kept", "thing/", "thing/inner"]
+        );
+        assert_eq!(source.added, [root.path().join("thing")]);
+        assert_eq!(source.removed, [root.path().join("box")]);
+
+        // Nothing changed since
+        assert!(!index.rescan(None, &mut source));
+        assert!(!index.rescan(Some(&root.path().join("thing")), &mut source));
+    }
+}
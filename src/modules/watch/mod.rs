@@ -0,0 +1,25 @@
+#[cfg(target_os = "linux")]
+mod fanotify_source;
+#[cfg(target_os = "linux")]
+mod inotify_source;
+#[cfg(target_os = "linux")]
+mod live_index;
+#[cfg(target_os = "linux")]
+pub mod watch_impl;
+#[cfg(not(target_os = "linux"))]
+mod watch_unsupported;
+
+#[cfg(target_os = "linux")]
+pub(crate) use live_index::LiveIndex;
+#[cfg(target_os = "linux")]
+pub(crate) use watch_impl::watch_volumes;
+#[cfg(target_os = "linux")]
+pub(crate) use watch_impl::write_index;
+#[cfg(target_os = "linux")]
+pub(crate) use watch_impl::Applied;
+#[cfg(target_os = "linux")]
+pub(crate) use watch_impl::PendingWrite;
+#[cfg(target_os = "linux")]
+pub(crate) use watch_impl::Watcher;
+#[cfg(not(target_os = "linux"))]
+pub(crate) use watch_unsupported::watch_volumes;
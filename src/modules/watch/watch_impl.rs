@@ -0,0 +1,328 @@
+use serde::Serialize;
+use std::io;
+use std::mem;
+use std::os::fd::RawFd;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::{Duration, Instant, SystemTime};
+use tracing::{debug, error, info, warn};
+
+use crate::config::constants::{WATCH_MAX_DELAY_MS, WATCH_SETTLE_MS};
+use crate::modules::errors::UFFSError;
+use crate::modules::locate::write_mlocate_db;
+use crate::modules::path_arena::PathArena;
+use crate::modules::watch::fanotify_source::FanotifySource;
+use crate::modules::watch::inotify_source::InotifySource;
+use crate::modules::watch::live_index::LiveIndex;
+
+/// A change to an entry below one of the watched volumes.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub(crate) enum Change {
+    // Also the destination of a rename
+    Created { path: PathBuf, is_dir: bool },
+    // Also the source of a rename
+    Removed { path: PathBuf },
+    // Size, times or attributes
+    Modified { path: PathBuf },
+}
+
+/// Where the changes come from: inotify watches on every directory, or a fanotify mark on every
+/// file system when running with CAP_SYS_ADMIN.
+pub(crate) trait ChangeSource {
+    fn name(&self) -> &'static str;
+
+    /// Waits up to `timeout` for changes and appends them to `changes`. Returns whether the
+    /// kernel dropped changes because its queue overflowed.
+    fn read_changes(&mut self, timeout: Duration, changes: &mut Vec<Change>) -> io::Result<bool>;
+
+    /// A directory entered the index. Called before the directory is read, so nothing created in
+    /// it afterwards is missed.
+    fn dir_added(&mut self, path: &Path);
+
+    fn dir_removed(&mut self, path: &Path);
+}
+
+// Waits until `fd` has something to read. False on timeout.
+pub(crate) fn wait_readable(fd: RawFd, timeout: Duration) -> io::Result<bool> {
+    let mut poll_fd = libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let timeout = timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+
+    // SAFETY: a single valid pollfd is passed
+    match unsafe { libc::poll(&mut poll_fd, 1, timeout) } {
+        -1 => {
+            let e = io::Error::last_os_error();
+            if e.kind() == io::ErrorKind::Interrupted {
+                Ok(false)
+            } else {
+                Err(e)
+            }
+        }
+        0 => Ok(false),
+        _ => Ok(true),
+    }
+}
+
+fn open_source(index: &LiveIndex) -> io::Result<Box<dyn ChangeSource>> {
+    match FanotifySource::new(&index.roots()) {
+        Ok(source) => {
+            info!("Watching the file systems of the volumes with fanotify");
+            return Ok(Box::new(source));
+        }
+        Err(e) => debug!("fanotify not available, using inotify: {}", e),
+    }
+
+    let mut source = InotifySource::new()?;
+    for dir in index.dir_paths() {
+        source.dir_added(&dir);
+    }
+    info!("Watching {} directories with inotify", source.len());
+    Ok(Box::new(source))
+}
+
+/// The changes one `Watcher::apply` made to the index.
+#[derive(Debug, Default, Serialize)]
+pub(crate) struct Applied {
+    pub(crate) changes: Vec<Change>,
+    // Directories were reread because the kernel dropped changes
+    pub(crate) rescanned: bool,
+}
+
+impl Applied {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.changes.is_empty() && !self.rescanned
+    }
+}
+
+/// Follows the changes to the volumes of a `LiveIndex`. Waiting for changes and applying them
+/// are separate steps, so that a shared index is only locked to apply them.
+pub(crate) struct Watcher {
+    source: Box<dyn ChangeSource>,
+    changes: Vec<Change>,
+    overflowed: bool,
+    // The last time the kernel queue was seen empty, changes it dropped were made after that
+    drained_at: SystemTime,
+}
+
+impl Watcher {
+    /// Starts watching the volumes of `index`. `scanned_at` is when the scan started, anything
+    /// changed since then is reread first.
+    pub(crate) fn new(index: &mut LiveIndex, scanned_at: SystemTime) -> io::Result<Self> {
+        let mut source = open_source(index)?;
+        // Changes made while scanning happened before anything was watched
+        index.resync(scanned_at, source.as_mut());
+
+        Ok(Self {
+            source,
+            changes: vec![],
+            overflowed: false,
+            drained_at: SystemTime::now(),
+        })
+    }
+
+    /// inotify or fanotify.
+    pub(crate) fn backend(&self) -> &'static str {
+        self.source.name()
+    }
+
+    /// Waits up to `timeout` for changes. Returns whether there are changes to apply.
+    pub(crate) fn wait(&mut self, timeout: Duration) -> io::Result<bool> {
+        let read_at = SystemTime::now();
+        self.overflowed |= self.source.read_changes(timeout, &mut self.changes)?;
+
+        if self.changes.is_empty() && !self.overflowed {
+            self.drained_at = read_at;
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    pub(crate) fn apply(&mut self, index: &mut LiveIndex) -> Applied {
+        let mut applied = Applied::default();
+        for change in self.changes.drain(..) {
+            if index.apply(&change, self.source.as_mut()) {
+                applied.changes.push(change);
+            }
+        }
+
+        if mem::take(&mut self.overflowed) {
+            warn!("The kernel dropped changes, rescanning the directories modified since");
+            applied.rescanned = index.resync(self.drained_at, self.source.as_mut());
+        }
+        applied
+    }
+
+    /// Rereads every directory below `under`, or all of them. Returns whether anything changed.
+    pub(crate) fn rescan(&mut self, index: &mut LiveIndex, under: Option<&Path>) -> bool {
+        index.rescan(under, self.source.as_mut())
+    }
+}
+
+/// Decides when the index is written: once the changes settle for `WATCH_SETTLE_MS`, and at least
+/// every `WATCH_MAX_DELAY_MS` while they keep coming.
+#[derive(Debug, Default)]
+pub(crate) struct PendingWrite {
+    // When the first and the last change not yet written were applied
+    changes: Option<(Instant, Instant)>,
+}
+
+impl PendingWrite {
+    pub(crate) fn due(&mut self, changed: bool) -> bool {
+        let now = Instant::now();
+        if changed {
+            let (first, _) = self.changes.unwrap_or((now, now));
+            self.changes = Some((first, now));
+        }
+
+        let Some((first, last)) = self.changes else {
+            return false;
+        };
+        let due = now - last >= Duration::from_millis(WATCH_SETTLE_MS)
+            || now - first >= Duration::from_millis(WATCH_MAX_DELAY_MS);
+        if due {
+            self.changes = None;
+        }
+        due
+    }
+}
+
+/// Drops the removed entries and writes the index to the mlocate database `path`. Searches can
+/// go on while the file is written.
+pub(crate) fn write_index(index: &RwLock<LiveIndex>, path: &Path) {
+    index.write().unwrap().compact();
+
+    let index = index.read().unwrap();
+    match write_mlocate_db(path, index.arenas()) {
+        Ok(written) => info!("Updated {} with {} entries", path.display(), written),
+        Err(e) => error!("Failed to update {}: {}", path.display(), e),
+    }
+}
+
+/// Keeps the entries of `arenas` up to date with the changes to their volumes and writes them to
+/// the mlocate database `index_path` whenever the changes settle, until the process is stopped.
+/// `scanned_at` is when the scan started, anything changed since then is rescanned first.
+pub(crate) fn watch_volumes(
+    arenas: Vec<PathArena>,
+    index_path: &Path,
+    scanned_at: SystemTime,
+) -> Result<(), UFFSError> {
+    let mut index = LiveIndex::new(arenas);
+    let mut watcher = Watcher::new(&mut index, scanned_at)?;
+    let index = RwLock::new(index);
+    write_index(&index, index_path);
+
+    let settle = Duration::from_millis(WATCH_SETTLE_MS);
+    let mut pending = PendingWrite::default();
+    loop {
+        let changed =
+            watcher.wait(settle)? && !watcher.apply(&mut index.write().unwrap()).is_empty();
+        if pending.due(changed) {
+            write_index(&index, index_path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    // Reports an overflow on its first read and never any change
+    #[derive(Default)]
+    struct OverflowingSource {
+        reads: usize,
+        added: Vec<PathBuf>,
+    }
+
+    impl ChangeSource for OverflowingSource {
+        fn name(&self) -> &'static str {
+            "test"
+        }
+
+        fn read_changes(
+            &mut self,
+            _timeout: Duration,
+            _changes: &mut Vec<Change>,
+        ) -> io::Result<bool> {
+            self.reads += 1;
+            Ok(self.reads == 1)
+        }
+
+        fn dir_added(&mut self, path: &Path) {
+            self.added.push(path.to_path_buf());
+        }
+
+        fn dir_removed(&mut self, _path: &Path) {}
+    }
+
+    fn watcher(drained_at: SystemTime) -> Watcher {
+        Watcher {
+            source: Box::<OverflowingSource>::default(),
+            changes: vec![],
+            overflowed: false,
+            drained_at,
+        }
+    }
+
+    fn names(index: &LiveIndex) -> Vec<PathBuf> {
+        let mut names: Vec<PathBuf> = index.search("").map(|(path, _)| path).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn overflow_rereads_the_directories_modified_since() {
+        let root = tempfile::tempdir().unwrap();
+        fs::write(root.path().join("kept"), b"").unwrap();
+        let mut arena = PathArena::new();
+        let root_id = arena.add_root(root.path());
+        arena.add_file(root_id, "kept".as_ref());
+        let mut index = LiveIndex::new(vec![arena]);
+
+        let mut watcher = watcher(SystemTime::now());
+        // Changes the kernel dropped
+        fs::write(root.path().join("missed"), b"").unwrap();
+        fs::create_dir(root.path().join("missed_dir")).unwrap();
+        fs::write(root.path().join("missed_dir").join("inner"), b"").unwrap();
+
+        assert!(watcher.wait(Duration::ZERO).unwrap());
+        let applied = watcher.apply(&mut index);
+        assert!(applied.rescanned);
+        assert!(applied.changes.is_empty());
+        assert_eq!(
+            names(&index),
+            vec![
+                root.path().join("kept"),
+                root.path().join("missed"),
+                root.path().join("missed_dir"),
+                root.path().join("missed_dir").join("inner"),
+            ]
+        );
+
+        // The overflow is handled once
+        assert!(!watcher.wait(Duration::ZERO).unwrap());
+        assert!(watcher.apply(&mut index).is_empty());
+    }
+
+    #[test]
+    fn overflow_keeps_the_directories_not_modified_since() {
+        let root = tempfile::tempdir().unwrap();
+        fs::write(root.path().join("kept"), b"").unwrap();
+        let mut arena = PathArena::new();
+        let root_id = arena.add_root(root.path());
+        arena.add_file(root_id, "kept".as_ref());
+        let mut index = LiveIndex::new(vec![arena]);
+
+        let mut watcher = watcher(SystemTime::now() + Duration::from_secs(60));
+        fs::write(root.path().join("missed"), b"").unwrap();
+
+        assert!(watcher.wait(Duration::ZERO).unwrap());
+        let applied = watcher.apply(&mut index);
+        assert!(!applied.rescanned);
+        assert_eq!(names(&index), vec![root.path().join("kept")]);
+    }
+}
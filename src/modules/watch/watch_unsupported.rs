@@ -0,0 +1,17 @@
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::modules::errors::UFFSError;
+use crate::modules::path_arena::PathArena;
+
+// Stands in for the watcher on platforms without inotify and fanotify
+pub(crate) fn watch_volumes(
+    _arenas: Vec<PathArena>,
+    _index_path: &Path,
+    _scanned_at: SystemTime,
+) -> Result<(), UFFSError> {
+    Err(UFFSError::Unsupported {
+        feature: "Watching for changes".to_string(),
+        platform: "Linux".to_string(),
+    })
+}